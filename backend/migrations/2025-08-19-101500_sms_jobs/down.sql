@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS idx_sms_jobs_status_next_attempt;
+DROP TABLE IF EXISTS sms_jobs;
@@ -0,0 +1,16 @@
+-- Your SQL goes here
+CREATE TABLE sms_jobs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER,  -- NULL if the sender didn't match a user when queued
+    encrypted_payload TEXT NOT NULL,  -- the inbound webhook payload as json
+    status TEXT NOT NULL,  -- 'pending', 'processing', 'done' or 'failed' (dead-lettered)
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at INTEGER NOT NULL,  -- Unix timestamp, when a worker may pick it up
+    last_error TEXT,
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_sms_jobs_status_next_attempt
+ON sms_jobs(status, next_attempt_at);
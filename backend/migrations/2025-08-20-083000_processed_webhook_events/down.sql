@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS idx_processed_webhook_events_created_at;
+DROP TABLE IF EXISTS processed_webhook_events;
@@ -0,0 +1,15 @@
+-- Your SQL goes here
+CREATE TABLE processed_webhook_events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    provider TEXT NOT NULL,  -- 'twilio', 'textbee' or 'elevenlabs'
+    event_id TEXT NOT NULL,  -- provider's id for the delivery, e.g. MessageSid
+    status TEXT NOT NULL,  -- 'processing' or 'done'
+    response_status INTEGER,  -- http status we answered with, set when done
+    response_body TEXT,  -- what we answered with, returned as is to redeliveries
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL,
+    UNIQUE (provider, event_id)
+);
+
+CREATE INDEX idx_processed_webhook_events_created_at
+ON processed_webhook_events(created_at);
@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+alter table user_settings drop column proactive_paused_until;
@@ -0,0 +1,2 @@
+-- Your SQL goes here
+alter table user_settings add column proactive_paused_until integer;
@@ -0,0 +1,9 @@
+-- Your SQL goes here
+CREATE TABLE conversation_summaries (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL UNIQUE,
+    encrypted_summary TEXT NOT NULL,
+    summarized_until INTEGER NOT NULL,  -- created_at of the newest message_history row folded into the summary
+    updated_at INTEGER NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
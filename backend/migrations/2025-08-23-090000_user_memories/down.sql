@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS idx_user_memories_user_id;
+DROP TABLE IF EXISTS user_memories;
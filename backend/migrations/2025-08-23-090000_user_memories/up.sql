@@ -0,0 +1,12 @@
+-- Your SQL goes here
+CREATE TABLE user_memories (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    encrypted_content TEXT NOT NULL,  -- the fact itself, e.g. "Sister is Laura (WhatsApp: Laura K)"
+    category TEXT NOT NULL,  -- 'person', 'schedule', 'health', 'preference' or 'other'
+    source TEXT NOT NULL,  -- 'user' when told with "remember that", 'extracted' when picked up from conversation
+    created_at INTEGER NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_user_memories_user_id ON user_memories(user_id);
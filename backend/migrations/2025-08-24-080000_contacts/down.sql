@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS idx_contacts_user_id;
+DROP TABLE IF EXISTS contacts;
@@ -0,0 +1,13 @@
+-- Your SQL goes here
+CREATE TABLE contacts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    encrypted_name TEXT NOT NULL,
+    encrypted_phone TEXT,
+    encrypted_email TEXT,
+    source TEXT NOT NULL,  -- 'mms' when added from a vCard the user sent
+    created_at INTEGER NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_contacts_user_id ON contacts(user_id);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS idx_audio_transcripts_created_at;
+DROP TABLE IF EXISTS audio_transcripts;
@@ -0,0 +1,12 @@
+-- Your SQL goes here
+CREATE TABLE audio_transcripts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    source_id TEXT NOT NULL,  -- matrix event id of the bridged voice note
+    encrypted_transcript TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    UNIQUE (user_id, source_id)
+);
+
+CREATE INDEX idx_audio_transcripts_created_at ON audio_transcripts(created_at);
@@ -0,0 +1,19 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE users ADD COLUMN confirm_send_event TEXT;
+
+CREATE TABLE temp_variables (
+    id INTEGER PRIMARY KEY NOT NULL,
+    user_id INTEGER NOT NULL,
+    confirm_send_event_type TEXT NOT NULL,
+    confirm_send_event_recipient TEXT,
+    confirm_send_event_subject TEXT,
+    confirm_send_event_content TEXT,
+    confirm_send_event_start_time TEXT,
+    confirm_send_event_duration TEXT,
+    confirm_send_event_id TEXT,
+    confirm_send_event_image_url TEXT,
+    FOREIGN KEY (user_id) REFERENCES users(id)
+);
+
+DROP INDEX IF EXISTS idx_pending_actions_user_id;
+DROP TABLE IF EXISTS pending_actions;
@@ -0,0 +1,17 @@
+-- Your SQL goes here
+CREATE TABLE pending_actions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    number INTEGER NOT NULL,  -- what the user refers to it by, "yes 2"
+    action_type TEXT NOT NULL,  -- 'calendar_event', 'chat_message', 'email_reply' or 'task'
+    encrypted_payload TEXT NOT NULL,  -- the action as json
+    expires_at INTEGER NOT NULL,
+    created_at INTEGER NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_pending_actions_user_id ON pending_actions(user_id);
+
+-- The single confirmation slot is replaced by pending_actions
+DROP TABLE temp_variables;
+ALTER TABLE users DROP COLUMN confirm_send_event;
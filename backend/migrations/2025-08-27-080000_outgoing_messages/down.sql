@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE user_settings DROP COLUMN send_delay_seconds;
+
+DROP INDEX IF EXISTS idx_outgoing_messages_user_id;
+DROP INDEX IF EXISTS idx_outgoing_messages_status_send_at;
+DROP TABLE IF EXISTS outgoing_messages;
@@ -0,0 +1,19 @@
+-- Your SQL goes here
+-- Confirmed chat messages and email replies wait here for the user's send delay, UNDO cancels them
+CREATE TABLE outgoing_messages (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    action_type TEXT NOT NULL,  -- 'chat_message' or 'email_reply'
+    encrypted_payload TEXT NOT NULL,  -- the confirmed action as json
+    status TEXT NOT NULL,  -- 'queued', 'sending', 'sent', 'cancelled' or 'failed'
+    send_at INTEGER NOT NULL,
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_outgoing_messages_status_send_at ON outgoing_messages(status, send_at);
+CREATE INDEX idx_outgoing_messages_user_id ON outgoing_messages(user_id);
+
+-- Seconds a confirmed message waits before it goes out, 0 sends right away
+ALTER TABLE user_settings ADD COLUMN send_delay_seconds INTEGER NOT NULL DEFAULT 0;
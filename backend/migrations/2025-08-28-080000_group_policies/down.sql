@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS idx_group_policies_user_service_chat;
+DROP TABLE IF EXISTS group_policies;
@@ -0,0 +1,12 @@
+-- Your SQL goes here
+-- When a bridged group chat may notify the user, groups without a row notify like any other chat
+CREATE TABLE group_policies (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    service_type TEXT NOT NULL,  -- 'whatsapp', 'telegram', ...
+    chat_name TEXT NOT NULL,  -- group name without the bridge suffix, '*' for every group of the service
+    policy TEXT NOT NULL,  -- 'mention', 'reply' or 'always'
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE UNIQUE INDEX idx_group_policies_user_service_chat ON group_policies(user_id, service_type, chat_name);
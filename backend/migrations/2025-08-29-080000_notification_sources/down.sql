@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS idx_notification_sources_user_id_created_at;
+DROP TABLE IF EXISTS notification_sources;
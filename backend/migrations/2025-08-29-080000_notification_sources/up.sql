@@ -0,0 +1,12 @@
+-- Your SQL goes here
+-- Where each notification came from, so "r: .." can answer the chat or email thread directly
+CREATE TABLE notification_sources (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    source_type TEXT NOT NULL,  -- 'chat' or 'email'
+    encrypted_payload TEXT NOT NULL,  -- platform and room or email uid as json
+    created_at INTEGER NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_notification_sources_user_id_created_at ON notification_sources(user_id, created_at);
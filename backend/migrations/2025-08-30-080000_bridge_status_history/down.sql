@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS idx_bridge_status_history_created_at;
+DROP INDEX IF EXISTS idx_bridge_status_history_user_bridge;
+DROP TABLE IF EXISTS bridge_status_history;
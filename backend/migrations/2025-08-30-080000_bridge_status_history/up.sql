@@ -0,0 +1,14 @@
+-- Your SQL goes here
+-- Result of every bridge health check, for admins and to count failures in a row
+CREATE TABLE bridge_status_history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    bridge_type TEXT NOT NULL,
+    status TEXT NOT NULL,  -- 'ok', 'resynced', 'failed' or 'disconnected'
+    detail TEXT,  -- what went wrong
+    created_at INTEGER NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_bridge_status_history_user_bridge ON bridge_status_history(user_id, bridge_type, created_at);
+CREATE INDEX idx_bridge_status_history_created_at ON bridge_status_history(created_at);
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER IF EXISTS indexed_messages_fts_delete;
+DROP TABLE IF EXISTS indexed_messages_fts;
+DROP INDEX IF EXISTS idx_indexed_messages_user_platform_sent_at;
+DROP INDEX IF EXISTS idx_indexed_messages_user_platform_key;
+DROP TABLE IF EXISTS indexed_messages;
@@ -0,0 +1,25 @@
+-- Your SQL goes here
+-- Local copy of bridged messages and emails so searching doesn't page through the homeserver
+CREATE TABLE indexed_messages (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    platform TEXT NOT NULL,  -- bridge service name or 'email'
+    message_key TEXT NOT NULL,  -- matrix event id or imap uid
+    encrypted_room_name TEXT NOT NULL,  -- room name with the bridge suffix, or the email subject
+    encrypted_sender TEXT NOT NULL,
+    encrypted_content TEXT NOT NULL,
+    message_type TEXT NOT NULL,  -- 'text', 'image', .. or 'email'
+    sent_at INTEGER NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE UNIQUE INDEX idx_indexed_messages_user_platform_key ON indexed_messages(user_id, platform, message_key);
+CREATE INDEX idx_indexed_messages_user_platform_sent_at ON indexed_messages(user_id, platform, sent_at);
+
+-- Keyed hashes of the words, never the words themselves, rowid is indexed_messages.id
+CREATE VIRTUAL TABLE indexed_messages_fts USING fts5(tokens);
+
+CREATE TRIGGER indexed_messages_fts_delete AFTER DELETE ON indexed_messages
+BEGIN
+    DELETE FROM indexed_messages_fts WHERE rowid = old.id;
+END;
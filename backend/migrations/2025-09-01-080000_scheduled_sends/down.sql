@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS idx_scheduled_sends_user_id;
+DROP INDEX IF EXISTS idx_scheduled_sends_status_send_at;
+DROP TABLE IF EXISTS scheduled_sends;
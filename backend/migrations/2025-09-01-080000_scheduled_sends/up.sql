@@ -0,0 +1,16 @@
+-- Your SQL goes here
+-- Chat messages, emails and texts to the user that go out at a time the user picked
+CREATE TABLE scheduled_sends (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    action_type TEXT NOT NULL,  -- 'chat_message', 'email' or 'sms'
+    encrypted_payload TEXT NOT NULL,  -- the send action as json
+    status TEXT NOT NULL,  -- 'scheduled', 'sending', 'sent', 'cancelled' or 'failed'
+    send_at INTEGER NOT NULL,
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_scheduled_sends_status_send_at ON scheduled_sends(status, send_at);
+CREATE INDEX idx_scheduled_sends_user_id ON scheduled_sends(user_id);
@@ -0,0 +1,12 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS idx_away_replies_user_platform_contact;
+DROP TABLE IF EXISTS away_replies;
+
+ALTER TABLE user_settings DROP COLUMN away_ends_at;
+ALTER TABLE user_settings DROP COLUMN away_starts_at;
+ALTER TABLE user_settings DROP COLUMN away_priority_only;
+ALTER TABLE user_settings DROP COLUMN away_reply_interval_hours;
+ALTER TABLE user_settings DROP COLUMN away_contacts;
+ALTER TABLE user_settings DROP COLUMN away_contact_mode;
+ALTER TABLE user_settings DROP COLUMN encrypted_away_message;
+ALTER TABLE user_settings DROP COLUMN away_enabled;
@@ -0,0 +1,22 @@
+-- Your SQL goes here
+-- Away mode: bridged contacts and email senders get an automatic reply while the user is offline
+ALTER TABLE user_settings ADD COLUMN away_enabled BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE user_settings ADD COLUMN encrypted_away_message TEXT;  -- reply template, {name} and {until} are filled in
+ALTER TABLE user_settings ADD COLUMN away_contact_mode TEXT NOT NULL DEFAULT 'all';  -- 'all', 'allowlist' or 'denylist'
+ALTER TABLE user_settings ADD COLUMN away_contacts TEXT;  -- json list of chat names, senders or email addresses
+ALTER TABLE user_settings ADD COLUMN away_reply_interval_hours INTEGER NOT NULL DEFAULT 24;
+ALTER TABLE user_settings ADD COLUMN away_priority_only BOOLEAN NOT NULL DEFAULT 1;  -- only priority senders notify while away
+ALTER TABLE user_settings ADD COLUMN away_starts_at INTEGER;  -- unix time, NULL starts right away
+ALTER TABLE user_settings ADD COLUMN away_ends_at INTEGER;  -- unix time, NULL lasts until turned off
+
+-- Last automatic reply per contact, for the reply interval
+CREATE TABLE away_replies (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    platform TEXT NOT NULL,  -- 'whatsapp', 'telegram', ... or 'email'
+    contact_key TEXT NOT NULL,  -- room id, or the sender address for email
+    replied_at INTEGER NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE UNIQUE INDEX idx_away_replies_user_platform_contact ON away_replies(user_id, platform, contact_key);
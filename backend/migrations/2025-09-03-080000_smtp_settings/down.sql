@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+alter table imap_connection drop column smtp_server;
+alter table imap_connection drop column smtp_port;
+alter table imap_connection drop column smtp_security;
+alter table imap_connection drop column smtp_username;
+alter table imap_connection drop column encrypted_smtp_password;
@@ -0,0 +1,8 @@
+-- Your SQL goes here
+-- Null smtp_server keeps the old behaviour: the IMAP host with imap -> smtp, STARTTLS on 587
+alter table imap_connection add column smtp_server TEXT;
+alter table imap_connection add column smtp_port INTEGER;
+alter table imap_connection add column smtp_security TEXT;
+-- Only set when sending needs a different login than IMAP
+alter table imap_connection add column smtp_username TEXT;
+alter table imap_connection add column encrypted_smtp_password TEXT;
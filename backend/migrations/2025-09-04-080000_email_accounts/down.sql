@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+update indexed_messages set message_key = substr(message_key, instr(message_key, ':') + 1)
+where platform = 'email';
+alter table priority_senders drop column imap_connection_id;
+alter table processed_emails drop column imap_connection_id;
+alter table imap_connection drop column monitoring_enabled;
+alter table imap_connection drop column label;
@@ -0,0 +1,17 @@
+-- Your SQL goes here
+-- Users can connect several email accounts, told apart by their label
+alter table imap_connection add column label TEXT;
+alter table imap_connection add column monitoring_enabled BOOLEAN NOT NULL DEFAULT 1;
+-- UIDs are only unique within one mailbox
+alter table processed_emails add column imap_connection_id INTEGER;
+update processed_emails set imap_connection_id = (
+    select min(imap_connection.id) from imap_connection where imap_connection.user_id = processed_emails.user_id
+);
+-- Null watches the sender on every email account
+alter table priority_senders add column imap_connection_id INTEGER;
+-- Indexed emails are keyed like the new email ids, "<account id>:<uid>"
+update indexed_messages set message_key = (
+    select min(imap_connection.id) from imap_connection where imap_connection.user_id = indexed_messages.user_id
+) || ':' || message_key
+where platform = 'email' and instr(message_key, ':') = 0
+    and exists (select 1 from imap_connection where imap_connection.user_id = indexed_messages.user_id);
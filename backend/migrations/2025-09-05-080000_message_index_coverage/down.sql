@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS idx_message_index_coverage_user_covered_until;
+DROP TABLE IF EXISTS message_index_coverage;
@@ -0,0 +1,11 @@
+-- Your SQL goes here
+-- Times a user's Matrix sync was running, so every bridged message of the span went through the index
+CREATE TABLE message_index_coverage (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    started_at INTEGER NOT NULL,  -- first successful sync of the span
+    covered_until INTEGER NOT NULL,  -- latest successful sync, moved forward while the sync runs
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_message_index_coverage_user_covered_until ON message_index_coverage(user_id, covered_until);
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+-- When a delivery run took the message, so one stuck in sending after a crash can be found
+alter table outgoing_messages add column claimed_at INTEGER;
@@ -13,27 +13,10 @@ use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::collections::HashMap;
 use chrono::TimeZone;
-use crate::handlers::imap_handlers::{fetch_emails_imap, fetch_single_email_imap};
+use crate::handlers::imap_handlers::fetch_single_email_imap;
 use crate::models::user_models::User;
 
 
-#[derive(Debug, Deserialize)]
-pub struct LocationCallPayload {
-    location: String,
-    units: String,
-}
-
-#[derive(Debug, Deserialize)]
-pub struct GmailFetchPayload {
-    user_id: i32,
-}
-
-#[derive(Debug, Deserialize)]
-pub struct WhatsAppFetchPayload {
-    start_time: String,  // RFC3339 format: "2024-03-16T00:00:00Z"
-    end_time: String,    // RFC3339 format: "2024-03-16T00:00:00Z"
-}
-
 #[derive(Debug, Deserialize)]
 pub struct MessageCallPayload {
     message: String,
@@ -289,6 +272,23 @@ pub async fn fetch_assistant(
                 .collect::<Vec<_>>()
                 .join("\n");
             dynamic_variables.insert("recent_conversation".to_string(), json!(history_string));
+            // Rolling summary of earlier sms and calls so the voice agent knows what's been going on,
+            // only for users who keep their history
+            let conversation_summary = if user_settings.save_context.unwrap_or(0) > 0 {
+                match state.user_repository.get_conversation_summary(user.id) {
+                    Ok(summary) => summary.map(|(summary, _)| summary).unwrap_or_default(),
+                    Err(e) => {
+                        tracing::error!("Failed to fetch conversation summary: {:?}", e);
+                        String::new()
+                    }
+                }
+            } else {
+                String::new()
+            };
+            dynamic_variables.insert("conversation_summary".to_string(), json!(conversation_summary));
+            // No query to rank by at call start, newest memories go in
+            let memories = crate::utils::user_memory::relevant_memories(&state, user.id, "", 20);
+            dynamic_variables.insert("user_memories".to_string(), json!(crate::utils::user_memory::format_memories(&memories)));
             //dynamic_variables.insert("conversation_history".to_string(), json!(history_string));
             let charge_back_threshold= std::env::var("CHARGE_BACK_THRESHOLD")
                 .expect("CHARGE_BACK_THRESHOLD not set")
@@ -320,23 +320,28 @@ pub async fn fetch_assistant(
                 tracing::error!("Failed to log call usage: {}", e);
                 // Continue execution even if logging fails
             }
-            // Fetch recent contacts for whatsapp
-            let whatsapp_contacts = crate::utils::bridge::fetch_recent_bridge_contacts("whatsapp", &state, user.id).await.unwrap_or_else(|e| {
-                tracing::error!("Failed to fetch whatsapp contacts: {}", e);
-                Vec::new()
-            });
-            let whatsapp_names: Vec<String> = whatsapp_contacts.iter().map(|r| r.display_name.clone()).collect();
-            let whatsapp_str = whatsapp_names.join(", ");
-            dynamic_variables.insert("recent_whatsapp_contacts".to_string(), json!(whatsapp_str));
-
-            // Fetch recent contacts for telegram
-            let telegram_contacts = crate::utils::bridge::fetch_recent_bridge_contacts("telegram", &state, user.id).await.unwrap_or_else(|e| {
-                tracing::error!("Failed to fetch telegram contacts: {}", e);
-                Vec::new()
-            });
-            let telegram_names: Vec<String> = telegram_contacts.iter().map(|r| r.display_name.clone()).collect();
-            let telegram_str = telegram_names.join(", ");
-            dynamic_variables.insert("recent_telegram_contacts".to_string(), json!(telegram_str));
+            // Recent contacts of every connected bridge, e.g. recent_slack_contacts. Every service
+            // gets its variable, empty when not connected, so the agent prompt can always refer to it
+            let mut all_contacts = Vec::new();
+            for service in crate::utils::bridge_service::all_services() {
+                let connected = matches!(
+                    state.user_repository.get_bridge(user.id, service.name()),
+                    Ok(Some(bridge)) if bridge.status == "connected"
+                );
+                let names = if connected {
+                    crate::utils::bridge::fetch_recent_bridge_contacts(service.name(), &state, user.id).await.unwrap_or_else(|e| {
+                        tracing::error!("Failed to fetch {} contacts: {}", service.name(), e);
+                        Vec::new()
+                    }).into_iter().map(|room| room.display_name).collect::<Vec<String>>().join(", ")
+                } else {
+                    String::new()
+                };
+                if !names.is_empty() {
+                    all_contacts.push(format!("{}: {}", service.display_name(), names));
+                }
+                dynamic_variables.insert(format!("recent_{}_contacts", service.name()), json!(names));
+            }
+            dynamic_variables.insert("recent_contacts".to_string(), json!(all_contacts.join("; ")));
         },
         Ok(None) => {
             tracing::debug!("No user found for number: {}", caller_number);
@@ -360,263 +365,6 @@ pub async fn fetch_assistant(
     Ok(Json(payload))
 }
 
-#[derive(Deserialize)]
-pub struct WaitingCheckPayload {
-    pub content: String,
-    pub service_type: String,
-    pub noti_type: Option<String>,
-}
-
-pub async fn handle_create_waiting_check_tool_call(
-    State(state): State<Arc<AppState>>,
-    axum::extract::Query(params): axum::extract::Query<HashMap<String, String>>,
-    Json(payload): Json<WaitingCheckPayload>,
-) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
-    tracing::debug!("Received waiting check creation request");
-
-    let user_id = match params.get("user_id").and_then(|id| id.parse::<i32>().ok()) {
-        Some(id) => id,
-        None => {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                Json(json!({
-                    "error": "Invalid or missing user_id parameter"
-                }))
-            ));
-        }
-    };
-    // Verify user exists
-    match state.user_core.find_by_id(user_id) {
-        Ok(Some(_user)) => {
-            let new_check = crate::models::user_models::NewWaitingCheck {
-                user_id: user_id,
-                content: payload.content,
-                service_type: payload.service_type,
-                noti_type: payload.noti_type,
-            };
-
-            match state.user_repository.create_waiting_check(&new_check) {
-                Ok(_) => {
-                    tracing::debug!("Successfully created waiting check for user: {}", 
-                        user_id);
-                    Ok(Json(json!({
-                        "response": "I'll keep an eye out for that and notify you when I find it.",
-                        "status": "success",
-                        "user_id": user_id,
-                    })))
-                },
-                Err(e) => {
-                    error!("Failed to create waiting check: {}", e);
-                    Err((
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(json!({
-                            "error": "Failed to create waiting check",
-                            "details": e.to_string()
-                        }))
-                    ))
-                }
-            }
-        },
-        Ok(None) => {
-            tracing::error!("User not found: {}", user_id);
-            Err((
-                StatusCode::NOT_FOUND,
-                Json(json!({
-                    "error": "User not found"
-                }))
-            ))
-        },
-        Err(e) => {
-            error!("Error fetching user: {}", e);
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({
-                    "error": "Failed to fetch user",
-                    "details": e.to_string()
-                }))
-            ))
-        }
-    }
-}
-
-#[derive(Deserialize)]
-pub struct SetProactiveAgentPayload {
-    pub user_id: i32,
-    pub enabled: bool,
-}
-
-pub async fn handle_update_monitoring_status_tool_call(
-    State(state): State<Arc<AppState>>,
-    Json(payload): Json<SetProactiveAgentPayload>,
-) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
-    tracing::debug!("Received monitoring status update request");
-
-    // Verify user exists
-    match state.user_core.find_by_id(payload.user_id) {
-        Ok(Some(_user)) => {
-            match state.user_core.update_proactive_agent_on(payload.user_id, payload.enabled) {
-                Ok(_) => {
-                    tracing::debug!("Successfully updated monitoring status for user: {}",
-                        payload.user_id);
-                    let status = if payload.enabled { "on" } else { "off" };
-                    Ok(Json(json!({
-                        "response": format!("Monitoring turned {}.", status),
-                        "status": "success",
-                        "user_id": payload.user_id,
-                    })))
-                },
-                Err(e) => {
-                    error!("Failed to update monitoring status: {}", e);
-                    Err((
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(json!({
-                            "error": "Failed to update monitoring status",
-                            "details": e.to_string()
-                        }))
-                    ))
-                }
-            }
-        },
-        Ok(None) => {
-            tracing::error!("User not found: {}", payload.user_id);
-            Err((
-                StatusCode::NOT_FOUND,
-                Json(json!({
-                    "error": "User not found"
-                }))
-            ))
-        },
-        Err(e) => {
-            error!("Error fetching user: {}", e);
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({
-                    "error": "Failed to fetch user",
-                    "details": e.to_string()
-                }))
-            ))
-        }
-    }
-}
-
-pub async fn handle_email_fetch_tool_call(
-    State(state): State<Arc<AppState>>,
-    axum::extract::Query(params): axum::extract::Query<HashMap<String, String>>,
-) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
-    // Extract and parse user_id from query params
-    let user_id = match params.get("user_id").and_then(|id| id.parse::<i32>().ok()) {
-        Some(id) => id,
-        None => {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                Json(json!({
-                    "error": "Invalid or missing user_id parameter"
-                }))
-            ));
-        }
-    };
-    tracing::debug!("Received email fetch request for user: {}", user_id);
-    
-    match crate::handlers::imap_handlers::fetch_emails_imap(&state, user_id, true, Some(10), false, false).await {
-        Ok(emails) => {
-            if emails.is_empty() {
-                return Ok(Json(json!({
-                    "response": "I don't see any recent emails in your inbox.",
-                    "emails": [],
-                    "total_count": 0
-                })));
-            }
-
-            // Format emails for voice response in a more natural way
-            let mut response_text = format!(
-                "I found {} recent emails in your inbox. ", 
-                emails.len()
-            );
-
-            // Group emails by read status
-            let unread_count = emails.iter().filter(|e| !e.is_read).count();
-            if unread_count > 0 {
-                response_text.push_str(&format!(
-                    "{} of them {} unread. ",
-                    unread_count,
-                    if unread_count == 1 { "is" } else { "are" }
-                ));
-            }
-
-            // Add details for each email in a conversational way
-            for (i, email) in emails.iter().enumerate() {
-                let from = email.from.as_deref().unwrap_or("an unknown sender");
-                let subject = email.subject.as_deref().unwrap_or("no subject");
-                let date = email.date_formatted.as_deref().unwrap_or("recently");
-                
-                // Truncate body if too long and clean it up
-                let body = email.body.as_ref()
-                    .map(|b| {
-                        let cleaned = b.replace('\n', " ").replace('\r', " ");
-                        let chars: Vec<char> = cleaned.chars().collect();
-                        if chars.len() > 150 {
-                            let truncated: String = chars.into_iter().take(150).collect();
-                            format!("{}...", truncated)
-                        } else {
-                            cleaned
-                        }
-                    })
-                    .unwrap_or_else(|| "no content".to_string());
-                // Format each email in a more natural way
-                let email_intro = if i == 0 {
-                    "The most recent email is"
-                } else if i == emails.len() - 1 {
-                    "And finally"
-                } else {
-                    "Next"
-                };
-
-                response_text.push_str(&format!(
-                    "{} from {}, sent {}. The subject is '{}'. {}. ",
-                    email_intro,
-                    from,
-                    date,
-                    subject,
-                    if email.is_read {
-                        format!("Here's what it says: {}", body)
-                    } else {
-                        format!("This unread email says: {}", body)
-                    }
-                ));
-            }
-
-            Ok(Json(json!({
-                "response": response_text,
-                "emails": emails.iter().map(|email| {
-                    json!({
-                        "id": email.id,
-                        "subject": email.subject,
-                        "from": email.from,
-                        "from_email": email.from_email,
-                        "date": email.date.map(|dt| dt.to_rfc3339()),
-                        "date_formatted": email.date_formatted,
-                        "body": email.body,
-                        "is_read": email.is_read
-                    })
-                }).collect::<Vec<_>>(),
-                "total_count": emails.len(),
-                "unread_count": unread_count
-            })))
-        },
-        Err(e) => {
-            error!("Failed to fetch emails: {:?}", e);
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({
-                    "error": "Failed to fetch emails",
-                    "details": format!("{:?}", e)
-                }))
-            ))
-        }
-    }
-}
-
-
 
 use base64::Engine as _;
 
@@ -800,1321 +548,34 @@ pub async fn handle_shazam_tool_call(
     })))
 }
 
-pub async fn handle_perplexity_tool_call(
-    State(state): State<Arc<AppState>>,
-    Json(payload): Json<MessageCallPayload>,
-) -> Json<serde_json::Value> {
-
-    let system_prompt = "You are assisting an AI voice calling service. The questions you receive are from voice conversations where users are seeking information or help. Please note: 1. Provide clear, conversational responses that can be easily read aloud 2. Avoid using any markdown, HTML, or other markup languages 3. Keep responses concise but informative 4. Use natural language sentence structure 5. When listing multiple points, use simple numbering (1, 2, 3) or natural language transitions (First... Second... Finally...) 6. Focus on the most relevant information that addresses the user's immediate needs 7. If specific numbers, dates, or proper names are important, spell them out clearly 8. Format numerical data in a way that's easy to read aloud (e.g., twenty-five percent instead of 25%) Your responses will be incorporated into a voice conversation, so clarity and natural flow are essential.";
-    
-    match crate::utils::tool_exec::ask_perplexity(&state, &payload.message, system_prompt).await {
-        Ok(response) => {
-            Json(json!({
-                "response": response
-            }))
-        },
-        Err(e) => {
-            error!("Error getting response from Perplexity: {}", e);
-            Json(json!({
-                "error": "Failed to get response from AI"
-            }))
-        }
-    }
-}
-
-
-#[derive(Debug, Deserialize)]
-pub struct FireCrawlCallPayload {
-    query: String,
-}
-
-pub async fn handle_firecrawl_tool_call(
-    State(state): State<Arc<AppState>>,
-    Json(payload): Json<FireCrawlCallPayload>,
-) -> Json<serde_json::Value> {
-    match crate::utils::tool_exec::handle_firecrawl_search(payload.query, 5).await {
-        Ok(response) => {
-            Json(json!({
-                "response": response
-            }))
-        },
-        Err(e) => {
-            error!("Error getting response from Firecrawl: {}", e);
-            Json(json!({
-                "error": "Failed to get response from AI"
-            }))
-        }
-    }
-}
-
-pub async fn handle_calendar_tool_call(
-    State(state): State<Arc<AppState>>,
-    axum::extract::Query(params): axum::extract::Query<HashMap<String, String>>,
-) -> Json<serde_json::Value> {
-    
-    // Extract required parameters from query
-    let user_id_str = match params.get("user_id") {
-        Some(id) => id,
-        None => {
-
-            return Json(json!({
-                "error": "Missing user_id parameter"
-            }));
-        }
-    };
-
-    let start = match params.get("start") {
-        Some(start) => start,
-        None => {
-
-            return Json(json!({
-                "error": "Missing start parameter"
-            }));
-        }
-    };
-
-    let end = match params.get("end") {
-        Some(end) => end,
-        None => {
-
-            return Json(json!({
-                "error": "Missing end parameter"
-            }));
-        }
-    };
-
-    // Parse user_id from string to i32
-    let user_id = match user_id_str.parse::<i32>() {
-        Ok(id) => id,
-        Err(_) => {
-
-            return Json(json!({
-                "error": "Invalid user ID format"
-            }));
-        }
-    };
-
-    // Call the handler in google_calendar.rs
-    match crate::handlers::google_calendar::handle_calendar_fetching(&state, user_id, start, end).await {
-        Ok(response) => response,
-        Err((_, json_response)) => json_response,
-    }
-}
-
-#[derive(Debug, Deserialize)]
-pub struct TaskCreatePayload {
-    pub title: String,
-    pub description: Option<String>,
-    pub due_time: Option<String>,
-}
-
-pub async fn handle_tasks_creation_tool_call(
-    State(state): State<Arc<AppState>>,
-    axum::extract::Query(params): axum::extract::Query<HashMap<String, String>>,
-    Json(task_payload): axum::extract::Json<TaskCreatePayload>,
+pub async fn make_notification_call(
+    state: &Arc<AppState>,
+    content_type: String,
+    notification_first_message: String,
+    notification_message: String,
+    user_id: String,
+    user_timezone: Option<String>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
-    
-    // Get user_id from query params
-    let user_id_str = match params.get("user_id") {
-        Some(id) => id,
-        None => {
+    // Get user information to check for discount tier
+    let user = match state.user_core.find_by_id(user_id.parse::<i32>().unwrap_or_default()) {
+        Ok(Some(user)) => user,
+        Ok(None) => {
+            error!("User not found for ID: {}", user_id);
             return Err((
-                StatusCode::BAD_REQUEST,
+                StatusCode::NOT_FOUND,
                 Json(json!({
-                    "error": "Missing user_id query parameter"
+                    "error": "User not found",
+                    "details": "Could not find user with provided ID"
                 }))
             ));
         }
-    };
-
-    // Convert String to i32
-    let user_id: i32 = match user_id_str.parse() {
-        Ok(id) => id,
-        Err(_) => {
+        Err(e) => {
+            error!("Error fetching user: {}", e);
             return Err((
-                StatusCode::BAD_REQUEST,
+                StatusCode::INTERNAL_SERVER_ERROR,
                 Json(json!({
-                    "error": "Invalid user_id format, must be an integer"
-                }))
-            ));
-        }
-    };
-
-    // Convert due_time string to DateTime<Utc> if provided
-    let due_time = match task_payload.due_time {
-        Some(time_str) => {
-            match chrono::DateTime::parse_from_rfc3339(&time_str) {
-                Ok(dt) => Some(dt.with_timezone(&chrono::Utc)),
-                Err(_) => {
-                    return Err((
-                        StatusCode::BAD_REQUEST,
-                        Json(json!({
-                            "error": "Invalid due_time format. Please use RFC3339 format."
-                        }))
-                    ));
-                }
-            }
-        },
-        None => None,
-    };
-
-    let task_request = crate::handlers::google_tasks::CreateTaskRequest {
-        title: task_payload.title,
-        description: task_payload.description,
-        due_time,
-    };
-
-    match crate::handlers::google_tasks::create_task(&state, user_id, &task_request).await {
-        Ok(response) => {
-            tracing::debug!("Successfully created task for user: {}", user_id);
-            Ok(response)
-        },
-        Err(e) => {
-            error!("Failed to create task: {:?}", e);
-            Err(e)
-        }
-    }
-}
-
-pub async fn handle_tasks_fetching_tool_call(
-    State(state): State<Arc<AppState>>,
-    axum::extract::Query(params): axum::extract::Query<HashMap<String, String>>,
-) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
-    // Extract and parse user_id from query params
-    let user_id = match params.get("user_id").and_then(|id| id.parse::<i32>().ok()) {
-        Some(id) => id,
-        None => {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                Json(json!({
-                    "error": "Invalid or missing user_id parameter"
-                }))
-            ));
-        }
-    };
-
-    tracing::debug!("Received tasks fetch request for user: {}", user_id);
-
-    match crate::handlers::google_tasks::get_tasks(&state, user_id).await {
-        Ok(response) => {
-            tracing::debug!("Successfully fetched tasks for user: {}", user_id);
-            Ok(response)
-        },
-        Err(e) => {
-            error!("Failed to fetch tasks: {:?}", e);
-            Err(e)
-        }
-    }
-}
-
-#[derive(Debug, Deserialize)]
-pub struct EmailSearchPayload {
-    pub search_term: String,
-    pub search_type: Option<String>, // "sender", "subject", or "all"
-}
-
-
-#[derive(Debug, Deserialize)]
-pub struct ChatSearchPayload {
-    search_term: String,
-}
-
-#[derive(Debug, Deserialize)]
-pub struct ChatConfirmPayload {
-    chat_name: String,
-    message: String,
-}
-
-#[derive(Debug, Deserialize)]
-pub struct CalendarEventConfirmPayload {
-    summary: String,
-    start_time: String,
-    duration_minutes: i32,
-    description: Option<String>,
-    add_notification: Option<bool>,
-}
-
-pub async fn handle_email_search_tool_call(
-    State(state): State<Arc<AppState>>,
-    axum::extract::Query(params): axum::extract::Query<HashMap<String, String>>,
-    Json(payload): Json<EmailSearchPayload>,
-) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
-
-    // Extract user_id from query parameters
-    let user_id = match params.get("user_id").and_then(|id| id.parse::<i32>().ok()) {
-        Some(id) => id,
-        None => {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                Json(json!({
-                    "error": "Missing or invalid user_id"
-                }))
-            ));
-        }
-    };
-
-    // First fetch recent emails with increased limit
-    match fetch_emails_imap(&state, user_id, true, Some(50), false, false).await {
-        Ok(emails) => {
-            let search_term = payload.search_term.to_lowercase();
-            let search_type = payload.search_type.as_deref().unwrap_or("all");
-
-            // Create a structure to hold email with its match score
-            #[derive(Debug)]
-            struct ScoredEmail {
-                email: crate::handlers::imap_handlers::ImapEmailPreview,
-                score: f64,
-                match_type: String,
-                matched_field: String,
-            }
-
-            let mut scored_emails: Vec<ScoredEmail> = Vec::new();
-            let now = chrono::Utc::now().timestamp() as f64;
-
-            for email in emails {
-                let mut best_score = 0.0;
-                let mut best_match_type = String::new();
-                let mut best_matched_field = String::new();
-
-                // Calculate time-based score factor (higher for more recent emails)
-                let time_factor = email.date
-                    .map(|date| {
-                        let age_in_days = (now - date.timestamp() as f64) / (24.0 * 60.0 * 60.0);
-                        // Exponential decay: score drops by half every 7 days, but never below 0.1
-                        (0.5f64.powf(age_in_days / 7.0)).max(0.1)
-                    })
-                    .unwrap_or(0.1); // Default factor for emails without dates
-
-                // Helper closure for scoring
-                let score_field = |field: &Option<String>, field_name: &str| -> Option<(f64, String)> {
-                    field.as_ref().map(|content| {
-                        let content_lower = content.to_lowercase();
-                        
-                        // Exact match
-                        if content_lower == search_term {
-                            return (1.0, "exact".to_string());
-                        }
-                        
-                        // Substring match
-                        if content_lower.contains(&search_term) {
-                            return (0.8, "substring".to_string());
-                        }
-                        
-                        // Similarity match using Jaro-Winkler
-                        let similarity = strsim::jaro_winkler(&content_lower, &search_term);
-                        if similarity >= 0.7 {
-                            return (similarity * 0.6, "similar".to_string());
-                        }
-                        
-                        (0.0, "none".to_string())
-                    })
-                };
-
-                // Score based on search type
-                match search_type {
-                    "sender" => {
-                        if let Some((score, match_type)) = score_field(&email.from, "sender") {
-                            if score > best_score {
-                                best_score = score;
-                                best_match_type = match_type;
-                                best_matched_field = "sender".to_string();
-                            }
-                        }
-                    },
-                    "subject" => {
-                        if let Some((score, match_type)) = score_field(&email.subject, "subject") {
-                            if score > best_score {
-                                best_score = score;
-                                best_match_type = match_type;
-                                best_matched_field = "subject".to_string();
-                            }
-                        }
-                    },
-                    _ => { // "all" or any other value
-                        // Check subject
-                        if let Some((score, match_type)) = score_field(&email.subject, "subject") {
-                            if score > best_score {
-                                best_score = score;
-                                best_match_type = match_type;
-                                best_matched_field = "subject".to_string();
-                            }
-                        }
-                        
-                        // Check sender
-                        if let Some((score, match_type)) = score_field(&email.from, "sender") {
-                            if score > best_score {
-                                best_score = score;
-                                best_match_type = match_type;
-                                best_matched_field = "sender".to_string();
-                            }
-                        }
-                        
-                        // Check body
-                        if let Some((score, match_type)) = score_field(&email.body, "body") {
-                            if score > best_score {
-                                best_score = score;
-                                best_match_type = match_type;
-                                best_matched_field = "body".to_string();
-                            }
-                        }
-                    }
-                }
-
-                // Add to scored emails if there's any match
-                if best_score > 0.0 {
-                    // Combine content match score with time factor
-                    let final_score = best_score * time_factor;
-                    scored_emails.push(ScoredEmail {
-                        email,
-                        score: final_score,
-                        match_type: best_match_type,
-                        matched_field: best_matched_field,
-                    });
-                }
-            }
-
-            // Sort by score (highest first)
-            scored_emails.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
-
-            if scored_emails.is_empty() {
-                return Ok(Json(json!({
-                    "response": format!("No emails found matching '{}'.", payload.search_term),
-                    "found": false
-                })));
-            }
-
-            // Get the best match
-            let best_match = &scored_emails[0];
-            
-            // Fetch the full email content for the best match
-            match fetch_single_email_imap(&state, user_id, &best_match.email.id).await {
-                Ok(full_email) => {
-                    // Format response text in a more natural, voice-friendly way
-                    let match_quality = match best_match.match_type.as_str() {
-                        "exact" => "an exact match",
-                        "substring" => "a matching part",
-                        "similar" => "a similar match",
-                        _ => "a match"
-                    };
-
-                    let from = full_email.from.as_ref().map_or("an unknown sender", String::as_str);
-                    let subject = full_email.subject.as_ref().map_or("no subject", String::as_str);
-                    let body = full_email.body.as_ref()
-                        .map(|b| {
-                            let chars: Vec<char> = b.chars().collect();
-                            if chars.len() > 200 {
-                                let truncated: String = chars.into_iter().take(200).collect();
-                                format!("{}...", truncated)
-                            } else {
-                                b.clone()
-                            }
-                        })
-                        .unwrap_or_else(|| "no content".to_string());
-
-                    let mut response_text = format!(
-                        "I found {} for your search in the {} field. This email is from {}. The subject is: {}. Here's what it says: {}. ",
-                        match_quality,
-                        best_match.matched_field,
-                        from,
-                        subject,
-                        body
-                    );
-
-                    // Add information about additional matches if any
-                    if scored_emails.len() > 1 {
-                        let additional_matches = scored_emails.len() - 1;
-                        
-                        if additional_matches == 1 {
-                            response_text.push_str("I also found one more matching email. ");
-                        } else {
-                            response_text.push_str(&format!("I also found {} more matching emails. ", additional_matches));
-                        }
-
-                        // Add brief info about next few matches in a more conversational way
-                        for (i, scored_email) in scored_emails.iter().skip(1).take(2).enumerate() {
-                            let from = scored_email.email.from.as_ref().map_or("an unknown sender", String::as_str);
-                            let match_desc = match scored_email.match_type.as_str() {
-                                "exact" => "exactly matches",
-                                "substring" => "contains",
-                                "similar" => "is similar to",
-                                _ => "matches"
-                            };
-
-                            if i == 0 {
-                                response_text.push_str(&format!(
-                                    "The next best match is from {}, where your search term {} the {}. ",
-                                    from,
-                                    match_desc,
-                                    scored_email.matched_field
-                                ));
-                            } else {
-                                response_text.push_str(&format!(
-                                    "Another match is from {}, with the search term matching the {}. ",
-                                    from,
-                                    scored_email.matched_field
-                                ));
-                            }
-                        }
-
-                        if additional_matches > 2 {
-                            response_text.push_str(&format!(
-                                "There are {} more matching emails that I haven't described. ",
-                                additional_matches - 2
-                            ));
-                        }
-                    }
-
-                    Ok(Json(json!({
-                        "response": response_text,
-                        "found": true,
-                        "primary_match": {
-                            "email": {
-                                "id": full_email.id,
-                                "subject": full_email.subject,
-                                "from": full_email.from,
-                                "from_email": full_email.from_email,
-                                "date": full_email.date.map(|dt| dt.to_rfc3339()),
-                                "date_formatted": full_email.date_formatted,
-                                "body": full_email.body,
-                                "is_read": full_email.is_read
-                            },
-                            "match_quality": {
-                                "score": best_match.score,
-                                "match_type": best_match.match_type,
-                                "matched_field": best_match.matched_field
-                            }
-                        },
-                        "additional_matches": scored_emails.iter().skip(1).take(4).map(|scored| {
-                            json!({
-                                "id": scored.email.id,
-                                "subject": scored.email.subject,
-                                "from": scored.email.from,
-                                "date_formatted": scored.email.date_formatted,
-                                "match_quality": {
-                                    "score": scored.score,
-                                    "match_type": scored.match_type,
-                                    "matched_field": scored.matched_field
-                                }
-                            })
-                        }).collect::<Vec<_>>(),
-                        "total_matches": scored_emails.len()
-                    })))
-                },
-                Err(e) => {
-                    error!("Failed to fetch full email content: {:?}", e);
-                    Err((
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(json!({
-                            "error": "Failed to fetch full email content",
-                            "details": format!("{:?}", e)
-                        }))
-                    ))
-                }
-            }
-        },
-        Err(e) => {
-            error!("Failed to fetch emails: {:?}", e);
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({
-                    "error": "Failed to fetch emails",
-                    "details": format!("{:?}", e)
-                }))
-            ))
-        }
-    }
-}
-
-pub async fn handle_confirm_send_chat_message(
-    State(state): State<Arc<AppState>>,
-    axum::extract::Query(params): axum::extract::Query<HashMap<String, String>>,
-    Json(payload): Json<ChatConfirmPayload>,
-) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
-    // Extract user_id from query parameters
-    let user_id = match params.get("user_id").and_then(|id| id.parse::<i32>().ok()) {
-        Some(id) => id,
-        None => {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                Json(json!({
-                    "error": "Missing or invalid user_id"
-                }))
-            ));
-        }
-    };
-    // Extract platform from query parameters
-    let platform = match params.get("platform") {
-        Some(p) if p == "telegram" || p == "whatsapp" => p.clone(),
-        _ => {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                Json(json!({
-                    "error": "Missing or invalid platform. Must be 'telegram' or 'whatsapp'."
-                }))
-            ));
-        }
-    };
-    // Get user from database
-    let user = match state.user_core.find_by_id(user_id) {
-        Ok(Some(user)) => user,
-        Ok(None) => {
-            return Err((
-                StatusCode::NOT_FOUND,
-                Json(json!({
-                    "error": "User not found"
-                }))
-            ));
-        }
-        Err(e) => {
-            error!("Error fetching user: {}", e);
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({
-                    "error": "Failed to fetch user"
-                }))
-            ));
-        }
-    };
-    // Get user settings to check confirmation preference
-    let user_settings = match state.user_core.get_user_settings(user_id) {
-        Ok(settings) => settings,
-        Err(e) => {
-            error!("Failed to get user settings: {}", e);
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({
-                    "error": "Failed to get user settings"
-                }))
-            ));
-        }
-    };
-    let capitalized_platform = platform.chars().next().map(|c| c.to_uppercase().collect::<String>()).unwrap_or_default() + &platform[1..];
-    let trim_suffix = if platform == "whatsapp" { " (WA)" } else { " (Telegram)" };
-    // If confirmation is not required, send the message directly
-    if !user_settings.require_confirmation {
-        // First find the room
-        match crate::utils::bridge::fetch_bridge_room_messages(
-            &platform,
-            &state,
-            user_id,
-            &payload.chat_name,
-            Some(1), // Limit to 1 message since we only need the room name
-        ).await {
-            Ok((_, room_name)) => {
-                let clean_room_name = room_name.trim_end_matches(trim_suffix).to_string();
-               
-                // Send the message directly
-                match crate::utils::bridge::send_bridge_message(
-                    &platform,
-                    &state,
-                    user_id,
-                    &clean_room_name,
-                    &payload.message,
-                    None, // No image URL in this case
-                ).await {
-                    Ok(_) => {
-                        let sent_msg = format!("{} message sent to '{}'", capitalized_platform, clean_room_name);
-                        Ok(Json(json!({
-                            "status": "success",
-                            "message": sent_msg,
-                            "room_name": clean_room_name,
-                        })))
-                    }
-                    Err(e) => {
-                        error!("Failed to send {} message: {}", capitalized_platform, e);
-                        Err((
-                            StatusCode::INTERNAL_SERVER_ERROR,
-                            Json(json!({
-                                "error": format!("Failed to send {} message", capitalized_platform),
-                                "details": e.to_string()
-                            }))
-                        ))
-                    }
-                }
-            }
-            Err(e) => {
-                error!("Failed to find {} room: {}", capitalized_platform, e);
-                Err((
-                    StatusCode::NOT_FOUND,
-                    Json(json!({
-                        "error": format!("Failed to find {} room", capitalized_platform),
-                        "details": e.to_string()
-                    }))
-                ))
-            }
-        }
-    } else {
-        // If confirmation is required, continue with the existing flow
-        match crate::utils::bridge::fetch_bridge_room_messages(
-            &platform,
-            &state,
-            user_id,
-            &payload.chat_name,
-            Some(1), // Limit to 1 message since we only need the room name
-        ).await {
-            Ok((_, room_name)) => {
-                // Get the actual room name without the suffix
-                let clean_room_name = room_name.trim_end_matches(trim_suffix).to_string();
-               
-                // Set the temporary variable for the message
-                if let Err(e) = state.user_core.set_temp_variable(
-                    user_id,
-                    Some(&platform),
-                    Some(&clean_room_name),
-                    None,
-                    Some(&payload.message),
-                    None,
-                    None,
-                    None,
-                    None,
-                ) {
-                    tracing::error!("Failed to set temporary variable: {}", e);
-                    return Err((
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(json!({
-                            "error": format!("Failed to prepare {} message", capitalized_platform),
-                            "details": e.to_string()
-                        }))
-                    ));
-                }
-                // Create confirmation message
-                let confirmation_message = format!(
-                    "Send {} to '{}' with content: '{}' (yes-> send, no -> discard) (free reply)",
-                    capitalized_platform,
-                    clean_room_name,
-                    payload.message
-                );
-                // Send the confirmation SMS
-                match crate::api::twilio_utils::send_conversation_message(
-                    &state,
-                    &confirmation_message,
-                    None,
-                    &user,
-                ).await {
-                    Ok(message_sid) => {
-                        // Deduct credits for the confirmation message
-                        if let Err(e) = crate::utils::usage::deduct_user_credits(&state, user_id, "message", None) {
-                            error!("Failed to deduct user credits: {}", e);
-                        }
-                        Ok(Json(json!({
-                            "status": "success",
-                            "message": format!("{} confirmation message sent", capitalized_platform),
-                            "room_name": clean_room_name,
-                            "message_sid": message_sid
-                        })))
-                    }
-                    Err(e) => {
-                        error!("Failed to send confirmation SMS: {}", e);
-                        Err((
-                            StatusCode::INTERNAL_SERVER_ERROR,
-                            Json(json!({
-                                "error": "Failed to send confirmation message",
-                                "details": e.to_string()
-                            }))
-                        ))
-                    }
-                }
-            },
-            Err(e) => {
-                error!("Failed to find {} room: {}", capitalized_platform, e);
-                Err((
-                    StatusCode::NOT_FOUND,
-                    Json(json!({
-                        "error": format!("Failed to find {} room", capitalized_platform),
-                        "details": e.to_string()
-                    }))
-                ))
-            }
-        }
-    }
-}
-
-pub async fn handle_calendar_event_confirm(
-    State(state): State<Arc<AppState>>,
-    axum::extract::Query(params): axum::extract::Query<HashMap<String, String>>,
-    Json(payload): Json<CalendarEventConfirmPayload>,
-) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
-
-    // Extract user_id from query parameters
-    let user_id = match params.get("user_id").and_then(|id| id.parse::<i32>().ok()) {
-        Some(id) => id,
-        None => {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                Json(json!({
-                    "error": "Missing or invalid user_id"
-                }))
-            ));
-        }
-    };
-
-    // Get user from database
-    let user = match state.user_core.find_by_id(user_id) {
-        Ok(Some(user)) => user,
-        Ok(None) => {
-            return Err((
-                StatusCode::NOT_FOUND,
-                Json(json!({
-                    "error": "User not found"
-                }))
-            ));
-        }
-        Err(e) => {
-            error!("Error fetching user: {}", e);
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({
-                    "error": "Failed to fetch user"
-                }))
-            ));
-        }
-    };
-
-    // Get user settings to check confirmation preference
-    let user_settings = match state.user_core.get_user_settings(user_id) {
-        Ok(settings) => settings,
-        Err(e) => {
-            error!("Failed to get user settings: {}", e);
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({
-                    "error": "Failed to get user settings"
-                }))
-            ));
-        }
-    };
-
-    // If confirmation is not required, create the calendar event directly
-    if !user_settings.require_confirmation {
-        // Parse the start time
-        let start_time = match chrono::DateTime::parse_from_rfc3339(&payload.start_time) {
-            Ok(dt) => dt.with_timezone(&chrono::Utc),
-            Err(_) => {
-                return Err((
-                    StatusCode::BAD_REQUEST,
-                    Json(json!({
-                        "error": "Invalid start_time format. Please use RFC3339 format."
-                    }))
-                ));
-            }
-        };
-
-        // Calculate end time
-        let end_time = start_time + chrono::Duration::minutes(payload.duration_minutes as i64);
-
-        // Create the event request
-        let event_request = crate::handlers::google_calendar::CreateEventRequest {
-            summary: payload.summary.clone(),
-            description: payload.description.clone(),
-            start_time,
-            duration_minutes: payload.duration_minutes.clone(),
-            add_notification: payload.add_notification.unwrap_or(false),
-        };
-
-        // Create the event directly
-        match crate::handlers::google_calendar::create_calendar_event(State(state.clone()), crate::handlers::auth_middleware::AuthUser { user_id, is_admin: false }, Json(event_request)).await {
-            Ok(response) => {
-                Ok(Json(json!({
-                    "status": "success",
-                    "message": "Calendar event created successfully",
-                    "event": response.0
-                })))
-            }
-            Err(e) => {
-                error!("Failed to create calendar event: {:?}", e);
-                Err(e)
-            }
-        }
-    } else {
-
-        // Format the confirmation message
-        let confirmation_message = if let Some(ref desc) = payload.description {
-            format!(
-                "Create calendar event: '{}' starting at '{}' for {} minutes with description: '{}' (yes-> send, no -> discard) (free reply)",
-                payload.summary, payload.start_time, payload.duration_minutes, desc
-            )
-        } else {
-            format!(
-                "Create calendar event: '{}' starting at '{}' for {} minutes (yes-> send, no -> discard) (free reply)",
-                payload.summary, payload.start_time, payload.duration_minutes
-            )
-        };
-
-        // Set the temporary variable for calendar event
-        if let Err(e) = state.user_core.set_temp_variable(
-            user_id,
-            Some("calendar"),
-            None,
-            Some(&payload.summary),
-            Some(&payload.description.unwrap_or_default()),
-            Some(&payload.start_time),
-            Some(&payload.duration_minutes.to_string()),
-            None,
-            None,
-        ) {
-            error!("Failed to set temporary variable: {}", e);
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({
-                    "error": "Failed to prepare calendar event",
-                    "details": e.to_string()
-                }))
-            ));
-        }
-
-        // Send the confirmation SMS
-        match crate::api::twilio_utils::send_conversation_message(
-            &state,
-            &confirmation_message,
-            None,
-            &user,
-        ).await {
-            Ok(message_sid) => {
-
-                // Deduct credits for the confirmation message
-                if let Err(e) = crate::utils::usage::deduct_user_credits(&state, user_id, "message", None) {
-                    error!("Failed to deduct user credits: {}", e);
-                }
-
-                Ok(Json(json!({
-                    "status": "success",
-                    "message": "Calendar event confirmation sent",
-                    "event_summary": payload.summary,
-                    "message_sid": message_sid
-                })))
-            }
-            Err(e) => {
-                error!("Failed to send confirmation SMS: {}", e);
-                Err((
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(json!({
-                        "error": "Failed to send confirmation message",
-                        "details": e.to_string()
-                    }))
-                ))
-            }
-        }
-    }
-}
-
-pub async fn handle_search_chat_contacts_tool_call(
-    State(state): State<Arc<AppState>>,
-    axum::extract::Query(params): axum::extract::Query<HashMap<String, String>>,
-    Json(payload): Json<ChatSearchPayload>,
-) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
-    // Extract user_id from query parameters
-    let user_id = match params.get("user_id").and_then(|id| id.parse::<i32>().ok()) {
-        Some(id) => id,
-        None => {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                Json(json!({
-                    "error": "Missing or invalid user_id"
-                }))
-            ));
-        }
-    };
-    // Extract platform from query parameters
-    let platform = match params.get("platform") {
-        Some(p) if p == "telegram" || p == "whatsapp" => p.clone(),
-        _ => {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                Json(json!({
-                    "error": "Missing or invalid platform. Must be 'telegram' or 'whatsapp'."
-                }))
-            ));
-        }
-    };
-    // Search for rooms using the existing utility function
-    match crate::utils::bridge::search_bridge_rooms(&platform, &state, user_id, &payload.search_term).await {
-        Ok(rooms) => {
-            let capitalized_platform = platform.chars().next().map(|c| c.to_uppercase().collect::<String>()).unwrap_or_default() + &platform[1..];
-            if rooms.is_empty() {
-                return Ok(Json(json!({
-                    "response": format!("No {} contacts found matching '{}'.", capitalized_platform, payload.search_term),
-                    "rooms": []
-                })));
-            }
-            // Format rooms for voice response
-            let mut response_text = format!(
-                "Found {} matching {} contacts. ",
-                rooms.len(),
-                capitalized_platform
-            );
-            // Add up to 5 most relevant rooms to the voice response
-            for (i, room) in rooms.iter().take(5).enumerate() {
-                response_text.push_str(&format!(
-                    "Contact {} is {}, last active {}. ",
-                    i + 1,
-                    room.display_name.trim_end_matches(" (WA)").trim_end_matches(" (Telegram)"),
-                    room.last_activity_formatted
-                ));
-            }
-            if rooms.len() > 5 {
-                response_text.push_str(&format!(
-                    "And {} more contacts found. ",
-                    rooms.len() - 5
-                ));
-            }
-            Ok(Json(json!({
-                "response": response_text,
-                "rooms": rooms,
-                "total_count": rooms.len()
-            })))
-        },
-        Err(e) => {
-            error!("Failed to search {} rooms: {}", platform, e);
-            let capitalized_platform = platform.chars().next().map(|c| c.to_uppercase().collect::<String>()).unwrap_or_default() + &platform[1..];
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({
-                    "error": format!("Failed to search {} contacts", capitalized_platform),
-                    "details": e.to_string()
-                }))
-            ))
-        }
-    }
-}
-
-pub async fn handle_fetch_specific_chat_messages_tool_call(
-    State(state): State<Arc<AppState>>,
-    axum::extract::Query(params): axum::extract::Query<HashMap<String, String>>,
-) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
-    tracing::debug!("Starting specific chat message fetch");
-    // Extract user_id from query parameters
-    let user_id = match params.get("user_id").and_then(|id| id.parse::<i32>().ok()) {
-        Some(id) => id,
-        None => {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                Json(json!({
-                    "error": "Missing or invalid user_id"
-                }))
-            ));
-        }
-    };
-    // Extract platform from query parameters
-    let platform = match params.get("platform") {
-        Some(p) if p == "telegram" || p == "whatsapp" => p.clone(),
-        _ => {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                Json(json!({
-                    "error": "Missing or invalid platform. Must be 'telegram' or 'whatsapp'."
-                }))
-            ));
-        }
-    };
-    let chat_room = match params.get("chat_room") {
-        Some(room) => room.clone(),
-        None => {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                Json(json!({
-                    "error": "Missing chat_room parameter"
-                }))
-            ));
-        }
-    };
-    // Fetch messages using the existing utility function
-    match crate::utils::bridge::fetch_bridge_room_messages(&platform, &state, user_id, &chat_room, Some(20)).await {
-        Ok((messages, room_name)) => {
-            let capitalized_platform = platform.chars().next().map(|c| c.to_uppercase().collect::<String>()).unwrap_or_default() + &platform[1..];
-            if messages.is_empty() {
-                return Ok(Json(json!({
-                    "response": format!("No {} messages found in chat room '{}'.", capitalized_platform, chat_room),
-                    "messages": []
-                })));
-            }
-            // Format messages for voice response
-            let mut response_text = format!(
-                "Here are the recent messages from {}: ",
-                room_name.trim_end_matches(" (WA)").trim_end_matches(" (Telegram)")
-            );
-            // Add messages to the voice response
-            for (i, msg) in messages.iter().take(20).enumerate() {
-                response_text.push_str(&format!(
-                    "Message {} from {}, sent on {}: {}. ",
-                    i + 1,
-                    msg.sender_display_name,
-                    msg.formatted_timestamp,
-                    msg.content
-                ));
-            }
-            Ok(Json(json!({
-                "response": response_text,
-                "messages": messages,
-                "room_name": room_name,
-                "total_count": messages.len()
-            })))
-        },
-        Err(e) => {
-            error!("Failed to fetch {} messages: {}", platform, e);
-            let capitalized_platform = platform.chars().next().map(|c| c.to_uppercase().collect::<String>()).unwrap_or_default() + &platform[1..];
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({
-                    "error": format!("Failed to fetch {} messages", capitalized_platform),
-                    "details": e.to_string()
-                }))
-            ))
-        }
-    }
-}
-
-pub async fn handle_fetch_recent_messages_tool_call(
-    State(state): State<Arc<AppState>>,
-    axum::extract::Query(params): axum::extract::Query<HashMap<String, String>>,
-) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
-    tracing::debug!("Starting chat message fetch with time range");
-    // Extract user_id from query parameters
-    let user_id = match params.get("user_id").and_then(|id| id.parse::<i32>().ok()) {
-        Some(id) => id,
-        None => {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                Json(json!({
-                    "error": "Missing or invalid user_id"
-                }))
-            ));
-        }
-    };
-    // Extract platform from query parameters
-    let platform = match params.get("platform") {
-        Some(p) if p == "telegram" || p == "whatsapp" => p.clone(),
-        _ => {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                Json(json!({
-                    "error": "Missing or invalid platform. Must be 'telegram' or 'whatsapp'."
-                }))
-            ));
-        }
-    };
-    // Set start_time to 2 days ago
-    let start_timestamp = (chrono::Utc::now() - chrono::Duration::days(1)).timestamp();
-    // Fetch messages using the existing utility function
-    match crate::utils::bridge::fetch_bridge_messages(&platform, &state, user_id, start_timestamp, false).await {
-        Ok(messages) => {
-            if messages.is_empty() {
-                let capitalized_platform = platform.chars().next().map(|c| c.to_uppercase().collect::<String>()).unwrap_or_default() + &platform[1..];
-                return Ok(Json(json!({
-                    "response": format!("No {} messages found for the specified time range.", capitalized_platform),
-                    "messages": []
-                })));
-            }
-            // Format messages for voice response
-            let capitalized_platform = platform.chars().next().map(|c| c.to_uppercase().collect::<String>()).unwrap_or_default() + &platform[1..];
-            let mut response_text = format!(
-                "Found {} {} messages. Here are the highlights: ",
-                messages.len(),
-                capitalized_platform
-            );
-            // Add up to 5 most recent messages to the voice response
-            for (i, msg) in messages.iter().take(20).enumerate() {
-                response_text.push_str(&format!(
-                    "Message {} in chat {}, sent on {}: {}. ",
-                    i + 1,
-                    msg.room_name,
-                    msg.formatted_timestamp,
-                    msg.content
-                ));
-            }
-            if messages.len() > 20 {
-                response_text.push_str(&format!(
-                    "And {} more messages. ",
-                    messages.len() - 20
-                ));
-            }
-            Ok(Json(json!({
-                "response": response_text,
-                "messages": messages,
-                "total_count": messages.len()
-            })))
-        },
-        Err(e) => {
-            error!("Failed to fetch {} messages: {}", platform, e);
-            let capitalized_platform = platform.chars().next().map(|c| c.to_uppercase().collect::<String>()).unwrap_or_default() + &platform[1..];
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({
-                    "error": format!("Failed to fetch {} messages", capitalized_platform),
-                    "details": e.to_string()
-                }))
-            ))
-        }
-    }
-}
-
-#[derive(Debug, Deserialize)]
-pub struct EmailResponsePayload {
-    pub email_id: String,
-    pub response_text: String,
-}
-// this is not used at the moment since it didn't work and not a priority rn
-pub async fn handle_email_response_tool_call(
-    State(state): State<Arc<AppState>>,
-    axum::extract::Query(params): axum::extract::Query<HashMap<String, String>>,
-    Json(payload): Json<EmailResponsePayload>,
-) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
-    tracing::debug!("Starting email response confirmation for email ID: {}", payload.email_id);
-
-    // Extract user_id from query parameters
-    let user_id = match params.get("user_id").and_then(|id| id.parse::<i32>().ok()) {
-        Some(id) => id,
-        None => {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                Json(json!({
-                    "error": "Missing or invalid user_id"
-                }))
-            ));
-        }
-    };
-
-    // Get user from database
-    let user = match state.user_core.find_by_id(user_id) {
-        Ok(Some(user)) => user,
-        Ok(None) => {
-            return Err((
-                StatusCode::NOT_FOUND,
-                Json(json!({
-                    "error": "User not found"
-                }))
-            ));
-        }
-        Err(e) => {
-            error!("Error fetching user: {}", e);
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({
-                    "error": "Failed to fetch user"
-                }))
-            ));
-        }
-    };
-
-    // Verify user has sufficient credits for email response
-    if let Err(msg) = crate::utils::usage::check_user_credits(&state, &user, "message", None).await {
-        return Err((
-            StatusCode::FORBIDDEN,
-            Json(json!({
-                "error": "Insufficient credits",
-                "message": msg
-            }))
-        ));
-    }
-
-    // Fetch the email details to include in confirmation message
-    match crate::handlers::imap_handlers::fetch_single_email_imap(&state, user_id, &payload.email_id).await {
-        Ok(email) => {
-            let subject = email.subject.unwrap_or_else(|| "No subject".to_string());
-
-            // Set the temporary variable for email response
-            if let Err(e) = state.user_core.set_temp_variable(
-                user_id,
-                Some("email"),
-                None,
-                Some(&subject),
-                Some(&payload.response_text),
-                None,
-                None,
-                Some(&payload.email_id),
-                None,
-            ) {
-                tracing::error!("Failed to set temporary variable: {}", e);
-                return Err((
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(json!({
-                        "error": "Failed to prepare email response",
-                        "details": e.to_string()
-                    }))
-                ));
-            }
-
-            // Create confirmation message
-            let confirmation_message = format!(
-                "Send email response '{}' regarding '{}' (yes-> send, no -> discard) (free reply)",
-                payload.response_text,
-                subject
-            );
-
-            // Send the confirmation SMS
-            match crate::api::twilio_utils::send_conversation_message(
-                &state,
-                &confirmation_message,
-                None,
-                &user,
-            ).await {
-                Ok(message_sid) => {
-                    // we should add here the call to save the message to history then if this is used
-                    // Deduct credits for the confirmation message
-                    if let Err(e) = crate::utils::usage::deduct_user_credits(&state, user_id, "message", None) {
-                        tracing::error!("Failed to deduct user credits: {}", e);
-                        // Continue execution even if credit deduction fails
-                    }
-                    
-                    Ok(Json(json!({
-                        "status": "success",
-                        "message": "Email response confirmation sent",
-                        "email_id": payload.email_id,
-                        "message_sid": message_sid
-                    })))
-                }
-                Err(e) => {
-                    error!("Failed to send confirmation SMS: {}", e);
-                    Err((
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(json!({
-                            "error": "Failed to send confirmation message",
-                            "details": e.to_string()
-                        }))
-                    ))
-                }
-            }
-        },
-        Err(e) => {
-            error!("Failed to fetch email details: {:?}", e);
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({
-                    "error": "Failed to fetch email details",
-                    "details": format!("{:?}", e)
-                }))
-            ))
-        }
-    }
-}
-
-pub async fn make_notification_call(
-    state: &Arc<AppState>,
-    content_type: String,
-    notification_first_message: String,
-    notification_message: String,
-    user_id: String,
-    user_timezone: Option<String>,
-) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
-    // Get user information to check for discount tier
-    let user = match state.user_core.find_by_id(user_id.parse::<i32>().unwrap_or_default()) {
-        Ok(Some(user)) => user,
-        Ok(None) => {
-            error!("User not found for ID: {}", user_id);
-            return Err((
-                StatusCode::NOT_FOUND,
-                Json(json!({
-                    "error": "User not found",
-                    "details": "Could not find user with provided ID"
-                }))
-            ));
-        }
-        Err(e) => {
-            error!("Error fetching user: {}", e);
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({
-                    "error": "Database error",
-                    "details": e.to_string()
+                    "error": "Database error",
+                    "details": e.to_string()
                 }))
             ));
         }
@@ -2336,77 +797,143 @@ pub async fn make_notification_call(
     })))
 }
 
-pub async fn handle_weather_tool_call(
-    State(state): State<Arc<AppState>>,
-    axum::extract::Query(params): axum::extract::Query<HashMap<String, String>>,
-    Json(payload): Json<LocationCallPayload>,
-) -> Json<serde_json::Value> {
+use crate::tool_call_utils::registry::{Channel, ToolContext, ToolOutput, ToolRegistry};
+
+/// Paths the voice tools had before they came from the registry, with the argument names
+/// that changed. Tool configs in ElevenLabs still point at these.
+const LEGACY_TOOL_PATHS: &[(&str, &str, &[(&str, &str)])] = &[
+    ("weather", "get_weather", &[]),
+    ("perplexity", "ask_perplexity", &[("message", "query")]),
+    ("firecrawl", "search_firecrawl", &[]),
+    ("directions", "get_directions", &[]),
+    ("calendar", "fetch_calendar_events", &[]),
+    ("calendar/confirm", "create_calendar_event", &[]),
+    ("email", "fetch_emails", &[]),
+    ("email/specific", "fetch_specific_email", &[("search_term", "query")]),
+    ("email/respond-confirm", "respond_to_email", &[]),
+    ("waiting_check", "create_waiting_check", &[]),
+    ("monitoring-status", "update_monitoring_status", &[]),
+    ("tasks", "fetch_tasks", &[]),
+    ("tasks/create", "create_task", &[]),
+    ("fetch-recent-messages", "fetch_recent_messages", &[]),
+    ("fetch-chat-messages", "fetch_chat_messages", &[("chat_room", "chat_name")]),
+    ("search-chat-contacts", "search_chat_contacts", &[]),
+    ("send-chat-message", "send_chat_message", &[]),
+];
+
+/// One `/api/call/{tool}` route per registry tool available on voice, plus the legacy paths.
+/// GET and POST both work, arguments come in the json body and/or the query, user_id in the query.
+pub fn tool_call_routes(registry: &ToolRegistry) -> axum::Router<Arc<AppState>> {
+    let mut router = axum::Router::new();
+    let names = registry.names(Channel::Voice);
+    let legacy = LEGACY_TOOL_PATHS
+        .iter()
+        .filter(|(_, name, _)| names.contains(name))
+        .map(|(path, name, renames)| (*path, *name, *renames));
+    for (path, name, renames) in names.iter().map(|name| (*name, *name, &[][..])).chain(legacy) {
+        let handler = move |state: State<Arc<AppState>>,
+                            params: axum::extract::Query<HashMap<String, String>>,
+                            body: axum::body::Bytes| handle_registry_tool_call(state, params, body, name, renames);
+        router = router.route(
+            &format!("/api/call/{}", path),
+            axum::routing::get(handler.clone()).post(handler),
+        );
+    }
+    router
+}
 
-    // Extract user_id from query parameters
-    let user_id = match params.get("user_id").and_then(|id| id.parse::<i32>().ok()) {
-        Some(id) => id,
-        None => {
-            return Json(json!({
-                "error": "Missing or invalid user_id",
-            }));
+// The tool arguments from the json body with the query parameters (other than user_id) added,
+// the body wins when both have one. Query values stay strings.
+fn merge_tool_args(body: &[u8], params: &HashMap<String, String>, renames: &[(&str, &str)]) -> String {
+    let rename = |key: &str| {
+        renames.iter().find(|(old, _)| *old == key).map(|(_, new)| new.to_string()).unwrap_or_else(|| key.to_string())
+    };
+    let mut args = if body.iter().all(|b| b.is_ascii_whitespace()) {
+        serde_json::Map::new()
+    } else {
+        match serde_json::from_slice::<serde_json::Value>(body) {
+            Ok(serde_json::Value::Object(map)) => map,
+            // Let the registry report it
+            _ => return String::from_utf8_lossy(body).into_owned(),
         }
     };
-    
-    match crate::utils::tool_exec::get_weather(&state, &payload.location, &payload.units, user_id).await {
-        Ok(weather_info) => {
-            Json(json!({
-                "response": weather_info
-            }))
-        },
-        Err(e) => {
-            error!("Error getting weather information: {}", e);
-            Json(json!({
-                "error": "Failed to get weather information",
-                "details": e.to_string()
-            }))
+    args = args.into_iter().map(|(key, value)| (rename(&key), value)).collect();
+    for (key, value) in params {
+        if key != "user_id" {
+            args.entry(rename(key)).or_insert_with(|| serde_json::Value::String(value.clone()));
         }
     }
+    serde_json::Value::Object(args).to_string()
 }
 
-
-#[derive(Deserialize)]
-pub struct DirectionsCallPayload {
-    pub start_address: String,
-    pub end_address: String,
-    pub mode: Option<String>,
-}
-
-pub async fn handle_directions_tool_call(
-    State(_state): State<Arc<AppState>>,
+pub async fn handle_registry_tool_call(
+    State(state): State<Arc<AppState>>,
     axum::extract::Query(params): axum::extract::Query<HashMap<String, String>>,
-    Json(payload): Json<DirectionsCallPayload>,
-) -> Json<serde_json::Value> {
-    // Extract user_id from query parameters
+    body: axum::body::Bytes,
+    tool_name: &'static str,
+    renames: &'static [(&'static str, &'static str)],
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    tracing::debug!("Received voice tool call: {}", tool_name);
+
     let user_id = match params.get("user_id").and_then(|id| id.parse::<i32>().ok()) {
         Some(id) => id,
         None => {
-            return Json(json!({
-                "error": "Missing or invalid user_id",
-            }));
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(json!({
+                    "error": "Missing or invalid user_id"
+                }))
+            ));
         }
     };
 
-    match crate::tool_call_utils::internet::handle_directions_tool(
-        payload.start_address,
-        payload.end_address,
-        payload.mode,
-    ).await {
-        Ok(directions_info) => {
-            Json(json!({
-                "response": directions_info
-            }))
-        },
+    let user = match state.user_core.find_by_id(user_id) {
+        Ok(Some(user)) => user,
+        Ok(None) => {
+            return Err((
+                StatusCode::NOT_FOUND,
+                Json(json!({
+                    "error": "User not found"
+                }))
+            ));
+        }
         Err(e) => {
-            error!("Error getting directions information: {}", e);
+            error!("Error fetching user: {}", e);
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({
+                    "error": "Failed to fetch user"
+                }))
+            ));
+        }
+    };
+
+    let user_given_info = state.user_core.get_user_info(user_id)
+        .ok()
+        .and_then(|info| info.info)
+        .unwrap_or_default();
+
+    let args = merge_tool_args(&body, &params, renames);
+    let ctx = ToolContext {
+        state: &state,
+        user: &user,
+        channel: Channel::Voice,
+        image_url: None,
+        media_url: None,
+        user_given_info: &user_given_info,
+    };
+
+    match state.tool_registry.execute(tool_name, &ctx, &args).await {
+        Some(ToolOutput::Answer(response)) | Some(ToolOutput::Handled(response)) => {
+            Ok(Json(json!({
+                "response": response
+            })))
+        }
+        None => Err((
+            StatusCode::BAD_REQUEST,
             Json(json!({
-                "error": "Failed to get directions information",
-                "details": e.to_string()
+                "error": format!("Invalid arguments for {}", tool_name)
             }))
-        }
+        )),
     }
 }
@@ -8,6 +8,7 @@ use axum::{
 use axum::middleware;
 use std::sync::Arc;
 use crate::AppState;
+use crate::repositories::webhook_events::WebhookClaim;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 
@@ -197,6 +198,52 @@ pub async fn elevenlabs_webhook(
     };
 
     tracing::info!("Successfully parsed webhook payload: {:?}", payload);
+
+    // ElevenLabs retries deliveries it didn't get a 2xx for, make sure a call is only billed once
+    let event_id = format!("{}:{}", payload.type_field, payload.data.conversation_id);
+    match state.user_repository.claim_webhook_event("elevenlabs", &event_id) {
+        Ok(WebhookClaim::New) => {}
+        Ok(WebhookClaim::InProgress) => {
+            tracing::info!("Duplicate elevenlabs delivery of {} while still processing", event_id);
+            return Err((
+                StatusCode::CONFLICT,
+                Json(json!({
+                    "error": "Event is already being processed"
+                }))
+            ));
+        }
+        Ok(WebhookClaim::Done { response_status, response_body }) => {
+            tracing::info!("Duplicate elevenlabs delivery of {}, returning original response", event_id);
+            let status = StatusCode::from_u16(response_status as u16).unwrap_or(StatusCode::OK);
+            let body: Value = serde_json::from_str(&response_body).unwrap_or(json!({"status": "received"}));
+            return if status.is_success() { Ok(Json(body)) } else { Err((status, Json(body))) };
+        }
+        Err(e) => error!("Failed to check elevenlabs event {} for duplicates: {}", event_id, e),
+    }
+
+    let result = process_call_event(&state, payload).await;
+
+    // Transient failures are released so the retry gets processed, anything else is final
+    let (status, body) = match &result {
+        Ok(Json(body)) => (StatusCode::OK, body),
+        Err((status, Json(body))) => (*status, body),
+    };
+    let stored = if status.is_server_error() {
+        state.user_repository.release_webhook_event("elevenlabs", &event_id)
+    } else {
+        state.user_repository.complete_webhook_event("elevenlabs", &event_id, status.as_u16() as i32, &body.to_string())
+    };
+    if let Err(e) = stored {
+        error!("Failed to record elevenlabs event {}: {}", event_id, e);
+    }
+
+    result
+}
+
+async fn process_call_event(
+    state: &Arc<AppState>,
+    payload: WebhookPayload,
+) -> Result<Json<Value>, (StatusCode, Json<serde_json::Value>)> {
     println!("Type: {}", payload.type_field);
     let conversation_id = payload.data.conversation_id;
     println!("Conversation ID: {}", conversation_id);
@@ -262,7 +309,7 @@ pub async fn elevenlabs_webhook(
     match state.user_repository.get_ongoing_usage(user_id) {
         Ok(Some(usage)) => {
             // Handle the ongoing usage log
-            if let Err(e) = crate::utils::usage::deduct_user_credits(&state, user.id, "voice", Some(call_duration_secs)) {
+            if let Err(e) = crate::utils::usage::deduct_user_credits(state, user.id, "voice", Some(call_duration_secs)) {
                 eprintln!("Failed to deduct user credits: {}", e);
                 return Err((
                     StatusCode::INTERNAL_SERVER_ERROR,
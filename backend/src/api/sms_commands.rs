@@ -0,0 +1,566 @@
+use std::sync::Arc;
+
+use axum::http::StatusCode;
+
+use crate::api::twilio_sms::{TwilioResponse, TwilioWebhookPayload};
+use crate::models::user_models::User;
+use crate::utils::bridge_service::{BridgeService, LoginArtifact};
+use crate::AppState;
+
+const DIGEST_NOW_HOURS: u32 = 12;
+const MAX_PAUSE_SECONDS: i64 = 30 * 24 * 3600;
+// Bridge login QR codes stop working after a few minutes anyway
+const QR_CODE_LIFETIME: std::time::Duration = std::time::Duration::from_secs(10 * 60);
+
+/// Fixed commands answered without the model. These are not billed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SmsCommand {
+    Status,
+    Credits,
+    Pause(Option<i64>), // seconds, None pauses until RESUME
+    Resume,
+    DigestNow,
+    Help,
+    Stop,
+    Start,
+    Remember(String), // "remember that .."
+    Forget(String),   // "forget that .."
+    Undo,             // cancels the message waiting out the send delay
+    Reconnect,        // logs bridges marked disconnected by the health check in again
+    Connect(Option<String>), // bridge service name, None asks which one
+    Scheduled,               // lists messages scheduled for later
+    Unschedule(Option<i32>), // cancels a scheduled message, None lists them
+    Away(Option<i64>),       // seconds, None is away until BACK
+    Back,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Keyword {
+    Status,
+    Credits,
+    Pause,
+    Resume,
+    DigestNow,
+    Help,
+    Stop,
+    Start,
+    Undo,
+    Reconnect,
+    Connect,
+    Scheduled,
+    Unschedule,
+    Away,
+    Back,
+}
+
+// Prefixes taking free text after them, English plus the agent_language ones.
+// Plain "forget .." is not here, that still means answering without history.
+fn memory_prefixes(language: &str) -> (Vec<&'static str>, Vec<&'static str>) {
+    let mut remember = vec!["remember that", "remember:"];
+    let mut forget = vec!["forget that"];
+    match language {
+        "fi" => {
+            remember.extend(["muista että", "muista:"]);
+            forget.extend(["unohda että"]);
+        }
+        "de" => {
+            remember.extend(["merk dir, dass", "merk dir dass", "merke dir, dass", "merke dir dass"]);
+            forget.extend(["vergiss, dass", "vergiss dass"]);
+        }
+        _ => {}
+    }
+    (remember, forget)
+}
+
+// Rest of `body` after `prefix`, matched case-insensitively
+fn strip_prefix_ci(body: &str, prefix: &str) -> Option<String> {
+    let lower = body.to_lowercase();
+    if !lower.starts_with(prefix) {
+        return None;
+    }
+    let rest: String = body.chars().skip(prefix.chars().count()).collect();
+    let rest = rest.trim().trim_start_matches(|c: char| c == ':' || c == ',').trim().to_string();
+    if rest.is_empty() { None } else { Some(rest) }
+}
+
+// English always works, these are accepted on top of it per agent_language
+fn localized_keywords(language: &str) -> &'static [(&'static str, Keyword)] {
+    match language {
+        "fi" => &[
+            ("TILA", Keyword::Status),
+            ("SALDO", Keyword::Credits),
+            ("KREDIITIT", Keyword::Credits),
+            ("TAUKO", Keyword::Pause),
+            ("JATKA", Keyword::Resume),
+            ("KOOSTE NYT", Keyword::DigestNow),
+            ("APUA", Keyword::Help),
+            ("OHJE", Keyword::Help),
+            ("LOPETA", Keyword::Stop),
+            ("ALOITA", Keyword::Start),
+            ("KUMOA", Keyword::Undo),
+            ("PERUUTA", Keyword::Undo),
+            ("YHDISTÄ UUDELLEEN", Keyword::Reconnect),
+            ("YHDISTÄ", Keyword::Connect),
+            ("AJASTETUT", Keyword::Scheduled),
+            ("POISSA", Keyword::Away),
+            ("TAKAISIN", Keyword::Back),
+        ],
+        "de" => &[
+            ("GUTHABEN", Keyword::Credits),
+            ("FORTSETZEN", Keyword::Resume),
+            ("ZUSAMMENFASSUNG JETZT", Keyword::DigestNow),
+            ("HILFE", Keyword::Help),
+            ("STOPP", Keyword::Stop),
+            ("STARTEN", Keyword::Start),
+            ("RÜCKGÄNGIG", Keyword::Undo),
+            ("NEU VERBINDEN", Keyword::Reconnect),
+            ("VERBINDEN", Keyword::Connect),
+            ("GEPLANT", Keyword::Scheduled),
+            ("ABWESEND", Keyword::Away),
+            ("ZURÜCK", Keyword::Back),
+        ],
+        _ => &[],
+    }
+}
+
+const ENGLISH_KEYWORDS: &[(&str, Keyword)] = &[
+    ("STATUS", Keyword::Status),
+    ("CREDITS", Keyword::Credits),
+    ("PAUSE", Keyword::Pause),
+    ("RESUME", Keyword::Resume),
+    ("DIGEST NOW", Keyword::DigestNow),
+    ("HELP", Keyword::Help),
+    // standard carrier opt-out and opt-in words
+    ("STOP", Keyword::Stop),
+    ("STOPALL", Keyword::Stop),
+    ("UNSUBSCRIBE", Keyword::Stop),
+    ("CANCEL", Keyword::Stop),
+    ("END", Keyword::Stop),
+    ("QUIT", Keyword::Stop),
+    ("START", Keyword::Start),
+    ("UNSTOP", Keyword::Start),
+    ("UNDO", Keyword::Undo),
+    ("RECONNECT", Keyword::Reconnect),
+    ("CONNECT", Keyword::Connect),
+    ("SCHEDULED", Keyword::Scheduled),
+    ("UNSCHEDULE", Keyword::Unschedule),
+    ("AWAY", Keyword::Away),
+    ("BACK", Keyword::Back),
+];
+
+impl SmsCommand {
+    /// Returns the command if the whole message is one (or starts with remember/forget that),
+    /// anything else goes to the agent.
+    pub fn parse(body: &str, language: &str) -> Option<Self> {
+        let (remember_prefixes, forget_prefixes) = memory_prefixes(language);
+        let trimmed = body.trim();
+        if let Some(rest) = remember_prefixes.iter().find_map(|p| strip_prefix_ci(trimmed, p)) {
+            return Some(SmsCommand::Remember(rest));
+        }
+        if let Some(rest) = forget_prefixes.iter().find_map(|p| strip_prefix_ci(trimmed, p)) {
+            return Some(SmsCommand::Forget(rest));
+        }
+
+        let normalized = body
+            .trim()
+            .trim_end_matches(|c: char| c == '.' || c == '!')
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_uppercase();
+
+        let keywords = ENGLISH_KEYWORDS.iter().chain(localized_keywords(language).iter());
+        for (word, keyword) in keywords {
+            if normalized == *word {
+                return Some(match keyword {
+                    Keyword::Status => SmsCommand::Status,
+                    Keyword::Credits => SmsCommand::Credits,
+                    Keyword::Pause => SmsCommand::Pause(None),
+                    Keyword::Resume => SmsCommand::Resume,
+                    Keyword::DigestNow => SmsCommand::DigestNow,
+                    Keyword::Help => SmsCommand::Help,
+                    Keyword::Stop => SmsCommand::Stop,
+                    Keyword::Start => SmsCommand::Start,
+                    Keyword::Undo => SmsCommand::Undo,
+                    Keyword::Reconnect => SmsCommand::Reconnect,
+                    Keyword::Connect => SmsCommand::Connect(None),
+                    Keyword::Scheduled => SmsCommand::Scheduled,
+                    Keyword::Unschedule => SmsCommand::Unschedule(None),
+                    Keyword::Away => SmsCommand::Away(None),
+                    Keyword::Back => SmsCommand::Back,
+                });
+            }
+            // PAUSE takes an optional duration like "PAUSE 2h"
+            if *keyword == Keyword::Pause {
+                if let Some(rest) = normalized.strip_prefix(&format!("{} ", word)) {
+                    return parse_duration(rest).map(|secs| SmsCommand::Pause(Some(secs)));
+                }
+            }
+            // and so does AWAY, "AWAY 3d"
+            if *keyword == Keyword::Away {
+                if let Some(rest) = normalized.strip_prefix(&format!("{} ", word)) {
+                    return parse_duration(rest).map(|secs| SmsCommand::Away(Some(secs)));
+                }
+            }
+            // CONNECT takes the service, "CONNECT WHATSAPP". Anything else after it goes to the agent.
+            if *keyword == Keyword::Connect {
+                if let Some(rest) = normalized.strip_prefix(&format!("{} ", word)) {
+                    return crate::utils::bridge_service::service_for(rest)
+                        .map(|service| SmsCommand::Connect(Some(service.name().to_string())));
+                }
+            }
+            // UNSCHEDULE takes the number from the listing, "UNSCHEDULE 4"
+            if *keyword == Keyword::Unschedule {
+                if let Some(rest) = normalized.strip_prefix(&format!("{} ", word)) {
+                    return rest.trim_start_matches('#').parse::<i32>().ok().map(|id| SmsCommand::Unschedule(Some(id)));
+                }
+            }
+        }
+        None
+    }
+
+    /// CANCEL is also a carrier opt-out word. While a message is in the undo window it means
+    /// UNDO, and while actions wait for confirmation it is left for the confirmation reply.
+    pub fn resolve_cancel(self, state: &Arc<AppState>, user: &User, body: &str) -> Option<Self> {
+        let is_cancel = body.trim().trim_end_matches(|c: char| c == '.' || c == '!').eq_ignore_ascii_case("cancel");
+        if self != SmsCommand::Stop || !is_cancel {
+            return Some(self);
+        }
+        if state.user_repository.has_queued_outgoing_messages(user.id).unwrap_or(false) {
+            return Some(SmsCommand::Undo);
+        }
+        if state.user_repository.get_pending_actions(user.id).map(|a| !a.is_empty()).unwrap_or(false) {
+            return None;
+        }
+        Some(self)
+    }
+}
+
+// "2h", "2 h", "30min", "1d", also fi/de units. Returns seconds.
+fn parse_duration(input: &str) -> Option<i64> {
+    let compact: String = input.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_lowercase();
+    let split_at = compact.find(|c: char| !c.is_ascii_digit())?;
+    let (amount, unit) = compact.split_at(split_at);
+    let amount: i64 = amount.parse().ok()?;
+
+    let unit_seconds = match unit {
+        "m" | "min" | "mins" | "minute" | "minutes" | "minuuttia" | "minuten" => 60,
+        "h" | "hr" | "hrs" | "hour" | "hours" | "t" | "tunti" | "tuntia" | "std" | "stunde" | "stunden" => 3600,
+        "d" | "day" | "days" | "pv" | "päivä" | "päivää" | "tag" | "tage" => 24 * 3600,
+        _ => return None,
+    };
+    let seconds = amount.checked_mul(unit_seconds)?;
+    if seconds <= 0 || seconds > MAX_PAUSE_SECONDS {
+        return None;
+    }
+    Some(seconds)
+}
+
+fn format_duration(seconds: i64) -> String {
+    let minutes = (seconds + 59) / 60;
+    match (minutes / 60, minutes % 60) {
+        (0, m) => format!("{}min", m),
+        (h, 0) => format!("{}h", h),
+        (h, m) => format!("{}h {}min", h, m),
+    }
+}
+
+fn help_text(language: &str) -> String {
+    // show the user's own words where they have them
+    let name = |keyword: Keyword, english: &str| {
+        localized_keywords(language)
+            .iter()
+            .find(|(_, k)| *k == keyword)
+            .map(|(word, _)| word.to_string())
+            .unwrap_or_else(|| english.to_string())
+    };
+    format!(
+        "Commands: {} (account overview), {} (balance), {} 2h / {} (notifications), {} (digest), {} (stop a message you just sent), r: .. / reply to 2: .. (answer a notification), {} WHATSAPP / SIGNAL (link a chat app), {} (log a dropped chat bridge back in), {} / {} 2 (messages scheduled for later), {} 3d / {} (automatic replies while you're away), {} / {} (opt out/in), remember that.. / forget that.. (memory). Anything else goes to the assistant.",
+        name(Keyword::Status, "STATUS"),
+        name(Keyword::Credits, "CREDITS"),
+        name(Keyword::Pause, "PAUSE"),
+        name(Keyword::Resume, "RESUME"),
+        name(Keyword::DigestNow, "DIGEST NOW"),
+        name(Keyword::Undo, "UNDO"),
+        name(Keyword::Connect, "CONNECT"),
+        name(Keyword::Reconnect, "RECONNECT"),
+        name(Keyword::Scheduled, "SCHEDULED"),
+        name(Keyword::Unschedule, "UNSCHEDULE"),
+        name(Keyword::Away, "AWAY"),
+        name(Keyword::Back, "BACK"),
+        name(Keyword::Stop, "STOP"),
+        name(Keyword::Start, "START"),
+    )
+}
+
+fn credits_text(user: &User) -> String {
+    format!("Credits left: {:.2} monthly + {:.2} purchased.", user.credits_left, user.credits)
+}
+
+fn status_text(state: &Arc<AppState>, user: &User) -> String {
+    let settings = match state.user_core.get_user_settings(user.id) {
+        Ok(settings) => settings,
+        Err(e) => {
+            tracing::error!("Failed to get settings for status command: {}", e);
+            return "Couldn't fetch your status right now, try again later.".to_string();
+        }
+    };
+
+    let mut services = Vec::new();
+    if matches!(state.user_repository.get_imap_credentials(user.id), Ok(Some(_))) {
+        services.push("email");
+    }
+    if state.user_repository.has_active_google_calendar(user.id).unwrap_or(false) {
+        services.push("calendar");
+    }
+    if state.user_repository.has_active_google_tasks(user.id).unwrap_or(false) {
+        services.push("tasks");
+    }
+    for bridge_service in crate::utils::bridge_service::all_services() {
+        if matches!(state.user_repository.get_bridge(user.id, bridge_service.name()), Ok(Some(bridge)) if bridge.status == "connected") {
+            services.push(bridge_service.name());
+        }
+    }
+    let services = if services.is_empty() { "none".to_string() } else { services.join(", ") };
+
+    let now = chrono::Utc::now().timestamp() as i32;
+    let monitoring = match (settings.proactive_agent_on, settings.proactive_paused_until) {
+        (true, _) => "on".to_string(),
+        (false, Some(until)) if until > now => format!("paused for {}", format_duration((until - now) as i64)),
+        (false, _) => "off".to_string(),
+    };
+    let critical = settings.critical_enabled.unwrap_or_else(|| "off".to_string());
+
+    format!(
+        "{} Connected: {}. Notifications: {}, critical alerts: {}.",
+        credits_text(user), services, monitoring, critical
+    )
+}
+
+// The QR code logs in whoever scans it, so it's only kept in memory behind a random token.
+// Twilio fetches it once, after that or QR_CODE_LIFETIME the link is dead.
+async fn send_qr_code(state: &Arc<AppState>, user: &User, service: &'static dyn BridgeService, mxc_url: &str) -> anyhow::Result<()> {
+    let image = crate::handlers::bridge_auth::download_qr_code(state, user.id, mxc_url).await?;
+    let token = uuid::Uuid::new_v4().to_string();
+    state.qr_codes.insert(token.clone(), image);
+
+    let server_url = std::env::var("SERVER_URL").unwrap_or_else(|_| "https://lightfriend.ai".to_string());
+    let media_url = format!("{}/api/qr/{}", server_url, token);
+    let caption = format!("{} login QR code", service.display_name());
+    let sent = crate::api::twilio_utils::send_conversation_mms(state, &caption, &media_url, user)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to send QR code: {}", e));
+
+    let state = state.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(QR_CODE_LIFETIME).await;
+        state.qr_codes.remove(&token);
+    });
+    sent.map(|_| ())
+}
+
+/// Hands a QR code from send_qr_code to Twilio, once.
+pub async fn serve_qr_code(
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    axum::extract::Path(token): axum::extract::Path<String>,
+) -> Result<([(axum::http::header::HeaderName, &'static str); 1], Vec<u8>), StatusCode> {
+    let (_, image) = state.qr_codes.remove(&token).ok_or(StatusCode::NOT_FOUND)?;
+    Ok(([(axum::http::header::CONTENT_TYPE, "image/png")], image))
+}
+
+// Starts a login and tells the user how to finish it from the phone. The bridge monitor
+// texts them again once it's connected, see bridge_auth::begin_connection.
+async fn login_text(state: &Arc<AppState>, user: &User, service: &'static dyn BridgeService) -> String {
+    match crate::handlers::bridge_auth::begin_connection(service, state, user.id, true).await {
+        Ok(LoginArtifact::PairingCode(code)) => format!(
+            "{} pairing code: {}. In {} go to Settings > Linked devices > Link a device > Link with phone number instead and enter it. I'll text you once it's connected.",
+            service.display_name(), code, service.display_name()
+        ),
+        Ok(LoginArtifact::LoginUrl(url)) => format!("Log {} in here: {}", service.display_name(), url),
+        Ok(LoginArtifact::QrCodeUrl(mxc_url)) => match send_qr_code(state, user, service, &mxc_url).await {
+            Ok(()) => format!(
+                "Sent you the {} QR code. In {} on your smartphone go to Settings > Linked devices, tap + and scan it. I'll text you once it's connected.",
+                service.display_name(), service.display_name()
+            ),
+            Err(e) => {
+                tracing::error!("Failed to send {} QR code to user {}: {}", service.name(), user.id, e);
+                format!("Couldn't send the {} QR code, you can scan it on the website instead.", service.display_name())
+            }
+        },
+        Ok(LoginArtifact::Instructions(_)) => format!(
+            "{} can't be connected over sms, it needs a token or cookies from your browser.", service.display_name()
+        ),
+        Err(e) => {
+            tracing::error!("Failed to start {} login for user {}: {}", service.name(), user.id, e);
+            if matches!(state.user_repository.get_bridge(user.id, service.name()), Ok(Some(bridge)) if bridge.status == "connected") {
+                return format!("{} is already connected.", service.display_name());
+            }
+            format!("Couldn't start a {} login right now, try again later.", service.display_name())
+        }
+    }
+}
+
+// Starts a new login for every bridge the health check gave up on, see utils::bridge_health
+async fn reconnect_text(state: &Arc<AppState>, user: &User) -> String {
+    let disconnected: Vec<_> = crate::utils::bridge_service::all_services()
+        .into_iter()
+        .filter(|service| matches!(state.user_repository.get_bridge(user.id, service.name()), Ok(Some(bridge)) if bridge.status == "disconnected"))
+        .collect();
+    if disconnected.is_empty() {
+        return "None of your chat bridges are disconnected.".to_string();
+    }
+
+    let mut replies = Vec::new();
+    for service in disconnected {
+        replies.push(login_text(state, user, service).await);
+    }
+    replies.join("\n")
+}
+
+async fn command_reply(state: &Arc<AppState>, user: &User, command: SmsCommand, language: &str) -> String {
+    match command {
+        SmsCommand::Status => status_text(state, user),
+        SmsCommand::Credits => credits_text(user),
+        SmsCommand::Pause(duration) => {
+            let until = duration.map(|secs| (chrono::Utc::now().timestamp() + secs) as i32);
+            match state.user_core.pause_proactive_agent(user.id, until) {
+                Ok(()) => match duration {
+                    Some(secs) => format!("Notifications paused for {}. Send RESUME to turn them back on sooner.", format_duration(secs)),
+                    None => "Notifications paused. Send RESUME to turn them back on.".to_string(),
+                },
+                Err(e) => {
+                    tracing::error!("Failed to pause proactive agent for user {}: {}", user.id, e);
+                    "Couldn't pause notifications right now, try again later.".to_string()
+                }
+            }
+        }
+        SmsCommand::Resume => match state.user_core.update_proactive_agent_on(user.id, true) {
+            Ok(()) => "Notifications are back on.".to_string(),
+            Err(e) => {
+                tracing::error!("Failed to resume proactive agent for user {}: {}", user.id, e);
+                "Couldn't turn notifications back on right now, try again later.".to_string()
+            }
+        },
+        SmsCommand::DigestNow => {
+            // same requirement as the scheduled digests
+            if user.sub_tier.as_deref() != Some("tier 2") {
+                return "Digests are part of the Sentinel plan. You can upgrade on the website.".to_string();
+            }
+            match crate::proactive::utils::generate_digest_now(state, user.id, DIGEST_NOW_HOURS).await {
+                Ok(digest) => digest,
+                Err(e) => {
+                    tracing::error!("Failed to generate digest on demand for user {}: {}", user.id, e);
+                    "Couldn't build your digest right now, try again later.".to_string()
+                }
+            }
+        }
+        SmsCommand::Help => help_text(language),
+        SmsCommand::Stop => {
+            let result = state.user_core.update_notify(user.id, false)
+                .and_then(|_| state.user_core.update_proactive_agent_on(user.id, false));
+            if let Err(e) = result {
+                tracing::error!("Failed to opt out user {}: {}", user.id, e);
+            }
+            "You have been unsubscribed from notifications. Reply START to subscribe again.".to_string()
+        }
+        SmsCommand::Start => {
+            let result = state.user_core.update_notify(user.id, true)
+                .and_then(|_| state.user_core.update_proactive_agent_on(user.id, true));
+            if let Err(e) = result {
+                tracing::error!("Failed to opt in user {}: {}", user.id, e);
+            }
+            "You are subscribed to notifications again. Reply STOP to unsubscribe.".to_string()
+        }
+        SmsCommand::Remember(fact) => match crate::utils::user_memory::remember(state, user.id, &fact) {
+            Ok(()) => "Got it, I'll remember that.".to_string(),
+            Err(e) => {
+                tracing::error!("Failed to store memory for user {}: {}", user.id, e);
+                "Couldn't save that right now, try again later.".to_string()
+            }
+        },
+        SmsCommand::Undo => crate::utils::outbox::undo_latest(state, user),
+        SmsCommand::Reconnect => reconnect_text(state, user).await,
+        SmsCommand::Connect(None) => "Text CONNECT WHATSAPP or CONNECT SIGNAL to link it to this number.".to_string(),
+        SmsCommand::Connect(Some(name)) => match crate::utils::bridge_service::service_for(&name) {
+            Some(service) => login_text(state, user, service).await,
+            None => "Text CONNECT WHATSAPP or CONNECT SIGNAL to link it to this number.".to_string(),
+        },
+        SmsCommand::Scheduled | SmsCommand::Unschedule(None) => crate::utils::scheduled_sends::list_text(state, user),
+        SmsCommand::Unschedule(Some(id)) => crate::utils::scheduled_sends::cancel(state, user, id),
+        SmsCommand::Away(duration) => {
+            let until = duration.map(|secs| (chrono::Utc::now().timestamp() + secs) as i32);
+            match crate::utils::away_mode::start(state, user.id, until) {
+                Ok(()) => match duration {
+                    Some(secs) => format!("Away mode is on for {}, chats and emails get your automatic reply. Send BACK to end it sooner.", format_duration(secs)),
+                    None => "Away mode is on, chats and emails get your automatic reply. Send BACK to end it.".to_string(),
+                },
+                Err(e) => {
+                    tracing::error!("Failed to turn on away mode for user {}: {}", user.id, e);
+                    "Couldn't turn on away mode right now, try again later.".to_string()
+                }
+            }
+        }
+        SmsCommand::Back => match state.user_core.set_away_enabled(user.id, false) {
+            Ok(()) => "Welcome back, away mode is off.".to_string(),
+            Err(e) => {
+                tracing::error!("Failed to turn off away mode for user {}: {}", user.id, e);
+                "Couldn't turn off away mode right now, try again later.".to_string()
+            }
+        },
+        SmsCommand::Forget(phrase) => match crate::utils::user_memory::forget(state, user.id, &phrase) {
+            Ok(forgotten) if forgotten.is_empty() => "I didn't find anything like that in what I remember about you.".to_string(),
+            Ok(forgotten) => format!("Forgot: {}", forgotten.join("; ")),
+            Err(e) => {
+                tracing::error!("Failed to forget memory for user {}: {}", user.id, e);
+                "Couldn't do that right now, try again later.".to_string()
+            }
+        },
+    }
+}
+
+/// Answers a command in place of the agent. Called from process_sms before the credit check
+/// so commands like STOP always work.
+pub async fn handle_sms_command(
+    state: &Arc<AppState>,
+    user: &User,
+    payload: &TwilioWebhookPayload,
+    command: SmsCommand,
+    language: &str,
+    is_test: bool,
+) -> (StatusCode, [(axum::http::HeaderName, &'static str); 1], axum::Json<TwilioResponse>) {
+    tracing::info!("Handling sms command {:?} for user {}", command, user.id);
+    let reply = command_reply(state, user, command, language).await;
+
+    if is_test {
+        return (
+            StatusCode::OK,
+            [(axum::http::header::CONTENT_TYPE, "application/json")],
+            axum::Json(TwilioResponse {
+                message: reply,
+            })
+        );
+    }
+
+    let state_clone = state.clone();
+    let msg_sid = payload.message_sid.clone();
+    let user_clone = user.clone();
+    tokio::spawn(async move {
+        if let Err(e) = crate::api::twilio_utils::delete_twilio_message(&state_clone, &msg_sid, &user_clone).await {
+            tracing::error!("Failed to delete incoming message {}: {}", msg_sid, e);
+        }
+    });
+
+    // The command has already run, so a failed reply must not turn into a retry of the whole
+    // job, which would run it a second time.
+    if let Err(e) = crate::api::twilio_utils::send_conversation_message(state, &reply, None, user).await {
+        tracing::error!("Failed to send sms command reply to user {}: {}", user.id, e);
+    }
+
+    (
+        StatusCode::OK,
+        [(axum::http::header::CONTENT_TYPE, "application/json")],
+        axum::Json(TwilioResponse {
+            message: "Command handled".to_string(),
+        })
+    )
+}
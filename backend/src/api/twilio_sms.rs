@@ -15,9 +15,12 @@ use axum::{
     Json,
 };
 use crate::tool_call_utils::utils::{
-    ChatMessage, create_openai_client, create_eval_tools, create_clarify_tools,
+    ChatMessage, create_eval_tools, create_clarify_tools,
     ClarifyResponse, EvalResponse,
 };
+use crate::tool_call_utils::registry::{Channel, ToolContext, ToolOutput};
+use crate::utils::llm_provider::{LlmChain, LlmPurpose};
+use crate::repositories::webhook_events::WebhookClaim;
 use chrono::Utc;
 
 // Thread-local storage for media SID mapping
@@ -29,7 +32,6 @@ use openai_api_rs::v1::{
     chat_completion,
     types,
     api::OpenAIClient,
-    common::GPT4_O,
 };
 
 
@@ -53,7 +55,7 @@ pub struct MediaItem {
     pub sid: String,
 }
 
-#[derive(Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct TwilioWebhookPayload {
     #[serde(rename = "From")]
     pub from: String,
@@ -69,6 +71,38 @@ pub struct TwilioWebhookPayload {
     pub media_content_type0: Option<String>,
     #[serde(rename = "MessageSid")]
     pub message_sid: String,
+    // MediaUrl1.., MediaContentType1.. and the rest of what Twilio posts
+    #[serde(flatten)]
+    pub other_fields: HashMap<String, String>,
+}
+
+impl TwilioWebhookPayload {
+    /// Every attachment of the message, Twilio numbers them from 0 to NumMedia - 1.
+    pub fn media(&self) -> Vec<MediaItem> {
+        let count = self.num_media.as_deref()
+            .and_then(|n| n.trim().parse::<usize>().ok())
+            .unwrap_or(0);
+
+        (0..count)
+            .filter_map(|i| {
+                let (url, content_type) = if i == 0 {
+                    (self.media_url0.clone(), self.media_content_type0.clone())
+                } else {
+                    (
+                        self.other_fields.get(&format!("MediaUrl{}", i)).cloned(),
+                        self.other_fields.get(&format!("MediaContentType{}", i)).cloned(),
+                    )
+                };
+                let url = url.filter(|url| !url.is_empty())?;
+                let sid = url.split("/Media/").nth(1).unwrap_or_default().to_string();
+                Some(MediaItem {
+                    content_type: content_type.unwrap_or_default(),
+                    url,
+                    sid,
+                })
+            })
+            .collect()
+    }
 }
 
 #[derive(Serialize, Debug)]
@@ -83,6 +117,8 @@ pub struct TextBeeWebhookPayload {
     pub sender: String,     // Maps to 'from'
     pub recipient: String,  // Maps to 'to' (your device's number)
     pub body: String,
+    #[serde(default, alias = "smsId")]
+    pub sms_id: Option<String>, // TextBee's id for the message, used to drop redeliveries
 }
 
 pub async fn handle_textbee_sms(
@@ -123,7 +159,9 @@ pub async fn handle_textbee_sms(
         num_media: None, 
         media_url0: None,
         media_content_type0: None,
-        message_sid: format!("tb_{}", Utc::now().timestamp()),  // Fake SID
+        // Fake SID, random when TextBee didn't give an id so it never matches another message
+        message_sid: format!("tb_{}", payload.sms_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string())),
+        other_fields: HashMap::new(),
     };
 
     handle_incoming_sms(State(state), Form(twilio_payload)).await
@@ -196,43 +234,85 @@ pub async fn handle_incoming_sms(
         );
     }
 
-    // Check for STOP command
-    if payload.body.trim().to_uppercase() == "STOP" {
-        if let Ok(Some(user)) = state.user_core.find_by_phone_number(&payload.from) {
-            if let Err(e) = state.user_core.update_notify(user.id, false) {
-                tracing::error!("Failed to update notify status: {}", e);
-            } else {
+    // Providers redeliver when they don't get an answer in time, only handle each message once
+    let (provider, event_id) = webhook_event_key(&payload);
+    if !event_id.is_empty() {
+        match state.user_repository.claim_webhook_event(provider, &event_id) {
+            Ok(WebhookClaim::New) => {}
+            Ok(WebhookClaim::InProgress) => {
+                tracing::info!("Duplicate {} delivery of {} while still processing", provider, event_id);
+                return (
+                    StatusCode::CONFLICT,
+                    [(axum::http::header::CONTENT_TYPE, "application/json")],
+                    axum::Json(TwilioResponse {
+                        message: "Message is already being processed".to_string(),
+                    })
+                );
+            }
+            Ok(WebhookClaim::Done { response_status, response_body }) => {
+                tracing::info!("Duplicate {} delivery of {}, returning original response", provider, event_id);
                 return (
-                    StatusCode::OK,
+                    StatusCode::from_u16(response_status as u16).unwrap_or(StatusCode::OK),
                     [(axum::http::header::CONTENT_TYPE, "application/json")],
                     axum::Json(TwilioResponse {
-                        message: "You have been unsubscribed from notifications.".to_string(),
+                        message: response_body,
                     })
                 );
             }
+            // Better to risk a double answer than to drop the message
+            Err(e) => tracing::error!("Failed to check {} event {} for duplicates: {}", provider, event_id, e),
         }
     }
 
-    // Process SMS in the background
-    tokio::spawn(async move {
-        let result = process_sms(&state, payload.clone(), false).await;
-        if result.0 != StatusCode::OK {
-            tracing::error!("Background SMS processing failed with status: {:?}", result.0);
-            tracing::error!("Error response: {:?}", result.1);
+    // Queue the message for the sms workers so a slow tool or a crash doesn't lose it
+    let user_id = state.user_core.find_by_phone_number(&payload.from).ok().flatten().map(|u| u.id);
+    let queued = serde_json::to_string(&payload)
+        .map_err(|e| e.to_string())
+        .and_then(|payload_json| {
+            state.user_repository.enqueue_sms_job(user_id, &payload_json).map_err(|e| e.to_string())
+        });
+
+    match queued {
+        Ok(()) => state.sms_job_notify.notify_one(),
+        Err(e) => {
+            tracing::error!("Failed to queue SMS job, processing inline: {}", e);
+            // Process SMS in the background
+            let state = state.clone();
+            tokio::spawn(async move {
+                let result = process_sms(&state, payload.clone(), false).await;
+                if result.0 != StatusCode::OK {
+                    tracing::error!("Background SMS processing failed with status: {:?}", result.0);
+                    tracing::error!("Error response: {:?}", result.1);
+                }
+            });
         }
-    });
-    
+    }
+
+    let message = "Message received, processing in progress".to_string();
+    if !event_id.is_empty() {
+        if let Err(e) = state.user_repository.complete_webhook_event(provider, &event_id, StatusCode::OK.as_u16() as i32, &message) {
+            tracing::error!("Failed to store response for {} event {}: {}", provider, event_id, e);
+        }
+    }
 
     // Immediately return a success response to Twilio
     (
         StatusCode::OK,
         [(axum::http::header::CONTENT_TYPE, "application/json")],
         axum::Json(TwilioResponse {
-            message: "Message received, processing in progress".to_string(),
+            message,
         })
     )
 }
 
+// TextBee messages get a "tb_" prefixed sid in handle_textbee_sms
+fn webhook_event_key(payload: &TwilioWebhookPayload) -> (&'static str, String) {
+    match payload.message_sid.strip_prefix("tb_") {
+        Some(sms_id) => ("textbee", sms_id.to_string()),
+        None => ("twilio", payload.message_sid.clone()),
+    }
+}
+
 
 pub async fn process_sms(
     state: &Arc<AppState>,
@@ -264,6 +344,16 @@ pub async fn process_sms(
         }
     };
 
+    // Fixed commands (STATUS, STOP, ..) are answered without the model and aren't billed
+    let language = state.user_core.get_user_settings(user.id)
+        .map(|settings| settings.agent_language)
+        .unwrap_or_else(|_| "en".to_string());
+    if let Some(command) = crate::api::sms_commands::SmsCommand::parse(&payload.body, &language)
+        .and_then(|command| command.resolve_cancel(state, &user, &payload.body))
+    {
+        return crate::api::sms_commands::handle_sms_command(&state, &user, &payload, command, &language, is_test).await;
+    }
+
     // Check if user has sufficient credits before processing the message
     if let Err(e) = crate::utils::usage::check_user_credits(&state, &user, "message", None).await {
         tracing::warn!("User {} has insufficient credits: {}", user.id, e);
@@ -279,15 +369,10 @@ pub async fn process_sms(
     
     // Log media information for admin user
     if user.id == 1 {
-        if let (Some(num_media), Some(media_url), Some(content_type)) = (
-            payload.num_media.as_ref(),
-            payload.media_url0.as_ref(),
-            payload.media_content_type0.as_ref()
-        ) {
-            tracing::debug!("Media information:");
-            tracing::debug!("  Number of media items: {}", num_media);
-            tracing::debug!("  Media URL: {}", media_url);
-            tracing::debug!("  Content type: {}", content_type);
+        for (i, item) in payload.media().iter().enumerate() {
+            tracing::debug!("Media item {}:", i);
+            tracing::debug!("  Media URL: {}", item.url.chars().take(100).collect::<String>());
+            tracing::debug!("  Content type: {}", item.content_type);
         }
     }
 
@@ -296,7 +381,8 @@ pub async fn process_sms(
         .unwrap()
         .as_secs() as i32;
 
-    // Store user's message in history
+    // The user's message goes in the history once it is certain this attempt won't fail and be
+    // retried by the sms worker, everything that can fail before that has no side effects
     let user_message = crate::models::user_models::NewMessageHistory {
         user_id: user.id,
         role: "user".to_string(),
@@ -307,30 +393,24 @@ pub async fn process_sms(
         created_at: current_time,
         conversation_id: "".to_string(),
     };
+    let store_user_message = || {
+        if let Err(e) = state.user_repository.create_message_history(&user_message) {
+            tracing::error!("Failed to store user message in history: {}", e);
+        }
+    };
 
-    if let Err(e) = state.user_repository.create_message_history(&user_message) {
-        tracing::error!("Failed to store user message in history: {}", e);
+    // "r: on my way" answers the chat or email the latest notification came from, no guessing who
+    if let Some(response) = crate::utils::notification_sources::handle_notification_reply(state, &user, &payload.body, is_test).await {
+        store_user_message();
+        return response;
     }
 
-    if user.confirm_send_event.is_some() {
-        // Handle confirmation logic
-        let confirmation_result = crate::tool_call_utils::confirm::handle_confirmation(
-            &state,
-            &user.clone(),
-            &user.clone().confirm_send_event.unwrap(),
-            &payload.body,
-            is_test.clone(),
-        ).await;
-
-        tracing::info!("Came back from handle_confirmation with should_continue as: {}", confirmation_result.should_continue);
-
-        if !confirmation_result.should_continue {
-            if let Some(response) = confirmation_result.response {
-                return response;
-            }
-        }
+    // Replies to pending confirmations ("yes 2", "change it to 4pm") are handled without the agent
+    if let Some(response) = crate::tool_call_utils::confirm::handle_pending_reply(state, &user, &payload.body, is_test).await {
+        store_user_message();
+        return response;
     }
-    
+
     // Get user settings to access timezone
     let user_settings = match state.user_core.get_user_settings(user.id) {
         Ok(settings) => settings,
@@ -403,6 +483,18 @@ pub async fn process_sms(
         content: chat_completion::Content::Text(format!("You are a direct and efficient AI assistant named lightfriend. The current date is {}. You must provide extremely concise responses (max 400 characters) while being accurate and helpful. Since users pay per message, always provide all available information immediately without asking follow-up questions unless confirming details for actions that involve sending information or making changes. Always use all tools immidiately that you think will be needed to complete the user's query and base your response to those responses. IMPORTANT: For calendar events, you must return the exact output from the calendar tool without any modifications, additional text, or formatting. Never add bullet points, markdown formatting (like **, -, #), or any other special characters.\n\n### Tool Usage Guidelines:\n- Provide all relevant details in the response immediately. \n- Tools that involve sending or creating something, you can call them straight away using the available information without confirming with the user. These tools will send extra confirmation message to user anyways before doing anything.\n- Never recommend that the user check apps, websites, or services manually, as they may not have access (e.g., on a dumbphone). Instead, use tools like ask_perplexity to fetch the information yourself.\n\n### Date and Time Handling:\n- Always work with times in the user's timezone: {} with offset {}.\n- When user mentions times without dates, assume they mean the nearest future occurrence.\n- For time inputs to tools, convert to RFC3339 format in UTC (e.g., '2024-03-23T14:30:00Z').\n- For displaying times to users:\n - Use 12-hour format with AM/PM (e.g., '2:30 PM')\n - Include timezone-adjusted dates in a friendly format (e.g., 'today', 'tomorrow', or 'Jun 15')\n - Show full date only when it's not today/tomorrow\n- If no specific time is mentioned:\n - For calendar queries: Show today's events (and tomorrow's if after 6 PM)\n - For other time ranges: Use current time to 24 hours ahead\n- For queries about:\n - 'Today': Use 00:00 to 23:59 of the current day in user's timezone\n - 'Tomorrow': Use 00:00 to 23:59 of tomorrow in user's timezone\n - 'This week': Use remaining days of current week\n - 'Next week': Use Monday to Sunday of next week\n\n### Additional Guidelines:\n- Weather Queries: If no location is specified, assume the user's home location from user info.\n- Email Queries: For fetch_specific_email, provide the whole message body or a summary if too long—never just the subject.\n- WhatsApp/Telegram Fetching: Use the room name directly from the user's message/context without searching rooms.\n- Follow-up Questions: If the user asks a follow-up question and the previous tool call response doesn't contain the answer, use the ask_perplexity tool to find the answer if possible, and mention in your response that you queried Perplexity for it.\n\nNever use markdown, HTML, or any special formatting characters in responses. Return all information in plain text only. User information: {}. Always use tools to fetch the latest information before answering.", formatted_time, timezone_str, offset, user_given_info)),
     }];
     
+    // Things the user has told us to remember or that were picked up from earlier messages
+    let memories = crate::utils::user_memory::relevant_memories(&state, user.id, &payload.body, 15);
+    if !memories.is_empty() {
+        chat_messages.push(ChatMessage {
+            role: "system".to_string(),
+            content: chat_completion::Content::Text(format!(
+                "Things you know about the user:\n{}",
+                crate::utils::user_memory::format_memories(&memories)
+            )),
+        });
+    }
+
     // Process the message body to remove "forget" if it exists at the start
     let processed_body = if payload.body.to_lowercase().starts_with("forget") {
         payload.body.trim_start_matches(|c: char| c.is_alphabetic()).trim().to_string()
@@ -410,22 +502,31 @@ pub async fn process_sms(
         payload.body.clone()
     };
 
-    // Delete media if present after processing
-    if let (Some(num_media), Some(media_url), Some(_)) = (
-        payload.num_media.as_ref(),
-        payload.media_url0.as_ref(),
-        payload.media_content_type0.as_ref()
-    ) {
-        if num_media != "0" {
-            // Extract media SID from URL
-            if let Some(media_sid) = media_url.split("/Media/").nth(1) {
-                tracing::debug!("Attempting to delete media with SID: {}", media_sid);
-                match crate::api::twilio_utils::delete_twilio_message_media(&state, &media_sid, &user).await {
-                    Ok(_) => tracing::debug!("Successfully deleted media: {}", media_sid),
-                    Err(e) => tracing::error!("Failed to delete media {}: {}", media_sid, e),
-                }
+    // Download every attachment, they are deleted from Twilio once a retry can't need them anymore
+    let media = payload.media();
+    let prepared_media = if media.is_empty() {
+        crate::utils::mms_media::PreparedMedia::default()
+    } else {
+        crate::utils::mms_media::prepare_media(&state, &user, &media).await
+    };
+
+    // Nothing we could open and nothing written, answer without the model
+    if !prepared_media.unsupported.is_empty() && prepared_media.is_empty() && processed_body.trim().is_empty() {
+        let reply = crate::utils::mms_media::unsupported_reply(&prepared_media.unsupported);
+        store_user_message();
+        crate::utils::mms_media::delete_media(&state, &user, &media).await;
+        if !is_test {
+            if let Err(e) = crate::api::twilio_utils::send_conversation_message(&state, &reply, None, &user).await {
+                tracing::error!("Failed to send unsupported media reply to user {}: {}", user.id, e);
             }
         }
+        return (
+            StatusCode::OK,
+            [(axum::http::header::CONTENT_TYPE, "application/json")],
+            axum::Json(TwilioResponse {
+                message: reply,
+            })
+        );
     }
 
     // Only include conversation history if message doesn't start with "forget"
@@ -435,117 +536,80 @@ pub async fn process_sms(
         let save_context = user_settings.save_context.unwrap_or(0);
         
         if save_context > 0 {
-            // Get the last N back-and-forth exchanges based on save_context
-            let history = state.user_repository
-                .get_conversation_history(
-                    user.id,
-                    save_context as i64,
-                    true,
-                )
-                .unwrap_or_default();
-
-            let mut context_messages: Vec<ChatMessage> = Vec::new();
-            
-            // Process messages in chronological order
-            for msg in history.into_iter().rev() {
-                let content = chat_completion::Content::Text(msg.encrypted_content);
-                let role = match msg.role.as_str() {
-                    "user" => "user",
-                    "assistant" => "assistant",
-                    "tool" => "tool",
-                    _ => continue,
-                };
-                
-                let mut chat_msg = ChatMessage {
-                    role: role.to_string(),
-                    content,
-                };
-                
-                context_messages.push(chat_msg);
-            }
-            
-            // Combine system message with conversation history
-            chat_messages.extend(context_messages);
-        }
-    }
-    if user.id == 1 {
-        println!("history: {:#?}", chat_messages);
-    }
+            // Older exchanges come as a summary, only the unsummarized tail is replayed
+            let context = crate::utils::conversation_memory::load_context(&state, user.id, save_context as i64);
 
-    // Handle image if present
-    let mut image_url = None;
-    
-    if let (Some(num_media), Some(media_url), Some(content_type)) = (
-        payload.num_media.as_ref(),
-        payload.media_url0.as_ref(),
-        payload.media_content_type0.as_ref()
-    ) {
-        if num_media != "0" && content_type.starts_with("image/") {
-            image_url = Some(media_url.clone());
-            
-            tracing::debug!("setting image_url var to: {:#?}", image_url);
-            // Add the image URL message with the text
-            chat_messages.push(ChatMessage {
-                role: "user".to_string(),
-                content: chat_completion::Content::ImageUrl(vec![
-                    chat_completion::ImageUrl {
-                        r#type: chat_completion::ContentType::image_url,
-                        text: Some(processed_body.clone()),
-                        image_url: Some(chat_completion::ImageUrlType {
-                            url: media_url.clone(),
-                        }),
-                    },
-                ]),
-
-            });
+            if let Some(summary) = context.summary {
+                chat_messages.push(ChatMessage {
+                    role: "system".to_string(),
+                    content: chat_completion::Content::Text(format!("Summary of the earlier conversation with the user: {}", summary)),
+                });
+            }
 
-            // Also add the text as a separate message if it's not empty 
-            if !processed_body.trim().is_empty() {
+            for msg in context.recent {
                 chat_messages.push(ChatMessage {
-                    role: "user".to_string(),
-                    content: chat_completion::Content::Text(format!("Text accompanying the image: {}", processed_body)),
+                    role: msg.role,
+                    content: chat_completion::Content::Text(msg.encrypted_content),
                 });
             }
-        } else {
-            // Add regular text message if no image
-            chat_messages.push(ChatMessage {
-                role: "user".to_string(),
-                content: chat_completion::Content::Text(processed_body),
-            });
         }
+    }
+    // Attachments other than images are described in text next to the user's message
+    let mut user_text = processed_body;
+    for note in &prepared_media.notes {
+        user_text.push_str("\n\n");
+        user_text.push_str(note);
+    }
+    if !prepared_media.unsupported.is_empty() {
+        user_text.push_str(&format!(
+            "\n\nThe user also attached files that can't be opened ({}). They can still be sent on to a chat with send_chat_message, otherwise tell them briefly that only photos, contact cards, PDFs and voice messages are supported.",
+            prepared_media.unsupported.join(", ")
+        ));
+    }
+
+    // scan_qr_code looks at the first image
+    let image_url = prepared_media.images.first().cloned();
+    // send_chat_message forwards the first attachment of any kind
+    let media_url = prepared_media.attachments.first().cloned();
+
+    if prepared_media.images.is_empty() {
+        chat_messages.push(ChatMessage {
+            role: "user".to_string(),
+            content: chat_completion::Content::Text(user_text),
+        });
     } else {
-        // Add regular text message if no media
+        tracing::debug!("Sending {} image(s) to the model", prepared_media.images.len());
+        // All images go in the same message so the model can look at them together
+        let text = if user_text.trim().is_empty() {
+            "(no text, see the attached images)".to_string()
+        } else {
+            user_text
+        };
+        let mut parts = vec![chat_completion::ImageUrl {
+            r#type: chat_completion::ContentType::text,
+            text: Some(text),
+            image_url: None,
+        }];
+        parts.extend(prepared_media.images.iter().map(|url| chat_completion::ImageUrl {
+            r#type: chat_completion::ContentType::image_url,
+            text: None,
+            image_url: Some(chat_completion::ImageUrlType {
+                url: url.clone(),
+            }),
+        }));
         chat_messages.push(ChatMessage {
             role: "user".to_string(),
-            content: chat_completion::Content::Text(processed_body),
+            content: chat_completion::Content::ImageUrl(parts),
         });
     }
 
-    // Define tools
-    let tools = vec![
-        crate::tool_call_utils::bridge::get_send_chat_message_tool(),
-        crate::tool_call_utils::bridge::get_fetch_chat_messages_tool(),
-        crate::tool_call_utils::bridge::get_fetch_recent_messages_tool(),
-        //crate::tool_call_utils::bridge::get_search_chat_contacts_tool(), // idk if we need this
-        crate::tool_call_utils::email::get_fetch_emails_tool(),
-        crate::tool_call_utils::email::get_fetch_specific_email_tool(),
-        crate::tool_call_utils::calendar::get_fetch_calendar_event_tool(),
-        crate::tool_call_utils::calendar::get_create_calendar_event_tool(),
-        crate::tool_call_utils::tasks::get_fetch_tasks_tool(),
-        crate::tool_call_utils::tasks::get_create_tasks_tool(),
-        crate::tool_call_utils::management::get_create_waiting_check_tool(),
-        crate::tool_call_utils::management::get_update_monitoring_status_tool(),
-        crate::tool_call_utils::internet::get_scan_qr_code_tool(),
-        crate::tool_call_utils::internet::get_ask_perplexity_tool(),
-        crate::tool_call_utils::internet::get_firecrawl_search_tool(),
-        crate::tool_call_utils::internet::get_weather_tool(),
-        crate::tool_call_utils::internet::get_directions_tool(),
-    ];
-
-    let client = match create_openai_client(&state) {
-        Ok(client) => client,
+    // Tools come from the shared registry, same ones the voice agent can call
+    let tools = state.tool_registry.definitions(Channel::Sms);
+
+    let llm = match LlmChain::for_purpose(&state, LlmPurpose::Agent, Some(user.id)) {
+        Ok(llm) => llm,
         Err(e) => {
-            tracing::error!("Failed to create OpenAI client: {}", e);
+            tracing::error!("Failed to set up agent llm chain: {}", e);
             return (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 [(axum::http::header::CONTENT_TYPE, "application/json")],
@@ -603,486 +667,152 @@ pub async fn process_sms(
     );
     */
 
-
-    let result = match client.chat_completion(chat_completion::ChatCompletionRequest::new(
-        GPT4_O.to_string(),
-        completion_messages.clone(),
-    )
-    .tools(tools)
-    .tool_choice(chat_completion::ToolChoiceType::Auto)
-    .max_tokens(250)).await {
-        Ok(result) => result,
-        Err(e) => {
-            tracing::error!("Failed to get chat completion: {}", e);
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                [(axum::http::header::CONTENT_TYPE, "application/json")],
-                axum::Json(TwilioResponse {
-                    message: "Failed to process your request".to_string(),
-                })
+    // Agent loop: let the model chain tool calls (e.g. check the calendar, then message someone
+    // based on the result) until it answers or the per-request budget runs out.
+    let limits = AgentLimits::from_env();
+    let mut agent_messages = completion_messages.clone();
+    let mut fail = false;
+    let mut tool_answers: HashMap<String, String> = HashMap::new(); // tool_call id and answer, across all steps
+    let mut steps: usize = 0;
+    let mut tokens_used: i64 = 0;
+
+    let final_response = loop {
+        let budget_left = steps < limits.max_steps
+            && start_time.elapsed() < limits.max_duration
+            && tokens_used < limits.max_tokens;
+
+        let mut request = chat_completion::ChatCompletionRequest::new(
+            llm.primary_model().to_string(),
+            agent_messages.clone(),
+        );
+        if budget_left {
+            request = request
+                .tools(tools.clone())
+                .tool_choice(chat_completion::ToolChoiceType::Auto)
+                .max_tokens(250);
+        } else {
+            // Out of budget: force a final text answer from what we have so far
+            tracing::debug!(
+                "Agent budget reached for user {} (steps: {}, tokens: {}, elapsed: {:?}), requesting final answer",
+                user.id, steps, tokens_used, start_time.elapsed()
             );
+            request = request.max_tokens(100);
         }
-    };
-
-    // TODO remove
-    if user.id == 1 {
-        println!("result: {:#?}", result);
-    }
 
+        let result = match llm.chat_completion(request).await {
+            Ok(result) => result,
+            Err(e) if steps == 0 => {
+                tracing::error!("Failed to get chat completion: {}", e);
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    [(axum::http::header::CONTENT_TYPE, "application/json")],
+                    axum::Json(TwilioResponse {
+                        message: "Failed to process your request".to_string(),
+                    })
+                );
+            }
+            Err(e) => {
+                tracing::error!("Failed to get follow-up completion at step {}: {}", steps, e);
+                fail = true;
+                break tool_answers.values().next()
+                    .map(|ans| format!("Based on my research: {} (you were not charged for this message)", ans.chars().take(370).collect::<String>()))
+                    .unwrap_or_else(|| "I apologize, but I encountered an error processing your request. (you were not charged for this message)".to_string());
+            }
+        };
+        tokens_used += result.usage.total_tokens as i64;
 
-    let mut fail = false;
-    let mut tool_answers: HashMap<String, String> = HashMap::new(); // tool_call id and answer
-    let final_response = match result.choices[0].finish_reason {
-        None | Some(chat_completion::FinishReason::stop) => {
-            tracing::debug!("Model provided direct response (no tool calls needed)");
-            // Direct response from the model
-            let resp = result.choices[0].message.content.clone().unwrap_or_default();
-            resp
-        }
-        Some(chat_completion::FinishReason::tool_calls) => {
-            tracing::debug!("Model requested tool calls - beginning tool execution phase");
-
-                        
-            let tool_calls = match result.choices[0].message.tool_calls.as_ref() {
-                Some(calls) => {
-                    tracing::debug!("Found {} tool call(s) in response", calls.len());
-                    calls
-                },
-                None => {
-                    tracing::error!("No tool calls found in response despite tool_calls finish reason");
-                    return (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        [(axum::http::header::CONTENT_TYPE, "application/json")],
-                        axum::Json(TwilioResponse {
-                            message: "Failed to process your request".to_string(),
-                        })
-                    );
-                }
-            };
+        match result.choices[0].finish_reason {
+            None | Some(chat_completion::FinishReason::stop) => {
+                tracing::debug!("Model provided final response after {} tool step(s)", steps);
+                break result.choices[0].message.content.clone().unwrap_or_default();
+            }
+            Some(chat_completion::FinishReason::tool_calls) if budget_left => {
+                tracing::debug!("Model requested tool calls - beginning tool execution step {}", steps + 1);
 
-            for tool_call in tool_calls {
-                let tool_call_id = tool_call.id.clone();
-                tracing::debug!("Processing tool call: {:?} with id: {:?}", tool_call, tool_call_id);
-                let name = match &tool_call.function.name {
-                    Some(n) => {
-                        tracing::debug!("Tool call function name: {}", n);
-                        n
+                let tool_calls = match result.choices[0].message.tool_calls.as_ref() {
+                    Some(calls) => {
+                        tracing::debug!("Found {} tool call(s) in response", calls.len());
+                        calls.clone()
                     },
+                    None if steps == 0 => {
+                        tracing::error!("No tool calls found in response despite tool_calls finish reason");
+                        return (
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                            [(axum::http::header::CONTENT_TYPE, "application/json")],
+                            axum::Json(TwilioResponse {
+                                message: "Failed to process your request".to_string(),
+                            })
+                        );
+                    }
+                    // Tools already ran, a retry would run them again
                     None => {
-                        tracing::debug!("Tool call missing function name, skipping");
-                        continue;
-                    },
-                };
-
-                let tool_call_time= std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs() as i32;
-
-                let history_entry = crate::models::user_models::NewMessageHistory {
-                    user_id: user.id,
-                    role: "tool".to_string(),
-                    // Store the entire tool-call JSON so it can be replayed later
-                    encrypted_content: serde_json::to_string(tool_call)
-                        .unwrap_or_else(|_| "{}".to_string()),
-                    tool_name: Some(tool_call
-                        .function
-                        .name
-                        .clone()
-                        .unwrap_or_else(|| "_tool_call".to_string())),
-                    tool_call_id: Some(tool_call.id.clone()),
-                    tool_calls_json: None,
-                    created_at: tool_call_time,
-                    conversation_id: "".to_string(),
-                };
-
-                if let Err(e) = state.user_repository.create_message_history(&history_entry) {
-                    tracing::error!("Failed to store tool-call message in history: {e}");
-                }
-
-                // Check if user has access to this tool
-                if crate::tool_call_utils::utils::requires_subscription(name, user.sub_tier.clone(), user.discount) {
-                    tracing::info!("Attempted to use subscription-only tool {} without proper subscription", name);
-                    tool_answers.insert(tool_call_id, format!("This feature ({}) requires a subscription. Please visit our website to subscribe.", name));
-                    continue;
-                }
-                let arguments = match &tool_call.function.arguments {
-                    Some(args) => args,
-                    None => continue,
-                };
-                if name == "ask_perplexity" {
-                    tracing::debug!("Executing ask_perplexity tool call");
-                    #[derive(Deserialize, Serialize)]
-                    struct PerplexityQuestion {
-                        query: String,
+                        tracing::error!("No tool calls found in response despite tool_calls finish reason at step {}", steps);
+                        fail = true;
+                        break tool_answers.values().next()
+                            .map(|ans| format!("Based on my research: {} (you were not charged for this message)", ans.chars().take(370).collect::<String>()))
+                            .unwrap_or_else(|| "I apologize, but I encountered an error processing your request. (you were not charged for this message)".to_string());
                     }
+                };
 
-                    let c: PerplexityQuestion = match serde_json::from_str(arguments) {
-                        Ok(q) => q,
-                        Err(e) => {
-                            tracing::error!("Failed to parse perplexity question: {}", e);
-                            continue;
-                        }
-                    };
-                    let query = format!("User info: {}. Query: {}", user_given_info, c.query);
-
-                    let sys_prompt = format!("You are assisting an AI text messaging service. The questions you receive are from text messaging conversations where users are seeking information or help. Please note: 1. Provide clear, conversational responses that can be easily read from a small screen 2. Avoid using any markdown, HTML, or other markup languages 3. Keep responses concise but informative 4. When listing multiple points, use simple numbering (1, 2, 3) 5. Focus on the most relevant information that addresses the user's immediate needs. This is what you should know about the user who this information is going to in their own words: {}", user_given_info);
-                    match crate::utils::tool_exec::ask_perplexity(&state, &query, &sys_prompt).await {
-                        Ok(answer) => {
-                            tracing::debug!("Successfully received Perplexity answer");
-                            tool_answers.insert(tool_call_id, answer);
-                        }
-                        Err(e) => {
-                            tracing::error!("Failed to get perplexity answer: {}", e);
-                            continue;
-                        }
-                    };
-                } else if name == "get_weather" {
-                    tracing::debug!("Executing get_weather tool call");
-                    #[derive(Deserialize, Serialize)]
-                    struct WeatherQuestion {
-                        location: String,
-                        units: String,
-                    }
-                    let c: WeatherQuestion = match serde_json::from_str(arguments) {
-                        Ok(q) => q,
-                        Err(e) => {
-                            tracing::error!("Failed to parse weather question: {}", e);
-                            continue;
-                        }
-                    };
-                    let location= c.location;
-                    let units= c.units;
+                let mut step_answers: HashMap<String, String> = HashMap::new();
+                for tool_call in tool_calls.iter() {
+                    tracing::debug!("Processing tool call: {:?} with id: {:?}", tool_call, tool_call.id);
+                    let tool_call_time = std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs() as i32;
 
-                    match crate::utils::tool_exec::get_weather(&state, &location, &units, user.id).await {
-                        Ok(answer) => {
-                            tracing::debug!("Successfully received weather answer");
-                            tool_answers.insert(tool_call_id, answer);
-                        }
-                        Err(e) => {
-                            tracing::error!("Failed to get weather answer: {}", e);
-                            continue;
-                        }
+                    let history_entry = crate::models::user_models::NewMessageHistory {
+                        user_id: user.id,
+                        role: "tool".to_string(),
+                        // Store the entire tool-call JSON so it can be replayed later
+                        encrypted_content: serde_json::to_string(tool_call)
+                            .unwrap_or_else(|_| "{}".to_string()),
+                        tool_name: Some(tool_call
+                            .function
+                            .name
+                            .clone()
+                            .unwrap_or_else(|| "_tool_call".to_string())),
+                        tool_call_id: Some(tool_call.id.clone()),
+                        tool_calls_json: None,
+                        created_at: tool_call_time,
+                        conversation_id: "".to_string(),
                     };
 
-                } else if name == "search_firecrawl" {
-                    tracing::debug!("Executing search_firecrawl tool call");
-                    #[derive(Deserialize, Serialize)]
-                    struct FireCrawlQuestion {
-                        query: String,
-                    }
-                    let c: FireCrawlQuestion = match serde_json::from_str(arguments) {
-                        Ok(q) => q,
-                        Err(e) => {
-                            tracing::error!("Failed to parse fire crawl question: {}", e);
-                            continue;
-                        }
-                    };
-                    let query = c.query;
-                    match crate::utils::tool_exec::handle_firecrawl_search(query, 5).await {
-                        Ok(answer) => {
-                            tracing::debug!("Successfully received fire crawl answer");
-                            tool_answers.insert(tool_call_id, answer);
-                        }
-                        Err(e) => {
-                            tracing::error!("Failed to get fire crawl answer: {}", e);
-                            continue;
-                        }
-                    };
-                } else if name == "get_directions" {
-                    tracing::debug!("Executing get_directions tool call");
-                    #[derive(Deserialize, Serialize)]
-                    struct DirectionsQuestion {
-                        start_address: String,
-                        end_address: String,
-                        mode: Option<String>,
+                    if let Err(e) = state.user_repository.create_message_history(&history_entry) {
+                        tracing::error!("Failed to store tool-call message in history: {e}");
                     }
-                    let c: DirectionsQuestion = match serde_json::from_str(arguments) {
-                        Ok(q) => q,
-                        Err(e) => {
-                            tracing::error!("Failed to parse directions question: {}", e);
-                            continue;
-                        }
-                    };
-                    let start_address = c.start_address;
-                    let end_address = c.end_address;
-                    let mode = c.mode;
-                    match crate::tool_call_utils::internet::handle_directions_tool(start_address, end_address, mode).await {
-                        Ok(answer) => {
-                            tracing::debug!("Successfully received directions answer");
-                            tool_answers.insert(tool_call_id, answer);
-                        }
-                        Err(e) => {
-                            tracing::error!("Failed to get directions answer: {}", e);
-                            continue;
-                        }
-                    };
-                } else if name == "use_shazam" {
-                    tool_answers.insert(tool_call_id, "The Shazam feature has been discontinued due to insufficient usage. Thank you for your understanding.".to_string());
-                } else if name == "fetch_emails" {
-                    tracing::debug!("Executing fetch_emails tool call");
-                    let response = crate::tool_call_utils::email::handle_fetch_emails(&state, user.id).await;
-                    tool_answers.insert(tool_call_id, response);
-                } else if name == "fetch_specific_email" {
-                    tracing::debug!("Executing fetch_specific_email tool call");
-                    #[derive(Deserialize)]
-                    struct EmailQuery {
-                        query: String,
-                    }
-                    
-                    let query: EmailQuery = match serde_json::from_str(arguments) {
-                        Ok(q) => q,
-                        Err(e) => {
-                            tracing::error!("Failed to parse email query: {}", e);
-                            continue;
-                        }
-                    };
 
-                    // First get the email ID
-                    let email_id = crate::tool_call_utils::email::handle_fetch_specific_email(&state, user.id, &query.query).await;
-                    let auth_user = crate::handlers::auth_middleware::AuthUser {
-                        user_id: user.id,
-                        is_admin: false,
-                    };
-                    
-                    // Then fetch the complete email with that ID
-                    match crate::handlers::imap_handlers::fetch_single_imap_email(axum::extract::State(state.clone()), auth_user, axum::extract::Path(email_id)).await {
-                        Ok(email) => {
-                            let email = &email["email"];
-                            
-                            // Upload attachments to Twilio if present
-                            /*
-                            let mut uploaded_attachments: Vec<(String, String)> = Vec::new(); // (filename, media_sid)
-                            if let Some(attachments) = email["attachments"].as_array() {
-                                for attachment_url in attachments {
-                                    if let Some(url) = attachment_url.as_str() {
-                                        // Download attachment content
-                                        if let Ok(response) = reqwest::get(url).await {
-                                            if let Some(content_type) = response.headers().get("content-type")
-                                                .and_then(|ct| ct.to_str().ok())
-                                                .map(|s| s.to_string()) {
-                                                if let Ok(bytes) = response.bytes().await {
-                                                    // Extract filename from URL or use default
-                                                    let filename = url.split('/').last()
-                                                        .unwrap_or("attachment")
-                                                        .to_string();
-                                                    
-                                                    // Upload to Twilio
-                                                    match crate::api::twilio_utils::upload_media_to_twilio(
-                                                        &state,
-                                                        &conversation.service_sid,
-                                                        &bytes,
-                                                        &content_type,
-                                                        &filename,
-                                                        &user
-                                                    ).await {
-                                                        Ok(media_sid) => {
-                                                            // Store in thread-local map
-                                                            MEDIA_SID_MAP.with(|map| {
-                                                                map.borrow_mut().insert(filename.clone(), media_sid.clone());
-                                                            });
-                                                            uploaded_attachments.push((filename.clone(), media_sid));
-                                                        },
-                                                        Err(e) => {
-                                                            tracing::error!("Failed to upload attachment to Twilio: {}", e);
-                                                        }
-                                                    }
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-
-                            // Add attachment information with just filenames
-                            if !uploaded_attachments.is_empty() {
-                                response.push_str("\n\nAttachments:\n");
-                                for (filename, _) in &uploaded_attachments {
-                                    response.push_str(&format!("- {}\n", filename));
-                                }
-                            }
-                            */
-
-                            // Format the response with all email details and just filenames for attachments
-                            let mut response = format!(
-                                "From: {}\nSubject: {}\nDate: {}\n\n{}",
-                                email["from"],
-                                email["subject"],
-                                email["date_formatted"],
-                                email["body"]
-                            );
-                            tool_answers.insert(tool_call_id, response);
-                        },
-                        Err(e) => {
-                            tool_answers.insert(tool_call_id, "Failed to fetch the complete email".to_string());
-                        }
-                    }
-                } else if name == "create_waiting_check" {
-                    tracing::debug!("Executing create_waiting_check tool call");
-                    match crate::tool_call_utils::management::handle_create_waiting_check(&state, user.id, arguments).await {
-                        Ok(answer) => {
-                            tool_answers.insert(tool_call_id, answer);
-                        }
-                        Err(e) => {
-                            tracing::error!("Failed to create waiting check: {}", e);
-                            tool_answers.insert(tool_call_id, "Sorry, I couldn't create a waiting check. (Contact rasmus@ahtava.com pls:D)".to_string());
-                        }
-                    }
-                } else if name == "update_monitoring_status" {
-                    tracing::debug!("Executing update_monitoring_status tool call");
-                    match crate::tool_call_utils::management::handle_set_proactive_agent(&state, user.id, arguments).await {
-                        Ok(answer) => {
-                            tool_answers.insert(tool_call_id, answer);
-                        }
-                        Err(e) => {
-                            tracing::error!("Failed to toggle monitoring status: {}", e);
-                            tool_answers.insert(tool_call_id, "Sorry, I failed to toggle monitoring status. (Contact rasmus@ahtava.com pls:D)".to_string());
-                        }
-                    }
-                } else if name == "create_calendar_event" {
-                    tracing::debug!("Executing create_calendar_event tool call");
-                    match crate::tool_call_utils::calendar::handle_create_calendar_event(
+                    match execute_tool_call(
                         &state,
-                        user.id,
-                        arguments,
-                        &user,
-                    ).await {
-                        Ok((status, headers, Json(twilio_response))) => {
-                            let history_entry = crate::models::user_models::NewMessageHistory {
-                                user_id: user.id,
-                                role: "assistant".to_string(),
-                                encrypted_content: twilio_response.message.clone(),
-                                tool_name: Some("create_calendar_event".to_string()),
-                                tool_call_id: Some(tool_call.id.clone()), 
-                                tool_calls_json: None,
-                                created_at: chrono::Utc::now().timestamp() as i32,
-                                conversation_id: "".to_string(),
-                            };
-
-                            if let Err(e) = state.user_repository.create_message_history(&history_entry) {
-                                tracing::error!("Failed to store calendar tool message in history: {}", e);
-                            }
-
-                            return (status, headers, Json(twilio_response));
-                        }
-                        Err(e) => {
-                            tracing::error!("Failed to handle calendar event creation: {}", e);
-                            return (
-                                StatusCode::INTERNAL_SERVER_ERROR,
-                                [(axum::http::header::CONTENT_TYPE, "application/json")],
-                                axum::Json(TwilioResponse {
-                                    message: "Failed to process calendar event request".to_string(),
-                                })
-                            );
-                        }
-                    }
-                } else if name == "create_task" {
-                    tracing::debug!("Executing create_task tool call");
-                    let response = crate::tool_call_utils::tasks::handle_create_task(&state, user.id, arguments).await;
-                    tool_answers.insert(tool_call_id, response);
-                } else if name == "fetch_tasks" {
-                    tracing::debug!("Executing fetch_tasks tool call");
-                    let response = crate::tool_call_utils::tasks::handle_fetch_tasks(&state, user.id, arguments).await;
-                    tool_answers.insert(tool_call_id, response);
-                } else if name == "search_chat_contacts" {
-                    tracing::debug!("Executing search_chat_contacts tool call");
-                    let response = crate::tool_call_utils::bridge::handle_search_chat_contacts(
-                        &state,
-                        user.id,
-                        arguments,
-                    ).await;
-                    tool_answers.insert(tool_call_id, response);
-                } else if name == "fetch_recent_messages" {
-                    tracing::debug!("Executing fetch_recent_messages tool call");
-                    let response = crate::tool_call_utils::bridge::handle_fetch_recent_messages(
-                        &state,
-                        user.id,
-                        arguments,
-                    ).await;
-                    tool_answers.insert(tool_call_id, response);
-                } else if name == "fetch_chat_messages" {
-                    tracing::debug!("Executing fetch_chat_messages tool call");
-                    let response = crate::tool_call_utils::bridge::handle_fetch_chat_messages(
-                        &state,
-                        user.id,
-                        arguments,
-                    ).await;
-                    tool_answers.insert(tool_call_id, response);
-                } else if name == "send_chat_message" {
-                    tracing::debug!("Executing send_chat_message tool call");
-                    match crate::tool_call_utils::bridge::handle_send_chat_message(
-                        &state,
-                        user.id,
-                        arguments,
                         &user,
+                        tool_call,
                         image_url.as_deref(),
+                        media_url.as_deref(),
+                        &user_given_info,
                     ).await {
-                        Ok((status, headers, Json(twilio_response))) => {
-                            let history_entry = crate::models::user_models::NewMessageHistory {
-                                user_id: user.id,
-                                role: "assistant".to_string(),
-                                encrypted_content: twilio_response.message.clone(),
-                                tool_name: Some("send_chat_message".to_string()),
-                                tool_call_id: Some(tool_call.id.clone()),
-                                tool_calls_json: None,
-                                created_at: chrono::Utc::now().timestamp() as i32,
-                                conversation_id: "".to_string(),
-                            };
-                            if let Err(e) = state.user_repository.create_message_history(&history_entry) {
-                                tracing::error!("Failed to store send chat message tool message in history: {}", e);
-                            }
-                            return (status, headers, Json(twilio_response));
-                        }
-                        Err(e) => {
-                            tracing::error!("Failed to handle chat message sending: {}", e);
-                            return (
-                                StatusCode::INTERNAL_SERVER_ERROR,
-                                [(axum::http::header::CONTENT_TYPE, "application/json")],
-                                axum::Json(TwilioResponse {
-                                    message: "Failed to process chat message request".to_string(),
-                                })
-                            );
+                        ToolCallOutcome::Answer(answer) => {
+                            step_answers.insert(tool_call.id.clone(), answer);
                         }
+                        ToolCallOutcome::Skipped => {}
+                        // Tools that hand over to the confirmation flow answer the user directly
+                        ToolCallOutcome::Respond(response) => return response,
                     }
-                } else if name == "scan_qr_code" {
-                    tracing::debug!("Executing scan_qr_code tool call with url: {:#?}", image_url);
-                    let response = crate::tool_call_utils::internet::handle_qr_scan(image_url.as_deref()).await;
-                    tool_answers.insert(tool_call_id, response);
-                } else if name == "fetch_calendar_events" {
-                    tracing::debug!("Executing fetch_calendar_events tool call");
-                    let response = crate::tool_call_utils::calendar::handle_fetch_calendar_events(
-                        &state,
-                        user.id,
-                        arguments,
-                    ).await;
-                    tool_answers.insert(tool_call_id, response);
                 }
-            }
-
-
-            let mut follow_up_messages = completion_messages.clone();
-            // Add the assistant's message with tool calls
-            follow_up_messages.push(chat_completion::ChatCompletionMessage {
-                role: chat_completion::MessageRole::assistant,
-                content: chat_completion::Content::Text(result.choices[0].message.content.clone().unwrap_or_default()),
-                name: None,
-                tool_calls: result.choices[0].message.tool_calls.clone(),
-                tool_call_id: None,
-            });
 
+                // Add the assistant's message with tool calls
+                agent_messages.push(chat_completion::ChatCompletionMessage {
+                    role: chat_completion::MessageRole::assistant,
+                    content: chat_completion::Content::Text(result.choices[0].message.content.clone().unwrap_or_default()),
+                    name: None,
+                    tool_calls: Some(tool_calls.clone()),
+                    tool_call_id: None,
+                });
 
-            // Add the tool response
-            if let Some(tool_calls) = &result.choices[0].message.tool_calls {
-                for tool_call in tool_calls {
-                    let tool_answer = match tool_answers.get(&tool_call.id) {
-                        Some(ans) => ans.clone(),
-                        None => "".to_string(),
-                    };
-                    // TODO remove
-                    if user.id == 1 {
-                        println!("response: {}", tool_answer);
-                    }
-                    follow_up_messages.push(chat_completion::ChatCompletionMessage {
+                // Add the tool responses
+                for tool_call in tool_calls.iter() {
+                    let tool_answer = step_answers.get(&tool_call.id).cloned().unwrap_or_default();
+                    agent_messages.push(chat_completion::ChatCompletionMessage {
                         role: chat_completion::MessageRole::tool,
                         content: chat_completion::Content::Text(tool_answer),
                         name: None,
@@ -1090,76 +820,74 @@ pub async fn process_sms(
                         tool_call_id: Some(tool_call.id.clone()),
                     });
                 }
-            }
-
-            let current_time = std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs() as i32;
-
-            // Store tool responses in history
-            for (tool_call_id, tool_response) in tool_answers.iter() {
-                let tool_message = crate::models::user_models::NewMessageHistory {
-                    user_id: user.id,
-                    role: "tool".to_string(),
-                    encrypted_content: tool_response.clone(),
-                    tool_name: None, // We could store this if needed
-                    tool_call_id: Some(tool_call_id.clone()),
-                    tool_calls_json: None,
-                    created_at: current_time,
-                    conversation_id: "".to_string(),
-                };
-
-                if let Err(e) = state.user_repository.create_message_history(&tool_message) {
-                    tracing::error!("Failed to store tool response in history: {}", e);
-                }
-            }
 
+                let current_time = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs() as i32;
 
-            tracing::debug!("Making follow-up request to model with tool call answers");
-            let follow_up_req = chat_completion::ChatCompletionRequest::new(
-                GPT4_O.to_string(),
-                follow_up_messages,
-            )
-            .max_tokens(100); // Consistent token limit for follow-up messages
+                // Store this step's tool responses in history
+                for (tool_call_id, tool_response) in step_answers.iter() {
+                    let tool_message = crate::models::user_models::NewMessageHistory {
+                        user_id: user.id,
+                        role: "tool".to_string(),
+                        encrypted_content: tool_response.clone(),
+                        tool_name: None, // We could store this if needed
+                        tool_call_id: Some(tool_call_id.clone()),
+                        tool_calls_json: None,
+                        created_at: current_time,
+                        conversation_id: "".to_string(),
+                    };
 
-            match client.chat_completion(follow_up_req).await {
-                Ok(follow_up_result) => {
-                    tracing::debug!("Received follow-up response from model");
-                    let response = follow_up_result.choices[0].message.content.clone().unwrap_or_default();
-                    response
-                }
-                Err(e) => {
-                    tracing::error!("Failed to get follow-up completion: {}", e);
-                    fail = true;
-                    tool_answers.values().next()
-                        .map(|ans| format!("Based on my research: {} (you were not charged for this message)", ans.chars().take(370).collect::<String>()))
-                        .unwrap_or_else(|| "I apologize, but I encountered an error processing your request. (you were not charged for this message)".to_string())
+                    if let Err(e) = state.user_repository.create_message_history(&tool_message) {
+                        tracing::error!("Failed to store tool response in history: {}", e);
+                    }
                 }
+
+                tool_answers.extend(step_answers);
+                steps += 1;
+            }
+            Some(chat_completion::FinishReason::tool_calls) => {
+                // Only reachable if the model ignores that no tools were offered on the final call
+                tracing::warn!("Model requested more tools after the agent budget was spent");
+                fail = true;
+                break tool_answers.values().next()
+                    .map(|ans| format!("Based on my research: {} (you were not charged for this message)", ans.chars().take(370).collect::<String>()))
+                    .unwrap_or_else(|| "I apologize, but I couldn't finish your request in time. (you were not charged for this message)".to_string());
+            }
+            Some(chat_completion::FinishReason::length) if steps > 0 => {
+                // Follow-up answers are capped short on purpose, use what we got
+                break result.choices[0].message.content.clone().unwrap_or_default();
+            }
+            Some(chat_completion::FinishReason::length) => {
+                fail = true;
+                break "I apologize, but my response was too long. Could you please ask your question in a more specific way? (you were not charged for this message)".to_string();
+            }
+            Some(chat_completion::FinishReason::content_filter) => {
+                fail = true;
+                break "I apologize, but I cannot provide an answer to that question due to content restrictions. (you were not charged for this message)".to_string();
+            }
+            Some(chat_completion::FinishReason::null) => {
+                fail = true;
+                break "I apologize, but something went wrong while processing your request. (you were not charged for this message)".to_string();
             }
-        }
-        Some(chat_completion::FinishReason::length) => {
-            fail = true;
-            "I apologize, but my response was too long. Could you please ask your question in a more specific way? (you were not charged for this message)".to_string()
-        }
-        Some(chat_completion::FinishReason::content_filter) => {
-            fail = true;
-            "I apologize, but I cannot provide an answer to that question due to content restrictions. (you were not charged for this message)".to_string()
-        }
-        Some(chat_completion::FinishReason::null) => {
-            fail = true;
-            "I apologize, but something went wrong while processing your request. (you were not charged for this message)".to_string()
         }
     };
 
     // Perform evaluation
-    let (eval_result, eval_reason) = crate::tool_call_utils::utils::perform_evaluation(
-        &client,
-        &chat_messages,
-        &payload.body,
-        &final_response,
-        fail
-    ).await;
+    let (eval_result, eval_reason) = match LlmChain::for_purpose(&state, LlmPurpose::Evaluation, Some(user.id)) {
+        Ok(eval_llm) => crate::tool_call_utils::utils::perform_evaluation(
+            &eval_llm,
+            &chat_messages,
+            &payload.body,
+            &final_response,
+            fail
+        ).await,
+        Err(e) => {
+            tracing::error!("Failed to set up evaluation llm chain: {}", e);
+            (!fail, None)
+        }
+    };
 
     let mut final_response_with_notice = final_response.clone();
 
@@ -1170,13 +898,10 @@ pub async fn process_sms(
 
     let processing_time_secs = start_time.elapsed().as_secs(); // Calculate processing time
 
-    // Clean up old message history based on save_context setting
-    let save_context = user_settings.save_context.unwrap_or(0);
-    if let Err(e) = state.user_repository.delete_old_message_history(
-        user.id,
-        save_context as i64
-    ) {
-        tracing::error!("Failed to clean up old message history: {}", e);
+    // From here on nothing is retried, the message is handled
+    store_user_message();
+    if !media.is_empty() {
+        crate::utils::mms_media::delete_media(&state, &user, &media).await;
     }
 
     let current_time = std::time::SystemTime::now()
@@ -1200,6 +925,19 @@ pub async fn process_sms(
         tracing::error!("Failed to store assistant message in history: {}", e);
     }
 
+    // Fold older messages into the rolling summary, then clean up old message history based
+    // on the save_context setting, only what the summary covers is deleted. Users who don't
+    // keep history get no summary of it either
+    let save_context = user_settings.save_context.unwrap_or(0) as i64;
+    if save_context > 0 {
+        let state_clone = state.clone();
+        let user_id = user.id;
+        tokio::spawn(async move {
+            crate::utils::conversation_memory::refresh_summary(&state_clone, user_id).await;
+            crate::utils::conversation_memory::prune_history(&state_clone, user_id, save_context);
+        });
+    }
+
     // If in test mode, skip sending the actual message and return the response directly
     if is_test {
         // Log the test usage without actually sending the message
@@ -1255,6 +993,16 @@ pub async fn process_sms(
         }
     });
 
+    // Pick up anything durable the user mentioned, only for users who keep their history
+    if user_settings.save_context.unwrap_or(0) > 0 {
+        let state_clone = state.clone();
+        let user_id = user.id;
+        let user_message = payload.body.clone();
+        tokio::spawn(async move {
+            crate::utils::user_memory::extract_memories(&state_clone, user_id, &user_message).await;
+        });
+    }
+
     // Send the actual message if not in test mode
     match crate::api::twilio_utils::send_conversation_message(
         &state,
@@ -1281,15 +1029,9 @@ pub async fn process_sms(
                 tracing::error!("Failed to log SMS usage: {}", e);
             }
 
+            // The reply is out, a failure here must not make the worker send it again
             if let Err(e) = crate::utils::usage::deduct_user_credits(&state, user.id, "message", None) {
-                tracing::error!("Failed to deduct user credits: {}", e);
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    [(axum::http::header::CONTENT_TYPE, "application/json")],
-                    axum::Json(TwilioResponse {
-                        message: "Failed to process credits points".to_string(),
-                    })
-                );
+                tracing::error!("Failed to deduct user credits for user {}: {}", user.id, e);
             }
                     
             match state.user_repository.is_credits_under_threshold(user.id) {
@@ -1339,8 +1081,9 @@ pub async fn process_sms(
             ) {
                 tracing::error!("Failed to log SMS usage after send error: {}", log_err);
             }
+            // Not a 5xx, the tools already ran and a retry would run them again
             (
-                StatusCode::INTERNAL_SERVER_ERROR,
+                StatusCode::OK,
                 [(axum::http::header::CONTENT_TYPE, "application/json")],
                 axum::Json(TwilioResponse {
                     message: "Failed to send message".to_string(),
@@ -1350,3 +1093,96 @@ pub async fn process_sms(
     }
 }
 
+
+/// Per-request ceilings for the SMS agent loop. Every extra step is another paid
+/// model call and more waiting for the user, so both are capped.
+pub struct AgentLimits {
+    pub max_steps: usize,                  // tool rounds before a final answer is forced
+    pub max_duration: std::time::Duration, // wall clock budget for the whole request
+    pub max_tokens: i64,                   // total model tokens (our cost proxy) for the request
+}
+
+impl AgentLimits {
+    pub fn from_env() -> Self {
+        let max_steps = env::var("AGENT_MAX_STEPS")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(4);
+        let max_seconds = env::var("AGENT_MAX_SECONDS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(45);
+        let max_tokens = env::var("AGENT_MAX_TOKENS")
+            .ok()
+            .and_then(|v| v.parse::<i64>().ok())
+            .unwrap_or(20_000);
+        Self {
+            max_steps,
+            max_duration: std::time::Duration::from_secs(max_seconds),
+            max_tokens,
+        }
+    }
+}
+
+pub enum ToolCallOutcome {
+    Answer(String),
+    Skipped, // unknown tool or bad arguments, model gets an empty answer
+    Respond((StatusCode, [(axum::http::HeaderName, &'static str); 1], axum::Json<TwilioResponse>)),
+}
+
+async fn execute_tool_call(
+    state: &Arc<AppState>,
+    user: &crate::models::user_models::User,
+    tool_call: &chat_completion::ToolCall,
+    image_url: Option<&str>,
+    media_url: Option<&str>,
+    user_given_info: &str,
+) -> ToolCallOutcome {
+    let name = match &tool_call.function.name {
+        Some(n) => {
+            tracing::debug!("Tool call function name: {}", n);
+            n.as_str()
+        },
+        None => {
+            tracing::debug!("Tool call missing function name, skipping");
+            return ToolCallOutcome::Skipped;
+        },
+    };
+    let arguments = tool_call.function.arguments.as_deref().unwrap_or("");
+
+    let ctx = ToolContext {
+        state,
+        user,
+        channel: Channel::Sms,
+        image_url,
+        media_url,
+        user_given_info,
+    };
+
+    match state.tool_registry.execute(name, &ctx, arguments).await {
+        Some(ToolOutput::Answer(answer)) => ToolCallOutcome::Answer(answer),
+        Some(ToolOutput::Handled(message)) => {
+            // The tool already texted the user (e.g. a confirmation prompt), keep it in history and stop here
+            let history_entry = crate::models::user_models::NewMessageHistory {
+                user_id: user.id,
+                role: "assistant".to_string(),
+                encrypted_content: message.clone(),
+                tool_name: Some(name.to_string()),
+                tool_call_id: Some(tool_call.id.clone()),
+                tool_calls_json: None,
+                created_at: chrono::Utc::now().timestamp() as i32,
+                conversation_id: "".to_string(),
+            };
+            if let Err(e) = state.user_repository.create_message_history(&history_entry) {
+                tracing::error!("Failed to store {} tool message in history: {}", name, e);
+            }
+
+            ToolCallOutcome::Respond((
+                StatusCode::OK,
+                [(axum::http::header::CONTENT_TYPE, "application/json")],
+                axum::Json(TwilioResponse { message })
+            ))
+        }
+        None => ToolCallOutcome::Skipped,
+    }
+}
@@ -487,6 +487,42 @@ pub async fn validate_twilio_signature(
 }
 
 
+/// Fetches an inbound MMS attachment. Media urls need the account credentials unless
+/// HTTP auth is turned off for media in the Twilio console.
+pub async fn download_twilio_media(
+    state: &Arc<AppState>,
+    media_url: &str,
+    user: &User,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    // Same account selection as delete_twilio_message_media
+    let (account_sid, auth_token) = if user.phone_number.starts_with("+1") ||
+       user.phone_number.starts_with("+358") ||
+       user.phone_number.starts_with("+31") ||
+       user.phone_number.starts_with("+44") ||
+       user.phone_number.starts_with("+61") {
+        (
+            env::var("TWILIO_ACCOUNT_SID")?,
+            env::var("TWILIO_AUTH_TOKEN")?,
+        )
+    } else {
+        state.user_core.get_twilio_credentials(user.id)?
+    };
+    let client = Client::new();
+
+    // Redirects to the storage host, reqwest drops the auth header when following it
+    let response = client
+        .get(media_url)
+        .basic_auth(&account_sid, Some(&auth_token))
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        return Err(format!("Failed to download message media: {}", response.status()).into());
+    }
+
+    Ok(response.bytes().await?.to_vec())
+}
+
 pub async fn delete_twilio_message_media(
     state: &Arc<AppState>,
     media_sid: &str,
@@ -613,6 +649,26 @@ pub async fn send_conversation_message(
     body: &str,
     media_sid: Option<&String>,
     user: &User,
+) -> Result<String, Box<dyn Error>> {
+    send_message(state, body, media_sid, None, user).await
+}
+
+/// Sends an MMS with an image Twilio can fetch itself, e.g. a login QR code from /api/qr.
+pub async fn send_conversation_mms(
+    state: &Arc<AppState>,
+    body: &str,
+    public_media_url: &str,
+    user: &User,
+) -> Result<String, Box<dyn Error>> {
+    send_message(state, body, None, Some(public_media_url), user).await
+}
+
+async fn send_message(
+    state: &Arc<AppState>,
+    body: &str,
+    media_sid: Option<&String>,
+    public_media_url: Option<&str>,
+    user: &User,
 ) -> Result<String, Box<dyn Error>> {
     let history_entry = crate::models::user_models::NewMessageHistory {
         user_id: user.id,
@@ -728,6 +784,8 @@ pub async fn send_conversation_message(
             account_sid, media_id
         );
         form_data.push(("MediaUrl", &media_url));
+    } else if let Some(url) = public_media_url {
+        form_data.push(("MediaUrl", url));
     }
 
     let resp = client
@@ -751,7 +809,7 @@ pub async fn send_conversation_message(
     let response: MessageResponse = resp.json().await?;
 
     tracing::debug!("Successfully sent message{} with SID: {}", 
-        if media_sid.is_some() { " with media" } else { "" },
+        if media_sid.is_some() || public_media_url.is_some() { " with media" } else { "" },
         response.sid);
 
     let state_clone = state.clone();
@@ -52,6 +52,26 @@ pub struct UsageLogResponse {
     zero_credits_timestamp: Option<i32>,
 }
 
+#[derive(Serialize)]
+pub struct FailedSmsJobResponse {
+    id: i32,
+    user_id: Option<i32>,
+    attempts: i32,
+    last_error: Option<String>,
+    created_at: i32,
+    failed_at: i32,
+}
+
+#[derive(Serialize)]
+pub struct BridgeStatusEventResponse {
+    id: i32,
+    user_id: i32,
+    bridge_type: String,
+    status: String,
+    detail: Option<String>,
+    created_at: i32,
+}
+
 use crate::AppState;
 
 
@@ -394,6 +414,52 @@ pub async fn get_usage_logs(
     Ok(Json(response_logs))
 }
 
+pub async fn get_failed_sms_jobs(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<FailedSmsJobResponse>>, (StatusCode, Json<serde_json::Value>)> {
+    let jobs = state.user_repository.get_failed_sms_jobs()
+        .map_err(|e| (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": format!("Database error: {}", e)}))
+        ))?;
+
+    let response_jobs: Vec<FailedSmsJobResponse> = jobs.into_iter()
+        .map(|job| FailedSmsJobResponse {
+            id: job.id.unwrap_or(0),
+            user_id: job.user_id,
+            attempts: job.attempts,
+            last_error: job.last_error,
+            created_at: job.created_at,
+            failed_at: job.updated_at,
+        })
+        .collect();
+
+    Ok(Json(response_jobs))
+}
+
+pub async fn retry_sms_job(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Path(job_id): axum::extract::Path<i32>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let requeued = state.user_repository.requeue_failed_sms_job(job_id)
+        .map_err(|e| (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": format!("Database error: {}", e)}))
+        ))?;
+
+    if !requeued {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "No failed sms job with that id"}))
+        ));
+    }
+    state.sms_job_notify.notify_one();
+
+    Ok(Json(json!({
+        "message": "SMS job requeued"
+    })))
+}
+
 pub async fn test_sms_with_image(
     State(state): State<Arc<AppState>>,
     mut multipart: Multipart,
@@ -469,6 +535,7 @@ pub async fn test_sms_with_image(
         media_url0: Some(image_data_url.as_ref().map(|(data_url, _)| data_url.clone()).unwrap_or_default()),
         media_content_type0: Some("image/jpeg".to_string()),
         message_sid: "".to_string(),
+        other_fields: std::collections::HashMap::new(),
     };
     println!("mock_payload.num_media: {:#?}",mock_payload.num_media);
     // Process the SMS using the existing handler with test mode
@@ -518,6 +585,7 @@ pub async fn test_sms(
         media_url0: None,
         media_content_type0: None,
         message_sid: "".to_string(),
+        other_fields: std::collections::HashMap::new(),
     };
 
     // Process the SMS using the existing handler with test mode
@@ -543,4 +611,25 @@ pub async fn test_sms(
     }
 }
 
+pub async fn get_bridge_status_history(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<BridgeStatusEventResponse>>, (StatusCode, Json<serde_json::Value>)> {
+    let events = state.user_repository.get_bridge_status_history(500)
+        .map_err(|e| (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": format!("Database error: {}", e)}))
+        ))?;
 
+    let response_events: Vec<BridgeStatusEventResponse> = events.into_iter()
+        .map(|event| BridgeStatusEventResponse {
+            id: event.id.unwrap_or(0),
+            user_id: event.user_id,
+            bridge_type: event.bridge_type,
+            status: event.status,
+            detail: event.detail,
+            created_at: event.created_at,
+        })
+        .collect();
+
+    Ok(Json(response_events))
+}
@@ -0,0 +1,876 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::Json as AxumJson,
+};
+use matrix_sdk::{
+    Client as MatrixClient,
+    config::SyncSettings as MatrixSyncSettings,
+    media::{MediaFormat, MediaRequestParameters},
+    ruma::{
+        api::client::room::create_room::v3::Request as CreateRoomRequest,
+        events::room::message::{RoomMessageEventContent, SyncRoomMessageEvent, MessageType},
+        events::room::MediaSource,
+        events::AnySyncTimelineEvent,
+        OwnedRoomId, OwnedUserId,
+    },
+};
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+use anyhow::{anyhow, Result};
+use tokio::time::{sleep, Duration};
+use crate::{
+    AppState,
+    handlers::auth_middleware::AuthUser,
+    models::user_models::NewBridge,
+    utils::{
+        bridge_service::{require_service, BridgeService, LoginArtifact},
+        matrix_auth,
+    },
+};
+
+use tokio::fs;
+use std::path::Path;
+
+// Connect, status, resync and disconnect for every bridge, the per service
+// differences live in utils::bridge_service
+
+type ApiError = (StatusCode, AxumJson<serde_json::Value>);
+
+// Routes are /api/auth/{service}/..., unknown names are a 404
+fn resolve_service(name: &str) -> Result<&'static dyn BridgeService, ApiError> {
+    require_service(name).map_err(|e| (StatusCode::NOT_FOUND, AxumJson(json!({"error": e.to_string()}))))
+}
+
+#[derive(Deserialize)]
+pub struct LoginInputRequest {
+    pub input: String,
+}
+
+// Helper function to detect the one-time key conflict error
+fn is_one_time_key_conflict(error: &anyhow::Error) -> bool {
+    if let Some(http_err) = error.downcast_ref::<matrix_sdk::HttpError>() {
+        let error_str = http_err.to_string();
+        return error_str.contains("One time key") && error_str.contains("already exists");
+    }
+    false
+}
+
+// Helper function to get the store path
+fn get_store_path(username: &str) -> Result<String> {
+    let persistent_store_path = std::env::var("MATRIX_HOMESERVER_PERSISTENT_STORE_PATH")
+        .map_err(|_| anyhow!("MATRIX_HOMESERVER_PERSISTENT_STORE_PATH not set"))?;
+    Ok(format!("{}/{}", persistent_store_path, username))
+}
+
+fn current_time() -> i32 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i32
+}
+
+// Wrapper function with retry logic
+async fn connect_with_retry(
+    service: &'static dyn BridgeService,
+    client: &mut Arc<MatrixClient>,
+    bridge_bot: &str,
+    phone_number: &str,
+    user_id: i32,
+    state: &Arc<AppState>,
+) -> Result<(OwnedRoomId, LoginArtifact)> {
+    const MAX_RETRIES: u32 = 3;
+    const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+    let username = client.user_id()
+        .ok_or_else(|| anyhow!("User ID not available"))?
+        .localpart()
+        .to_string();
+
+    for retry_count in 0..MAX_RETRIES {
+        match connect(service, client, bridge_bot, phone_number).await {
+            Ok(result) => return Ok(result),
+            Err(e) => {
+                if retry_count < MAX_RETRIES - 1 && is_one_time_key_conflict(&e) {
+                    tracing::warn!(
+                        "One-time key conflict detected for user {} (attempt {}/{}), resetting client store",
+                        user_id,
+                        retry_count + 1,
+                        MAX_RETRIES
+                    );
+
+                    // Clear the store
+                    let store_path = get_store_path(&username)?;
+                    if Path::new(&store_path).exists() {
+                        fs::remove_dir_all(&store_path).await?;
+                        sleep(Duration::from_millis(500)).await; // Small delay before recreation
+                        fs::create_dir_all(&store_path).await?;
+                        tracing::info!("Cleared store directory: {}", store_path);
+                    }
+
+                    // Add delay before retry
+                    sleep(RETRY_DELAY).await;
+
+                    // Reinitialize client (bypass cache since we're recovering from an error)
+                    match matrix_auth::get_client(user_id, &state).await {
+                        Ok(new_client) => {
+                            *client = new_client.into(); // Update the client reference
+                            tracing::info!("Client reinitialized, retrying operation");
+                            continue;
+                        },
+                        Err(init_err) => {
+                            tracing::error!("Failed to reinitialize client: {}", init_err);
+                            return Err(init_err);
+                        }
+                    }
+                } else if is_one_time_key_conflict(&e) {
+                    return Err(anyhow!("Failed after {} attempts to resolve one-time key conflict: {}", MAX_RETRIES, e));
+                } else {
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    Err(anyhow!("Exceeded maximum retry attempts ({})", MAX_RETRIES))
+}
+
+async fn connect(
+    service: &'static dyn BridgeService,
+    client: &MatrixClient,
+    bridge_bot: &str,
+    phone_number: &str,
+) -> Result<(OwnedRoomId, LoginArtifact)> {
+    tracing::debug!("🚀 Starting {} connection process", service.display_name());
+
+    let bot_user_id = OwnedUserId::try_from(bridge_bot)?;
+
+    let request = CreateRoomRequest::new();
+    let response = client.create_room(request).await?;
+    let room_id = response.room_id();
+
+    tracing::debug!("🏠 Created room with ID: {}", room_id);
+
+    let room = client.get_room(&room_id).ok_or(anyhow!("Room not found"))?;
+
+    tracing::debug!("🤖 Inviting bot user: {}", bot_user_id);
+    room.invite_user_by_id(&bot_user_id).await?;
+
+    // Single sync to get the invitation processed
+    client.sync_once(MatrixSyncSettings::default().timeout(Duration::from_secs(5))).await?;
+
+    for attempt in 1..=15 {
+        tracing::debug!("🔍 Check attempt {}/15 for bot join status", attempt);
+        let members = room.members(matrix_sdk::RoomMemberships::JOIN).await?;
+        if members.iter().any(|m| m.user_id() == bot_user_id) {
+            tracing::debug!("✅ Bot has joined the room");
+            break;
+        }
+        sleep(Duration::from_millis(500)).await;
+    }
+
+    // Quick membership check
+    let members = room.members(matrix_sdk::RoomMemberships::empty()).await?;
+    if !members.iter().any(|m| m.user_id() == bot_user_id) {
+        tracing::error!("❌ Bot failed to join room after all attempts");
+        return Err(anyhow!("Bot {} failed to join room", bot_user_id));
+    }
+
+    // Get rid of a previous login that was never finished
+    if let Some(cancel_command) = service.cancel_command() {
+        room.send(RoomMessageEventContent::text_plain(cancel_command)).await?;
+    }
+
+    let login_command = service.login_command(phone_number);
+    tracing::debug!("📤 Sending {} login command", service.display_name());
+    room.send(RoomMessageEventContent::text_plain(&login_command)).await?;
+
+    let mut artifact = None;
+    tracing::debug!("⏳ Waiting for the {} login details", service.display_name());
+
+    // Use shorter sync timeout for faster response
+    let sync_settings = MatrixSyncSettings::default().timeout(Duration::from_millis(1500));
+
+    'attempts: for attempt in 1..=60 {
+        tracing::debug!("📡 Sync attempt #{}/60", attempt);
+        client.sync_once(sync_settings.clone()).await?;
+
+        if let Some(room) = client.get_room(&room_id) {
+            let mut options = matrix_sdk::room::MessagesOptions::new(matrix_sdk::ruma::api::Direction::Backward);
+            options.limit = matrix_sdk::ruma::UInt::new(5).unwrap();
+            let messages = room.messages(options).await?;
+
+            for msg in messages.chunk.iter() {
+                let Ok(event) = msg.raw().deserialize() else { continue };
+                if event.sender() != bot_user_id {
+                    continue;
+                }
+                if let AnySyncTimelineEvent::MessageLike(
+                    matrix_sdk::ruma::events::AnySyncMessageLikeEvent::RoomMessage(SyncRoomMessageEvent::Original(original_event))
+                ) = event {
+                    if let Some(found) = service.login_artifact(&original_event.content.msgtype)? {
+                        tracing::debug!("🔑 Found {} login details", service.display_name());
+                        artifact = Some(found);
+                        break 'attempts;
+                    }
+                }
+            }
+        }
+
+        // Balanced delay - fast enough for responsiveness, long enough for user input
+        sleep(Duration::from_millis(500)).await;
+    }
+
+    let artifact = artifact.ok_or(anyhow!("{} login details not received within 30 seconds. Please try again.", service.display_name()))?;
+    Ok((room_id.into(), artifact))
+}
+
+/// Logs the user in to the service's bridge and returns what they need to finish it, e.g. the
+/// WhatsApp pairing code. A task watches the bot until the login goes through. Also used by
+/// CONNECT and RECONNECT over sms, a bridge that isn't connected anymore is replaced.
+/// With `confirm_by_sms` the user is texted once the login went through or failed.
+pub async fn begin_connection(
+    service: &'static dyn BridgeService,
+    state: &Arc<AppState>,
+    user_id: i32,
+    confirm_by_sms: bool,
+) -> Result<LoginArtifact> {
+    tracing::debug!("🚀 Starting {} connection process for user {}", service.display_name(), user_id);
+
+    if let Some(bridge) = state.user_repository.get_bridge(user_id, service.name())? {
+        if bridge.status == "connected" {
+            return Err(anyhow!("{} is already connected", service.display_name()));
+        }
+        state.user_repository.delete_bridge(user_id, service.name())?;
+    }
+
+    // Only some bots log in with the phone number, it's always set though
+    let phone_number = state
+        .user_core
+        .find_by_id(user_id)?
+        .ok_or_else(|| anyhow!("Phone number not set"))?
+        .phone_number;
+
+    // Get or create Matrix client using the centralized function
+    let client = matrix_auth::get_cached_client(user_id, state)
+        .await
+        .map_err(|e| anyhow!("Failed to initialize Matrix client: {}", e))?;
+
+    let bridge_bot = service.bot_user_id()?;
+
+    tracing::debug!("🔗 Connecting to {} bridge...", service.display_name());
+    let mut client_clone = Arc::clone(&client);
+    let (room_id, artifact) = connect_with_retry(
+        service,
+        &mut client_clone,
+        &bridge_bot,
+        &phone_number,
+        user_id,
+        state,
+    )
+    .await?;
+
+    tracing::info!("Generated {} login details", service.display_name());
+
+    let new_bridge = NewBridge {
+        user_id,
+        bridge_type: service.name().to_string(),
+        status: "connecting".to_string(),
+        room_id: Some(room_id.to_string()),
+        data: None,
+        created_at: Some(current_time()),
+    };
+    state.user_repository.create_bridge(new_bridge)?;
+
+    // Spawn a task to monitor the connection status
+    let state_clone = state.clone();
+    let room_id_clone = room_id.clone();
+    let client_clone = client.clone();
+
+    tokio::spawn(async move {
+        match monitor_connection(
+            service,
+            &client_clone,
+            &room_id_clone,
+            &bridge_bot,
+            user_id,
+            state_clone.clone(),
+        ).await {
+            Ok(_) => {
+                tracing::info!("{} connection monitoring completed successfully for user {}", service.display_name(), user_id);
+                if confirm_by_sms {
+                    let message = format!("{} is connected, its messages will now reach you here.", service.display_name());
+                    send_connection_sms(&state_clone, user_id, &message).await;
+                }
+            },
+            Err(e) => {
+                tracing::error!("{} connection monitoring failed for user {}: {}", service.display_name(), user_id, e);
+                if confirm_by_sms {
+                    let message = format!(
+                        "{} didn't connect. Text CONNECT {} to try again.",
+                        service.display_name(),
+                        service.name().to_uppercase()
+                    );
+                    send_connection_sms(&state_clone, user_id, &message).await;
+                }
+            }
+        }
+    });
+
+    Ok(artifact)
+}
+
+async fn send_connection_sms(state: &Arc<AppState>, user_id: i32, message: &str) {
+    let user = match state.user_core.find_by_id(user_id) {
+        Ok(Some(user)) => user,
+        Ok(None) => return,
+        Err(e) => {
+            tracing::error!("Failed to get user {} for connection sms: {}", user_id, e);
+            return;
+        }
+    };
+    // Box<dyn Error> isn't Send, turn it into a string right away
+    if let Err(e) = crate::api::twilio_utils::send_conversation_message(state, message, None, &user).await.map_err(|e| e.to_string()) {
+        tracing::error!("Failed to send connection sms to user {}: {}", user_id, e);
+    }
+}
+
+/// Downloads the QR code image the bot posted, so it can be texted to the user as an MMS.
+pub async fn download_qr_code(state: &Arc<AppState>, user_id: i32, mxc_url: &str) -> Result<Vec<u8>> {
+    let client = matrix_auth::get_cached_client(user_id, state)
+        .await
+        .map_err(|e| anyhow!("Failed to initialize Matrix client: {}", e))?;
+    let request = MediaRequestParameters {
+        source: MediaSource::Plain(mxc_url.into()),
+        format: MediaFormat::File,
+    };
+    client.media().get_media_content(&request, true)
+        .await
+        .map_err(|e| anyhow!("Failed to download QR code: {}", e))
+}
+
+pub async fn start_connection(
+    axum::extract::Path(service): axum::extract::Path<String>,
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+) -> Result<AxumJson<serde_json::Value>, ApiError> {
+    let service = resolve_service(&service)?;
+    let artifact = begin_connection(service, &state, auth_user.user_id, false)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to connect to {} bridge: {}", service.display_name(), e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                AxumJson(json!({"error": format!("Failed to connect to {} bridge: {}", service.display_name(), e)})),
+            )
+        })?;
+
+    // Each service has its own field, e.g. pairing_code for WhatsApp
+    let mut response = serde_json::Map::new();
+    response.insert(artifact.response_key().to_string(), json!(artifact.value()));
+    Ok(AxumJson(serde_json::Value::Object(response)))
+}
+
+/// Passes what the user pasted, e.g. a token or cookies, to the bot and returns the bot's answer.
+/// That is either the next question or the login result, the monitor task picks up the login itself.
+pub async fn send_login_input(
+    axum::extract::Path(service): axum::extract::Path<String>,
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    AxumJson(request): AxumJson<LoginInputRequest>,
+) -> Result<AxumJson<serde_json::Value>, ApiError> {
+    let service = resolve_service(&service)?;
+    let input = request.input.trim().to_string();
+    if input.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            AxumJson(json!({"error": "Nothing to send"})),
+        ));
+    }
+
+    let bridge = state.user_repository.get_bridge(auth_user.user_id, service.name())
+        .map_err(|e| {
+            tracing::error!("Failed to get {} bridge: {}", service.display_name(), e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                AxumJson(json!({"error": format!("Failed to get {} bridge info", service.display_name())})),
+            )
+        })?;
+    let Some(bridge) = bridge.filter(|bridge| bridge.status == "connecting") else {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            AxumJson(json!({"error": format!("No {} login in progress", service.display_name())})),
+        ));
+    };
+
+    let bot_user_id = service.bot_user_id()
+        .and_then(|bot| OwnedUserId::try_from(bot.as_str()).map_err(|e| anyhow!(e)))
+        .map_err(|e| {
+            tracing::error!("No {} bridge bot configured: {}", service.display_name(), e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                AxumJson(json!({"error": format!("{} bridge is not configured", service.display_name())})),
+            )
+        })?;
+
+    let client = matrix_auth::get_cached_client(auth_user.user_id, &state)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to get Matrix client: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                AxumJson(json!({"error": format!("Failed to initialize Matrix client: {}", e)})),
+            )
+        })?;
+
+    let room_id = OwnedRoomId::try_from(bridge.room_id.unwrap_or_default())
+        .map_err(|_| (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            AxumJson(json!({"error": "Invalid room ID format"})),
+        ))?;
+    let Some(room) = client.get_room(&room_id) else {
+        return Err((
+            StatusCode::NOT_FOUND,
+            AxumJson(json!({"error": format!("{} bridge room not found", service.display_name())})),
+        ));
+    };
+
+    // A bit of slack for the homeserver clock
+    let sent_at = chrono::Utc::now().timestamp_millis() - 2000;
+    if let Err(e) = room.send(RoomMessageEventContent::text_plain(&input)).await {
+        tracing::error!("Failed to send {} login input: {}", service.display_name(), e);
+        return Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            AxumJson(json!({"error": "Failed to send to the bridge"})),
+        ));
+    }
+
+    match wait_for_bot_reply(&room, &bot_user_id, sent_at).await {
+        Some(reply) => Ok(AxumJson(json!({
+            "connected": service.is_login_success(&reply),
+            "reply": reply,
+        }))),
+        None => Ok(AxumJson(json!({
+            "reply": null,
+            "connected": false,
+        }))),
+    }
+}
+
+/// Waits up to 20 seconds for the bot to answer something sent after `sent_at` (ms).
+/// The monitor or sync task keeps syncing, /messages is enough to see the answer.
+async fn wait_for_bot_reply(room: &matrix_sdk::room::Room, bot_user_id: &OwnedUserId, sent_at: i64) -> Option<String> {
+    for _ in 0..20 {
+        sleep(Duration::from_secs(1)).await;
+
+        let mut options = matrix_sdk::room::MessagesOptions::new(matrix_sdk::ruma::api::Direction::Backward);
+        options.limit = matrix_sdk::ruma::UInt::new(10).unwrap();
+        let Ok(messages) = room.messages(options).await else { continue };
+
+        let mut replies: Vec<String> = messages.chunk.iter()
+            .filter_map(|msg| match msg.raw().deserialize().ok()? {
+                AnySyncTimelineEvent::MessageLike(
+                    matrix_sdk::ruma::events::AnySyncMessageLikeEvent::RoomMessage(SyncRoomMessageEvent::Original(e))
+                ) if e.sender == *bot_user_id && i64::from(e.origin_server_ts.0) >= sent_at => match e.content.msgtype {
+                    MessageType::Text(text_content) => Some(text_content.body),
+                    MessageType::Notice(notice_content) => Some(notice_content.body),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect();
+
+        if !replies.is_empty() {
+            // Oldest first so multi part answers read in order
+            replies.reverse();
+            return Some(replies.join("\n"));
+        }
+    }
+    None
+}
+
+/// Asks the bridge bot whether the user's login is still alive. Errors when the bot can't be
+/// reached or doesn't say either way.
+pub async fn check_connection(service: &'static dyn BridgeService, state: &Arc<AppState>, user_id: i32) -> Result<bool> {
+    let bridge = state.user_repository.get_bridge(user_id, service.name())?
+        .ok_or_else(|| anyhow!("{} bridge not found", service.display_name()))?;
+    let bot_user_id = OwnedUserId::try_from(service.bot_user_id()?.as_str())?;
+    let room_id = OwnedRoomId::try_from(bridge.room_id.unwrap_or_default())
+        .map_err(|_| anyhow!("Invalid room ID format"))?;
+
+    let client = matrix_auth::get_cached_client(user_id, state).await?;
+    let room = client.get_room(&room_id)
+        .ok_or_else(|| anyhow!("{} bridge room not found", service.display_name()))?;
+
+    // A bit of slack for the homeserver clock
+    let sent_at = chrono::Utc::now().timestamp_millis() - 2000;
+    room.send(RoomMessageEventContent::text_plain(service.ping_command())).await?;
+
+    let reply = wait_for_bot_reply(&room, &bot_user_id, sent_at)
+        .await
+        .ok_or_else(|| anyhow!("{} bridge bot did not answer", service.display_name()))?;
+    service.ping_result(&reply)
+        .ok_or_else(|| anyhow!("Unclear answer from the {} bridge bot: {}", service.display_name(), reply))
+}
+
+/// Sends the sync commands again, without starting another sync loop.
+pub async fn resync_bridge(service: &'static dyn BridgeService, state: &Arc<AppState>, user_id: i32) -> Result<()> {
+    let bridge = state.user_repository.get_bridge(user_id, service.name())?
+        .ok_or_else(|| anyhow!("{} bridge not found", service.display_name()))?;
+    let room_id = OwnedRoomId::try_from(bridge.room_id.unwrap_or_default())
+        .map_err(|_| anyhow!("Invalid room ID format"))?;
+
+    let client = matrix_auth::get_cached_client(user_id, state).await?;
+    let room = client.get_room(&room_id)
+        .ok_or_else(|| anyhow!("{} bridge room not found", service.display_name()))?;
+    send_sync_commands(service, &room).await
+}
+
+pub async fn get_status(
+    axum::extract::Path(service): axum::extract::Path<String>,
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+) -> Result<AxumJson<serde_json::Value>, ApiError> {
+    let service = resolve_service(&service)?;
+    tracing::debug!("📊 Checking {} status for user {}", service.display_name(), auth_user.user_id);
+    let bridge = state.user_repository.get_bridge(auth_user.user_id, service.name())
+        .map_err(|e| {
+            tracing::error!("Failed to get {} bridge status: {}", service.display_name(), e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                AxumJson(json!({"error": format!("Failed to get {} status", service.display_name())})),
+            )
+        })?;
+
+    match bridge {
+        Some(bridge) => Ok(AxumJson(json!({
+            "connected": bridge.status == "connected",
+            "status": bridge.status,
+            "created_at": bridge.created_at.unwrap_or(0),
+        }))),
+        None => Ok(AxumJson(json!({
+            "connected": false,
+            "status": "not_connected",
+            "created_at": 0,
+        }))),
+    }
+}
+
+/// Sends the service's sync commands so portals get created for existing chats
+async fn send_sync_commands(service: &'static dyn BridgeService, room: &matrix_sdk::room::Room) -> Result<()> {
+    for (i, command) in service.sync_commands().iter().enumerate() {
+        if i > 0 {
+            sleep(Duration::from_millis(500)).await;
+        }
+        room.send(RoomMessageEventContent::text_plain(*command)).await?;
+        tracing::debug!("Sent {} sync command: {}", service.display_name(), command);
+    }
+    Ok(())
+}
+
+async fn monitor_connection(
+    service: &'static dyn BridgeService,
+    client: &MatrixClient,
+    room_id: &OwnedRoomId,
+    bridge_bot: &str,
+    user_id: i32,
+    state: Arc<AppState>,
+) -> Result<(), anyhow::Error> {
+    tracing::debug!("👀 Starting {} connection monitoring for user {} in room {}", service.display_name(), user_id, room_id);
+    let bot_user_id = OwnedUserId::try_from(bridge_bot)?;
+
+    let sync_settings = MatrixSyncSettings::default().timeout(Duration::from_secs(10));
+    let (attempts, interval) = service.monitor_schedule();
+
+    for attempt in 1..=attempts {
+        tracing::debug!("🔄 Monitoring attempt #{} for user {}", attempt, user_id);
+        if let Some(command) = service.login_poll_command() {
+            if let Some(room) = client.get_room(room_id) {
+                room.send(RoomMessageEventContent::text_plain(command)).await?;
+            }
+        }
+
+        let _ = client.sync_once(sync_settings.clone()).await?;
+
+        if let Some(room) = client.get_room(room_id) {
+            let mut options = matrix_sdk::room::MessagesOptions::new(matrix_sdk::ruma::api::Direction::Backward);
+            options.limit = matrix_sdk::ruma::UInt::new(20).unwrap();
+            let messages = room.messages(options).await?;
+
+            for msg in messages.chunk {
+                let Ok(event) = msg.raw().deserialize() else { continue };
+                if event.sender() != bot_user_id {
+                    continue;
+                }
+                let AnySyncTimelineEvent::MessageLike(
+                    matrix_sdk::ruma::events::AnySyncMessageLikeEvent::RoomMessage(SyncRoomMessageEvent::Original(original_event))
+                ) = event else { continue };
+
+                let content = match original_event.content.msgtype {
+                    MessageType::Text(text_content) => text_content.body,
+                    MessageType::Notice(notice_content) => notice_content.body,
+                    _ => continue,
+                };
+
+                if service.is_login_success(&content) {
+                    tracing::debug!("🎉 {} successfully connected for user {}", service.display_name(), user_id);
+
+                    let new_bridge = NewBridge {
+                        user_id,
+                        bridge_type: service.name().to_string(),
+                        status: "connected".to_string(),
+                        room_id: Some(room_id.to_string()),
+                        data: None,
+                        created_at: Some(current_time()),
+                    };
+
+                    state.user_repository.delete_bridge(user_id, service.name())?;
+                    state.user_repository.create_bridge(new_bridge)?;
+
+                    // Add client to app state and start sync
+                    let mut matrix_clients = state.matrix_clients.lock().await;
+                    let mut sync_tasks = state.matrix_sync_tasks.lock().await;
+
+                    // Add event handlers before storing/cloning the client
+                    use matrix_sdk::ruma::events::room::message::OriginalSyncRoomMessageEvent;
+                    use matrix_sdk::room::Room;
+
+                    let state_for_handler = Arc::clone(&state);
+                    client.add_event_handler(move |ev: OriginalSyncRoomMessageEvent, room: Room, client| {
+                        let state = Arc::clone(&state_for_handler);
+                        async move {
+                            tracing::debug!("📨 Received message in room {}: {:?}", room.room_id(), ev);
+                            crate::utils::bridge::handle_bridge_message(ev, room, client, state).await;
+                        }
+                    });
+
+                    let client_arc = Arc::new(client.clone());
+                    matrix_clients.insert(user_id, client_arc.clone());
+
+                    let sync_settings = MatrixSyncSettings::default()
+                        .timeout(Duration::from_secs(30))
+                        .full_state(true);
+
+                    let state_for_sync = Arc::clone(&state);
+                    let handle = tokio::spawn(async move {
+                        loop {
+                            // a new span every time the sync (re)starts, see message_index::IndexCoverage
+                            let coverage = crate::utils::message_index::IndexCoverage::new(&state_for_sync, user_id);
+                            let synced = move |_| {
+                                coverage.synced();
+                                async { matrix_sdk::LoopCtrl::Continue }
+                            };
+                            match client_arc.sync_with_callback(sync_settings.clone(), synced).await {
+                                Ok(_) => {
+                                    tracing::debug!("Sync completed normally for user {}", user_id);
+                                    tokio::time::sleep(Duration::from_secs(1)).await;
+                                },
+                                Err(e) => {
+                                    tracing::error!("Matrix sync error for user {}: {}", user_id, e);
+                                    tokio::time::sleep(Duration::from_secs(30)).await;
+                                }
+                            }
+                        }
+                    });
+
+                    sync_tasks.insert(user_id, handle);
+
+                    if let Some(room) = client.get_room(room_id) {
+                        send_sync_commands(service, &room).await?;
+                    } else {
+                        tracing::error!("{} room not found for sync commands", service.display_name());
+                    }
+
+                    return Ok(());
+                }
+
+                if service.is_login_failure(&content) {
+                    tracing::error!("❌ {} connection failed for user {}: {}", service.display_name(), user_id, content);
+                    state.user_repository.delete_bridge(user_id, service.name())?;
+                    return Err(anyhow!("{} connection failed: {}", service.display_name(), content));
+                }
+            }
+        }
+
+        sleep(interval).await;
+    }
+
+    // If we reach here, connection timed out
+    state.user_repository.delete_bridge(user_id, service.name())?;
+    Err(anyhow!(
+        "{} connection timed out after {} minutes",
+        service.display_name(),
+        (interval * attempts).as_secs() / 60
+    ))
+}
+
+pub async fn resync(
+    axum::extract::Path(service): axum::extract::Path<String>,
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+) -> Result<AxumJson<serde_json::Value>, ApiError> {
+    let service = resolve_service(&service)?;
+    tracing::info!("🔄 Starting {} resync process for user {}", service.display_name(), auth_user.user_id);
+
+    let bridge = state.user_repository.get_bridge(auth_user.user_id, service.name())
+        .map_err(|e| {
+            tracing::error!("Failed to get {} bridge: {}", service.display_name(), e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                AxumJson(json!({"error": format!("Failed to get {} bridge info", service.display_name())})),
+            )
+        })?;
+
+    let Some(bridge) = bridge else {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            AxumJson(json!({"error": format!("{} is not connected", service.display_name())})),
+        ));
+    };
+
+    let client = matrix_auth::get_cached_client(auth_user.user_id, &state)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to get Matrix client: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                AxumJson(json!({"error": format!("Failed to initialize Matrix client: {}", e)})),
+            )
+        })?;
+
+    let room_id = OwnedRoomId::try_from(bridge.room_id.unwrap_or_default())
+        .map_err(|_| (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            AxumJson(json!({"error": "Invalid room ID format"})),
+        ))?;
+
+    let Some(room) = client.get_room(&room_id) else {
+        return Err((
+            StatusCode::NOT_FOUND,
+            AxumJson(json!({"error": format!("{} bridge room not found", service.display_name())})),
+        ));
+    };
+
+    // Start continuous sync in the background
+    let sync_client = client.clone();
+    tokio::spawn(async move {
+        tracing::info!("🔄 Starting continuous Matrix sync for {} bridge", service.display_name());
+        let sync_settings = MatrixSyncSettings::default()
+            .timeout(Duration::from_secs(30))
+            .full_state(true);
+
+        if let Err(e) = sync_client.sync(sync_settings).await {
+            tracing::error!("❌ Matrix sync error: {}", e);
+        }
+        tracing::info!("🛑 Continuous sync ended");
+    });
+
+    // Give the sync a moment to start up
+    sleep(Duration::from_secs(2)).await;
+
+    if let Err(e) = send_sync_commands(service, &room).await {
+        tracing::error!("Failed to send {} sync commands: {}", service.display_name(), e);
+        return Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            AxumJson(json!({"error": "Failed to send sync commands"})),
+        ));
+    }
+
+    tracing::debug!("✅ {} resync process completed for user {}", service.display_name(), auth_user.user_id);
+    Ok(AxumJson(json!({
+        "message": format!("{} resync initiated successfully", service.display_name())
+    })))
+}
+
+pub async fn disconnect(
+    axum::extract::Path(service): axum::extract::Path<String>,
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+) -> Result<AxumJson<serde_json::Value>, ApiError> {
+    let service = resolve_service(&service)?;
+    tracing::debug!("🔌 Starting {} disconnection process for user {}", service.display_name(), auth_user.user_id);
+
+    let bridge = state.user_repository.get_bridge(auth_user.user_id, service.name())
+        .map_err(|e| {
+            tracing::error!("Failed to get {} bridge: {}", service.display_name(), e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                AxumJson(json!({"error": format!("Failed to get {} bridge info", service.display_name())})),
+            )
+        })?;
+
+    let Some(bridge) = bridge else {
+        return Ok(AxumJson(json!({
+            "message": format!("{} was not connected", service.display_name())
+        })));
+    };
+
+    let client = matrix_auth::get_cached_client(auth_user.user_id, &state)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to get or create Matrix client: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                AxumJson(json!({"error": format!("Failed to initialize Matrix client: {}", e)})),
+            )
+        })?;
+
+    let room_id = OwnedRoomId::try_from(bridge.room_id.unwrap_or_default())
+        .map_err(|_| (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            AxumJson(json!({"error": "Invalid room ID format"})),
+        ))?;
+
+    if let Some(room) = client.get_room(&room_id) {
+        for command in service.logout_commands() {
+            tracing::debug!("📤 Sending {} command: {}", service.display_name(), command);
+            // Not every bot knows every cleanup command, keep going either way
+            if let Err(e) = room.send(RoomMessageEventContent::text_plain(*command)).await {
+                tracing::error!("Failed to send {} command: {}", command, e);
+            }
+            // Wait a moment for the bot to process it
+            sleep(Duration::from_secs(5)).await;
+        }
+    }
+
+    state.user_repository.delete_bridge(auth_user.user_id, service.name())
+        .map_err(|e| {
+            tracing::error!("Failed to delete {} bridge: {}", service.display_name(), e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                AxumJson(json!({"error": "Failed to delete bridge record"})),
+            )
+        })?;
+
+    // The Matrix client is shared by all bridges of the user
+    let has_active_bridges = state.user_repository.has_active_bridges(auth_user.user_id)
+        .map_err(|e| {
+            tracing::error!("Failed to check active bridges: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                AxumJson(json!({"error": "Failed to check active bridges"})),
+            )
+        })?;
+
+    if !has_active_bridges {
+        let mut matrix_clients = state.matrix_clients.lock().await;
+        let mut sync_tasks = state.matrix_sync_tasks.lock().await;
+
+        if let Some(task) = sync_tasks.remove(&auth_user.user_id) {
+            task.abort();
+            tracing::debug!("Aborted sync task for user {}", auth_user.user_id);
+        }
+
+        if matrix_clients.remove(&auth_user.user_id).is_some() {
+            tracing::debug!("Removed Matrix client for user {}", auth_user.user_id);
+        }
+    } else {
+        tracing::debug!("Other active bridges exist for user {}, keeping Matrix client", auth_user.user_id);
+    }
+
+    tracing::debug!("✅ {} disconnection completed for user {}", service.display_name(), auth_user.user_id);
+    Ok(AxumJson(json!({
+        "message": format!("{} disconnected successfully", service.display_name())
+    })))
+}
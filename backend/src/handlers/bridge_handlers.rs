@@ -0,0 +1,185 @@
+use axum::{
+    extract::{Json, Path, Query, State},
+    http::StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use chrono::Utc;
+use crate::{
+    AppState,
+    handlers::auth_middleware::AuthUser,
+    utils::{
+        bridge::{fetch_bridge_messages, search_bridge_rooms, send_bridge_message, BridgeMessage, BridgeRoom},
+        bridge_service::{require_service, BridgeService},
+    },
+};
+
+// Message endpoints for every bridge, the service comes from the path, e.g. /api/whatsapp/send
+
+#[derive(Serialize)]
+pub struct BridgeMessagesResponse {
+    messages: Vec<BridgeMessage>,
+}
+
+#[derive(Deserialize)]
+pub struct SearchBridgeRoomsRequest {
+    search_term: String,
+}
+
+#[derive(Serialize)]
+pub struct SearchBridgeRoomsResponse {
+    rooms: Vec<BridgeRoom>,
+}
+
+#[derive(Deserialize)]
+pub struct SendBridgeMessageRequest {
+    chat_name: String,
+    message: String,
+    #[serde(alias = "image_url")]
+    media_url: Option<String>, // image, audio or any other file, sent with the message as caption
+}
+
+#[derive(Serialize)]
+pub struct SendBridgeMessageResponse {
+    message: BridgeMessage,
+}
+
+#[derive(Deserialize)]
+pub struct SearchQuery {
+    search: String,
+}
+
+fn require_connected(service: &'static dyn BridgeService, state: &AppState, user_id: i32) -> Result<(), String> {
+    let bridge = state.user_repository.get_bridge(user_id, service.name())
+        .map_err(|e| format!("Failed to get bridge info: {}", e))?
+        .ok_or_else(|| format!("{} bridge not found", service.display_name()))?;
+
+    tracing::info!("Found {} bridge: status={}, room_id={:?}", service.display_name(), bridge.status, bridge.room_id);
+
+    if bridge.status != "connected" {
+        return Err(format!("{} is not connected", service.display_name()));
+    }
+    Ok(())
+}
+
+pub async fn send_message(
+    Path(service): Path<String>,
+    State(state): State<std::sync::Arc<AppState>>,
+    auth_user: AuthUser,
+    Json(request): Json<SendBridgeMessageRequest>,
+) -> Result<Json<SendBridgeMessageResponse>, String> {
+    let service = require_service(&service).map_err(|e| e.to_string())?;
+    require_connected(service, &state, auth_user.user_id)?;
+
+    match send_bridge_message(
+        service.name(),
+        &state,
+        auth_user.user_id,
+        &request.chat_name,
+        None,
+        &request.message,
+        request.media_url,
+    ).await {
+        Ok(message) => {
+            tracing::info!("Successfully sent {} message to {}", service.display_name(), request.chat_name);
+            Ok(Json(SendBridgeMessageResponse { message }))
+        }
+        Err(e) => {
+            tracing::error!("Failed to send {} message: {}", service.display_name(), e);
+            Err(format!("Failed to send {} message: {}", service.display_name(), e))
+        }
+    }
+}
+
+pub async fn test_fetch_messages(
+    Path(service): Path<String>,
+    State(state): State<std::sync::Arc<AppState>>,
+    auth_user: AuthUser,
+) -> Result<Json<BridgeMessagesResponse>, String> {
+    let service = require_service(&service).map_err(|e| e.to_string())?;
+    require_connected(service, &state, auth_user.user_id)?;
+
+    // Get a wider time range - last 24 hours
+    let now = Utc::now().naive_utc();
+    let start_time = (now - chrono::Duration::hours(24)).timestamp();
+
+    match fetch_bridge_messages(service.name(), &state, auth_user.user_id, start_time, false).await {
+        Ok(messages) => {
+            tracing::info!("Found {} messages", messages.len());
+
+            // Print message details in a readable format for testing
+            println!("\n📱 {} Messages Summary:", service.display_name());
+            println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+
+            for msg in messages.iter() {
+                let message_type_icon = match msg.message_type.as_str() {
+                    "text" => "💬",
+                    "notice" => "📢",
+                    "image" => "🖼️",
+                    "video" => "🎥",
+                    "file" => "📎",
+                    "audio" => "🔊",
+                    "location" => "📍",
+                    "emote" => "🎭",
+                    _ => "📝",
+                };
+
+                println!("\n{} Room: {}", message_type_icon, msg.room_name);
+                println!("👤 {}", msg.sender_display_name);
+                println!("🕒 {}", msg.formatted_timestamp);
+                println!("📄 {}", msg.content);
+                println!("─────────────────────────────────────");
+            }
+
+            println!("\nTotal messages: {}\n", messages.len());
+
+            Ok(Json(BridgeMessagesResponse { messages }))
+        }
+        Err(e) => {
+            tracing::error!("Error fetching {} messages: {}", service.display_name(), e);
+            Err(format!("Failed to fetch messages: {}", e))
+        }
+    }
+}
+
+/// Fetches only the rooms of the given service for the user
+pub async fn search_service_rooms(
+    Path(service): Path<String>,
+    State(state): State<std::sync::Arc<AppState>>,
+    auth_user: AuthUser,
+    Json(request): Json<SearchBridgeRoomsRequest>,
+) -> Result<Json<SearchBridgeRoomsResponse>, String> {
+    let service = require_service(&service).map_err(|e| e.to_string())?;
+    require_connected(service, &state, auth_user.user_id)?;
+
+    match search_bridge_rooms(service.name(), &state, auth_user.user_id, &request.search_term).await {
+        Ok(rooms) => {
+            tracing::debug!("Found {} matching {} rooms", rooms.len(), service.display_name());
+
+            if rooms.is_empty() {
+                tracing::error!("No rooms found matching search term: '{}'", request.search_term);
+            }
+
+            Ok(Json(SearchBridgeRoomsResponse { rooms }))
+        }
+        Err(e) => {
+            tracing::error!("Failed to search {} rooms: {}", service.display_name(), e);
+            Err(format!("Failed to search {} rooms: {}", service.display_name(), e))
+        }
+    }
+}
+
+pub async fn search_rooms(
+    Path(service): Path<String>,
+    State(state): State<std::sync::Arc<AppState>>,
+    auth_user: AuthUser,
+    Query(params): Query<SearchQuery>,
+) -> Result<Json<Vec<BridgeRoom>>, StatusCode> {
+    let service = require_service(&service).map_err(|_| StatusCode::NOT_FOUND)?;
+    match search_bridge_rooms(service.name(), &state, auth_user.user_id, &params.search).await {
+        Ok(rooms) => Ok(Json(rooms)),
+        Err(e) => {
+            tracing::error!("Failed to search {} rooms for user {}: {}", service.display_name(), auth_user.user_id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
@@ -12,7 +12,7 @@ use crate::{
     AppState,
     models::user_models::{
         NewWaitingCheck, NewPrioritySender,
-        NewKeyword
+        NewKeyword, NewGroupPolicy
     },
     handlers::auth_middleware::AuthUser,
 };
@@ -30,6 +30,14 @@ pub struct PrioritySenderRequest {
     sender: String,
     service_type: String, // imap, whatsapp, etc.
     noti_type: Option<String>,
+    #[serde(default)]
+    imap_connection_id: Option<i32>, // for imap, only this email account instead of all
+}
+
+#[derive(Deserialize)]
+pub struct GroupPolicyRequest {
+    chat_name: String, // group name without the bridge suffix, "*" for every group of the service
+    policy: String, // "mention" (the default), "reply" or "always"
 }
 
 #[derive(Deserialize)]
@@ -59,6 +67,15 @@ pub struct PrioritySenderResponse {
     sender: String,
     service_type: String,
     noti_type: Option<String>,
+    imap_connection_id: Option<i32>,
+}
+
+#[derive(Serialize)]
+pub struct GroupPolicyResponse {
+    user_id: i32,
+    chat_name: String,
+    service_type: String,
+    policy: String,
 }
 
 #[derive(Serialize)]
@@ -174,6 +191,7 @@ pub async fn create_priority_sender(
         sender: request.sender.clone(),
         service_type: request.service_type,
         noti_type: request.noti_type,
+        imap_connection_id: request.imap_connection_id,
     };
 
     match state.user_repository.create_priority_sender(&new_sender) {
@@ -254,6 +272,7 @@ pub async fn get_priority_senders(
         sender: sender.sender,
         service_type: sender.service_type,
         noti_type: sender.noti_type,
+        imap_connection_id: sender.imap_connection_id,
     }).collect();
     let full_response = json!({
         "contacts": response,
@@ -264,6 +283,108 @@ pub async fn get_priority_senders(
 }
 
 
+// Group policy handlers
+pub async fn set_group_policy(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(service_type): Path<String>,
+    Json(request): Json<GroupPolicyRequest>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    println!("Attempting to set group policy for user {} with type: {}", auth_user.user_id, service_type);
+
+    if crate::utils::bridge_service::service_for(&service_type).is_none() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": format!("Unknown service type: {}", service_type)}))
+        ));
+    }
+    if !["mention", "reply", "always"].contains(&request.policy.as_str()) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "Policy must be 'mention', 'reply' or 'always'"}))
+        ));
+    }
+    let chat_name = request.chat_name.trim().to_string();
+    if chat_name.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "Chat name is required"}))
+        ));
+    }
+
+    let new_policy = NewGroupPolicy {
+        user_id: auth_user.user_id,
+        service_type,
+        chat_name: chat_name.clone(),
+        policy: request.policy,
+    };
+
+    match state.user_repository.set_group_policy(&new_policy) {
+        Ok(_) => {
+            println!("Successfully set group policy for {} for user {}", chat_name, auth_user.user_id);
+            Ok(Json(json!({"message": "Group policy saved successfully"})))
+        },
+        Err(e) => {
+            tracing::error!("Failed to set group policy for user {}: {}", auth_user.user_id, e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": format!("Database error: {}", e)}))
+            ))
+        },
+    }
+}
+
+pub async fn delete_group_policy(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path((service_type, chat_name)): Path<(String, String)>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    println!("Attempting to delete group policy {} for user {}", chat_name, auth_user.user_id);
+
+    match state.user_repository.delete_group_policy(auth_user.user_id, &service_type, &chat_name) {
+        Ok(_) => {
+            println!("Successfully deleted group policy {} for user {}", chat_name, auth_user.user_id);
+            Ok(Json(json!({"message": "Group policy deleted successfully"})))
+        },
+        Err(DieselError::NotFound) => Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "Group policy not found"}))
+        )),
+        Err(e) => {
+            tracing::error!("Failed to delete group policy {}: {}", chat_name, e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": format!("Database error: {}", e)}))
+            ))
+        },
+    }
+}
+
+pub async fn get_group_policies(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser
+) -> Result<Json<Vec<GroupPolicyResponse>>, (StatusCode, Json<serde_json::Value>)> {
+    println!("Fetching group policies for user {}", auth_user.user_id);
+
+    let policies = state.user_repository.get_group_policies_all(auth_user.user_id)
+        .map_err(|e| {
+            tracing::error!("Failed to fetch group policies for user {}: {}", auth_user.user_id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": format!("Database error: {}", e)}))
+            )
+        })?;
+
+    let response: Vec<GroupPolicyResponse> = policies.into_iter().map(|policy| GroupPolicyResponse {
+        user_id: policy.user_id,
+        chat_name: policy.chat_name,
+        service_type: policy.service_type,
+        policy: policy.policy,
+    }).collect();
+
+    Ok(Json(response))
+}
+
 // Keywords handlers
 pub async fn create_keyword(
     State(state): State<Arc<AppState>>,
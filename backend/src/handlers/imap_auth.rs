@@ -1,5 +1,5 @@
 use axum::{
-    extract::{Json, State},
+    extract::{Json, Path, State},
     http::StatusCode,
     response::{Json as AxumJson},
 };
@@ -13,23 +13,61 @@ use crate::{
 use imap::Session;
 use native_tls::TlsConnector;
 use std::error::Error;
+use crate::utils::smtp::{default_port, derived_server, discover, SmtpSettings, SECURITY_MODES};
 
 // Struct to deserialize the incoming IMAP credentials from the frontend
 #[derive(Deserialize)]
 pub struct ImapCredentials {
     email: String,
     password: String,
+    #[serde(default)]
+    label: Option<String>,       // e.g., "work", the address is shown when there is none
     #[serde(default)] 
     imap_server: Option<String>, // e.g., "mail.privateemail.com" or "imap.gmail.com"
     #[serde(default)]
     imap_port: Option<u16>,      // e.g., 993
+    // Anything not given is discovered from the email domain, see utils::smtp::discover
+    #[serde(default)]
+    smtp_server: Option<String>, // e.g., "smtp.fastmail.com"
+    #[serde(default)]
+    smtp_port: Option<u16>,      // e.g., 465
+    #[serde(default)]
+    smtp_security: Option<String>, // "starttls" or "tls"
+    #[serde(default)]
+    smtp_username: Option<String>, // only when sending needs a different login
+    #[serde(default)]
+    smtp_password: Option<String>,
 }
 
-// Struct to serialize the IMAP status response
+// Struct to serialize the IMAP status response, the top level fields are of the first account
 #[derive(Serialize)]
 pub struct ImapStatus {
     connected: bool,
     email: Option<String>,
+    smtp_server: Option<String>,
+    smtp_port: Option<u16>,
+    smtp_security: Option<String>,
+    accounts: Vec<ImapAccountStatus>,
+}
+
+#[derive(Serialize)]
+pub struct ImapAccountStatus {
+    id: i32,
+    label: Option<String>,
+    email: String,
+    monitoring_enabled: bool,
+    smtp_server: Option<String>,
+    smtp_port: Option<u16>,
+    smtp_security: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ImapAccountUpdate {
+    // Missing fields stay as they are, an empty label removes it
+    #[serde(default)]
+    label: Option<String>,
+    #[serde(default)]
+    monitoring_enabled: Option<bool>,
 }
 
 use native_tls::TlsStream;
@@ -64,33 +102,107 @@ pub async fn imap_login(
 
     let email = payload.email;
     let password = payload.password;
-    let imap_server = payload.imap_server.as_deref(); // Convert Option<String> to Option<&str>
-    let imap_port = payload.imap_port;
 
-    // Attempt to connect to Gmail's IMAP server to verify credentials
-    match connect_imap(&email, &password, imap_server, imap_port).await {
+    if let Some(security) = payload.smtp_security.as_deref() {
+        if !SECURITY_MODES.contains(&security) {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                AxumJson(json!({"error": format!("smtp_security must be one of: {}", SECURITY_MODES.join(", "))})),
+            ));
+        }
+    }
+
+    // Fill in the servers that weren't given from what the email domain publishes
+    let discovered = if payload.imap_server.is_none() || payload.smtp_server.is_none() {
+        discover(&email).await
+    } else {
+        Default::default()
+    };
+    let (imap_server, imap_port) = match (payload.imap_server, discovered.imap) {
+        (Some(server), _) => (Some(server), payload.imap_port),
+        (None, Some((server, port))) => (Some(server), Some(payload.imap_port.unwrap_or(port))),
+        (None, None) => (None, payload.imap_port),
+    };
+    let (smtp_server, discovered_port, discovered_security) = match (payload.smtp_server, discovered.smtp) {
+        (Some(server), _) => (server, None, None),
+        (None, Some((server, port, security))) => (server, Some(port), Some(security)),
+        (None, None) => (derived_server(imap_server.as_deref()), None, None),
+    };
+    // A discovered port only goes with the discovered security
+    let discovered_port = discovered_port.filter(|_| payload.smtp_security.is_none());
+    let security = payload.smtp_security.or(discovered_security).unwrap_or_else(|| "starttls".to_string());
+    let smtp = SmtpSettings {
+        port: payload.smtp_port.or(discovered_port).unwrap_or_else(|| default_port(&security)),
+        server: smtp_server,
+        security,
+        username: payload.smtp_username.filter(|u| !u.trim().is_empty()).unwrap_or_else(|| email.clone()),
+        password: payload.smtp_password.filter(|p| !p.is_empty()).unwrap_or_else(|| password.clone()),
+    };
+    let imap_server = imap_server.as_deref(); // Convert Option<String> to Option<&str>
+
+    // Attempt to connect to the IMAP server to verify credentials
+    // The error is turned into a String since a boxed error would make this handler not Send
+    match connect_imap(&email, &password, imap_server, imap_port).await.map_err(|e| e.to_string()) {
         Ok(mut session) => {
             // Logout immediately after verification to avoid keeping the session open
             if let Err(e) = session.logout() {
                 tracing::warn!("Failed to logout IMAP session: {}", e);
             }
 
-            if let Err(e) = state.user_repository.set_imap_credentials(
+            // Sending is checked too so a wrong SMTP setting shows up now and not on the first reply
+            let smtp_to_verify = smtp.clone();
+            let verified = tokio::task::spawn_blocking(move || smtp_to_verify.verify())
+                .await
+                .unwrap_or_else(|e| Err(format!("SMTP check failed: {}", e)));
+            if let Err(e) = verified {
+                tracing::error!("SMTP connection failed for user {}: {}", auth_user.user_id, e);
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    AxumJson(json!({
+                        "error": "Connected to IMAP but couldn't log in to SMTP, check the SMTP settings",
+                        "details": e,
+                        "smtp_server": smtp.server,
+                        "smtp_port": smtp.port,
+                        "smtp_security": smtp.security,
+                    })),
+                ));
+            }
+
+            // Adds the account, a login to an address already connected updates that one only
+            let label = payload.label.as_deref().map(str::trim).filter(|label| !label.is_empty());
+            let account_id = match state.user_repository.set_imap_credentials(
                 auth_user.user_id,
+                label,
                 &email,
                 &password,
                 imap_server,
                 imap_port,
+                &smtp,
             ) {
-                tracing::error!("Failed to store IMAP credentials: {}", e);
-                return Err((
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    AxumJson(json!({"error": "Failed to store IMAP credentials"})),
-                ));
-            }
+                Ok(account_id) => account_id,
+                Err(e) => {
+                    tracing::error!("Failed to store IMAP credentials: {}", e);
+                    return Err((
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        AxumJson(json!({"error": "Failed to store IMAP credentials"})),
+                    ));
+                }
+            };
+
+            // Restart the account's inbox watcher with the new credentials
+            crate::jobs::imap_idle::stop_imap_watcher(&state, account_id).await;
+            crate::jobs::imap_idle::sync_imap_watchers(&state).await;
 
-            tracing::info!("Successfully stored IMAP credentials for user {}", auth_user.user_id);
-            Ok(AxumJson(json!({"message": "IMAP connected successfully"})))
+            tracing::info!("Successfully stored IMAP credentials for user {} account {}", auth_user.user_id, account_id);
+            Ok(AxumJson(json!({
+                "message": "IMAP connected successfully",
+                "account_id": account_id,
+                "imap_server": imap_server,
+                "imap_port": imap_port,
+                "smtp_server": smtp.server,
+                "smtp_port": smtp.port,
+                "smtp_security": smtp.security,
+            })))
         }
         Err(e) => {
             tracing::error!("IMAP connection failed for user {}: {}", auth_user.user_id, e);
@@ -109,40 +221,103 @@ pub async fn imap_status(
 ) -> Result<AxumJson<ImapStatus>, (StatusCode, AxumJson<serde_json::Value>)> {
     tracing::info!("Checking IMAP status for user {}", auth_user.user_id);
 
-    let credentials = state
+    let accounts = state
         .user_repository
-        .get_imap_credentials(auth_user.user_id)
+        .get_imap_accounts(auth_user.user_id)
         .map_err(|e| {
-            tracing::error!("Failed to fetch IMAP credentials: {}", e);
+            tracing::error!("Failed to fetch IMAP accounts: {}", e);
             (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 Json(json!({"error": "Failed to fetch IMAP status"})),
             )
         })?;
 
-    match credentials {
-        Some((email, _password, imap_server, imap_port)) => {
+    let accounts: Vec<ImapAccountStatus> = accounts
+        .into_iter()
+        .filter_map(|account| {
+            let id = account.id?;
+            let smtp = state.user_repository.get_smtp_settings(auth_user.user_id, Some(id)).ok().flatten();
+            Some(ImapAccountStatus {
+                id,
+                label: account.label,
+                email: account.description,
+                monitoring_enabled: account.monitoring_enabled,
+                smtp_server: smtp.as_ref().map(|smtp| smtp.server.clone()),
+                smtp_port: smtp.as_ref().map(|smtp| smtp.port),
+                smtp_security: smtp.map(|smtp| smtp.security),
+            })
+        })
+        .collect();
+
+    let first = accounts.first();
+    Ok(Json(ImapStatus {
+        connected: first.is_some(),
+        email: first.map(|account| account.email.clone()),
+        smtp_server: first.and_then(|account| account.smtp_server.clone()),
+        smtp_port: first.and_then(|account| account.smtp_port),
+        smtp_security: first.and_then(|account| account.smtp_security.clone()),
+        accounts,
+    }))
+}
 
-            Ok(Json(ImapStatus {
-                connected: true,
-                email: Some(email),
-            }))
+// Handler to rename an email account or turn its monitoring on or off
+pub async fn update_imap_account(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(account_id): Path<i32>,
+    Json(payload): Json<ImapAccountUpdate>,
+) -> Result<AxumJson<serde_json::Value>, (StatusCode, AxumJson<serde_json::Value>)> {
+    tracing::info!("Updating IMAP account {} for user {}", account_id, auth_user.user_id);
+
+    let account = match state.user_repository.get_imap_accounts(auth_user.user_id) {
+        Ok(accounts) => accounts.into_iter().find(|account| account.id == Some(account_id)),
+        Err(e) => {
+            tracing::error!("Failed to fetch IMAP accounts: {}", e);
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                AxumJson(json!({"error": "Failed to update IMAP account"})),
+            ));
         }
-        None => Ok(Json(ImapStatus {
-            connected: false,
-            email: None,
-        })),
+    };
+    let Some(account) = account else {
+        return Err((
+            StatusCode::NOT_FOUND,
+            AxumJson(json!({"error": "IMAP account not found"})),
+        ));
+    };
+
+    let label = match payload.label {
+        Some(label) => Some(label.trim().to_string()).filter(|label| !label.is_empty()),
+        None => account.label,
+    };
+    let monitoring_enabled = payload.monitoring_enabled.unwrap_or(account.monitoring_enabled);
+    if let Err(e) = state.user_repository.update_imap_account(auth_user.user_id, account_id, label.as_deref(), monitoring_enabled) {
+        tracing::error!("Failed to update IMAP account {}: {}", account_id, e);
+        return Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            AxumJson(json!({"error": "Failed to update IMAP account"})),
+        ));
     }
+
+    // Starts or stops the account's watcher to match the monitoring toggle
+    crate::jobs::imap_idle::sync_imap_watchers(&state).await;
+
+    Ok(AxumJson(json!({
+        "message": "IMAP account updated successfully",
+        "id": account_id,
+        "label": label,
+        "monitoring_enabled": monitoring_enabled,
+    })))
 }
 
-// Handler to delete the IMAP connection
+// Handler to delete all of the user's IMAP connections
 pub async fn delete_imap_connection(
     State(state): State<Arc<AppState>>,
     auth_user: AuthUser,
 ) -> Result<AxumJson<serde_json::Value>, (StatusCode, AxumJson<serde_json::Value>)> {
     tracing::info!("Received request to delete IMAP connection for user {}", auth_user.user_id);
 
-    if let Err(e) = state.user_repository.delete_imap_credentials(auth_user.user_id) {
+    if let Err(e) = state.user_repository.delete_imap_credentials(auth_user.user_id, None) {
         tracing::error!("Failed to delete IMAP credentials: {}", e);
         return Err((
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -150,6 +325,31 @@ pub async fn delete_imap_connection(
         ));
     }
 
+    // Stops the watchers of the deleted accounts
+    crate::jobs::imap_idle::sync_imap_watchers(&state).await;
+
     tracing::info!("Successfully deleted IMAP connection for user {}", auth_user.user_id);
     Ok(AxumJson(json!({"message": "IMAP connection deleted successfully"})))
 }
+
+// Handler to delete one of the user's email accounts
+pub async fn delete_imap_account(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(account_id): Path<i32>,
+) -> Result<AxumJson<serde_json::Value>, (StatusCode, AxumJson<serde_json::Value>)> {
+    tracing::info!("Received request to delete IMAP account {} for user {}", account_id, auth_user.user_id);
+
+    if let Err(e) = state.user_repository.delete_imap_credentials(auth_user.user_id, Some(account_id)) {
+        tracing::error!("Failed to delete IMAP account {}: {}", account_id, e);
+        return Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            AxumJson(json!({"error": "Failed to delete IMAP account"})),
+        ));
+    }
+
+    crate::jobs::imap_idle::stop_imap_watcher(&state, account_id).await;
+
+    tracing::info!("Successfully deleted IMAP account {} for user {}", account_id, auth_user.user_id);
+    Ok(AxumJson(json!({"message": "IMAP account deleted successfully"})))
+}
@@ -16,6 +16,7 @@ use mail_parser::{self, MimeHeaders};
 use crate::{
     AppState,
     handlers::auth_middleware::AuthUser,
+    models::user_models::ImapConnection,
     utils::imap_utils::upload_media_to_twilio,
 };
 
@@ -46,6 +47,8 @@ fn format_timestamp(timestamp: i64, timezone: Option<String>) -> String {
 #[derive(Debug, Serialize, Clone)]
 pub struct ImapEmailPreview {
     pub id: String,
+    pub account_id: i32,
+    pub account: String,
     pub subject: Option<String>,
     pub from: Option<String>,
     pub from_email: Option<String>,
@@ -54,6 +57,8 @@ pub struct ImapEmailPreview {
     pub snippet: Option<String>,
     pub body: Option<String>,
     pub is_read: bool,
+    #[serde(skip)]
+    pub auto_generated: bool, // list mail, bulk mail or an auto reply, never answered automatically
 }
 
 #[derive(Debug, Serialize)]
@@ -67,6 +72,7 @@ pub struct EmailAttachment {
 #[derive(Debug, Serialize)]
 pub struct ImapEmail {
     pub id: String,
+    pub account: String,
     pub subject: Option<String>,
     pub from: Option<String>,
     pub from_email: Option<String>,
@@ -90,6 +96,8 @@ pub enum ImapError {
 #[derive(Debug, Deserialize)]
 pub struct FetchEmailsQuery {
     pub limit: Option<u32>,
+    // imap_connection id, all accounts when missing
+    pub account: Option<i32>,
 }
 
 pub async fn fetch_imap_previews(
@@ -99,7 +107,7 @@ pub async fn fetch_imap_previews(
 ) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
     tracing::info!("Starting IMAP preview fetch for user {} with limit {:?}", auth_user.user_id, params.limit);
 
-    match fetch_emails_imap(&state, auth_user.user_id, true, params.limit, false, false).await {
+    match fetch_emails_imap(&state, auth_user.user_id, params.account, true, params.limit, false, false).await {
         Ok(previews) => {
             tracing::info!("Fetched {} IMAP previews", previews.len());
             
@@ -108,6 +116,7 @@ pub async fn fetch_imap_previews(
                 .map(|p| {
                     json!({
                         "id": p.id,
+                        "account": p.account,
                         "subject": p.subject.unwrap_or_else(|| "No subject".to_string()),
                         "from": p.from.unwrap_or_else(|| "Unknown sender".to_string()),
                         "date": p.date.map(|dt| dt.to_rfc3339()),
@@ -146,7 +155,7 @@ pub async fn fetch_full_imap_emails(
         testing = true;
     }
 
-    match fetch_emails_imap(&state, auth_user.user_id, false, limit, false, false).await {
+    match fetch_emails_imap(&state, auth_user.user_id, params.account, false, limit, false, false).await {
         Ok(previews) => {
             tracing::info!("Fetched {} IMAP full emails", previews.len());
             
@@ -162,6 +171,7 @@ pub async fn fetch_full_imap_emails(
 
                     json!({
                         "id": p.id,
+                        "account": p.account,
                         "subject": p.subject.unwrap_or_else(|| "No subject".to_string()),
                         "from": p.from_email.unwrap_or_else(|| "Unknown sender".to_string()),
                         "date": p.date.map(|dt| dt.to_rfc3339()),
@@ -202,74 +212,37 @@ pub async fn respond_to_email(
 ) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
     tracing::info!("Responding to email {} for user {}", request.email_id, auth_user.user_id);
 
-    // Validate email_id is a valid number
-    if !request.email_id.chars().all(|c| c.is_ascii_digit()) {
+    // Validate email_id is "<account>:<uid>" or a bare uid
+    let Some((account_id, uid)) = parse_email_id(&request.email_id) else {
         tracing::error!("Invalid email ID format: {}", request.email_id);
         return Err((
             StatusCode::BAD_REQUEST,
             Json(json!({ "error": "Invalid email ID format" }))
         ));
-    }
+    };
 
-    // Get IMAP credentials
-    let (email, password, imap_server, imap_port) = match state
-        .user_repository
-        .get_imap_credentials(auth_user.user_id)
-    {
-        Ok(Some(creds)) => creds,
-        Ok(None) => return Err((
+    // Log in to the account the email is in
+    let (mut imap_session, _, account) = match connect_inbox(&state, auth_user.user_id, account_id) {
+        Ok(connected) => connected,
+        Err(ImapError::NoConnection) => return Err((
             StatusCode::BAD_REQUEST,
             Json(json!({ "error": "No IMAP connection found" }))
         )),
-        Err(e) => return Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({ "error": format!("Failed to get IMAP credentials: {}", e) }))
-        )),
-    };
-    tracing::info!("setting up tls");
-
-    // Set up TLS
-    let tls = match TlsConnector::builder().build() {
-        Ok(tls) => tls,
-        Err(e) => return Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({ "error": format!("Failed to create TLS connector: {}", e) }))
+        Err(ImapError::CredentialsError(msg)) => return Err((
+            StatusCode::UNAUTHORIZED,
+            Json(json!({ "error": msg }))
         )),
-    };
-
-    let server = imap_server.as_deref().unwrap_or("imap.gmail.com");
-    let port = imap_port.unwrap_or(993);
-
-    // Connect to IMAP server
-    let client = match imap::connect((server, port as u16), server, &tls) {
-        Ok(client) => client,
         Err(e) => return Err((
             StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({ "error": format!("Failed to connect to IMAP server: {}", e) }))
-        )),
-    };
-
-    // Login
-    let mut imap_session = match client.login(&email, &password) {
-        Ok(session) => session,
-        Err((e, _)) => return Err((
-            StatusCode::UNAUTHORIZED,
-            Json(json!({ "error": format!("Failed to login: {}", e) }))
+            Json(json!({ "error": format!("{:?}", e) }))
         )),
     };
+    let email = account.description.clone();
 
     tracing::info!("logged in");
 
-    // Select INBOX
-    if let Err(e) = imap_session.select("INBOX") {
-        return Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({ "error": format!("Failed to select INBOX: {}", e) }))
-        ));
-    }
-
     // Fetch the original message to get subject and other details
-    let messages = match imap_session.uid_fetch(&request.email_id, "(ENVELOPE)") {
+    let messages = match imap_session.uid_fetch(uid, "(ENVELOPE)") {
         Ok(messages) => messages,
         Err(e) => return Err((
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -329,25 +302,23 @@ pub async fn respond_to_email(
     };
 
     // Create SMTP transport
-    let smtp_server = imap_server
-        .as_deref()
-        .unwrap_or("smtp.gmail.com")
-        .replace("imap", "smtp");
-    
-    let smtp_port = 587; // Standard SMTP port
-
-    let creds = lettre::transport::smtp::authentication::Credentials::new(
-        email.clone(),
-        password.clone(),
-    );
+    let smtp = match state.user_repository.get_smtp_settings(auth_user.user_id, account.id) {
+        Ok(Some(smtp)) => smtp,
+        Ok(None) => return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "No IMAP connection found" }))
+        )),
+        Err(e) => return Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": format!("Failed to get SMTP settings: {}", e) }))
+        )),
+    };
+    let mailer = smtp.mailer().map_err(|e| (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(json!({ "error": e }))
+    ))?;
     tracing::info!("created the smtp transport");
 
-    let mailer = lettre::SmtpTransport::relay(&smtp_server)
-        .unwrap()
-        .port(smtp_port)
-        .credentials(creds)
-        .build();
-
     use lettre::{Message, Transport};
 
     // Create email message
@@ -365,7 +336,7 @@ pub async fn respond_to_email(
     };
 
     tracing::info!("Attempting to send email via SMTP...");
-    tracing::info!("SMTP Configuration - Server: {}, Port: {}", smtp_server, smtp_port);
+    tracing::info!("SMTP Configuration - Server: {}, Port: {}, Security: {}", smtp.server, smtp.port, smtp.security);
     
     // Attempt to send the email with detailed error logging
     let send_result = mailer.send(&email_message);
@@ -392,7 +363,7 @@ pub async fn respond_to_email(
             tracing::error!("SMTP error details: {}", e.to_string());
             
             // Log SMTP connection details for debugging (excluding credentials)
-            tracing::debug!("SMTP connection details - Server: {}, Port: {}", smtp_server, smtp_port);
+            tracing::debug!("SMTP connection details - Server: {}, Port: {}", smtp.server, smtp.port);
             
             // Attempt IMAP logout even if SMTP failed
             if let Err(logout_err) = imap_session.logout() {
@@ -410,6 +381,75 @@ pub async fn respond_to_email(
     }
 }
 
+/// Sends a new email from one of the user's accounts, the first one for None,
+/// over its SMTP server. Blocking like the rest of the mail code.
+// Marks mail the user didn't write themselves, RFC 3834
+#[derive(Clone)]
+struct AutoSubmitted(String);
+
+impl lettre::message::header::Header for AutoSubmitted {
+    fn name() -> lettre::message::header::HeaderName {
+        lettre::message::header::HeaderName::new_from_ascii_str("Auto-Submitted")
+    }
+
+    fn parse(s: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(Self(s.trim().to_string()))
+    }
+
+    fn display(&self) -> lettre::message::header::HeaderValue {
+        lettre::message::header::HeaderValue::new(Self::name(), self.0.clone())
+    }
+}
+
+// Mail that shouldn't get an automatic answer: other auto replies, mailing lists and bulk mail
+fn is_auto_generated(message: &mail_parser::Message) -> bool {
+    let header = |name: &'static str| message.header_raw(name).map(|value| value.trim().to_lowercase());
+    header("Auto-Submitted").is_some_and(|value| value != "no")
+        || header("Precedence").is_some_and(|value| ["bulk", "list", "junk"].contains(&value.as_str()))
+        || header("List-Id").is_some()
+}
+
+/// Sends through the account's SMTP server. Blocking, `auto_reply` marks the mail as Auto-Submitted.
+pub fn send_email_smtp(
+    state: &AppState,
+    user_id: i32,
+    account_id: Option<i32>,
+    to: &str,
+    subject: &str,
+    body: &str,
+    auto_reply: bool,
+) -> Result<(), String> {
+    use lettre::{Message, Transport};
+
+    let email = match state.user_repository.get_imap_account(user_id, account_id) {
+        Ok(Some(account)) => account.description,
+        Ok(None) => return Err("No email account connected".to_string()),
+        Err(e) => return Err(format!("Failed to get IMAP credentials: {}", e)),
+    };
+    let mailer = match state.user_repository.get_smtp_settings(user_id, account_id) {
+        Ok(Some(smtp)) => smtp.mailer()?,
+        Ok(None) => return Err("No email account connected".to_string()),
+        Err(e) => return Err(format!("Failed to get SMTP settings: {}", e)),
+    };
+
+    let mut builder = Message::builder()
+        .from(email.parse().map_err(|e| format!("Invalid sender address: {}", e))?)
+        .to(to.parse().map_err(|_| format!("'{}' is not a valid email address", to))?)
+        .subject(subject);
+    if auto_reply {
+        builder = builder.header(AutoSubmitted("auto-replied".to_string()));
+    }
+    let message = builder
+        .body(body.to_string())
+        .map_err(|e| format!("Failed to create email message: {}", e))?;
+
+    mailer.send(&message).map_err(|e| {
+        tracing::error!("SMTP send error for user {}: {:?}", user_id, e);
+        format!("Failed to send email via SMTP: {}", e)
+    })?;
+    Ok(())
+}
+
 pub async fn fetch_single_imap_email(
     State(state): State<Arc<AppState>>,
     auth_user: AuthUser,
@@ -417,8 +457,8 @@ pub async fn fetch_single_imap_email(
 ) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
     tracing::info!("Fetching single IMAP email {} for user {}", email_id, auth_user.user_id);
 
-    // Validate email_id is a valid number and not empty
-    if email_id.trim().is_empty() || !email_id.chars().all(|c| c.is_ascii_digit()) {
+    // Validate email_id is "<account>:<uid>" or a bare uid and not empty
+    if email_id.trim().is_empty() || parse_email_id(&email_id).is_none() {
         let error_msg = if email_id.trim().is_empty() {
             "Email ID cannot be empty"
         } else {
@@ -446,6 +486,7 @@ pub async fn fetch_single_imap_email(
                 "success": true,
                 "email": {
                     "id": email.id,
+                    "account": email.account,
                     "subject": email.subject.unwrap_or_else(|| "No subject".to_string()),
                     "from": email.from.unwrap_or_else(|| "Unknown sender".to_string()),
                     "from_email": email.from_email.unwrap_or_else(|| "unknown@email.com".to_string()),
@@ -489,40 +530,97 @@ pub async fn fetch_single_imap_email(
     }
 }
 
-pub async fn fetch_emails_imap(
+pub type ImapSession = imap::Session<native_tls::TlsStream<std::net::TcpStream>>;
+
+/// Emails are known by "<account id>:<uid>" since UIDs are only unique within one mailbox.
+pub fn format_email_id(account_id: i32, uid: &str) -> String {
+    format!("{}:{}", account_id, uid)
+}
+
+/// The account (None for the first one) and UID of an email id, None if it isn't one.
+/// A bare UID, as handed out before there were multiple accounts, is from the first account.
+pub fn parse_email_id(email_id: &str) -> Option<(Option<i32>, &str)> {
+    let (account_id, uid) = match email_id.trim().split_once(':') {
+        Some((account_id, uid)) => (Some(account_id.parse().ok()?), uid),
+        None => (None, email_id.trim()),
+    };
+    if uid.is_empty() || !uid.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some((account_id, uid))
+}
+
+/// The account emails go out from when none is named, the user's first one.
+pub fn default_account_id(state: &AppState, user_id: i32) -> Option<i32> {
+    match state.user_repository.get_imap_account(user_id, None) {
+        Ok(account) => account.and_then(|account| account.id),
+        Err(e) => {
+            tracing::error!("Failed to get default email account of user {}: {}", user_id, e);
+            None
+        }
+    }
+}
+
+/// How an account is shown to the user: its label, or the address when it has none.
+pub fn account_label(account: &ImapConnection) -> String {
+    account.label.clone().filter(|label| !label.trim().is_empty()).unwrap_or_else(|| account.description.clone())
+}
+
+/// Finds the user's account the user means by `name` ("work", or part of the address).
+/// The error is a text for the user listing the accounts there are.
+pub fn resolve_account(state: &AppState, user_id: i32, name: &str) -> Result<i32, String> {
+    let accounts = state.user_repository.get_imap_accounts(user_id).map_err(|e| {
+        tracing::error!("Failed to get email accounts of user {}: {}", user_id, e);
+        "Failed to get your email accounts.".to_string()
+    })?;
+    let name = name.trim().to_lowercase();
+    let matches = |account: &&ImapConnection| {
+        account.label.as_deref().is_some_and(|label| label.to_lowercase() == name)
+    };
+    let partial = |account: &&ImapConnection| {
+        account_label(account).to_lowercase().contains(&name) || account.description.to_lowercase().contains(&name)
+    };
+    match accounts.iter().find(matches).or_else(|| accounts.iter().find(partial)).and_then(|account| account.id) {
+        Some(id) => Ok(id),
+        None if accounts.is_empty() => Err("No email account is connected.".to_string()),
+        None => Err(format!(
+            "No email account '{}', the accounts are: {}",
+            name,
+            accounts.iter().map(account_label).collect::<Vec<_>>().join(", ")
+        )),
+    }
+}
+
+/// Logs in to one of the user's email accounts, the first one for None, and selects INBOX.
+pub fn connect_inbox(
     state: &AppState,
     user_id: i32,
-    preview_only: bool,
-    limit: Option<u32>,
-    unprocessed: bool,
-    unread_only: bool,
-) -> Result<Vec<ImapEmailPreview>, ImapError> {
-    tracing::debug!("Starting fetch_emails_imap for user {} with preview_only: {}, limit: {:?}, unprocessed: {}", 
-        user_id, preview_only, limit, unprocessed);
+    account_id: Option<i32>,
+) -> Result<(ImapSession, imap::types::Mailbox, ImapConnection), ImapError> {
     // Get IMAP credentials
-    let (email, password, imap_server, imap_port) = state
+    let account = state
         .user_repository
-        .get_imap_credentials(user_id)
+        .get_imap_account(user_id, account_id)
         .map_err(|e| ImapError::CredentialsError(e.to_string()))?
         .ok_or_else(|| ImapError::NoConnection)?;
 
     // Add logging for debugging (remove in production)
-    tracing::debug!("Fetching IMAP emails for user {} with email {}", user_id, email);
+    tracing::debug!("Connecting to IMAP for user {} with email {}", user_id, account.description);
 
     // Set up TLS
     let tls = TlsConnector::builder()
         .build()
         .map_err(|e| ImapError::ConnectionError(format!("Failed to create TLS connector: {}", e)))?;
 
-    let server = imap_server.as_deref().unwrap_or("imap.gmail.com");
-    let port = imap_port.unwrap_or(993);
+    let server = account.imap_server.as_deref().unwrap_or("imap.gmail.com");
+    let port = account.imap_port.unwrap_or(993);
     // Connect to IMAP server
     let client = imap::connect((server, port as u16), server, &tls)
     .map_err(|e| ImapError::ConnectionError(format!("Failed to connect to IMAP server: {}", e)))?;
 
     // Login
     let mut imap_session = client
-        .login(&email, &password)
+        .login(&account.description, &account.encrypted_password)
         .map_err(|(e, _)| ImapError::CredentialsError(format!("Failed to login: {}", e)))?;
 
     // Select INBOX
@@ -530,23 +628,109 @@ pub async fn fetch_emails_imap(
         .select("INBOX")
         .map_err(|e| ImapError::FetchError(format!("Failed to select INBOX: {}", e)))?;
 
+    Ok((imap_session, mailbox, account))
+}
+
+/// The latest emails of one account, or of all the user's accounts for None,
+/// oldest first in both cases. An account that fails is skipped unless all do.
+pub async fn fetch_emails_imap(
+    state: &AppState,
+    user_id: i32,
+    account_id: Option<i32>,
+    preview_only: bool,
+    limit: Option<u32>,
+    unprocessed: bool,
+    unread_only: bool,
+) -> Result<Vec<ImapEmailPreview>, ImapError> {
+    tracing::debug!("Starting fetch_emails_imap for user {} account {:?} with preview_only: {}, limit: {:?}, unprocessed: {}", 
+        user_id, account_id, preview_only, limit, unprocessed);
     // Calculate how many messages to fetch based on limit parameter
     let limit = limit.unwrap_or(20);
-    let sequence_set = format!("{}:{}", (mailbox.exists.saturating_sub(limit - 1)), mailbox.exists);
-    let messages = imap_session
-        .fetch(
-            &sequence_set,
-            "(UID FLAGS ENVELOPE BODY.PEEK[])",  // PEEK to not mark the email as read
-        )
-        .map_err(|e| ImapError::FetchError(format!("Failed to fetch messages: {}", e)))?;
+    if account_id.is_some() {
+        return fetch_account_emails(state, user_id, account_id, limit, unprocessed, unread_only);
+    }
 
+    let accounts = state
+        .user_repository
+        .get_imap_accounts(user_id)
+        .map_err(|e| ImapError::CredentialsError(e.to_string()))?;
+    if accounts.is_empty() {
+        return Err(ImapError::NoConnection);
+    }
     let mut email_previews = Vec::new();
+    let mut errors = Vec::new();
+    let account_count = accounts.len();
+    for account in accounts {
+        match fetch_account_emails(state, user_id, account.id, limit, unprocessed, unread_only) {
+            Ok(previews) => email_previews.extend(previews),
+            Err(e) => {
+                tracing::error!("Failed to fetch emails of account {:?} for user {}: {:?}", account.id, user_id, e);
+                errors.push(e);
+            }
+        }
+    }
+    if errors.len() == account_count {
+        return Err(errors.remove(0));
+    }
+
+    // Newest `limit` of them all, keeping the oldest first order of a single account
+    email_previews.sort_by_key(|email| email.date);
+    let skip = email_previews.len().saturating_sub(limit as usize);
+    Ok(email_previews.split_off(skip))
+}
+
+fn fetch_account_emails(
+    state: &AppState,
+    user_id: i32,
+    account_id: Option<i32>,
+    limit: u32,
+    unprocessed: bool,
+    unread_only: bool,
+) -> Result<Vec<ImapEmailPreview>, ImapError> {
+    let (mut imap_session, mailbox, account) = connect_inbox(state, user_id, account_id)?;
+
+    let mut email_previews = Vec::new();
+    if mailbox.exists > 0 {
+        let sequence_set = format!("{}:{}", (mailbox.exists.saturating_sub(limit - 1)).max(1), mailbox.exists);
+        let messages = imap_session
+            .fetch(
+                &sequence_set,
+                "(UID FLAGS ENVELOPE BODY.PEEK[])",  // PEEK to not mark the email as read
+            )
+            .map_err(|e| ImapError::FetchError(format!("Failed to fetch messages: {}", e)))?;
+
+        email_previews = parse_email_previews(state, user_id, &account, &messages, unprocessed, unread_only)?;
+    }
+
+    // Logout
+    imap_session
+        .logout()
+        .map_err(|e| ImapError::ConnectionError(format!("Failed to logout: {}", e)))?;
+
+    // Reverse the order so newest emails appear first
+    //email_previews.reverse();
 
-    for message in messages.iter() {
+    Ok(email_previews)
+}
+
+/// Turns messages of `account` fetched with "(UID FLAGS ENVELOPE BODY.PEEK[])" into previews and
+/// indexes them. With `unprocessed` already processed ones are skipped and the rest marked processed.
+pub fn parse_email_previews(
+    state: &AppState,
+    user_id: i32,
+    account: &ImapConnection,
+    messages: &[imap::types::Fetch],
+    unprocessed: bool,
+    unread_only: bool,
+) -> Result<Vec<ImapEmailPreview>, ImapError> {
+    let account_id = account.id.ok_or(ImapError::NoConnection)?;
+    let mut email_previews = Vec::new();
+
+    for message in messages {
         let uid = message.uid.unwrap_or(0).to_string();
         
         // Check if email is already processed using repository method
-        let is_processed = state.user_repository.is_email_processed(user_id, &uid)
+        let is_processed = state.user_repository.is_email_processed(user_id, account_id, &uid)
             .map_err(|e| ImapError::FetchError(format!("Failed to check email processed status: {}", e)))?;
         
         // Skip processed emails if unprocessed is true
@@ -628,10 +812,11 @@ pub async fn fetch_emails_imap(
 
         let body_content = full_body.or(text_body);
 
-        let (body, snippet) = body_content.as_ref().map(|content| {
+        let (body, snippet, auto_generated) = body_content.as_ref().map(|content| {
             // Create a parser and parse the content into an Option<Message>
             let parser = MessageParser::default();
             let parsed = parser.parse(content.as_bytes());
+            let auto_generated = parsed.as_ref().is_some_and(is_auto_generated);
 
             // Get the best available body content, if parsing succeeded
             let clean_content = parsed.map(|msg| {
@@ -650,8 +835,8 @@ pub async fn fetch_emails_imap(
             // Generate a snippet from the clean body
             let snippet = clean_content.chars().take(200).collect::<String>();
 
-            (clean_content, snippet)
-        }).unwrap_or_else(|| (String::new(), String::new()));
+            (clean_content, snippet, auto_generated)
+        }).unwrap_or_else(|| (String::new(), String::new(), false));
 
             let user_timezone = state.user_core.get_user_info(user_id)
                 .ok()
@@ -669,8 +854,22 @@ pub async fn fetch_emails_imap(
 
             tracing::debug!("Final formatted date: {:?}", date_formatted);
 
+            // Makes the email findable with search_messages
+            crate::utils::message_index::index_message(state, crate::models::user_models::NewIndexedMessage {
+                user_id,
+                platform: "email".to_string(),
+                message_key: format_email_id(account_id, &uid),
+                encrypted_room_name: subject.clone().unwrap_or_default(),
+                encrypted_sender: format!("{} {}", from, from_email).trim().to_string(),
+                encrypted_content: body.clone(),
+                message_type: "email".to_string(),
+                sent_at: date.map(|dt| dt.timestamp()).unwrap_or_else(|| Utc::now().timestamp()) as i32,
+            });
+
             email_previews.push(ImapEmailPreview {
-                id: uid.clone(),
+                id: format_email_id(account_id, &uid),
+                account_id,
+                account: account_label(account),
                 subject: subject.clone(),
                 from: Some(from.clone()),
                 from_email: Some(from_email.clone()),
@@ -679,11 +878,12 @@ pub async fn fetch_emails_imap(
                 snippet: Some(snippet),
                 body: Some(body),
                 is_read,
+                auto_generated,
             });
 
         // Mark email as processed if unprocessed is true
         if unprocessed {
-            match state.user_repository.mark_email_as_processed(user_id, &uid) {
+            match state.user_repository.mark_email_as_processed(user_id, account_id, &uid) {
                 Ok(_) => {
                     tracing::info!("Marked email {} as processed", uid);
                 }
@@ -695,14 +895,6 @@ pub async fn fetch_emails_imap(
         }
     }
 
-    // Logout
-    imap_session
-        .logout()
-        .map_err(|e| ImapError::ConnectionError(format!("Failed to logout: {}", e)))?;
-
-    // Reverse the order so newest emails appear first
-    //email_previews.reverse();
-
     Ok(email_previews)
 }
 
@@ -711,39 +903,14 @@ pub async fn fetch_single_email_imap(
     user_id: i32,
     email_id: &str,
 ) -> Result<ImapEmail, ImapError> {
-
-    // Get IMAP credentials
-    let (email, password, imap_server, imap_port) = state
-        .user_repository
-        .get_imap_credentials(user_id)
-        .map_err(|e| ImapError::CredentialsError(e.to_string()))?
-        .ok_or(ImapError::NoConnection)?;
-
-    // Set up TLS
-    let tls = TlsConnector::builder()
-        .build()
-        .map_err(|e| ImapError::ConnectionError(format!("Failed to create TLS connector: {}", e)))?;
-
-    let server = imap_server.as_deref().unwrap_or("imap.gmail.com");
-    let port = imap_port.unwrap_or(993);
-    // Connect to IMAP server
-    let client = imap::connect((server, port as u16), server, &tls)
-    .map_err(|e| ImapError::ConnectionError(format!("Failed to connect to IMAP server: {}", e)))?;
-
-    // Login
-    let mut imap_session = client
-        .login(&email, &password)
-        .map_err(|(e, _)| ImapError::CredentialsError(format!("Failed to login: {}", e)))?;
-
-    // Select INBOX
-    imap_session
-        .select("INBOX")
-        .map_err(|e| ImapError::FetchError(format!("Failed to select INBOX: {}", e)))?;
+    let (account_id, uid) = parse_email_id(email_id)
+        .ok_or_else(|| ImapError::FetchError(format!("Invalid email ID {}", email_id)))?;
+    let (mut imap_session, _, account) = connect_inbox(state, user_id, account_id)?;
 
     // Fetch specific message with body structure for attachments
     // Using BODY.PEEK[] to avoid marking the email as read
     let messages = match imap_session.uid_fetch(
-        email_id,
+        uid,
         "(UID FLAGS ENVELOPE BODY.PEEK[] BODYSTRUCTURE)",
     ) {
         Ok(messages) => messages,
@@ -767,9 +934,9 @@ pub async fn fetch_single_email_imap(
         ImapError::ParseError("Message has no UID".to_string())
     })?;
 
-    if msg_uid.to_string() != email_id {
-        tracing::error!("UID mismatch: expected {}, got {}", email_id, msg_uid);
-        return Err(ImapError::FetchError(format!("Message UID mismatch: expected {}, got {}", email_id, msg_uid)));
+    if msg_uid.to_string() != uid {
+        tracing::error!("UID mismatch: expected {}, got {}", uid, msg_uid);
+        return Err(ImapError::FetchError(format!("Message UID mismatch: expected {}, got {}", uid, msg_uid)));
     }
 
     let envelope = message
@@ -939,7 +1106,8 @@ pub async fn fetch_single_email_imap(
         .and_then(|info| info.timezone)));
 
     Ok(ImapEmail {
-        id: email_id.to_string(),
+        id: format_email_id(account.id.unwrap_or_default(), uid),
+        account: account_label(&account),
         subject,
         from,
         from_email,
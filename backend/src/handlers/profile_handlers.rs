@@ -96,6 +96,8 @@ pub struct ProfileResponse {
     twilio_sid: Option<String>,
     twilio_token: Option<String>,
     openrouter_api_key: Option<String>,
+    llm_model: Option<String>,
+    send_delay_seconds: i32,
     textbee_device_id: Option<String>,
     textbee_api_key: Option<String>,
     estimated_monitoring_cost: f32,
@@ -283,6 +285,8 @@ pub async fn get_profile(
                 twilio_sid: twilio_sid,
                 twilio_token: twilio_token,
                 openrouter_api_key: openrouter_api_key,
+                llm_model: user_settings.llm_model,
+                send_delay_seconds: user_settings.send_delay_seconds,
                 textbee_device_id: textbee_device_id,
                 textbee_api_key: textbee_api_key,
                 estimated_monitoring_cost,
@@ -860,6 +864,12 @@ pub async fn update_profile(
                     Json(json!({"error": format!("Failed to update agent language: {}", e)}))
                 ));
             }
+            // Turning history off also drops the summary made of it
+            if update_req.save_context.unwrap_or(0) <= 0 {
+                if let Err(e) = state.user_repository.delete_conversation_summary(auth_user.user_id) {
+                    tracing::error!("Failed to delete conversation summary: {}", e);
+                }
+            }
             // Set phone country after update
             if let Err(e) = set_user_phone_country(&state, auth_user.user_id, &update_req.phone_number).await {
                 tracing::error!("Failed to set phone country after profile update: {}", e);
@@ -1197,6 +1207,461 @@ pub async fn get_proactive_agent_on(
     }
 }
 
+#[derive(Deserialize)]
+pub struct LlmOverrideRequest {
+    openrouter_api_key: Option<String>,
+    model: Option<String>,
+}
+
+pub async fn update_llm_override(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Json(request): Json<LlmOverrideRequest>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    // on self hosted instances the key field holds the instance key, that is set through the self hosting setup
+    if std::env::var("ENVIRONMENT") == Ok("self_hosted".to_string()) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "Model settings are configured through the server environment on self hosted instances"}))
+        ));
+    }
+
+    let api_key = request.openrouter_api_key.as_deref().map(str::trim).filter(|k| !k.is_empty());
+    let model = request.model.as_deref().map(str::trim).filter(|m| !m.is_empty());
+
+    if let Some(model) = model {
+        // without their own key the model runs on the instance key, so only operator approved ones
+        if api_key.is_none() && !crate::utils::llm_provider::model_allowed_on_instance_key(model) {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "Choosing this model requires your own OpenRouter API key"}))
+            ));
+        }
+        match crate::utils::llm_provider::openrouter_model_exists(model).await {
+            Ok(true) => {}
+            Ok(false) => {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({"error": format!("Unknown model: {}", model)}))
+                ));
+            }
+            Err(e) => {
+                tracing::error!("Failed to check model {} against OpenRouter: {}", model, e);
+                return Err((
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    Json(json!({"error": "Couldn't verify the model right now, try again later"}))
+                ));
+            }
+        }
+    }
+
+    match state.user_core.update_llm_override(auth_user.user_id, api_key, model) {
+        Ok(_) => Ok(Json(json!({
+            "message": "Model settings updated successfully"
+        }))),
+        Err(e) => {
+            tracing::error!("Failed to update model settings: {}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": format!("Failed to update model settings: {}", e)}))
+            ))
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SendDelayRequest {
+    seconds: i32,
+}
+
+pub async fn update_send_delay(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Json(request): Json<SendDelayRequest>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let max = crate::utils::outbox::MAX_SEND_DELAY_SECONDS;
+    if request.seconds < 0 || request.seconds > max {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": format!("Send delay must be between 0 and {} seconds", max)}))
+        ));
+    }
+
+    match state.user_core.update_send_delay(auth_user.user_id, request.seconds) {
+        Ok(_) => Ok(Json(json!({
+            "message": "Send delay updated successfully"
+        }))),
+        Err(e) => {
+            tracing::error!("Failed to update send delay: {}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": format!("Failed to update send delay: {}", e)}))
+            ))
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct AwayModeResponse {
+    enabled: bool,
+    active: bool, // enabled and within the start and end times
+    message: Option<String>,
+    contact_mode: String,
+    contacts: Vec<String>,
+    reply_interval_hours: i32,
+    priority_only: bool,
+    starts_at: Option<i32>,
+    ends_at: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct AwayModeRequest {
+    enabled: bool,
+    message: Option<String>, // None or empty uses the default reply
+    contact_mode: Option<String>,
+    contacts: Option<Vec<String>>,
+    reply_interval_hours: Option<i32>,
+    priority_only: Option<bool>,
+    starts_at: Option<i32>,
+    ends_at: Option<i32>,
+}
+
+pub async fn get_away_mode(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+) -> Result<Json<AwayModeResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let settings = state.user_core.get_user_settings(auth_user.user_id)
+        .map_err(|e| (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": format!("Database error: {}", e)}))
+        ))?;
+
+    Ok(Json(AwayModeResponse {
+        enabled: settings.away_enabled,
+        active: crate::utils::away_mode::AwayMode::active(&state, auth_user.user_id).is_some(),
+        message: settings.encrypted_away_message
+            .as_deref()
+            .and_then(|message| crate::utils::encryption::decrypt(message).ok()),
+        contact_mode: settings.away_contact_mode,
+        contacts: settings.away_contacts
+            .as_deref()
+            .and_then(|contacts| serde_json::from_str(contacts).ok())
+            .unwrap_or_default(),
+        reply_interval_hours: settings.away_reply_interval_hours,
+        priority_only: settings.away_priority_only,
+        starts_at: settings.away_starts_at,
+        ends_at: settings.away_ends_at,
+    }))
+}
+
+pub async fn update_away_mode(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Json(request): Json<AwayModeRequest>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    use crate::utils::away_mode::{CONTACT_MODES, MAX_MESSAGE_CHARS, MAX_REPLY_INTERVAL_HOURS};
+
+    let bad_request = |error: String| (StatusCode::BAD_REQUEST, Json(json!({"error": error})));
+    let contact_mode = request.contact_mode.unwrap_or_else(|| "all".to_string());
+    if !CONTACT_MODES.contains(&contact_mode.as_str()) {
+        return Err(bad_request(format!("Invalid contact mode. Must be one of: {}", CONTACT_MODES.join(", "))));
+    }
+    let message = request.message
+        .map(|message| message.trim().to_string())
+        .filter(|message| !message.is_empty());
+    if message.as_ref().is_some_and(|message| message.chars().count() > MAX_MESSAGE_CHARS) {
+        return Err(bad_request(format!("Away message can be at most {} characters", MAX_MESSAGE_CHARS)));
+    }
+    let reply_interval_hours = request.reply_interval_hours.unwrap_or(24);
+    if !(1..=MAX_REPLY_INTERVAL_HOURS).contains(&reply_interval_hours) {
+        return Err(bad_request(format!("Reply interval must be between 1 and {} hours", MAX_REPLY_INTERVAL_HOURS)));
+    }
+    if let (Some(starts_at), Some(ends_at)) = (request.starts_at, request.ends_at) {
+        if ends_at <= starts_at {
+            return Err(bad_request("Away mode has to end after it starts".to_string()));
+        }
+    }
+    let contacts: Vec<String> = request.contacts
+        .unwrap_or_default()
+        .into_iter()
+        .map(|contact| contact.trim().to_string())
+        .filter(|contact| !contact.is_empty())
+        .collect();
+    if contact_mode == "allowlist" && contacts.is_empty() {
+        return Err(bad_request("An allowlist needs at least one contact".to_string()));
+    }
+
+    let was_enabled = state.user_core.get_user_settings(auth_user.user_id)
+        .map(|settings| settings.away_enabled)
+        .unwrap_or(false);
+    let away_mode = crate::models::user_models::AwayModeSettings {
+        away_enabled: request.enabled,
+        encrypted_away_message: message,
+        away_contact_mode: contact_mode,
+        away_contacts: Some(serde_json::to_string(&contacts).unwrap_or_else(|_| "[]".to_string())),
+        away_reply_interval_hours: reply_interval_hours,
+        away_priority_only: request.priority_only.unwrap_or(true),
+        away_starts_at: request.starts_at,
+        away_ends_at: request.ends_at,
+    };
+    state.user_core.update_away_mode(auth_user.user_id, &away_mode)
+        .map_err(|e| (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": format!("Failed to update away mode: {}", e)}))
+        ))?;
+
+    // A new away period answers everyone again
+    if request.enabled && !was_enabled {
+        if let Err(e) = state.user_repository.clear_away_replies(auth_user.user_id) {
+            tracing::error!("Failed to clear away replies for user {}: {}", auth_user.user_id, e);
+        }
+    }
+
+    Ok(Json(json!({
+        "message": "Away mode updated successfully"
+    })))
+}
+
+#[derive(Serialize)]
+pub struct UserMemoryResponse {
+    id: i32,
+    content: String,
+    category: String,
+    source: String,
+    created_at: i32,
+}
+
+#[derive(Deserialize)]
+pub struct NewMemoryRequest {
+    content: String,
+    category: Option<String>,
+}
+
+pub async fn get_memories(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+) -> Result<Json<Vec<UserMemoryResponse>>, (StatusCode, Json<serde_json::Value>)> {
+    let memories = state.user_repository.get_user_memories(auth_user.user_id)
+        .map_err(|e| (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": format!("Database error: {}", e)}))
+        ))?;
+
+    Ok(Json(memories.into_iter()
+        .map(|memory| UserMemoryResponse {
+            id: memory.id.unwrap_or(0),
+            content: memory.encrypted_content,
+            category: memory.category,
+            source: memory.source,
+            created_at: memory.created_at,
+        })
+        .collect()))
+}
+
+pub async fn add_memory(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Json(request): Json<NewMemoryRequest>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let content = request.content.trim();
+    if content.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "Memory can't be empty"}))
+        ));
+    }
+    let category = request.category.unwrap_or_else(|| "other".to_string());
+    if !crate::utils::user_memory::CATEGORIES.contains(&category.as_str()) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": format!("Invalid category. Must be one of: {}", crate::utils::user_memory::CATEGORIES.join(", "))}))
+        ));
+    }
+
+    state.user_repository.add_user_memory(auth_user.user_id, content, &category, "user")
+        .map_err(|e| (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": format!("Database error: {}", e)}))
+        ))?;
+
+    Ok(Json(json!({
+        "message": "Memory added successfully"
+    })))
+}
+
+pub async fn delete_memory(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    axum::extract::Path(memory_id): axum::extract::Path<i32>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let deleted = state.user_repository.delete_user_memory(auth_user.user_id, memory_id)
+        .map_err(|e| (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": format!("Database error: {}", e)}))
+        ))?;
+
+    if !deleted {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "Memory not found"}))
+        ));
+    }
+
+    Ok(Json(json!({
+        "message": "Memory deleted successfully"
+    })))
+}
+
+#[derive(Serialize)]
+pub struct ScheduledSendResponse {
+    id: i32,
+    action_type: String,
+    description: String,
+    payload: serde_json::Value,
+    send_at: i32,
+    created_at: i32,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateScheduledSendRequest {
+    message: Option<String>,
+    send_at: Option<String>, // rfc3339, or local time in the user's timezone
+}
+
+pub async fn get_scheduled_sends(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+) -> Result<Json<Vec<ScheduledSendResponse>>, (StatusCode, Json<serde_json::Value>)> {
+    let sends = state.user_repository.get_scheduled_sends(auth_user.user_id)
+        .map_err(|e| (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": format!("Database error: {}", e)}))
+        ))?;
+    let timezone = crate::tool_call_utils::confirm::user_timezone(&state, auth_user.user_id);
+
+    Ok(Json(sends.into_iter()
+        .filter_map(|send| {
+            let description = crate::utils::scheduled_sends::describe_send(&send, &timezone)?;
+            Some(ScheduledSendResponse {
+                id: send.id?,
+                action_type: send.action_type,
+                description,
+                payload: serde_json::from_str(&send.encrypted_payload).ok()?,
+                send_at: send.send_at,
+                created_at: send.created_at,
+            })
+        })
+        .collect()))
+}
+
+pub async fn update_scheduled_send(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    axum::extract::Path(send_id): axum::extract::Path<i32>,
+    Json(request): Json<UpdateScheduledSendRequest>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    if request.message.is_none() && request.send_at.is_none() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "Nothing to change"}))
+        ));
+    }
+
+    match crate::utils::scheduled_sends::update(
+        &state,
+        auth_user.user_id,
+        send_id,
+        request.message.as_deref(),
+        request.send_at.as_deref(),
+    ) {
+        Ok(description) => Ok(Json(json!({
+            "message": description
+        }))),
+        Err(e) => Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": e}))
+        )),
+    }
+}
+
+pub async fn cancel_scheduled_send(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    axum::extract::Path(send_id): axum::extract::Path<i32>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let cancelled = state.user_repository.cancel_scheduled_send(auth_user.user_id, send_id)
+        .map_err(|e| (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": format!("Database error: {}", e)}))
+        ))?;
+
+    if !cancelled {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "Scheduled message not found or already sent"}))
+        ));
+    }
+
+    Ok(Json(json!({
+        "message": "Scheduled message cancelled"
+    })))
+}
+
+#[derive(Serialize)]
+pub struct ContactResponse {
+    id: i32,
+    name: String,
+    phone: Option<String>,
+    email: Option<String>,
+    source: String,
+    created_at: i32,
+}
+
+pub async fn get_contacts(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+) -> Result<Json<Vec<ContactResponse>>, (StatusCode, Json<serde_json::Value>)> {
+    let contacts = state.user_repository.get_contacts(auth_user.user_id)
+        .map_err(|e| (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": format!("Database error: {}", e)}))
+        ))?;
+
+    Ok(Json(contacts.into_iter()
+        .map(|contact| ContactResponse {
+            id: contact.id.unwrap_or(0),
+            name: contact.encrypted_name,
+            phone: contact.encrypted_phone,
+            email: contact.encrypted_email,
+            source: contact.source,
+            created_at: contact.created_at,
+        })
+        .collect()))
+}
+
+pub async fn delete_contact(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    axum::extract::Path(contact_id): axum::extract::Path<i32>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let deleted = state.user_repository.delete_contact(auth_user.user_id, contact_id)
+        .map_err(|e| (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": format!("Database error: {}", e)}))
+        ))?;
+
+    if !deleted {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "Contact not found"}))
+        ));
+    }
+
+    Ok(Json(json!({
+        "message": "Contact deleted successfully"
+    })))
+}
+
 pub async fn delete_user(
     State(state): State<Arc<AppState>>,
     auth_user: AuthUser,
@@ -0,0 +1,484 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tracing::{debug, error, info, warn};
+
+use crate::handlers::imap_handlers::{self, ImapEmailPreview, ImapError, ImapSession};
+use crate::models::user_models::ImapConnection;
+use crate::AppState;
+
+// A timeout also renews the IDLE, servers may drop one that runs past 30 minutes
+const IDLE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+const POLL_INTERVAL: Duration = Duration::from_secs(60); // for servers without IDLE
+const BACKOFF_BASE_SECONDS: u64 = 5;
+const BACKOFF_MAX_SECONDS: u64 = 300;
+// Emails looked at on (re)connect to catch up on what arrived in between, processed ones are skipped
+const CATCH_UP_LIMIT: u32 = 10;
+const FETCH_QUERY: &str = "(UID FLAGS ENVELOPE BODY.PEEK[])"; // PEEK to not mark the email as read
+// Every IDLE keeps a thread blocked for up to IDLE_TIMEOUT, watchers past this many poll instead
+const MAX_IDLE_WATCHERS: usize = 200;
+
+static IDLE_WATCHERS: AtomicUsize = AtomicUsize::new(0);
+
+// One of the MAX_IDLE_WATCHERS slots, free again when the watch holding it is dropped
+struct IdleSlot;
+
+impl IdleSlot {
+    fn acquire() -> Option<Self> {
+        IDLE_WATCHERS
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| (count < MAX_IDLE_WATCHERS).then_some(count + 1))
+            .ok()
+            .map(|_| IdleSlot)
+    }
+}
+
+impl Drop for IdleSlot {
+    fn drop(&mut self) {
+        IDLE_WATCHERS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// Lets the user answer an email notification with "r: .."
+fn email_source(email: &ImapEmailPreview) -> crate::utils::notification_sources::NotificationSource {
+    crate::utils::notification_sources::NotificationSource::Email {
+        email_id: email.id.clone(),
+        subject: email.subject.clone().unwrap_or_else(|| "No subject".to_string()),
+    }
+}
+
+/// Starts an inbox watcher for every monitored email account of tier 2 users and stops the ones
+/// that no longer qualify or have given up. Run on startup and then every minute by the scheduler.
+pub async fn sync_imap_watchers(state: &Arc<AppState>) {
+    let accounts = match state.user_repository.get_monitored_imap_accounts() {
+        Ok(accounts) => accounts,
+        Err(e) => {
+            error!("Failed to get monitored email accounts for watchers: {}", e);
+            return;
+        }
+    };
+    let tier_2_users: HashSet<i32> = match state.user_core.get_users_by_tier("tier 2") {
+        Ok(users) => users.into_iter().map(|user| user.id).collect(),
+        Err(e) => {
+            error!("Failed to get tier 2 users for IMAP watchers: {}", e);
+            return;
+        }
+    };
+    let watched: HashMap<i32, i32> = accounts
+        .into_iter()
+        .filter(|(_, user_id)| tier_2_users.contains(user_id))
+        .collect();
+
+    let mut tasks = state.imap_idle_tasks.lock().await;
+    tasks.retain(|account_id, task| {
+        let keep = watched.contains_key(account_id) && !task.is_finished();
+        if !keep {
+            task.abort();
+        }
+        keep
+    });
+    for (account_id, user_id) in watched {
+        tasks.entry(account_id).or_insert_with(|| {
+            debug!("Starting IMAP watcher for user {} account {}", user_id, account_id);
+            tokio::spawn(watch_inbox(Arc::clone(state), user_id, account_id))
+        });
+    }
+}
+
+/// Stops the account's watcher, e.g. when it is disconnected or its monitoring turned off. The next
+/// sync starts a new one if there is still something to watch, so this also picks up new credentials.
+pub async fn stop_imap_watcher(state: &Arc<AppState>, account_id: i32) {
+    if let Some(task) = state.imap_idle_tasks.lock().await.remove(&account_id) {
+        task.abort();
+    }
+}
+
+struct Watch {
+    session: ImapSession,
+    account: ImapConnection,
+    idle_slot: Option<IdleSlot>, // None when the server has no IDLE or all slots are taken, then it polls
+    last_uid: u32,
+}
+
+// Runs an IDLE wait on a thread of its own, on tokio's blocking pool every watcher would hold a
+// thread for minutes and starve everything else that needs one. The watch moves along with its
+// slot, so an aborted watcher still counts until its thread is done.
+async fn on_own_thread<T: Send + 'static>(work: impl FnOnce() -> T + Send + 'static) -> Result<T, String> {
+    let (sender, receiver) = tokio::sync::oneshot::channel();
+    std::thread::Builder::new()
+        .name("imap-idle".to_string())
+        .spawn(move || {
+            let _ = sender.send(work());
+        })
+        .map_err(|e| format!("Failed to start IDLE thread: {}", e))?;
+    receiver.await.map_err(|_| "IDLE thread panicked".to_string())
+}
+
+async fn watch_inbox(state: Arc<AppState>, user_id: i32, account_id: i32) {
+    let mut backoff = BACKOFF_BASE_SECONDS;
+    loop {
+        let connect_state = Arc::clone(&state);
+        let (mut watch, emails) = match tokio::task::spawn_blocking(move || connect(&connect_state, user_id, account_id)).await {
+            Ok(Ok(connected)) => connected,
+            Ok(Err(ImapError::NoConnection)) => {
+                debug!("IMAP watcher for user {} stopped, account {} is gone", user_id, account_id);
+                return;
+            }
+            Ok(Err(e)) => {
+                warn!("IMAP watcher for user {} account {} failed to connect, retrying in {}s: {:?}", user_id, account_id, backoff, e);
+                tokio::time::sleep(Duration::from_secs(backoff)).await;
+                backoff = (backoff * 2).min(BACKOFF_MAX_SECONDS);
+                continue;
+            }
+            Err(e) => {
+                error!("IMAP watcher for user {} account {} panicked while connecting: {}", user_id, account_id, e);
+                tokio::time::sleep(Duration::from_secs(backoff)).await;
+                backoff = (backoff * 2).min(BACKOFF_MAX_SECONDS);
+                continue;
+            }
+        };
+        info!("IMAP watcher connected for user {} account {} ({})", user_id, account_id, if watch.idle_slot.is_some() { "idle" } else { "polling" });
+        backoff = BACKOFF_BASE_SECONDS;
+        process_new_emails(&state, user_id, account_id, emails).await;
+
+        // The session goes to a thread for each wait and comes back with what arrived. Polling
+        // only blocks briefly, that can use the blocking pool.
+        let error = loop {
+            let idle = watch.idle_slot.is_some();
+            if !idle {
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+            let wait_state = Arc::clone(&state);
+            let wait = move || {
+                let result = wait_and_fetch(&wait_state, user_id, &mut watch);
+                (watch, result)
+            };
+            let waited = if idle {
+                on_own_thread(wait).await
+            } else {
+                tokio::task::spawn_blocking(wait).await.map_err(|e| e.to_string())
+            };
+            match waited {
+                Ok((returned, Ok(emails))) => {
+                    watch = returned;
+                    process_new_emails(&state, user_id, account_id, emails).await;
+                }
+                Ok((_, Err(e))) => break format!("{:?}", e),
+                Err(e) => break e,
+            }
+        };
+        warn!("IMAP watcher for user {} account {} lost its connection, reconnecting in {}s: {}", user_id, account_id, backoff, error);
+        tokio::time::sleep(Duration::from_secs(backoff)).await;
+        backoff = (backoff * 2).min(BACKOFF_MAX_SECONDS);
+    }
+}
+
+// Opens the session and returns the recent unprocessed emails with it
+fn connect(state: &AppState, user_id: i32, account_id: i32) -> Result<(Watch, Vec<ImapEmailPreview>), ImapError> {
+    let (mut session, mailbox, account) = imap_handlers::connect_inbox(state, user_id, Some(account_id))?;
+    let supports_idle = session
+        .capabilities()
+        .map(|capabilities| capabilities.has_str("IDLE"))
+        .map_err(|e| ImapError::ConnectionError(format!("Failed to get capabilities: {}", e)))?;
+    let idle_slot = if supports_idle { IdleSlot::acquire() } else { None };
+    if supports_idle && idle_slot.is_none() {
+        warn!("All {} IDLE slots taken, user {} account {} polls instead", MAX_IDLE_WATCHERS, user_id, account_id);
+    }
+
+    let mut last_uid = mailbox.uid_next.unwrap_or(1).saturating_sub(1);
+    let mut emails = Vec::new();
+    if mailbox.exists > 0 {
+        let sequence_set = format!("{}:{}", mailbox.exists.saturating_sub(CATCH_UP_LIMIT - 1).max(1), mailbox.exists);
+        let messages = session
+            .fetch(&sequence_set, FETCH_QUERY)
+            .map_err(|e| ImapError::FetchError(format!("Failed to fetch messages: {}", e)))?;
+        last_uid = messages.iter().filter_map(|message| message.uid).fold(last_uid, u32::max);
+        emails = imap_handlers::parse_email_previews(state, user_id, &account, &messages, true, true)?;
+    }
+
+    Ok((Watch { session, account, idle_slot, last_uid }, emails))
+}
+
+// Blocks until the server reports a change (or the IDLE times out, or a NOOP for polling)
+// and fetches whatever arrived after last_uid
+fn wait_and_fetch(state: &AppState, user_id: i32, watch: &mut Watch) -> Result<Vec<ImapEmailPreview>, ImapError> {
+    let waited = if watch.idle_slot.is_some() {
+        watch.session.idle().and_then(|handle| handle.wait_with_timeout(IDLE_TIMEOUT)).map(|_| ())
+    } else {
+        watch.session.noop()
+    };
+    waited.map_err(|e| ImapError::ConnectionError(format!("Failed to wait for new emails: {}", e)))?;
+
+    // "n:*" always matches the newest message, even when its uid is below n
+    let mut uids: Vec<u32> = watch.session
+        .uid_search(format!("UID {}:*", watch.last_uid + 1))
+        .map_err(|e| ImapError::FetchError(format!("Failed to search new emails: {}", e)))?
+        .into_iter()
+        .filter(|uid| *uid > watch.last_uid)
+        .collect();
+    if uids.is_empty() {
+        return Ok(Vec::new());
+    }
+    uids.sort_unstable();
+    watch.last_uid = uids[uids.len() - 1];
+
+    let uid_set = uids.iter().map(u32::to_string).collect::<Vec<_>>().join(",");
+    let messages = watch.session
+        .uid_fetch(&uid_set, FETCH_QUERY)
+        .map_err(|e| ImapError::FetchError(format!("Failed to fetch new emails: {}", e)))?;
+    imap_handlers::parse_email_previews(state, user_id, &watch.account, &messages, true, true)
+}
+
+/// Runs new emails through priority senders, away replies, waiting checks and the importance check.
+async fn process_new_emails(state: &Arc<AppState>, user_id: i32, account_id: i32, emails: Vec<ImapEmailPreview>) {
+    if emails.is_empty() {
+        return;
+    }
+
+    match state.user_repository.get_processed_emails(user_id, account_id) {
+        Ok(mut processed_emails) => {
+            // Define constants
+            let fetch_window = CATCH_UP_LIMIT as usize;  // Number of emails the watcher looks at when it connects
+            let cleanup_threshold = 100;  // Only cleanup when we have significantly more than fetch window
+
+            if processed_emails.len() > cleanup_threshold {
+                // Sort by processed_at timestamp (newest first)
+                processed_emails.sort_by(|a, b| b.processed_at.cmp(&a.processed_at));
+
+                // Keep at least fetch_window emails plus some buffer
+                let keep_count = fetch_window * 2;  // Keep 20 emails (double the fetch window)
+
+                // Get emails to delete (older than our keep_count)
+                let emails_to_delete: Vec<_> = processed_emails
+                    .iter()
+                    .skip(keep_count)
+                    .collect();
+
+                // Delete old processed emails
+                for email in emails_to_delete {
+                    if let Err(e) = state.user_repository.delete_processed_email(user_id, account_id, &email.email_uid) {
+                        error!("Failed to delete old processed email {}: {}", email.email_uid, e);
+                    } else {
+                        debug!("Deleted old processed email {} for user {}", email.email_uid, user_id);
+                    }
+                }
+
+                // Update the original collection
+                processed_emails.truncate(keep_count);
+
+                // Also clean up old email judgments
+                if let Err(e) = state.user_repository.delete_old_email_judgments(user_id) {
+                    error!("Failed to delete old email judgments for user {}: {}", user_id, e);
+                } else {
+                    debug!("Successfully cleaned up old email judgments for user {}", user_id);
+                }
+            }
+        }
+        Err(e) => error!("Failed to fetch processed emails for garbage collection: {}", e),
+    }
+
+    // Sort emails by date in descending order (most recent first)
+    let mut sorted_emails = emails;
+    sorted_emails.sort_by(|a, b| {
+        let a_date = a.date.unwrap_or_else(|| chrono::Utc::now());
+        let b_date = b.date.unwrap_or_else(|| chrono::Utc::now());
+        b_date.cmp(&a_date)
+    });
+
+    // Senders without an account apply to all of the user's accounts
+    let priority_senders = match state.user_repository.get_priority_senders(user_id, "imap") {
+        Ok(senders) => senders
+            .into_iter()
+            .filter(|sender| sender.imap_connection_id.map_or(true, |id| id == account_id))
+            .collect::<Vec<_>>(),
+        Err(e) => {
+            tracing::error!("Failed to get priority senders for user {}: {}", user_id, e);
+            Vec::new()
+        }
+    };
+    let away_mode = crate::utils::away_mode::AwayMode::active(state, user_id);
+    // Mark emails as processed and format them for importance checking
+    let mut emails_content = String::from("New emails:\n");
+    let mut checked_emails = Vec::new();
+    for email in &sorted_emails {
+        if let Some(away) = &away_mode {
+            away.reply_to_email(state, user_id, email).await;
+        }
+        // Check if sender matches priority senders and send the noti anyways about it
+        if let Some(matched_sender) = priority_senders.iter().find(|priority_sender| {
+            let priority_lower = priority_sender.sender.to_lowercase();
+            // Check 'from' (display name)
+            let from_matches = email.from.as_deref().unwrap_or("Unknown").to_lowercase().contains(&priority_lower);
+            // Also check 'from_email' (actual email address)
+            let from_email_matches = email.from_email.as_deref().unwrap_or("Unknown").to_lowercase().contains(&priority_lower);
+            from_matches || from_email_matches
+        }) {
+            tracing::info!("Fast check: Priority sender matched for user {}", user_id);
+
+            // Determine suffix based on noti_type
+            let suffix = match matched_sender.noti_type.as_ref().map(|s| s.as_str()) {
+                Some("call") => "_call",
+                _ => "_sms",
+            };
+            let notification_type = format!("email_priority{}", suffix);
+
+            // Format the notification message with sender and content
+            let message = format!(
+                "Email from: {}\nSubject: {}\nContent: {}",
+                email.from.as_deref().unwrap_or("Unknown"),
+                email.subject.as_deref().unwrap_or("No subject"),
+                email.body.as_deref().unwrap_or("No content").chars().take(200).collect::<String>()
+            );
+            let first_message = format!("Hello, you have a critical email from {} with subject: {}",
+                email.from.as_deref().unwrap_or("Unknown"),
+                email.subject.as_deref().unwrap_or("No subject")
+            );
+
+            // Spawn a new task for sending notification
+            let state_clone = state.clone();
+            let source = email_source(email);
+            tokio::spawn(async move {
+                crate::proactive::utils::send_notification(
+                    &state_clone,
+                    user_id,
+                    &message,
+                    notification_type,
+                    Some(first_message),
+                    Some(source),
+                ).await;
+            });
+            continue;
+        }
+        // While away only priority senders get through
+        if away_mode.as_ref().is_some_and(|away| away.priority_only) {
+            continue;
+        }
+        // Format email content for checking
+        let email_content = format!(
+            "From: {}\nSubject: {}\nDate: {}\nBody: {}\n---\n",
+            email.from.as_deref().unwrap_or("Unknown"),
+            email.subject.as_deref().unwrap_or("No subject"),
+            email.date_formatted.as_deref().unwrap_or("Unknown date"),
+            email.body.as_deref().unwrap_or("No content")
+        );
+
+                                                // Check waiting checks first if they exist
+        let waiting_checks = match state.user_repository.get_waiting_checks(user_id, "email") {
+            Ok(checks) => checks,
+            Err(e) => {
+                tracing::error!("Failed to get waiting checks for user {}: {}", user_id, e);
+                Vec::new()
+            }
+        };
+        if !waiting_checks.is_empty() {
+            // Check if any waiting checks match the message
+            if let Ok((check_id_option, message, first_message)) = crate::proactive::utils::check_waiting_check_match(
+                state,
+                user_id,
+                &email_content,
+                &waiting_checks,
+            ).await {
+                if let Some(check_id) = check_id_option {
+                    let message = message.unwrap_or("Waiting check matched in Email, but failed to get content".to_string());
+                    let first_message = first_message.unwrap_or("Hey, I found a match for one of your waiting checks in Email.".to_string());
+
+                    // Find the matched waiting check to determine noti_type
+                    let matched_waiting_check = waiting_checks.iter().find(|wc| wc.id == Some(check_id)).cloned();
+                    let suffix = if let Some(wc) = matched_waiting_check {
+                        match wc.noti_type.as_ref().map(|s| s.as_str()) {
+                            Some("call") => "_call",
+                            _ => "_sms",
+                        }
+                    } else {
+                        "_sms"
+                    };
+                    let notification_type = format!("email_waiting_check{}", suffix);
+
+                    // Delete the matched waiting check
+                    if let Err(e) = state.user_repository.delete_waiting_check_by_id(user_id, check_id) {
+                        tracing::error!("Failed to delete waiting check {}: {}", check_id, e);
+                    }
+
+                    // Send notification
+                    let state_clone = state.clone();
+                    let source = email_source(email);
+                    tokio::spawn(async move {
+                        crate::proactive::utils::send_notification(
+                            &state_clone,
+                            user_id,
+                            &message,
+                            notification_type,
+                            Some(first_message),
+                            Some(source),
+                        ).await;
+                    });
+                    continue;
+                }
+            }
+        }
+
+        // Add email to content string for importance checking
+        emails_content.push_str(&email_content);
+        checked_emails.push(email);
+    }
+    if checked_emails.is_empty() {
+        return;
+    }
+
+    // Check message importance based on waiting checks and criticality
+    let user_settings = match state.user_core.get_user_settings(user_id) {
+        Ok(settings) => settings,
+        Err(e) => {
+            tracing::error!("Failed to get user settings: {}", e);
+            return;
+        }
+    };
+
+    if user_settings.critical_enabled.is_none() {
+        tracing::debug!("Critical message checking disabled for user {}", user_id);
+        return;
+    }
+
+    // Check message importance based on criticality
+    match crate::proactive::utils::check_message_importance(state, user_id, &emails_content).await {
+        Ok((is_critical, message, first_message)) => {
+            if is_critical {
+                let message = message.unwrap_or("Critical email found, check email to see it (failed to fetch actual content, pls report)".to_string());
+                let first_message = first_message.unwrap_or("Hey, I found some critical email you should know.".to_string());
+                tracing::info!(
+                    "Email critical check passed for user {}: {}",
+                    user_id, message
+                );
+
+                // Spawn a new task for sending critical message notification
+                let state_clone = state.clone();
+                let message_clone= message.clone();
+                // Only a single checked email is clearly what the notification is about
+                let source = match checked_emails.as_slice() {
+                    [email] => Some(email_source(email)),
+                    _ => None,
+                };
+                tokio::spawn(async move {
+                    crate::proactive::utils::send_notification(
+                        &state_clone,
+                        user_id,
+                        &message_clone,
+                        "email_critical".to_string(),
+                        Some(first_message),
+                        source,
+                    ).await;
+                });
+            } else {
+                tracing::debug!(
+                    "Email not considered important for user {}: {}",
+                    user_id, message.unwrap_or("failed to get the email content".to_string())
+                );
+
+            }
+        }
+        Err(e) => {
+            tracing::error!("Failed to check email importance: {}", e);
+        }
+    }
+}
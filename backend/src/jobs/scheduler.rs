@@ -3,11 +3,8 @@ use std::sync::Arc;
 use tracing::{debug, error};
 use crate::AppState;
 
-use crate::handlers::imap_handlers::ImapEmailPreview;
-
 use std::env;
 
-use crate::handlers::imap_handlers;
 use crate::api::twilio_utils;
 use reqwest::StatusCode;
 
@@ -55,9 +52,16 @@ async fn initialize_matrix_clients(state: Arc<AppState>) {
                             .timeout(std::time::Duration::from_secs(30))
                             .full_state(true);
 
+                        let state_for_sync = Arc::clone(&state);
                         let handle = tokio::spawn(async move {
                             loop {
-                                match client.sync(sync_settings.clone()).await {
+                                // a new span every time the sync (re)starts, see message_index::IndexCoverage
+                                let coverage = crate::utils::message_index::IndexCoverage::new(&state_for_sync, user_id);
+                                let synced = move |_| {
+                                    coverage.synced();
+                                    async { matrix_sdk::LoopCtrl::Continue }
+                                };
+                                match client.sync_with_callback(sync_settings.clone(), synced).await {
                                     Ok(_) => {
                                         tracing::debug!("Sync completed normally for user {}", user_id);
                                         tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
@@ -86,259 +90,20 @@ pub async fn start_scheduler(state: Arc<AppState>) {
     // Initialize matrix clients and sync tasks once on startup
     tracing::debug!("Initializing Matrix clients and sync tasks...");
     initialize_matrix_clients(Arc::clone(&state)).await;
+    crate::jobs::imap_idle::sync_imap_watchers(&state).await;
 
     let sched = JobScheduler::new().await.expect("Failed to create scheduler");
 
-    // Create a job that runs every 10 minutes to check for new IMAP messages
+    // Create a job that runs every minute to start and stop the IMAP inbox watchers
     let state_clone = Arc::clone(&state);
-    // TODO CHANGE BEFORE PROD
-    //let message_monitor_job = Job::new_async("0 */10 * * * *", move |_, _| {
-    let message_monitor_job = Job::new_async("*/30 * * * * *", move |_, _| {
+    let imap_watcher_job = Job::new_async("0 * * * * *", move |_, _| {
         let state = state_clone.clone();
         Box::pin(async move {
-            
-            // Process each subscribed user
-            for user in state.user_core.get_users_by_tier("tier 2").unwrap_or(Vec::new()){
-
-                // Check IMAP service
-                if let Ok(imap_users) = state.user_repository.get_active_imap_connection_users() {
-                    if imap_users.contains(&user.id) {
-                        match imap_handlers::fetch_emails_imap(&state, user.id, true, Some(10), true, true).await {
-                            Ok(emails) => {
-                                match state.user_repository.get_processed_emails(user.id) {
-                                    Ok(mut processed_emails) => {
-                                        // Define constants
-                                        let fetch_window = 10;  // Number of emails your scheduler fetches
-                                        let cleanup_threshold = 100;  // Only cleanup when we have significantly more than fetch window
-
-                                        if processed_emails.len() > cleanup_threshold {
-                                            // Sort by processed_at timestamp (newest first)
-                                            processed_emails.sort_by(|a, b| b.processed_at.cmp(&a.processed_at));
-                                            
-                                            // Keep at least fetch_window emails plus some buffer
-                                            let keep_count = fetch_window * 2;  // Keep 20 emails (double the fetch window)
-                                            
-                                            // Get emails to delete (older than our keep_count)
-                                            let emails_to_delete: Vec<_> = processed_emails
-                                                .iter()
-                                                .skip(keep_count)
-                                                .collect();
-
-                                            // Delete old processed emails
-                                            for email in emails_to_delete {
-                                                if let Err(e) = state.user_repository.delete_processed_email(user.id, &email.email_uid) {
-                                                    error!("Failed to delete old processed email {}: {}", email.email_uid, e);
-                                                } else {
-                                                    debug!("Deleted old processed email {} for user {}", email.email_uid, user.id);
-                                                }
-                                            }
-
-                                            // Update the original collection
-                                            processed_emails.truncate(keep_count);
-
-                                            // Also clean up old email judgments
-                                            if let Err(e) = state.user_repository.delete_old_email_judgments(user.id) {
-                                                error!("Failed to delete old email judgments for user {}: {}", user.id, e);
-                                            } else {
-                                                debug!("Successfully cleaned up old email judgments for user {}", user.id);
-                                            }
-                                        }
-                                    }
-                                    Err(e) => error!("Failed to fetch processed emails for garbage collection: {}", e),
-                                }
-                                
-                                if !emails.is_empty() {
-                                    // Sort emails by date in descending order (most recent first)
-                                    let mut sorted_emails = emails;
-                                    sorted_emails.sort_by(|a, b| {
-                                        let a_date = a.date.unwrap_or_else(|| chrono::Utc::now());
-                                        let b_date = b.date.unwrap_or_else(|| chrono::Utc::now());
-                                        b_date.cmp(&a_date)
-                                    });
-
-                                    let priority_senders = match state.user_repository.get_priority_senders(user.id, "imap") {
-                                        Ok(senders) => senders,
-                                        Err(e) => {
-                                            tracing::error!("Failed to get priority senders for user {}: {}", user.id, e);
-                                            Vec::new()
-                                        }
-                                    };
-                                    // Mark emails as processed and format them for importance checking
-                                    let mut emails_content = String::from("New emails:\n");
-                                    for email in &sorted_emails {
-                                        // Check if sender matches priority senders and send the noti anyways about it
-                                        if let Some(matched_sender) = priority_senders.iter().find(|priority_sender| {
-                                            let priority_lower = priority_sender.sender.to_lowercase();
-                                            // Check 'from' (display name)
-                                            let from_matches = email.from.as_deref().unwrap_or("Unknown").to_lowercase().contains(&priority_lower);
-                                            // Also check 'from_email' (actual email address)
-                                            let from_email_matches = email.from_email.as_deref().unwrap_or("Unknown").to_lowercase().contains(&priority_lower);
-                                            from_matches || from_email_matches
-                                        }) {
-                                            tracing::info!("Fast check: Priority sender matched for user {}", user.id);
-                                           
-                                            // Determine suffix based on noti_type
-                                            let suffix = match matched_sender.noti_type.as_ref().map(|s| s.as_str()) {
-                                                Some("call") => "_call",
-                                                _ => "_sms",
-                                            };
-                                            let notification_type = format!("email_priority{}", suffix);
-                                           
-                                            // Format the notification message with sender and content
-                                            let message = format!(
-                                                "Email from: {}\nSubject: {}\nContent: {}",
-                                                email.from.as_deref().unwrap_or("Unknown"),
-                                                email.subject.as_deref().unwrap_or("No subject"),
-                                                email.body.as_deref().unwrap_or("No content").chars().take(200).collect::<String>()
-                                            );
-                                            let first_message = format!("Hello, you have a critical email from {} with subject: {}",
-                                                email.from.as_deref().unwrap_or("Unknown"),
-                                                email.subject.as_deref().unwrap_or("No subject")
-                                            );
-                                           
-                                            // Spawn a new task for sending notification
-                                            let state_clone = state.clone();
-                                            tokio::spawn(async move {
-                                                crate::proactive::utils::send_notification(
-                                                    &state_clone,
-                                                    user.id,
-                                                    &message,
-                                                    notification_type,
-                                                    Some(first_message),
-                                                ).await;
-                                            });
-                                            continue;
-                                        }
-                                        // Format email content for checking
-                                        let email_content = format!(
-                                            "From: {}\nSubject: {}\nDate: {}\nBody: {}\n---\n",
-                                            email.from.as_deref().unwrap_or("Unknown"),
-                                            email.subject.as_deref().unwrap_or("No subject"),
-                                            email.date_formatted.as_deref().unwrap_or("Unknown date"),
-                                            email.body.as_deref().unwrap_or("No content")
-                                        );
-
-                                                                                // Check waiting checks first if they exist
-                                        let waiting_checks = match state.user_repository.get_waiting_checks(user.id, "email") {
-                                            Ok(checks) => checks,
-                                            Err(e) => {
-                                                tracing::error!("Failed to get waiting checks for user {}: {}", user.id, e);
-                                                Vec::new()
-                                            }
-                                        };
-                                        if !waiting_checks.is_empty() {
-                                            // Check if any waiting checks match the message
-                                            if let Ok((check_id_option, message, first_message)) = crate::proactive::utils::check_waiting_check_match(
-                                                &state,
-                                                &email_content,
-                                                &waiting_checks,
-                                            ).await {
-                                                if let Some(check_id) = check_id_option {
-                                                    let message = message.unwrap_or("Waiting check matched in Email, but failed to get content".to_string());
-                                                    let first_message = first_message.unwrap_or("Hey, I found a match for one of your waiting checks in Email.".to_string());
-                                                   
-                                                    // Find the matched waiting check to determine noti_type
-                                                    let matched_waiting_check = waiting_checks.iter().find(|wc| wc.id == Some(check_id)).cloned();
-                                                    let suffix = if let Some(wc) = matched_waiting_check {
-                                                        match wc.noti_type.as_ref().map(|s| s.as_str()) {
-                                                            Some("call") => "_call",
-                                                            _ => "_sms",
-                                                        }
-                                                    } else {
-                                                        "_sms"
-                                                    };
-                                                    let notification_type = format!("email_waiting_check{}", suffix);
-                                                   
-                                                    // Delete the matched waiting check
-                                                    if let Err(e) = state.user_repository.delete_waiting_check_by_id(user.id, check_id) {
-                                                        tracing::error!("Failed to delete waiting check {}: {}", check_id, e);
-                                                    }
-                                                   
-                                                    // Send notification
-                                                    let state_clone = state.clone();
-                                                    let user_id = user.id;
-                                                    tokio::spawn(async move {
-                                                        crate::proactive::utils::send_notification(
-                                                            &state_clone,
-                                                            user_id,
-                                                            &message,
-                                                            notification_type,
-                                                            Some(first_message),
-                                                        ).await;
-                                                    });
-                                                    continue;
-                                                }
-                                            }
-                                        }
-
-                                        // Add email to content string for importance checking
-                                        emails_content.push_str(&email_content);
-                                    }
-
-
-                                    // Check message importance based on waiting checks and criticality
-                                    let user_settings = match state.user_core.get_user_settings(user.id) {
-                                        Ok(settings) => settings,
-                                        Err(e) => {
-                                            tracing::error!("Failed to get user settings: {}", e);
-                                            return;
-                                        }
-                                    };
-
-                                    if user_settings.critical_enabled.is_none() {
-                                        tracing::debug!("Critical message checking disabled for user {}", user.id);
-                                        return;
-                                    }
-
-                                    // Check message importance based on criticality
-                                    match crate::proactive::utils::check_message_importance(&state, &emails_content).await {
-                                        Ok((is_critical, message, first_message)) => {
-                                            if is_critical {
-                                                let message = message.unwrap_or("Critical email found, check email to see it (failed to fetch actual content, pls report)".to_string());
-                                                let first_message = first_message.unwrap_or("Hey, I found some critical email you should know.".to_string());
-                                                tracing::info!(
-                                                    "Email critical check passed for user {}: {}",
-                                                    user.id, message
-                                                );
-                                                                
-                                                // Spawn a new task for sending critical message notification
-                                                let state_clone = state.clone();
-                                                let message_clone= message.clone();
-                                                tokio::spawn(async move {
-                                                    crate::proactive::utils::send_notification(
-                                                        &state_clone,
-                                                        user.id,
-                                                        &message_clone,
-                                                        "email_critical".to_string(),
-                                                        Some(first_message),
-                                                    ).await;
-                                                });
-                                            } else {
-                                                tracing::debug!(
-                                                    "Email not considered important for user {}: {}",
-                                                    user.id, message.unwrap_or("failed to get the email content".to_string())
-                                                );
-
-                                            }
-                                        }
-                                        Err(e) => {
-                                            tracing::error!("Failed to check email importance: {}", e);
-                                        }
-                                    }
-                                }
-                            },
-                            Err(e) => {
-                                error!("Failed to fetch IMAP emails for user {}: Error: {:?}", user.id, e);
-                            }
-                        }
-                    }
-                }
-            }
-
+            crate::jobs::imap_idle::sync_imap_watchers(&state).await;
         })
-    }).expect("Failed to create message monitor job");
+    }).expect("Failed to create IMAP watcher job");
 
-    sched.add(message_monitor_job).await.expect("Failed to add message monitor job to scheduler");
+    sched.add(imap_watcher_job).await.expect("Failed to add IMAP watcher job to scheduler");
 
     /*
 
@@ -493,6 +258,219 @@ pub async fn start_scheduler(state: Arc<AppState>) {
 
     sched.add(task_cleanup_job).await.expect("Failed to add task cleanup job to scheduler");
 
+    // Create a job that turns notifications back on when a PAUSE sms command runs out
+    let state_clone = Arc::clone(&state);
+    let pause_expiry_job = Job::new_async("0 * * * * *", move |_, _| {  // Runs every minute
+        let state = state_clone.clone();
+        Box::pin(async move {
+            let now = chrono::Utc::now().timestamp() as i32;
+            match state.user_core.resume_expired_proactive_pauses(now) {
+                Ok(0) => {}
+                Ok(count) => debug!("Resumed notifications for {} users after pause", count),
+                Err(e) => error!("Failed to resume expired notification pauses: {}", e),
+            }
+        })
+    }).expect("Failed to create pause expiry job");
+
+    sched.add(pause_expiry_job).await.expect("Failed to add pause expiry job to scheduler");
+
+    // Create a job that runs daily to clean up finished sms jobs
+    let state_clone = Arc::clone(&state);
+    let sms_job_cleanup_job = Job::new_async("0 30 0 * * *", move |_, _| {  // Runs at 00:30 every day
+        let state = state_clone.clone();
+        Box::pin(async move {
+            debug!("Running sms job cleanup...");
+
+            // Keep done and failed jobs for 7 days so admins can still look at them
+            let seven_days_ago = (chrono::Utc::now() - chrono::Duration::days(7)).timestamp() as i32;
+
+            match state.user_repository.delete_old_sms_jobs(seven_days_ago) {
+                Ok(count) => debug!("Cleaned up {} old sms jobs", count),
+                Err(e) => error!("Failed to clean up old sms jobs: {}", e),
+            }
+        })
+    }).expect("Failed to create sms job cleanup job");
+
+    sched.add(sms_job_cleanup_job).await.expect("Failed to add sms job cleanup job to scheduler");
+
+    // Create a job that runs daily to forget processed webhook events past retention
+    let state_clone = Arc::clone(&state);
+    let webhook_event_cleanup_job = Job::new_async("0 45 0 * * *", move |_, _| {  // Runs at 00:45 every day
+        let state = state_clone.clone();
+        Box::pin(async move {
+            debug!("Running processed webhook event cleanup...");
+
+            // Providers stop redelivering within hours, a week is plenty by default
+            let retention_days = env::var("WEBHOOK_EVENT_RETENTION_DAYS")
+                .ok()
+                .and_then(|v| v.parse::<i64>().ok())
+                .unwrap_or(7);
+            let cutoff = (chrono::Utc::now() - chrono::Duration::days(retention_days)).timestamp() as i32;
+
+            match state.user_repository.delete_old_webhook_events(cutoff) {
+                Ok(count) => debug!("Cleaned up {} processed webhook events", count),
+                Err(e) => error!("Failed to clean up processed webhook events: {}", e),
+            }
+        })
+    }).expect("Failed to create webhook event cleanup job");
+
+    sched.add(webhook_event_cleanup_job).await.expect("Failed to add webhook event cleanup job to scheduler");
+
+    // Create a job that runs daily to drop voice note transcripts past retention
+    let state_clone = Arc::clone(&state);
+    let audio_transcript_cleanup_job = Job::new_async("0 50 0 * * *", move |_, _| {  // Runs at 00:50 every day
+        let state = state_clone.clone();
+        Box::pin(async move {
+            debug!("Running audio transcript cleanup...");
+
+            // Only needed while the notes still show up in fetches and digests
+            let retention_days = env::var("AUDIO_TRANSCRIPT_RETENTION_DAYS")
+                .ok()
+                .and_then(|v| v.parse::<i64>().ok())
+                .unwrap_or(30);
+            let cutoff = (chrono::Utc::now() - chrono::Duration::days(retention_days)).timestamp() as i32;
+
+            match state.user_repository.delete_old_audio_transcripts(cutoff) {
+                Ok(count) => debug!("Cleaned up {} audio transcripts", count),
+                Err(e) => error!("Failed to clean up audio transcripts: {}", e),
+            }
+        })
+    }).expect("Failed to create audio transcript cleanup job");
+
+    sched.add(audio_transcript_cleanup_job).await.expect("Failed to add audio transcript cleanup job to scheduler");
+
+    // Create a job that runs every 15 minutes to drop confirmations nobody answered
+    let state_clone = Arc::clone(&state);
+    let pending_action_cleanup_job = Job::new_async("0 */15 * * * *", move |_, _| {
+        let state = state_clone.clone();
+        Box::pin(async move {
+            match state.user_repository.delete_expired_pending_actions() {
+                Ok(count) if count > 0 => debug!("Cleaned up {} expired pending actions", count),
+                Ok(_) => {}
+                Err(e) => error!("Failed to clean up expired pending actions: {}", e),
+            }
+        })
+    }).expect("Failed to create pending action cleanup job");
+
+    sched.add(pending_action_cleanup_job).await.expect("Failed to add pending action cleanup job to scheduler");
+
+    // Create a job that runs every 5 seconds to send messages whose undo window has passed
+    let state_clone = Arc::clone(&state);
+    let outbox_job = Job::new_async("*/5 * * * * *", move |_, _| {
+        let state = state_clone.clone();
+        Box::pin(async move {
+            crate::utils::outbox::deliver_due_messages(&state).await;
+        })
+    }).expect("Failed to create outbox job");
+
+    sched.add(outbox_job).await.expect("Failed to add outbox job to scheduler");
+
+    // Create a job that runs every minute to fail outbox messages a crashed send left behind
+    let state_clone = Arc::clone(&state);
+    let outbox_stuck_job = Job::new_async("0 * * * * *", move |_, _| {
+        let state = state_clone.clone();
+        Box::pin(async move {
+            crate::utils::outbox::fail_stuck_messages(&state).await;
+        })
+    }).expect("Failed to create stuck outbox job");
+
+    sched.add(outbox_stuck_job).await.expect("Failed to add stuck outbox job to scheduler");
+
+    // Create a job that runs daily to drop sent and cancelled outbox rows
+    let state_clone = Arc::clone(&state);
+    let outbox_cleanup_job = Job::new_async("0 55 0 * * *", move |_, _| {  // Runs at 00:55 every day
+        let state = state_clone.clone();
+        Box::pin(async move {
+            let cutoff = (chrono::Utc::now() - chrono::Duration::days(7)).timestamp() as i32;
+            match state.user_repository.delete_old_outgoing_messages(cutoff) {
+                Ok(count) => debug!("Cleaned up {} outgoing messages", count),
+                Err(e) => error!("Failed to clean up outgoing messages: {}", e),
+            }
+        })
+    }).expect("Failed to create outbox cleanup job");
+
+    sched.add(outbox_cleanup_job).await.expect("Failed to add outbox cleanup job to scheduler");
+
+    // Create a job that runs every 30 minutes to check that bridges are still logged in
+    let state_clone = Arc::clone(&state);
+    let bridge_health_job = Job::new_async("0 */30 * * * *", move |_, _| {
+        let state = state_clone.clone();
+        Box::pin(async move {
+            debug!("Running bridge health check...");
+            crate::utils::bridge_health::check_all_bridges(&state).await;
+        })
+    }).expect("Failed to create bridge health job");
+
+    sched.add(bridge_health_job).await.expect("Failed to add bridge health job to scheduler");
+
+    // Create a job that runs daily to drop old bridge health checks
+    let state_clone = Arc::clone(&state);
+    let bridge_health_cleanup_job = Job::new_async("0 5 1 * * *", move |_, _| {  // Runs at 01:05 every day
+        let state = state_clone.clone();
+        Box::pin(async move {
+            let cutoff = (chrono::Utc::now() - chrono::Duration::days(30)).timestamp() as i32;
+            match state.user_repository.delete_old_bridge_status_history(cutoff) {
+                Ok(count) => debug!("Cleaned up {} bridge status history rows", count),
+                Err(e) => error!("Failed to clean up bridge status history: {}", e),
+            }
+        })
+    }).expect("Failed to create bridge health cleanup job");
+
+    sched.add(bridge_health_cleanup_job).await.expect("Failed to add bridge health cleanup job to scheduler");
+
+    // Create a job that runs daily to apply the message index retention limits
+    let state_clone = Arc::clone(&state);
+    let message_index_cleanup_job = Job::new_async("0 20 1 * * *", move |_, _| {  // Runs at 01:20 every day
+        let state = state_clone.clone();
+        Box::pin(async move {
+            crate::utils::message_index::prune(&state);
+        })
+    }).expect("Failed to create message index cleanup job");
+
+    sched.add(message_index_cleanup_job).await.expect("Failed to add message index cleanup job to scheduler");
+
+    // Create a job that runs every 15 seconds to send scheduled messages that are due
+    let state_clone = Arc::clone(&state);
+    let scheduled_send_job = Job::new_async("*/15 * * * * *", move |_, _| {
+        let state = state_clone.clone();
+        Box::pin(async move {
+            crate::utils::scheduled_sends::deliver_due_sends(&state).await;
+        })
+    }).expect("Failed to create scheduled send job");
+
+    sched.add(scheduled_send_job).await.expect("Failed to add scheduled send job to scheduler");
+
+    // Create a job that runs daily to drop sent, cancelled and failed scheduled messages
+    let state_clone = Arc::clone(&state);
+    let scheduled_send_cleanup_job = Job::new_async("0 25 1 * * *", move |_, _| {  // Runs at 01:25 every day
+        let state = state_clone.clone();
+        Box::pin(async move {
+            let cutoff = (chrono::Utc::now() - chrono::Duration::days(30)).timestamp() as i32;
+            match state.user_repository.delete_old_scheduled_sends(cutoff) {
+                Ok(count) => debug!("Cleaned up {} scheduled sends", count),
+                Err(e) => error!("Failed to clean up scheduled sends: {}", e),
+            }
+        })
+    }).expect("Failed to create scheduled send cleanup job");
+
+    sched.add(scheduled_send_cleanup_job).await.expect("Failed to add scheduled send cleanup job to scheduler");
+
+    // Create a job that runs daily to forget away replies past the longest reply interval
+    let state_clone = Arc::clone(&state);
+    let away_reply_cleanup_job = Job::new_async("0 30 1 * * *", move |_, _| {  // Runs at 01:30 every day
+        let state = state_clone.clone();
+        Box::pin(async move {
+            let max_interval_seconds = crate::utils::away_mode::MAX_REPLY_INTERVAL_HOURS as i64 * 3600;
+            let cutoff = (chrono::Utc::now().timestamp() - max_interval_seconds) as i32;
+            match state.user_repository.delete_old_away_replies(cutoff) {
+                Ok(count) => debug!("Cleaned up {} away replies", count),
+                Err(e) => error!("Failed to clean up away replies: {}", e),
+            }
+        })
+    }).expect("Failed to create away reply cleanup job");
+
+    sched.add(away_reply_cleanup_job).await.expect("Failed to add away reply cleanup job to scheduler");
+
     // Create a job that runs every hour to check morning digests
     let state_clone = Arc::clone(&state);
     let digest_check_job = Job::new_async("0 0 * * * *", move |_, _| {
@@ -641,6 +619,7 @@ pub async fn start_scheduler(state: Arc<AppState>) {
                                             &notification,
                                             "calendar_notification".to_string(),
                                             Some(first_message),
+                                            None,
                                         ).await;
                                     });
                                 }
@@ -0,0 +1,144 @@
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::http::StatusCode;
+use tracing::{debug, error, info, warn};
+
+use crate::api::twilio_sms::{process_sms, TwilioWebhookPayload};
+use crate::models::user_models::SmsJob;
+use crate::AppState;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1); // fallback for retries that come due, new jobs wake a worker directly
+const BACKOFF_BASE_SECONDS: i64 = 10;
+const BACKOFF_MAX_SECONDS: i64 = 600;
+
+/// Starts the pool of workers that process queued inbound sms messages.
+/// Webhooks only store the message in `sms_jobs` and wake a worker, see handle_incoming_sms.
+///   SMS_WORKERS          number of concurrent workers, default 4
+///   SMS_JOB_MAX_ATTEMPTS attempts before a job is dead-lettered, default 3
+pub async fn start_sms_workers(state: Arc<AppState>) {
+    let worker_count = env::var("SMS_WORKERS")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .filter(|n| *n > 0)
+        .unwrap_or(4);
+
+    // Nothing can be processing yet, anything marked so was cut off by a restart. It may have
+    // replied or run a tool already, so it's left failed for an admin to requeue from sms-jobs
+    let now = chrono::Utc::now().timestamp() as i32;
+    match state.user_repository.fail_stale_sms_jobs(now + 1) {
+        Ok(0) => {}
+        Ok(count) => warn!("Marked {} sms jobs interrupted by restart as failed", count),
+        Err(e) => error!("Failed to mark interrupted sms jobs failed: {}", e),
+    }
+
+    info!("Starting {} sms workers", worker_count);
+    for worker_id in 0..worker_count {
+        let state = Arc::clone(&state);
+        tokio::spawn(async move {
+            run_worker(state, worker_id).await;
+        });
+    }
+}
+
+async fn run_worker(state: Arc<AppState>, worker_id: usize) {
+    loop {
+        match state.user_repository.claim_next_sms_job() {
+            Ok(Some(job)) => {
+                debug!("SMS worker {} picked up job {:?} (attempt {})", worker_id, job.id, job.attempts);
+                process_job(&state, job).await;
+            }
+            Ok(None) => {
+                tokio::select! {
+                    _ = state.sms_job_notify.notified() => {}
+                    _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                }
+            }
+            Err(e) => {
+                error!("SMS worker {} failed to claim job: {}", worker_id, e);
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        }
+    }
+}
+
+async fn process_job(state: &Arc<AppState>, job: SmsJob) {
+    let Some(job_id) = job.id else { return };
+
+    let payload: TwilioWebhookPayload = match serde_json::from_str(&job.encrypted_payload) {
+        Ok(payload) => payload,
+        Err(e) => {
+            error!("SMS job {} has an invalid payload: {}", job_id, e);
+            if let Err(e) = state.user_repository.fail_sms_job(job_id, &format!("Invalid payload: {}", e)) {
+                error!("Failed to mark sms job {} failed: {}", job_id, e);
+            }
+            return;
+        }
+    };
+
+    // Run in its own task so a panic in the pipeline fails this job instead of killing the worker
+    let state_for_job = Arc::clone(state);
+    let result = tokio::spawn(async move {
+        let (status, _, response) = process_sms(&state_for_job, payload, false).await;
+        (status, response.0.message)
+    })
+    .await;
+
+    match result {
+        // 4xx means the message itself can't be handled (unknown user, no credits..), retrying won't help.
+        // process_sms only answers 5xx for failures before anything was stored, sent or run, so
+        // a retry can't send a second reply or repeat a tool call
+        Ok((status, _)) if status == StatusCode::OK || status.is_client_error() => {
+            if status != StatusCode::OK {
+                warn!("SMS job {} finished with status {}", job_id, status);
+            }
+            if let Err(e) = state.user_repository.complete_sms_job(job_id) {
+                error!("Failed to mark sms job {} done: {}", job_id, e);
+            }
+        }
+        Ok((status, message)) => {
+            retry_or_fail(state, &job, job_id, &format!("{}: {}", status, message)).await;
+        }
+        Err(e) => {
+            retry_or_fail(state, &job, job_id, &format!("Processing panicked: {}", e)).await;
+        }
+    }
+}
+
+async fn retry_or_fail(state: &Arc<AppState>, job: &SmsJob, job_id: i32, error_message: &str) {
+    let max_attempts = env::var("SMS_JOB_MAX_ATTEMPTS")
+        .ok()
+        .and_then(|v| v.parse::<i32>().ok())
+        .unwrap_or(3);
+
+    if job.attempts < max_attempts {
+        // 10s, 20s, 40s... capped at 10 minutes
+        let backoff = (BACKOFF_BASE_SECONDS << (job.attempts - 1).clamp(0, 16)).min(BACKOFF_MAX_SECONDS);
+        let next_attempt_at = (chrono::Utc::now().timestamp() + backoff) as i32;
+        warn!("SMS job {} attempt {} failed, retrying in {}s: {}", job_id, job.attempts, backoff, error_message);
+        if let Err(e) = state.user_repository.retry_sms_job_later(job_id, error_message, next_attempt_at) {
+            error!("Failed to schedule retry for sms job {}: {}", job_id, e);
+        }
+        return;
+    }
+
+    error!("SMS job {} failed after {} attempts, moving to dead letter: {}", job_id, job.attempts, error_message);
+    if let Err(e) = state.user_repository.fail_sms_job(job_id, error_message) {
+        error!("Failed to mark sms job {} failed: {}", job_id, e);
+    }
+
+    // Let the user know their message was dropped instead of leaving them waiting
+    if let Some(user_id) = job.user_id {
+        if let Ok(Some(user)) = state.user_core.find_by_id(user_id) {
+            if let Err(e) = crate::api::twilio_utils::send_conversation_message(
+                state,
+                "Sorry, I couldn't process your last message. Please try again in a moment.",
+                None,
+                &user,
+            ).await {
+                error!("Failed to send sms job failure notice to user {}: {}", user_id, e);
+            }
+        }
+    }
+}
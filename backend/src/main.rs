@@ -1,6 +1,6 @@
 use dotenvy::dotenv;
 use axum::{
-    routing::{get, post, delete},
+    routing::{get, post, delete, patch},
     Router,
     middleware
 };
@@ -44,12 +44,8 @@ mod handlers {
     pub mod imap_handlers;
     pub mod google_tasks_auth;
     pub mod google_tasks;
-    pub mod whatsapp_auth;
-    pub mod whatsapp_handlers;
-    pub mod signal_auth;
-    pub mod signal_handlers;
-    pub mod telegram_auth;
-    pub mod telegram_handlers;
+    pub mod bridge_auth;
+    pub mod bridge_handlers;
     pub mod self_host_handlers;
     pub mod uber_auth;
     pub mod uber;
@@ -62,9 +58,22 @@ mod utils {
     pub mod usage;
     pub mod matrix_auth;
     pub mod bridge;
+    pub mod bridge_service;
     pub mod elevenlabs_prompts;
     pub mod imap_utils;
     pub mod qr_utils;
+    pub mod llm_provider;
+    pub mod conversation_memory;
+    pub mod user_memory;
+    pub mod mms_media;
+    pub mod transcription;
+    pub mod outbox;
+    pub mod notification_sources;
+    pub mod bridge_health;
+    pub mod message_index;
+    pub mod scheduled_sends;
+    pub mod away_mode;
+    pub mod smtp;
 }
 
 mod proactive {
@@ -80,12 +89,15 @@ mod tool_call_utils {
     pub mod management;
     pub mod confirm;
     pub mod bridge;
+    pub mod scheduled;
+    pub mod registry;
 }
 
 mod api {
     pub mod vapi_endpoints;
     pub mod vapi_dtos;
     pub mod twilio_sms;
+    pub mod sms_commands;
     pub mod twilio_utils;
     pub mod elevenlabs;
     pub mod elevenlabs_webhook;
@@ -103,10 +115,26 @@ mod repositories {
     pub mod user_repository;
     pub mod user_subscriptions;
     pub mod connection_auth;
+    pub mod sms_jobs;
+    pub mod webhook_events;
+    pub mod conversation_summary;
+    pub mod user_memories;
+    pub mod contacts;
+    pub mod audio_transcripts;
+    pub mod pending_actions;
+    pub mod outgoing_messages;
+    pub mod group_policies;
+    pub mod notification_sources;
+    pub mod bridge_status_history;
+    pub mod message_index;
+    pub mod scheduled_sends;
+    pub mod away_replies;
 }
 mod schema;
 mod jobs {
     pub mod scheduler;
+    pub mod sms_worker;
+    pub mod imap_idle;
 }
 
 use repositories::user_core::UserCore;
@@ -116,8 +144,7 @@ use handlers::{
     auth_handlers, self_host_handlers, profile_handlers, billing_handlers,
     admin_handlers, stripe_handlers, google_calendar_auth, google_calendar,
     google_tasks_auth, google_tasks, imap_auth, imap_handlers,
-    whatsapp_auth, whatsapp_handlers, telegram_auth, telegram_handlers,
-    signal_auth, signal_handlers, filter_handlers, twilio_handlers, uber_auth,
+    bridge_auth, bridge_handlers, filter_handlers, twilio_handlers, uber_auth,
 };
 use api::{twilio_sms, elevenlabs, elevenlabs_webhook, shazam_call};
 
@@ -146,10 +173,15 @@ pub struct AppState {
     matrix_sync_tasks: Arc<Mutex<HashMap<i32, tokio::task::JoinHandle<()>>>>,
     matrix_invitation_tasks: Arc<Mutex<HashMap<i32, tokio::task::JoinHandle<()>>>>,
     matrix_clients: Arc<Mutex<HashMap<i32, Arc<matrix_sdk::Client>>>>,
+    imap_idle_tasks: Arc<Mutex<HashMap<i32, tokio::task::JoinHandle<()>>>>, // inbox watchers by imap_connection id, see jobs::imap_idle
     password_reset_otps: DashMap<String, (String, u64)>, // (email, (otp, expiration))
     phone_verify_limiter: DashMap<String, RateLimiter<String, DefaultKeyedStateStore<String>, DefaultClock>>,
     phone_verify_verify_limiter: DashMap<String, RateLimiter<String, DefaultKeyedStateStore<String>, DefaultClock>>,
     phone_verify_otps: DashMap<String, (String, u64)>,
+    tool_registry: Arc<tool_call_utils::registry::ToolRegistry>,
+    sms_job_notify: Arc<tokio::sync::Notify>, // wakes an sms worker when a job is queued
+    qr_codes: DashMap<String, Vec<u8>>, // login QR codes sent by mms, by one-time token, see sms_commands::serve_qr_code
+    memory_extraction_limiter: DashMap<String, RateLimiter<String, DefaultKeyedStateStore<String>, DefaultClock>>, // by user id, see user_memory::extract_memories
 }
 
 pub fn validate_env() {
@@ -263,9 +295,14 @@ async fn main() {
         matrix_sync_tasks,
         matrix_invitation_tasks,
         matrix_clients,
+        imap_idle_tasks: Arc::new(Mutex::new(HashMap::new())),
         phone_verify_limiter: DashMap::new(),
         phone_verify_verify_limiter: DashMap::new(),
         password_reset_otps: DashMap::new(),
+        tool_registry: Arc::new(tool_call_utils::registry::ToolRegistry::new()),
+        sms_job_notify: Arc::new(tokio::sync::Notify::new()),
+        qr_codes: DashMap::new(),
+        memory_extraction_limiter: DashMap::new(),
     });
 
     let twilio_routes = Router::new()
@@ -282,31 +319,18 @@ async fn main() {
 
     let elevenlabs_free_routes = Router::new()
         .route("/api/call/assistant", post(elevenlabs::fetch_assistant))
-        .route("/api/call/weather", post(elevenlabs::handle_weather_tool_call))
-        .route("/api/call/perplexity", post(elevenlabs::handle_perplexity_tool_call))
         .route_layer(middleware::from_fn(elevenlabs::validate_elevenlabs_secret));
 
     let elevenlabs_routes = Router::new()
         .route("/api/call/sms", post(elevenlabs::handle_send_sms_tool_call))
         .route("/api/call/shazam", get(elevenlabs::handle_shazam_tool_call))
-        .route("/api/call/calendar", get(elevenlabs::handle_calendar_tool_call))
-        .route("/api/call/calendar/confirm", get(elevenlabs::handle_calendar_event_confirm))
-        .route("/api/call/email", get(elevenlabs::handle_email_fetch_tool_call))
-        .route("/api/call/email/specific", post(elevenlabs::handle_email_search_tool_call))
-        .route("/api/call/waiting_check", post(elevenlabs::handle_create_waiting_check_tool_call))
-        .route("/api/call/monitoring-status", post(elevenlabs::handle_update_monitoring_status_tool_call))
-        .route("/api/call/email/respond-confirm", post(elevenlabs::handle_email_response_tool_call))
-        .route("/api/call/tasks", get(elevenlabs::handle_tasks_fetching_tool_call))
-        .route("/api/call/tasks/create", post(elevenlabs::handle_tasks_creation_tool_call))
-        .route("/api/call/fetch-recent-messages", get(elevenlabs::handle_fetch_recent_messages_tool_call))
-        .route("/api/call/fetch-chat-messages", get(elevenlabs::handle_fetch_specific_chat_messages_tool_call))
-        .route("/api/call/search-chat-contacts", post(elevenlabs::handle_search_chat_contacts_tool_call))
-        .route("/api/call/send-chat-message", post(elevenlabs::handle_confirm_send_chat_message))
-        .route("/api/call/directions", post(elevenlabs::handle_directions_tool_call))
-        .route("/api/call/firecrawl", post(elevenlabs::handle_firecrawl_tool_call))
         .layer(middleware::from_fn_with_state(state.clone(), handlers::auth_middleware::check_subscription_access))
         .route_layer(middleware::from_fn(elevenlabs::validate_elevenlabs_secret));
 
+    // Generated from the tool registry, subscription access is checked per tool by the registry
+    let elevenlabs_tool_routes = elevenlabs::tool_call_routes(&state.tool_registry)
+        .route_layer(middleware::from_fn(elevenlabs::validate_elevenlabs_secret));
+
     let elevenlabs_webhook_routes = Router::new()
         .route("/api/webhook/elevenlabs", post(elevenlabs_webhook::elevenlabs_webhook))
         .route_layer(middleware::from_fn(elevenlabs_webhook::validate_elevenlabs_hmac));
@@ -332,7 +356,8 @@ async fn main() {
         .route("/api/self-hosting-status", get(self_host_handlers::self_hosted_status))
         .route("/api/check-pairing", post(self_host_handlers::check_pairing))
         .route("/api/self-host-ping", post(self_host_handlers::self_host_ping))
-        .route("/api/country-info", post(twilio_handlers::get_country_info));
+        .route("/api/country-info", post(twilio_handlers::get_country_info))
+        .route("/api/qr/{token}", get(api::sms_commands::serve_qr_code));
 
     // Admin routes that need admin authentication
     let admin_routes = Router::new()
@@ -349,6 +374,9 @@ async fn main() {
         .route("/api/admin/test-sms-with-image", post(admin_handlers::test_sms_with_image))
         .route("/api/admin/monthly-credits/{user_id}/{amount}", post(admin_handlers::update_monthly_credits))
         .route("/api/admin/discount-tier/{user_id}/{tier}", post(admin_handlers::update_discount_tier))
+        .route("/api/admin/sms-jobs/failed", get(admin_handlers::get_failed_sms_jobs))
+        .route("/api/admin/sms-jobs/{job_id}/retry", post(admin_handlers::retry_sms_job))
+        .route("/api/admin/bridge-health", get(admin_handlers::get_bridge_status_history))
         .route_layer(middleware::from_fn_with_state(state.clone(), handlers::auth_middleware::require_admin));
 
     // Protected routes that need user authentication
@@ -370,6 +398,18 @@ async fn main() {
         .route("/api/profile/critical", get(profile_handlers::get_critical_enabled))
         .route("/api/profile/proactive-agent", post(profile_handlers::update_proactive_agent_on))
         .route("/api/profile/proactive-agent", get(profile_handlers::get_proactive_agent_on))
+        .route("/api/profile/llm-override", post(profile_handlers::update_llm_override))
+        .route("/api/profile/send-delay", post(profile_handlers::update_send_delay))
+        .route("/api/profile/away-mode", get(profile_handlers::get_away_mode))
+        .route("/api/profile/away-mode", post(profile_handlers::update_away_mode))
+        .route("/api/profile/memories", get(profile_handlers::get_memories))
+        .route("/api/profile/memories", post(profile_handlers::add_memory))
+        .route("/api/profile/memories/{memory_id}", delete(profile_handlers::delete_memory))
+        .route("/api/profile/scheduled-sends", get(profile_handlers::get_scheduled_sends))
+        .route("/api/profile/scheduled-sends/{send_id}", post(profile_handlers::update_scheduled_send))
+        .route("/api/profile/scheduled-sends/{send_id}", delete(profile_handlers::cancel_scheduled_send))
+        .route("/api/profile/contacts", get(profile_handlers::get_contacts))
+        .route("/api/profile/contacts/{contact_id}", delete(profile_handlers::delete_contact))
         .route("/api/profile/get_nearby_places", get(profile_handlers::get_nearby_places))
 
         .route("/api/billing/increase-credits/{user_id}", post(billing_handlers::increase_credits))
@@ -402,37 +442,21 @@ async fn main() {
         .route("/api/auth/imap/login", post(imap_auth::imap_login))
         .route("/api/auth/imap/status", get(imap_auth::imap_status))
         .route("/api/auth/imap/disconnect", delete(imap_auth::delete_imap_connection))
+        .route("/api/auth/imap/accounts/{account_id}", patch(imap_auth::update_imap_account).delete(imap_auth::delete_imap_account))
         .route("/api/imap/previews", get(imap_handlers::fetch_imap_previews))
         .route("/api/imap/message/{email_id}", get(imap_handlers::fetch_single_imap_email))
         .route("/api/imap/full_emails", get(imap_handlers::fetch_full_imap_emails))
         .route("/api/imap/reply", post(imap_handlers::respond_to_email))
 
-        .route("/api/auth/telegram/status", get(telegram_auth::get_telegram_status))
-        .route("/api/auth/telegram/connect", get(telegram_auth::start_telegram_connection))
-        .route("/api/auth/telegram/disconnect", delete(telegram_auth::disconnect_telegram))
-        .route("/api/auth/telegram/resync", post(telegram_auth::resync_telegram))
-        .route("/api/telegram/test-messages", get(telegram_handlers::test_fetch_messages))
-        .route("/api/telegram/send", post(telegram_handlers::send_message))
-        .route("/api/telegram/search-rooms", post(telegram_handlers::search_telegram_rooms_handler))
-        .route("/api/telegram/search-rooms", get(telegram_handlers::search_rooms_handler))
-
-        .route("/api/auth/signal/status", get(signal_auth::get_signal_status))
-        .route("/api/auth/signal/connect", get(signal_auth::start_signal_connection))
-        .route("/api/auth/signal/disconnect", delete(signal_auth::disconnect_signal))
-        .route("/api/auth/signal/resync", post(signal_auth::resync_signal))
-        .route("/api/signal/test-messages", get(signal_handlers::test_fetch_messages))
-        .route("/api/signal/send", post(signal_handlers::send_message))
-        .route("/api/signal/search-rooms", post(signal_handlers::search_signal_rooms_handler))
-        .route("/api/signal/search-rooms", get(signal_handlers::search_rooms_handler))
-
-        .route("/api/auth/whatsapp/status", get(whatsapp_auth::get_whatsapp_status))
-        .route("/api/auth/whatsapp/connect", get(whatsapp_auth::start_whatsapp_connection))
-        .route("/api/auth/whatsapp/disconnect", delete(whatsapp_auth::disconnect_whatsapp))
-        .route("/api/auth/whatsapp/resync", post(whatsapp_auth::resync_whatsapp))
-        .route("/api/whatsapp/test-messages", get(whatsapp_handlers::test_fetch_messages))
-        .route("/api/whatsapp/send", post(whatsapp_handlers::send_message))
-        .route("/api/whatsapp/search-rooms", post(whatsapp_handlers::search_whatsapp_rooms_handler))
-        .route("/api/whatsapp/search-rooms", get(whatsapp_handlers::search_rooms_handler))
+        // Every bridge, e.g. /api/auth/whatsapp/connect or /api/slack/send, see utils::bridge_service
+        .route("/api/auth/{service}/status", get(bridge_auth::get_status))
+        .route("/api/auth/{service}/connect", get(bridge_auth::start_connection))
+        .route("/api/auth/{service}/login-input", post(bridge_auth::send_login_input))
+        .route("/api/auth/{service}/disconnect", delete(bridge_auth::disconnect))
+        .route("/api/auth/{service}/resync", post(bridge_auth::resync))
+        .route("/api/{service}/test-messages", get(bridge_handlers::test_fetch_messages))
+        .route("/api/{service}/send", post(bridge_handlers::send_message))
+        .route("/api/{service}/search-rooms", post(bridge_handlers::search_service_rooms).get(bridge_handlers::search_rooms))
 
 
         // Filter routes
@@ -448,6 +472,10 @@ async fn main() {
         .route("/api/filters/priority-sender/{service_type}/{sender}", delete(filter_handlers::delete_priority_sender))
         .route("/api/filters/priority-senders/{service_type}", get(filter_handlers::get_priority_senders))
 
+        .route("/api/filters/group-policies", get(filter_handlers::get_group_policies))
+        .route("/api/filters/group-policy/{service_type}", post(filter_handlers::set_group_policy))
+        .route("/api/filters/group-policy/{service_type}/{chat_name}", delete(filter_handlers::delete_group_policy))
+
         .route("/api/filters/keyword/{service_type}", post(filter_handlers::create_keyword))
         .route("/api/filters/keyword/{service_type}/{keyword}", delete(filter_handlers::delete_keyword))
 
@@ -470,6 +498,7 @@ async fn main() {
         .merge(twilio_routes)       // More general routes last
         .merge(elevenlabs_routes)
         .merge(elevenlabs_free_routes)
+        .merge(elevenlabs_tool_routes)
         .merge(elevenlabs_webhook_routes)
         .nest_service("/uploads", ServeDir::new("uploads"))
         // Serve static files (robots.txt, sitemap.xml) at the root
@@ -502,6 +531,11 @@ async fn main() {
         jobs::scheduler::start_scheduler(state_for_scheduler).await;
     });
 
+    let state_for_sms_workers = state.clone();
+    tokio::spawn(async move {
+        jobs::sms_worker::start_sms_workers(state_for_sms_workers).await;
+    });
+
     let shazam_state = crate::api::shazam_call::ShazamState {
         sessions: state.sessions.clone(),
         user_calls: state.user_calls.clone(),
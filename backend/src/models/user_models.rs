@@ -14,10 +14,24 @@ use crate::schema::google_tasks;
 use crate::schema::task_notifications;
 use crate::schema::calendar_notifications;
 use crate::schema::user_settings;
-use crate::schema::temp_variables;
+use crate::schema::pending_actions;
 use crate::schema::message_history;
 use crate::schema::user_info;
 use crate::schema::uber;
+use crate::schema::sms_jobs;
+use crate::schema::processed_webhook_events;
+use crate::schema::conversation_summaries;
+use crate::schema::user_memories;
+use crate::schema::contacts;
+use crate::schema::audio_transcripts;
+use crate::schema::outgoing_messages;
+use crate::schema::group_policies;
+use crate::schema::notification_sources;
+use crate::schema::bridge_status_history;
+use crate::schema::indexed_messages;
+use crate::schema::message_index_coverage;
+use crate::schema::scheduled_sends;
+use crate::schema::away_replies;
 
 
 
@@ -50,7 +64,6 @@ pub struct User {
     pub discount: bool, // if user can get buy overage credits without subscription(for early adopters)
     pub discount_tier: Option<String>, // could be None, "msg", "voice" or "full"
     pub free_reply: bool, // flag that gets set when previous message needs more information to finish the reply
-    pub confirm_send_event: Option<String>, // flag for if sending event needs confirmation. can be "whatsapp", "email" or "calendar"
     pub waiting_checks_count: i32, // how many waiting checks the user currently has(max 5 is possible)
     pub next_billing_date_timestamp: Option<i32>, // when is user next billed for their subscription
     pub phone_number_country: Option<String>, // "US", "CA", .. diff between us and ca phone numbers so we don't have to use api to look up each time
@@ -84,37 +97,6 @@ pub struct NewUserInfo {
     pub recent_contacts: Option<String>,
 }
 
-#[derive(Queryable, Selectable, Insertable)]
-#[diesel(table_name = temp_variables)]
-#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
-pub struct TempVariable {
-    pub id: i32,
-    pub user_id: i32,
-    pub confirm_send_event_type: String, // 'whatsapp', 'calendar' or 'email'
-    pub confirm_send_event_recipient: Option<String>, 
-    pub confirm_send_event_subject: Option<String>,
-    pub confirm_send_event_content: Option<String>,
-    pub confirm_send_event_start_time: Option<String>,
-    pub confirm_send_event_duration: Option<String>,
-    pub confirm_send_event_id: Option<String>,
-    pub confirm_send_event_image_url: Option<String>,
-}
-
-#[derive(Insertable)]
-#[diesel(table_name = temp_variables)]
-pub struct NewTempVariable {
-    pub user_id: i32,
-    pub confirm_send_event_type: String,
-    pub confirm_send_event_recipient: Option<String>,
-    pub confirm_send_event_subject: Option<String>,
-    pub confirm_send_event_content: Option<String>,
-    pub confirm_send_event_start_time: Option<String>,
-    pub confirm_send_event_duration: Option<String>,
-    pub confirm_send_event_id: Option<String>,
-    pub confirm_send_event_image_url: Option<String>,
-}
-
-
 #[derive(Queryable, Selectable, Insertable)]
 #[diesel(table_name = task_notifications)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
@@ -148,6 +130,13 @@ pub struct ImapConnection {
     pub expires_in: i32,
     pub imap_server: Option<String>,
     pub imap_port: Option<i32>,
+    pub smtp_server: Option<String>,
+    pub smtp_port: Option<i32>,
+    pub smtp_security: Option<String>,
+    pub smtp_username: Option<String>,
+    pub encrypted_smtp_password: Option<String>,
+    pub label: Option<String>, // the email address is shown when there is none
+    pub monitoring_enabled: bool,
 }
 
 #[derive(Insertable)]
@@ -163,6 +152,13 @@ pub struct NewImapConnection {
     pub expires_in: i32,
     pub imap_server: Option<String>,
     pub imap_port: Option<i32>,
+    pub smtp_server: Option<String>,
+    pub smtp_port: Option<i32>,
+    pub smtp_security: Option<String>,
+    pub smtp_username: Option<String>,
+    pub encrypted_smtp_password: Option<String>,
+    pub label: Option<String>, // the email address is shown when there is none
+    pub monitoring_enabled: bool,
 }
 
 #[derive(Queryable, Selectable, Insertable)]
@@ -173,6 +169,7 @@ pub struct ProcessedEmail {
     pub user_id: i32,
     pub email_uid: String,
     pub processed_at: i32, 
+    pub imap_connection_id: Option<i32>,
 }
 
 #[derive(Insertable)]
@@ -181,6 +178,7 @@ pub struct NewProcessedEmail {
     pub user_id: i32,
     pub email_uid: String,
     pub processed_at: i32, 
+    pub imap_connection_id: Option<i32>,
 }
 
 #[derive(Queryable, Selectable, Insertable)]
@@ -214,7 +212,7 @@ pub struct NewEmailJudgment {
 pub struct Bridge {
     pub id: Option<i32>, // Assuming auto-incrementing primary key
     pub user_id: i32,
-    pub bridge_type: String, // whatsapp, telegram
+    pub bridge_type: String, // whatsapp, telegram, signal, discord, slack, instagram, messenger
     pub status: String, // connected, disconnected
     pub room_id: Option<String>,
     pub data: Option<String>,
@@ -226,7 +224,7 @@ pub struct Bridge {
 #[diesel(table_name = bridges)]
 pub struct NewBridge {
     pub user_id: i32, 
-    pub bridge_type: String, // whatsapp, telegram
+    pub bridge_type: String, // whatsapp, telegram, signal, discord, slack, instagram, messenger
     pub status: String, // connected, disconnected
     pub room_id: Option<String>,
     pub data: Option<String>,
@@ -427,6 +425,7 @@ pub struct PrioritySender {
     pub sender: String,
     pub service_type: String, // like email, whatsapp, .. 
     pub noti_type: Option<String>, // "sms", "call"
+    pub imap_connection_id: Option<i32>, // only this email account, None for all
 }
 
 #[derive(Insertable)]
@@ -436,6 +435,7 @@ pub struct NewPrioritySender {
     pub sender: String,
     pub service_type: String, 
     pub noti_type: Option<String>, 
+    pub imap_connection_id: Option<i32>,
 }
 
 #[derive(Queryable, Selectable, Insertable)]
@@ -487,6 +487,32 @@ pub struct UserSettings {
     pub encrypted_textbee_api_key: Option<String>,
     pub elevenlabs_phone_number_id: Option<String>, // used to make outbound calls(we get this from elevenlabs api call when adding the phone number)
     pub proactive_agent_on: bool, // whether the user wants to receive any kinds of notifications
+    pub llm_model: Option<String>, // user's preferred OpenRouter model, goes first in the llm chains
+    pub proactive_paused_until: Option<i32>, // set by the PAUSE sms command, proactive_agent_on is turned back on after this timestamp
+    pub send_delay_seconds: i32, // undo window for confirmed chat messages and email replies, 0 sends right away
+    pub away_enabled: bool, // away mode, see utils::away_mode
+    pub encrypted_away_message: Option<String>, // automatic reply template, {name} and {until} get filled in
+    pub away_contact_mode: String, // "all", "allowlist" or "denylist" of away_contacts
+    pub away_contacts: Option<String>, // json list of chat names, senders or email addresses
+    pub away_reply_interval_hours: i32, // a contact gets at most one automatic reply per this many hours
+    pub away_priority_only: bool, // while away only priority senders notify the user
+    pub away_starts_at: Option<i32>, // None starts right away
+    pub away_ends_at: Option<i32>, // None lasts until turned off
+}
+
+/// Away mode part of user_settings, written as a whole by UserCore::update_away_mode.
+/// encrypted_away_message comes in as plain text and is encrypted there.
+#[derive(AsChangeset, Debug, Clone)]
+#[diesel(table_name = user_settings, treat_none_as_null = true)]
+pub struct AwayModeSettings {
+    pub away_enabled: bool,
+    pub encrypted_away_message: Option<String>,
+    pub away_contact_mode: String,
+    pub away_contacts: Option<String>,
+    pub away_reply_interval_hours: i32,
+    pub away_priority_only: bool,
+    pub away_starts_at: Option<i32>,
+    pub away_ends_at: Option<i32>,
 }
 
 #[derive(Insertable)]
@@ -532,3 +558,345 @@ pub struct NewMessageHistory {
     pub conversation_id: String,
     pub tool_calls_json: Option<String>, 
 }
+
+#[derive(Queryable, Selectable, Insertable, Debug, Clone)]
+#[diesel(table_name = sms_jobs)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct SmsJob {
+    pub id: Option<i32>,
+    pub user_id: Option<i32>,
+    pub encrypted_payload: String, // inbound TwilioWebhookPayload as json, decrypted when read
+    pub status: String, // "pending", "processing", "done" or "failed"
+    pub attempts: i32,
+    pub next_attempt_at: i32, // unix timestamp before which workers leave it alone
+    pub last_error: Option<String>,
+    pub created_at: i32,
+    pub updated_at: i32,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = sms_jobs)]
+pub struct NewSmsJob {
+    pub user_id: Option<i32>,
+    pub encrypted_payload: String,
+    pub status: String,
+    pub attempts: i32,
+    pub next_attempt_at: i32,
+    pub created_at: i32,
+    pub updated_at: i32,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = processed_webhook_events)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct ProcessedWebhookEvent {
+    pub id: Option<i32>,
+    pub provider: String,
+    pub event_id: String,
+    pub status: String, // "processing" or "done"
+    pub response_status: Option<i32>,
+    pub response_body: Option<String>,
+    pub created_at: i32,
+    pub updated_at: i32,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = processed_webhook_events)]
+pub struct NewProcessedWebhookEvent {
+    pub provider: String,
+    pub event_id: String,
+    pub status: String,
+    pub created_at: i32,
+    pub updated_at: i32,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = conversation_summaries)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct ConversationSummary {
+    pub id: Option<i32>,
+    pub user_id: i32,
+    pub encrypted_summary: String,
+    pub summarized_until: i32, // created_at of the newest message folded into the summary
+    pub updated_at: i32,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = conversation_summaries)]
+pub struct NewConversationSummary {
+    pub user_id: i32,
+    pub encrypted_summary: String,
+    pub summarized_until: i32,
+    pub updated_at: i32,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = user_memories)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct UserMemory {
+    pub id: Option<i32>,
+    pub user_id: i32,
+    pub encrypted_content: String, // decrypted when read
+    pub category: String, // "person", "schedule", "health", "preference" or "other"
+    pub source: String, // "user" (remember that..) or "extracted" (picked up from conversation)
+    pub created_at: i32,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = user_memories)]
+pub struct NewUserMemory {
+    pub user_id: i32,
+    pub encrypted_content: String,
+    pub category: String,
+    pub source: String,
+    pub created_at: i32,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = contacts)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Contact {
+    pub id: Option<i32>,
+    pub user_id: i32,
+    pub encrypted_name: String, // decrypted when read
+    pub encrypted_phone: Option<String>, // decrypted when read
+    pub encrypted_email: Option<String>, // decrypted when read
+    pub source: String, // "mms" (vCard sent by the user)
+    pub created_at: i32,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = contacts)]
+pub struct NewContact {
+    pub user_id: i32,
+    pub encrypted_name: String,
+    pub encrypted_phone: Option<String>,
+    pub encrypted_email: Option<String>,
+    pub source: String,
+    pub created_at: i32,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = audio_transcripts)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct AudioTranscript {
+    pub id: Option<i32>,
+    pub user_id: i32,
+    pub source_id: String, // matrix event id
+    pub encrypted_transcript: String,
+    pub created_at: i32,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = audio_transcripts)]
+pub struct NewAudioTranscript {
+    pub user_id: i32,
+    pub source_id: String,
+    pub encrypted_transcript: String,
+    pub created_at: i32,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = pending_actions)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct PendingAction {
+    pub id: Option<i32>,
+    pub user_id: i32,
+    pub number: i32, // shown to the user, smallest free one when created
+    pub action_type: String, // "calendar_event", "chat_message", "email_reply" or "task"
+    pub encrypted_payload: String, // decrypted when read, see tool_call_utils::confirm::ActionPayload
+    pub expires_at: i32,
+    pub created_at: i32,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = pending_actions)]
+pub struct NewPendingAction {
+    pub user_id: i32,
+    pub number: i32,
+    pub action_type: String,
+    pub encrypted_payload: String,
+    pub expires_at: i32,
+    pub created_at: i32,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = outgoing_messages)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct OutgoingMessage {
+    pub id: Option<i32>,
+    pub user_id: i32,
+    pub action_type: String, // "chat_message" or "email_reply"
+    pub encrypted_payload: String, // decrypted when read, see tool_call_utils::confirm::ActionPayload
+    pub status: String, // "queued", "sending", "sent", "cancelled" or "failed"
+    pub send_at: i32,
+    pub created_at: i32,
+    pub updated_at: i32,
+    pub claimed_at: Option<i32>, // when a delivery run took it, see outbox::fail_stuck_messages
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = outgoing_messages)]
+pub struct NewOutgoingMessage {
+    pub user_id: i32,
+    pub action_type: String,
+    pub encrypted_payload: String,
+    pub status: String,
+    pub send_at: i32,
+    pub created_at: i32,
+    pub updated_at: i32,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = group_policies)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct GroupPolicy {
+    pub id: Option<i32>,
+    pub user_id: i32,
+    pub service_type: String, // whatsapp, telegram, ..
+    pub chat_name: String, // group name without the bridge suffix, "*" for all groups of the service
+    pub policy: String, // "mention", "reply" or "always"
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = group_policies)]
+pub struct NewGroupPolicy {
+    pub user_id: i32,
+    pub service_type: String,
+    pub chat_name: String,
+    pub policy: String,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = notification_sources)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct NotificationSourceRecord {
+    pub id: Option<i32>,
+    pub user_id: i32,
+    pub source_type: String, // "chat" or "email"
+    pub encrypted_payload: String, // decrypted when read, see utils::notification_sources::NotificationSource
+    pub created_at: i32,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = notification_sources)]
+pub struct NewNotificationSourceRecord {
+    pub user_id: i32,
+    pub source_type: String,
+    pub encrypted_payload: String,
+    pub created_at: i32,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = bridge_status_history)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct BridgeStatusEvent {
+    pub id: Option<i32>,
+    pub user_id: i32,
+    pub bridge_type: String,
+    pub status: String, // "ok", "resynced", "failed", "unreachable", "disconnected" or "reconnected"
+    pub detail: Option<String>,
+    pub created_at: i32,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = bridge_status_history)]
+pub struct NewBridgeStatusEvent {
+    pub user_id: i32,
+    pub bridge_type: String,
+    pub status: String,
+    pub detail: Option<String>,
+    pub created_at: i32,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = indexed_messages)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct IndexedMessage {
+    pub id: Option<i32>,
+    pub user_id: i32,
+    pub platform: String, // bridge service name or "email"
+    pub message_key: String,
+    pub encrypted_room_name: String, // the encrypted_ fields are decrypted when read
+    pub encrypted_sender: String,
+    pub encrypted_content: String,
+    pub message_type: String,
+    pub sent_at: i32,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = indexed_messages)]
+pub struct NewIndexedMessage {
+    pub user_id: i32,
+    pub platform: String,
+    pub message_key: String,
+    pub encrypted_room_name: String,
+    pub encrypted_sender: String,
+    pub encrypted_content: String,
+    pub message_type: String,
+    pub sent_at: i32,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = message_index_coverage)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct MessageIndexCoverage {
+    pub id: Option<i32>,
+    pub user_id: i32,
+    pub started_at: i32,
+    pub covered_until: i32,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = message_index_coverage)]
+pub struct NewMessageIndexCoverage {
+    pub user_id: i32,
+    pub started_at: i32,
+    pub covered_until: i32,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = scheduled_sends)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct ScheduledSend {
+    pub id: Option<i32>,
+    pub user_id: i32,
+    pub action_type: String, // "chat_message", "email" or "sms"
+    pub encrypted_payload: String, // decrypted when read, see tool_call_utils::confirm::ActionPayload
+    pub status: String, // "scheduled", "sending", "sent", "cancelled" or "failed"
+    pub send_at: i32,
+    pub created_at: i32,
+    pub updated_at: i32,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = scheduled_sends)]
+pub struct NewScheduledSend {
+    pub user_id: i32,
+    pub action_type: String,
+    pub encrypted_payload: String,
+    pub status: String,
+    pub send_at: i32,
+    pub created_at: i32,
+    pub updated_at: i32,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = away_replies)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct AwayReply {
+    pub id: Option<i32>,
+    pub user_id: i32,
+    pub platform: String, // bridge service name or "email"
+    pub contact_key: String, // room id, or the sender address for email
+    pub replied_at: i32,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = away_replies)]
+pub struct NewAwayReply {
+    pub user_id: i32,
+    pub platform: String,
+    pub contact_key: String,
+    pub replied_at: i32,
+}
@@ -4,10 +4,9 @@ use std::sync::Arc;
 use openai_api_rs::v1::{
     chat_completion,
     types,
-    common::GPT4_O,
 };
 use chrono::Timelike;
-use crate::tool_call_utils::utils::create_openai_client;
+use crate::utils::llm_provider::{LlmChain, LlmPurpose};
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc, Duration};
 
@@ -107,10 +106,11 @@ pub struct WaitingCheckMatchResponse {
 /// Returns `(waiting_check_id, sms_message, first_message)`.
 pub async fn check_waiting_check_match(
     state: &Arc<AppState>,
+    user_id: i32,
     message: &str,
     waiting_checks: &Vec<WaitingCheck>,
 ) -> Result<(Option<i32>, Option<String>, Option<String>), Box<dyn std::error::Error>> {
-    let client = create_openai_client(&state)?;
+    let llm = LlmChain::for_purpose(state, LlmPurpose::WaitingCheckMatch, Some(user_id))?;
 
     let waiting_checks_str = waiting_checks
         .iter()
@@ -179,14 +179,14 @@ pub async fn check_waiting_check_match(
     }];
 
     let request = chat_completion::ChatCompletionRequest::new(
-        GPT4_O.to_string(),
+        llm.primary_model().to_string(),
         messages)
         .tools(tools)
         .tool_choice(chat_completion::ToolChoiceType::Required)
         .temperature(0.0)
         .max_tokens(200);
 
-    let result = client.chat_completion(request).await?;
+    let result = llm.chat_completion(request).await?;
     let tool_call = result.choices[0]
         .message
         .tool_calls
@@ -245,10 +245,11 @@ pub struct MatchResponse {
 /// Returns `(is_critical, what_to_inform, first_message)`.
 pub async fn check_message_importance(
     state: &Arc<AppState>,
+    user_id: i32,
     message: &str,
 ) -> Result<(bool, Option<String>, Option<String>), Box<dyn std::error::Error>> {
     // Build the chat payload ----------------------------------------------
-    let client = create_openai_client(&state)?;
+    let llm = LlmChain::for_purpose(state, LlmPurpose::ImportanceCheck, Some(user_id))?;
 
 
     let messages = vec![
@@ -312,7 +313,7 @@ pub async fn check_message_importance(
     }];
 
     let request = chat_completion::ChatCompletionRequest::new(
-        GPT4_O.to_string(),
+        llm.primary_model().to_string(),
         messages)
         .tools(tools)
         .tool_choice(chat_completion::ToolChoiceType::Required)
@@ -321,7 +322,7 @@ pub async fn check_message_importance(
         .max_tokens(200);
 
     // ---------------------------------------------------------------------
-    match client.chat_completion(request).await {
+    match llm.chat_completion(request).await {
         Ok(result) => {
             if let Some(tool_calls) = result.choices[0].message.tool_calls.as_ref() {
                 if let Some(first_call) = tool_calls.first() {
@@ -471,7 +472,7 @@ pub async fn check_morning_digest(state: &Arc<AppState>, user_id: i32) -> Result
             // Check if user has IMAP credentials before fetching emails
             let mut messages = if state.user_repository.get_imap_credentials(user_id)?.is_some() {
                 // Fetch and filter emails
-                match crate::handlers::imap_handlers::fetch_emails_imap(state, user_id, false, Some(50), false, true).await {
+                match crate::handlers::imap_handlers::fetch_emails_imap(state, user_id, None, false, Some(50), false, true).await {
                     Ok(emails) => {
                         emails.into_iter()
                             .filter(|email| {
@@ -507,66 +508,37 @@ pub async fn check_morning_digest(state: &Arc<AppState>, user_id: i32) -> Result
                 hours_since_prev
             );
 
-            // Fetch WhatsApp messages
-            if let Some(bridge) = state.user_repository.get_bridge(user_id, "whatsapp")? {
-                match crate::utils::bridge::fetch_bridge_messages("whatsapp", state, user_id, start_timestamp, true).await {
-                    Ok(whatsapp_messages) => {
-                        // Convert WhatsAppMessage to MessageInfo and add to messages
-                        let whatsapp_infos: Vec<MessageInfo> = whatsapp_messages.into_iter()
-                            .map(|msg| MessageInfo {
-                                sender: msg.room_name,
-                                content: msg.content,
-                                timestamp_rfc: msg.formatted_timestamp,
-                                platform: "whatsapp".to_string(),
-                            })
-                            .collect();
-                        
-                        tracing::debug!(
-                            "Fetched {} WhatsApp messages from the last {} hours for digest",
-                            whatsapp_infos.len(),
-                            hours_since_prev
-                        );
-
-                        // Extend messages with WhatsApp messages
-                        messages.extend(whatsapp_infos);
-
-                        // Sort all messages by timestamp (most recent first)
-                        messages.sort_by(|a, b| b.timestamp_rfc.cmp(&a.timestamp_rfc));
-                    }
-                    Err(e) => {
-                        tracing::error!("Failed to fetch WhatsApp messages for digest: {}", e);
-                    }
+            // Fetch messages from every connected bridge
+            for bridge_service in crate::utils::bridge_service::all_services() {
+                let service = bridge_service.name();
+                if state.user_repository.get_bridge(user_id, service)?.is_none() {
+                    continue;
                 }
-            }
-
-            // Fetch Telegram messages
-            if let Some(bridge) = state.user_repository.get_bridge(user_id, "telegram")? {
-                match crate::utils::bridge::fetch_bridge_messages("telegram", state, user_id, start_timestamp, true).await {
-                    Ok(telegram_messages) => {
-                        // Convert TelegramMessage to MessageInfo and add to messages
-                        let telegram_infos: Vec<MessageInfo> = telegram_messages.into_iter()
+                match crate::utils::bridge::fetch_bridge_messages(service, state, user_id, start_timestamp, true).await {
+                    Ok(bridge_messages) => {
+                        let bridge_infos: Vec<MessageInfo> = bridge_messages.into_iter()
                             .map(|msg| MessageInfo {
                                 sender: msg.room_name,
                                 content: msg.content,
                                 timestamp_rfc: msg.formatted_timestamp,
-                                platform: "telegram".to_string(),
+                                platform: service.to_string(),
                             })
                             .collect();
-                        
+
                         tracing::debug!(
-                            "Fetched {} Telegram messages from the last {} hours for digest",
-                            telegram_infos.len(),
+                            "Fetched {} {} messages from the last {} hours for digest",
+                            bridge_infos.len(),
+                            bridge_service.display_name(),
                             hours_since_prev
                         );
 
-                        // Extend messages with Telegram messages
-                        messages.extend(telegram_infos);
+                        messages.extend(bridge_infos);
 
                         // Sort all messages by timestamp (most recent first)
                         messages.sort_by(|a, b| b.timestamp_rfc.cmp(&a.timestamp_rfc));
                     }
                     Err(e) => {
-                        tracing::error!("Failed to fetch Telegram messages for digest: {}", e);
+                        tracing::error!("Failed to fetch {} messages for digest: {}", bridge_service.display_name(), e);
                     }
                 }
             }
@@ -590,7 +562,7 @@ pub async fn check_morning_digest(state: &Arc<AppState>, user_id: i32) -> Result
             };
 
             // Generate the digest
-            let digest_message = match generate_digest(&state, digest_data).await {
+            let digest_message = match generate_digest(&state, user_id, digest_data).await {
                 Ok(digest) => format!("Good morning! {}",digest),
                 Err(_) => format!(
                     "Good morning! Here's your morning digest covering the last {} hours. Next digest in {} hours.",
@@ -607,6 +579,7 @@ pub async fn check_morning_digest(state: &Arc<AppState>, user_id: i32) -> Result
                 &digest_message,
                 "morning_digest".to_string(),
                 Some("Good morning! Want to hear your morning digest?".to_string()),
+                None,
             ).await;
         }
     }
@@ -705,7 +678,7 @@ pub async fn check_day_digest(state: &Arc<AppState>, user_id: i32) -> Result<(),
             // Check if user has IMAP credentials before fetching emails
             let mut messages = if state.user_repository.get_imap_credentials(user_id)?.is_some() {
                 // Fetch and filter emails
-                match crate::handlers::imap_handlers::fetch_emails_imap(state, user_id, false, Some(50), false, true).await {
+                match crate::handlers::imap_handlers::fetch_emails_imap(state, user_id, None, false, Some(50), false, true).await {
                     Ok(emails) => {
                         emails.into_iter()
                             .filter(|email| {
@@ -741,69 +714,37 @@ pub async fn check_day_digest(state: &Arc<AppState>, user_id: i32) -> Result<(),
                 hours_since_prev
             );
 
-            // Fetch WhatsApp messages
-            if let Some(bridge) = state.user_repository.get_bridge(user_id, "whatsapp")? {
-                match crate::utils::bridge::fetch_bridge_messages("whatsapp", state, user_id, start_timestamp, true).await {
-                    Ok(whatsapp_messages) => {
-                        // Convert WhatsAppMessage to MessageInfo and add to messages
-                        let whatsapp_infos: Vec<MessageInfo> = whatsapp_messages.into_iter()
-                            .map(|msg| MessageInfo {
-                                sender: msg.room_name,
-                                content: msg.content,
-                                timestamp_rfc: msg.formatted_timestamp,
-                                platform: "whatsapp".to_string(),
-                            })
-                            .collect();
-                        
-                        tracing::debug!(
-                            "Fetched {} WhatsApp messages from the last {} hours for digest",
-                            whatsapp_infos.len(),
-                            hours_since_prev
-                        );
-
-                        // Extend messages with WhatsApp messages
-                        messages.extend(whatsapp_infos);
-
-                        // Sort all messages by timestamp (most recent first)
-                        messages.sort_by(|a, b| b.timestamp_rfc.cmp(&a.timestamp_rfc));
-                    }
-                    Err(e) => {
-                        tracing::error!("Failed to fetch WhatsApp messages for digest: {}", e);
-                    }
+            // Fetch messages from every connected bridge
+            for bridge_service in crate::utils::bridge_service::all_services() {
+                let service = bridge_service.name();
+                if state.user_repository.get_bridge(user_id, service)?.is_none() {
+                    continue;
                 }
-            }
-
-            // Fetch Telegram messages
-            if let Some(bridge) = state.user_repository.get_bridge(user_id, "telegram")? {
-                match crate::utils::bridge::fetch_bridge_messages("telegram", state, user_id, start_timestamp, true).await {
-                    Ok(telegram_messages) => {
-                        // Convert TelegramMessage to MessageInfo and add to messages
-                        let telegram_infos: Vec<MessageInfo> = telegram_messages.into_iter()
+                match crate::utils::bridge::fetch_bridge_messages(service, state, user_id, start_timestamp, true).await {
+                    Ok(bridge_messages) => {
+                        let bridge_infos: Vec<MessageInfo> = bridge_messages.into_iter()
                             .map(|msg| MessageInfo {
                                 sender: msg.room_name,
                                 content: msg.content,
                                 timestamp_rfc: msg.formatted_timestamp,
-                                platform: "telegram".to_string(),
+                                platform: service.to_string(),
                             })
                             .collect();
-                        
+
                         tracing::debug!(
-                            "Fetched {} Telegram messages from the last {} hours for digest",
-                            telegram_infos.len(),
+                            "Fetched {} {} messages from the last {} hours for digest",
+                            bridge_infos.len(),
+                            bridge_service.display_name(),
                             hours_since_prev
                         );
-                        if user_id == 1 {
-                            println!("telegram_infos: {:#?}", telegram_infos);
-                        }
 
-                        // Extend messages with Telegram messages
-                        messages.extend(telegram_infos);
+                        messages.extend(bridge_infos);
 
                         // Sort all messages by timestamp (most recent first)
                         messages.sort_by(|a, b| b.timestamp_rfc.cmp(&a.timestamp_rfc));
                     }
                     Err(e) => {
-                        tracing::error!("Failed to fetch Telegram messages for digest: {}", e);
+                        tracing::error!("Failed to fetch {} messages for digest: {}", bridge_service.display_name(), e);
                     }
                 }
             }
@@ -827,7 +768,7 @@ pub async fn check_day_digest(state: &Arc<AppState>, user_id: i32) -> Result<(),
             };
 
             // Generate the digest
-            let digest_message = match generate_digest(&state, digest_data).await {
+            let digest_message = match generate_digest(&state, user_id, digest_data).await {
                 Ok(digest) => format!("Hello! {}",digest),
                 Err(_) => format!(
                     "Hello! Here's your daily digest covering the last {} hours. Next digest in {} hours.",
@@ -844,6 +785,7 @@ pub async fn check_day_digest(state: &Arc<AppState>, user_id: i32) -> Result<(),
                 &digest_message,
                 "day_digest".to_string(),
                 Some("Hello! Want to hear your daily digest?".to_string()),
+                None,
             ).await;
         }
     }
@@ -957,7 +899,7 @@ pub async fn check_evening_digest(state: &Arc<AppState>, user_id: i32) -> Result
             // Check if user has IMAP credentials before fetching emails
             let mut messages = if state.user_repository.get_imap_credentials(user_id)?.is_some() {
                 // Fetch and filter emails
-                match crate::handlers::imap_handlers::fetch_emails_imap(state, user_id, false, Some(50), false, true).await {
+                match crate::handlers::imap_handlers::fetch_emails_imap(state, user_id, None, false, Some(50), false, true).await {
                     Ok(emails) => {
                         emails.into_iter()
                             .filter(|email| {
@@ -993,66 +935,37 @@ pub async fn check_evening_digest(state: &Arc<AppState>, user_id: i32) -> Result
                 hours_since_prev
             );
 
-            // Fetch WhatsApp messages
-            if let Some(bridge) = state.user_repository.get_bridge(user_id, "whatsapp")? {
-                match crate::utils::bridge::fetch_bridge_messages("whatsapp", state, user_id, start_timestamp, true).await {
-                    Ok(whatsapp_messages) => {
-                        // Convert WhatsAppMessage to MessageInfo and add to messages
-                        let whatsapp_infos: Vec<MessageInfo> = whatsapp_messages.into_iter()
-                            .map(|msg| MessageInfo {
-                                sender: msg.room_name,
-                                content: msg.content,
-                                timestamp_rfc: msg.formatted_timestamp,
-                                platform: "whatsapp".to_string(),
-                            })
-                            .collect();
-                        
-                        tracing::debug!(
-                            "Fetched {} WhatsApp messages from the last {} hours for digest",
-                            whatsapp_infos.len(),
-                            hours_since_prev
-                        );
-
-                        // Extend messages with WhatsApp messages
-                        messages.extend(whatsapp_infos);
-
-                        // Sort all messages by timestamp (most recent first)
-                        messages.sort_by(|a, b| b.timestamp_rfc.cmp(&a.timestamp_rfc));
-                    }
-                    Err(e) => {
-                        tracing::error!("Failed to fetch WhatsApp messages for digest: {}", e);
-                    }
+            // Fetch messages from every connected bridge
+            for bridge_service in crate::utils::bridge_service::all_services() {
+                let service = bridge_service.name();
+                if state.user_repository.get_bridge(user_id, service)?.is_none() {
+                    continue;
                 }
-            }
-
-            // Fetch Telegram messages
-            if let Some(bridge) = state.user_repository.get_bridge(user_id, "telegram")? {
-                match crate::utils::bridge::fetch_bridge_messages("telegram", state, user_id, start_timestamp, true).await {
-                    Ok(telegram_messages) => {
-                        // Convert Telegram to MessageInfo and add to messages
-                        let telegram_infos: Vec<MessageInfo> = telegram_messages.into_iter()
+                match crate::utils::bridge::fetch_bridge_messages(service, state, user_id, start_timestamp, true).await {
+                    Ok(bridge_messages) => {
+                        let bridge_infos: Vec<MessageInfo> = bridge_messages.into_iter()
                             .map(|msg| MessageInfo {
                                 sender: msg.room_name,
                                 content: msg.content,
                                 timestamp_rfc: msg.formatted_timestamp,
-                                platform: "telegram".to_string(),
+                                platform: service.to_string(),
                             })
                             .collect();
-                        
+
                         tracing::debug!(
-                            "Fetched {} Telegram messages from the last {} hours for digest",
-                            telegram_infos.len(),
+                            "Fetched {} {} messages from the last {} hours for digest",
+                            bridge_infos.len(),
+                            bridge_service.display_name(),
                             hours_since_prev
                         );
 
-                        // Extend messages with Telegram messages
-                        messages.extend(telegram_infos);
+                        messages.extend(bridge_infos);
 
                         // Sort all messages by timestamp (most recent first)
                         messages.sort_by(|a, b| b.timestamp_rfc.cmp(&a.timestamp_rfc));
                     }
                     Err(e) => {
-                        tracing::error!("Failed to fetch Telegram messages for digest: {}", e);
+                        tracing::error!("Failed to fetch {} messages for digest: {}", bridge_service.display_name(), e);
                     }
                 }
             }
@@ -1076,7 +989,7 @@ pub async fn check_evening_digest(state: &Arc<AppState>, user_id: i32) -> Result
             };
 
             // Generate the digest
-            let digest_message = match generate_digest(&state, digest_data).await {
+            let digest_message = match generate_digest(&state, user_id, digest_data).await {
                 Ok(digest) => format!("Good evening! {}",digest),
                 Err(_) => format!(
                     "Hello! Here's your evening digest covering the last {} hours. Next digest in {} hours.",
@@ -1093,6 +1006,7 @@ pub async fn check_evening_digest(state: &Arc<AppState>, user_id: i32) -> Result
                 &digest_message,
                 "evening_digest".to_string(),
                 Some("Good evening! Want to hear your evening digest?".to_string()),
+                None,
             ).await;
         }
     }
@@ -1119,11 +1033,85 @@ Return JSON with a single field:
 • `digest` – the plain-text SMS message, with newlines separating items.
 "#;
 
+/// Digest on demand (the DIGEST NOW sms command) covering the last `hours` of messages
+/// and calendar events for the next `hours`. Returns the text instead of sending it.
+pub async fn generate_digest_now(
+    state: &Arc<AppState>,
+    user_id: i32,
+    hours: u32,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let now = Utc::now();
+    let cutoff_time = now - Duration::hours(hours as i64);
+
+    let calendar_events = if state.user_repository.has_active_google_calendar(user_id)? {
+        let start_time = now.to_rfc3339();
+        let end_time = (now + Duration::hours(hours as i64)).to_rfc3339();
+        match crate::handlers::google_calendar::handle_calendar_fetching(state.as_ref(), user_id, &start_time, &end_time).await {
+            Ok(axum::Json(value)) => value.get("events")
+                .and_then(|e| e.as_array())
+                .map(|events| events.iter().filter_map(|event| {
+                    Some(CalendarEvent {
+                        title: event.get("summary")?.as_str()?.to_string(),
+                        start_time_rfc: event.get("start")?.as_str()?.parse().ok()?,
+                        duration_minutes: event.get("duration_minutes")?.as_str()?.parse().ok()?,
+                    })
+                }).collect())
+                .unwrap_or_default(),
+            Err(_) => Vec::new(),
+        }
+    } else {
+        Vec::new()
+    };
+
+    let mut messages = Vec::new();
+    if state.user_repository.get_imap_credentials(user_id)?.is_some() {
+        match crate::handlers::imap_handlers::fetch_emails_imap(state, user_id, None, false, Some(50), false, true).await {
+            Ok(emails) => messages.extend(emails.into_iter()
+                .filter(|email| email.date.map_or(false, |date| date >= cutoff_time))
+                .map(|email| MessageInfo {
+                    sender: email.from.unwrap_or_else(|| "Unknown sender".to_string()),
+                    content: email.snippet.unwrap_or_else(|| "No content".to_string()),
+                    timestamp_rfc: email.date_formatted.unwrap_or_else(|| "No Timestamp".to_string()),
+                    platform: "email".to_string(),
+                })),
+            Err(e) => tracing::error!("Failed to fetch emails for digest: {:#?}", e),
+        }
+    }
+
+    for bridge_service in crate::utils::bridge_service::all_services() {
+        let service = bridge_service.name();
+        if state.user_repository.get_bridge(user_id, service)?.is_none() {
+            continue;
+        }
+        match crate::utils::bridge::fetch_bridge_messages(service, state, user_id, cutoff_time.timestamp(), true).await {
+            Ok(bridge_messages) => messages.extend(bridge_messages.into_iter().map(|msg| MessageInfo {
+                sender: msg.room_name,
+                content: msg.content,
+                timestamp_rfc: msg.formatted_timestamp,
+                platform: service.to_string(),
+            })),
+            Err(e) => tracing::error!("Failed to fetch {} messages for digest: {}", service, e),
+        }
+    }
+    messages.sort_by(|a, b| b.timestamp_rfc.cmp(&a.timestamp_rfc));
+
+    if messages.is_empty() && calendar_events.is_empty() {
+        return Ok(format!("Nothing new in the last {} hours and no upcoming events.", hours));
+    }
+
+    generate_digest(state, user_id, DigestData {
+        messages,
+        calendar_events,
+        time_period_hours: hours,
+    }).await
+}
+
 pub async fn generate_digest(
     state: &Arc<AppState>,
+    user_id: i32,
     data: DigestData
 ) -> Result<String, Box<dyn std::error::Error>> {
-    let client = create_openai_client(&state)?;
+    let llm = LlmChain::for_purpose(state, LlmPurpose::Digest, Some(user_id))?;
 
     // Format messages for the prompt
     let messages_str = data.messages
@@ -1202,14 +1190,14 @@ pub async fn generate_digest(
     }];
 
     let request = chat_completion::ChatCompletionRequest::new(
-        GPT4_O.to_string(),
+        llm.primary_model().to_string(),
         messages,
     )
     .tools(tools)
     .tool_choice(chat_completion::ToolChoiceType::Required)
     .max_tokens(200);
 
-    match client.chat_completion(request).await {
+    match llm.chat_completion(request).await {
         Ok(result) => {
             if let Some(tool_calls) = result.choices[0].message.tool_calls.as_ref() {
                 if let Some(first_call) = tool_calls.first() {
@@ -1258,6 +1246,7 @@ pub async fn send_notification(
     notification: &str,
     content_type: String,
     first_message: Option<String>,
+    source: Option<crate::utils::notification_sources::NotificationSource>, // lets the user answer with "r: .."
 ) {
     // Get current timestamp for message history
     let current_time = std::time::SystemTime::now()
@@ -1332,6 +1321,7 @@ pub async fn send_notification(
                         }
                     }
                     tracing::debug!("Successfully initiated call notification for user {}", user.id);
+                    crate::utils::notification_sources::record(state, user.id, source.as_ref());
                     
                     // Store notification in message history
                     let first_msg = first_message.unwrap_or("Hello, I have a critical notification to tell you about".to_string());
@@ -1423,6 +1413,7 @@ pub async fn send_notification(
             ).await {
                 Ok(response_sid) => {
                     tracing::info!("Successfully sent notification to user {}", user_id);
+                    crate::utils::notification_sources::record(state, user.id, source.as_ref());
                     println!("SMS notification sent successfully for user {}", user_id);
                     
                     // Store notification in message history
@@ -0,0 +1,61 @@
+use diesel::prelude::*;
+use diesel::result::Error as DieselError;
+use crate::{
+    models::user_models::{AudioTranscript, NewAudioTranscript},
+    schema::audio_transcripts,
+    utils::encryption::{encrypt, decrypt},
+};
+
+impl crate::repositories::user_repository::UserRepository {
+    // Bridged voice notes show up in every fetch, digest and check, transcribe each one only once
+
+    pub fn get_audio_transcript(&self, user_id: i32, source_id: &str) -> Result<Option<String>, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        let transcript = audio_transcripts::table
+            .filter(audio_transcripts::user_id.eq(user_id))
+            .filter(audio_transcripts::source_id.eq(source_id))
+            .select(AudioTranscript::as_select())
+            .first::<AudioTranscript>(&mut conn)
+            .optional()?;
+
+        match transcript {
+            Some(transcript) => match decrypt(&transcript.encrypted_transcript) {
+                Ok(text) => Ok(Some(text)),
+                Err(e) => {
+                    tracing::error!("Failed to decrypt audio transcript: {:?}", e);
+                    Ok(None)
+                }
+            },
+            None => Ok(None),
+        }
+    }
+
+    pub fn save_audio_transcript(&self, user_id: i32, source_id: &str, transcript: &str) -> Result<(), DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        let encrypted_transcript = encrypt(transcript).map_err(|e| {
+            tracing::error!("Failed to encrypt audio transcript: {:?}", e);
+            DieselError::RollbackTransaction
+        })?;
+
+        // Two fetches can transcribe the same note at once, the first one wins
+        diesel::insert_into(audio_transcripts::table)
+            .values(&NewAudioTranscript {
+                user_id,
+                source_id: source_id.to_string(),
+                encrypted_transcript,
+                created_at: chrono::Utc::now().timestamp() as i32,
+            })
+            .on_conflict_do_nothing()
+            .execute(&mut conn)?;
+        Ok(())
+    }
+
+    pub fn delete_old_audio_transcripts(&self, older_than: i32) -> Result<usize, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        diesel::delete(
+            audio_transcripts::table
+                .filter(audio_transcripts::created_at.lt(older_than))
+        )
+        .execute(&mut conn)
+    }
+}
@@ -0,0 +1,73 @@
+use diesel::prelude::*;
+use diesel::result::Error as DieselError;
+use crate::{
+    models::user_models::{AwayReply, NewAwayReply},
+    schema::away_replies,
+};
+
+impl crate::repositories::user_repository::UserRepository {
+    // Automatic away replies already sent, see utils::away_mode
+
+    /// Records a reply to the contact unless it got one within `interval_seconds`.
+    /// Returns whether the reply may go out, so a burst of messages gets a single reply.
+    pub fn claim_away_reply(&self, user_id: i32, platform: &str, contact_key: &str, interval_seconds: i32) -> Result<bool, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        let now = chrono::Utc::now().timestamp() as i32;
+
+        conn.immediate_transaction(|conn| {
+            let previous = away_replies::table
+                .filter(away_replies::user_id.eq(user_id))
+                .filter(away_replies::platform.eq(platform))
+                .filter(away_replies::contact_key.eq(contact_key))
+                .select(AwayReply::as_select())
+                .first::<AwayReply>(conn)
+                .optional()?;
+
+            match previous {
+                Some(previous) if previous.replied_at > now - interval_seconds => Ok(false),
+                Some(previous) => {
+                    diesel::update(away_replies::table.filter(away_replies::id.eq(previous.id)))
+                        .set(away_replies::replied_at.eq(now))
+                        .execute(conn)?;
+                    Ok(true)
+                }
+                None => {
+                    diesel::insert_into(away_replies::table)
+                        .values(&NewAwayReply {
+                            user_id,
+                            platform: platform.to_string(),
+                            contact_key: contact_key.to_string(),
+                            replied_at: now,
+                        })
+                        .execute(conn)?;
+                    Ok(true)
+                }
+            }
+        })
+    }
+
+    /// Forgets who was replied to, so a new away period answers everyone again.
+    pub fn clear_away_replies(&self, user_id: i32) -> Result<usize, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        diesel::delete(away_replies::table.filter(away_replies::user_id.eq(user_id)))
+            .execute(&mut conn)
+    }
+
+    /// Drops a claim whose reply couldn't be sent, so the next message tries again.
+    pub fn release_away_reply(&self, user_id: i32, platform: &str, contact_key: &str) -> Result<usize, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        diesel::delete(
+            away_replies::table
+                .filter(away_replies::user_id.eq(user_id))
+                .filter(away_replies::platform.eq(platform))
+                .filter(away_replies::contact_key.eq(contact_key)),
+        )
+        .execute(&mut conn)
+    }
+
+    pub fn delete_old_away_replies(&self, older_than: i32) -> Result<usize, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        diesel::delete(away_replies::table.filter(away_replies::replied_at.lt(older_than)))
+            .execute(&mut conn)
+    }
+}
@@ -0,0 +1,74 @@
+use diesel::prelude::*;
+use diesel::result::Error as DieselError;
+use crate::{
+    models::user_models::{BridgeStatusEvent, NewBridgeStatusEvent},
+    schema::{bridge_status_history, bridges},
+};
+
+fn now() -> i32 {
+    chrono::Utc::now().timestamp() as i32
+}
+
+impl crate::repositories::user_repository::UserRepository {
+    // Bridge health checks, see utils::bridge_health
+
+    pub fn record_bridge_status(&self, user_id: i32, bridge_type: &str, status: &str, detail: Option<String>) -> Result<(), DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        diesel::insert_into(bridge_status_history::table)
+            .values(&NewBridgeStatusEvent {
+                user_id,
+                bridge_type: bridge_type.to_string(),
+                status: status.to_string(),
+                detail,
+                created_at: now(),
+            })
+            .execute(&mut conn)?;
+        Ok(())
+    }
+
+    /// How many of the latest checks of the bridge failed in a row. Checks where the bot
+    /// didn't answer are skipped, they neither count nor break the streak.
+    pub fn count_consecutive_bridge_failures(&self, user_id: i32, bridge_type: &str) -> Result<usize, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        let statuses = bridge_status_history::table
+            .filter(bridge_status_history::user_id.eq(user_id))
+            .filter(bridge_status_history::bridge_type.eq(bridge_type))
+            .order((bridge_status_history::created_at.desc(), bridge_status_history::id.desc()))
+            .limit(20)
+            .select(bridge_status_history::status)
+            .load::<String>(&mut conn)?;
+        Ok(statuses
+            .iter()
+            .filter(|status| *status != "unreachable")
+            .take_while(|status| *status == "failed")
+            .count())
+    }
+
+    /// Latest checks of every user for the admin view, newest first.
+    pub fn get_bridge_status_history(&self, limit: i64) -> Result<Vec<BridgeStatusEvent>, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        bridge_status_history::table
+            .order((bridge_status_history::created_at.desc(), bridge_status_history::id.desc()))
+            .limit(limit)
+            .select(BridgeStatusEvent::as_select())
+            .load::<BridgeStatusEvent>(&mut conn)
+    }
+
+    pub fn delete_old_bridge_status_history(&self, cutoff: i32) -> Result<usize, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        diesel::delete(bridge_status_history::table.filter(bridge_status_history::created_at.lt(cutoff)))
+            .execute(&mut conn)
+    }
+
+    pub fn set_bridge_status(&self, user_id: i32, bridge_type: &str, status: &str) -> Result<(), DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        diesel::update(
+            bridges::table
+                .filter(bridges::user_id.eq(user_id))
+                .filter(bridges::bridge_type.eq(bridge_type))
+        )
+        .set(bridges::status.eq(status))
+        .execute(&mut conn)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,80 @@
+use diesel::prelude::*;
+use diesel::result::Error as DieselError;
+use crate::{
+    models::user_models::{Contact, NewContact},
+    schema::contacts,
+    utils::encryption::{encrypt, decrypt, EncryptionError},
+};
+
+fn encrypt_field(value: &str) -> Result<String, DieselError> {
+    encrypt(value).map_err(|e| {
+        tracing::error!("Failed to encrypt contact: {:?}", e);
+        DieselError::RollbackTransaction
+    })
+}
+
+impl crate::repositories::user_repository::UserRepository {
+    /// Returns false when the user already has a contact with the same name and phone number.
+    pub fn add_contact(&self, user_id: i32, name: &str, phone: Option<&str>, email: Option<&str>, source: &str) -> Result<bool, DieselError> {
+        let existing = self.get_contacts(user_id)?;
+        if existing.iter().any(|c| {
+            c.encrypted_name.eq_ignore_ascii_case(name) && c.encrypted_phone.as_deref() == phone
+        }) {
+            return Ok(false);
+        }
+
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        diesel::insert_into(contacts::table)
+            .values(&NewContact {
+                user_id,
+                encrypted_name: encrypt_field(name)?,
+                encrypted_phone: phone.map(encrypt_field).transpose()?,
+                encrypted_email: email.map(encrypt_field).transpose()?,
+                source: source.to_string(),
+                created_at: chrono::Utc::now().timestamp() as i32,
+            })
+            .execute(&mut conn)?;
+        Ok(true)
+    }
+
+    /// All contacts of the user sorted by name, decrypted.
+    pub fn get_contacts(&self, user_id: i32) -> Result<Vec<Contact>, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        let rows = contacts::table
+            .filter(contacts::user_id.eq(user_id))
+            .select(Contact::as_select())
+            .load::<Contact>(&mut conn)?;
+
+        let mut contacts: Vec<Contact> = rows.into_iter()
+            .filter_map(|mut contact| {
+                let decrypted = (|| -> Result<(), EncryptionError> {
+                    contact.encrypted_name = decrypt(&contact.encrypted_name)?;
+                    contact.encrypted_phone = contact.encrypted_phone.as_deref().map(decrypt).transpose()?;
+                    contact.encrypted_email = contact.encrypted_email.as_deref().map(decrypt).transpose()?;
+                    Ok(())
+                })();
+                match decrypted {
+                    Ok(()) => Some(contact),
+                    Err(e) => {
+                        tracing::error!("Failed to decrypt contact: {:?}", e);
+                        None
+                    }
+                }
+            })
+            .collect();
+        contacts.sort_by_key(|c| c.encrypted_name.to_lowercase());
+        Ok(contacts)
+    }
+
+    /// Returns false if the user has no contact with that id.
+    pub fn delete_contact(&self, user_id: i32, contact_id: i32) -> Result<bool, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        let deleted = diesel::delete(
+            contacts::table
+                .filter(contacts::id.eq(contact_id))
+                .filter(contacts::user_id.eq(user_id))
+        )
+        .execute(&mut conn)?;
+        Ok(deleted > 0)
+    }
+}
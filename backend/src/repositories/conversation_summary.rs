@@ -0,0 +1,88 @@
+use diesel::prelude::*;
+use diesel::result::Error as DieselError;
+use crate::{
+    models::user_models::{ConversationSummary, NewConversationSummary, MessageHistory},
+    schema::{conversation_summaries, message_history},
+    utils::encryption::{encrypt, decrypt},
+};
+
+impl crate::repositories::user_repository::UserRepository {
+    /// Returns the decrypted rolling summary and the timestamp it covers messages up to.
+    pub fn get_conversation_summary(&self, user_id: i32) -> Result<Option<(String, i32)>, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        let summary = conversation_summaries::table
+            .filter(conversation_summaries::user_id.eq(user_id))
+            .select(ConversationSummary::as_select())
+            .first::<ConversationSummary>(&mut conn)
+            .optional()?;
+
+        match summary {
+            Some(summary) => match decrypt(&summary.encrypted_summary) {
+                Ok(text) => Ok(Some((text, summary.summarized_until))),
+                Err(e) => {
+                    tracing::error!("Failed to decrypt conversation summary for user {}: {:?}", user_id, e);
+                    Ok(None)
+                }
+            },
+            None => Ok(None),
+        }
+    }
+
+    pub fn save_conversation_summary(&self, user_id: i32, summary: &str, summarized_until: i32) -> Result<(), DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        let encrypted_summary = encrypt(summary).map_err(|e| {
+            tracing::error!("Failed to encrypt conversation summary: {:?}", e);
+            DieselError::RollbackTransaction
+        })?;
+        let now = chrono::Utc::now().timestamp() as i32;
+
+        diesel::insert_into(conversation_summaries::table)
+            .values(&NewConversationSummary {
+                user_id,
+                encrypted_summary: encrypted_summary.clone(),
+                summarized_until,
+                updated_at: now,
+            })
+            .on_conflict(conversation_summaries::user_id)
+            .do_update()
+            .set((
+                conversation_summaries::encrypted_summary.eq(encrypted_summary),
+                conversation_summaries::summarized_until.eq(summarized_until),
+                conversation_summaries::updated_at.eq(now),
+            ))
+            .execute(&mut conn)?;
+        Ok(())
+    }
+
+    /// Drops the user's summary, e.g. when they stop keeping conversation history.
+    pub fn delete_conversation_summary(&self, user_id: i32) -> Result<usize, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        diesel::delete(conversation_summaries::table.filter(conversation_summaries::user_id.eq(user_id)))
+            .execute(&mut conn)
+    }
+
+    /// User and assistant messages newer than `after`, oldest first and decrypted.
+    /// Tool results are left out, the summary only needs what was said.
+    pub fn get_messages_since(&self, user_id: i32, after: i32) -> Result<Vec<MessageHistory>, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        let messages = message_history::table
+            .filter(message_history::user_id.eq(user_id))
+            .filter(message_history::created_at.gt(after))
+            .filter(message_history::role.eq_any(vec!["user", "assistant"]))
+            .order_by(message_history::created_at.asc())
+            .load::<MessageHistory>(&mut conn)?;
+
+        Ok(messages.into_iter()
+            .filter_map(|mut msg| match decrypt(&msg.encrypted_content) {
+                Ok(content) => {
+                    msg.encrypted_content = content;
+                    Some(msg)
+                }
+                Err(e) => {
+                    tracing::error!("Failed to decrypt message content: {:?}", e);
+                    None
+                }
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,67 @@
+use diesel::prelude::*;
+use diesel::result::Error as DieselError;
+use crate::{
+    models::user_models::{GroupPolicy, NewGroupPolicy},
+    schema::group_policies,
+};
+
+impl crate::repositories::user_repository::UserRepository {
+    // When bridged group chats may notify the user, see utils::bridge::handle_bridge_message
+
+    /// Sets the policy of the group, replacing the one it had.
+    pub fn set_group_policy(&self, new_policy: &NewGroupPolicy) -> Result<(), DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        conn.immediate_transaction(|conn| {
+            diesel::delete(
+                group_policies::table
+                    .filter(group_policies::user_id.eq(new_policy.user_id))
+                    .filter(group_policies::service_type.eq(&new_policy.service_type))
+                    .filter(group_policies::chat_name.eq(&new_policy.chat_name))
+            )
+            .execute(conn)?;
+
+            diesel::insert_into(group_policies::table)
+                .values(new_policy)
+                .execute(conn)?;
+            Ok(())
+        })
+    }
+
+    pub fn delete_group_policy(&self, user_id: i32, service_type: &str, chat_name: &str) -> Result<(), DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        let deleted = diesel::delete(
+            group_policies::table
+                .filter(group_policies::user_id.eq(user_id))
+                .filter(group_policies::service_type.eq(service_type))
+                .filter(group_policies::chat_name.eq(chat_name))
+        )
+        .execute(&mut conn)?;
+        if deleted == 0 {
+            return Err(DieselError::NotFound);
+        }
+        Ok(())
+    }
+
+    pub fn get_group_policies_all(&self, user_id: i32) -> Result<Vec<GroupPolicy>, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        group_policies::table
+            .filter(group_policies::user_id.eq(user_id))
+            .select(GroupPolicy::as_select())
+            .load::<GroupPolicy>(&mut conn)
+    }
+
+    /// Policy of the group, falling back to the user's "*" policy for the service.
+    /// None when neither is set.
+    pub fn get_group_policy(&self, user_id: i32, service_type: &str, chat_name: &str) -> Result<Option<String>, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        let policies = group_policies::table
+            .filter(group_policies::user_id.eq(user_id))
+            .filter(group_policies::service_type.eq(service_type))
+            .select(GroupPolicy::as_select())
+            .load::<GroupPolicy>(&mut conn)?;
+
+        let exact = policies.iter().find(|p| p.chat_name.eq_ignore_ascii_case(chat_name.trim()));
+        let fallback = policies.iter().find(|p| p.chat_name == "*");
+        Ok(exact.or(fallback).map(|p| p.policy.clone()))
+    }
+}
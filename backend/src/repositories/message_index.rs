@@ -0,0 +1,222 @@
+use diesel::prelude::*;
+use diesel::result::Error as DieselError;
+use diesel::sql_types::{BigInt, Integer, Nullable, Text};
+use crate::{
+    models::user_models::{IndexedMessage, MessageIndexCoverage, NewIndexedMessage, NewMessageIndexCoverage},
+    schema::{indexed_messages, message_index_coverage},
+    utils::encryption::{encrypt, decrypt},
+};
+
+#[derive(QueryableByName)]
+struct MatchedId {
+    #[diesel(sql_type = Integer)]
+    id: i32,
+}
+
+fn encrypt_field(value: &str) -> Result<String, DieselError> {
+    encrypt(value).map_err(|e| {
+        tracing::error!("Failed to encrypt indexed message: {:?}", e);
+        DieselError::RollbackTransaction
+    })
+}
+
+fn decrypt_message(mut message: IndexedMessage) -> Option<IndexedMessage> {
+    let decrypt_field = |value: &str| {
+        decrypt(value)
+            .map_err(|e| tracing::error!("Failed to decrypt indexed message {:?}: {:?}", message.id, e))
+            .ok()
+    };
+    let room_name = decrypt_field(&message.encrypted_room_name)?;
+    let sender = decrypt_field(&message.encrypted_sender)?;
+    let content = decrypt_field(&message.encrypted_content)?;
+    message.encrypted_room_name = room_name;
+    message.encrypted_sender = sender;
+    message.encrypted_content = content;
+    Some(message)
+}
+
+impl crate::repositories::user_repository::UserRepository {
+    // Local message index, see utils::message_index. The encrypted_ fields of NewIndexedMessage
+    // come in as plain text and are encrypted here.
+
+    /// Stores the message and its hashed search tokens. Returns false when it was indexed already.
+    pub fn index_message(&self, new_message: &NewIndexedMessage, tokens: &str) -> Result<bool, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        let encrypted_message = NewIndexedMessage {
+            user_id: new_message.user_id,
+            platform: new_message.platform.clone(),
+            message_key: new_message.message_key.clone(),
+            encrypted_room_name: encrypt_field(&new_message.encrypted_room_name)?,
+            encrypted_sender: encrypt_field(&new_message.encrypted_sender)?,
+            encrypted_content: encrypt_field(&new_message.encrypted_content)?,
+            message_type: new_message.message_type.clone(),
+            sent_at: new_message.sent_at,
+        };
+
+        conn.immediate_transaction(|conn| {
+            let existing = indexed_messages::table
+                .filter(indexed_messages::user_id.eq(new_message.user_id))
+                .filter(indexed_messages::platform.eq(&new_message.platform))
+                .filter(indexed_messages::message_key.eq(&new_message.message_key))
+                .select(indexed_messages::id)
+                .first::<Option<i32>>(conn)
+                .optional()?;
+            if existing.is_some() {
+                return Ok(false);
+            }
+
+            diesel::insert_into(indexed_messages::table)
+                .values(&encrypted_message)
+                .execute(conn)?;
+
+            let id = indexed_messages::table
+                .filter(indexed_messages::user_id.eq(new_message.user_id))
+                .filter(indexed_messages::platform.eq(&new_message.platform))
+                .filter(indexed_messages::message_key.eq(&new_message.message_key))
+                .select(indexed_messages::id)
+                .first::<Option<i32>>(conn)?
+                .ok_or(DieselError::NotFound)?;
+
+            diesel::sql_query("INSERT INTO indexed_messages_fts (rowid, tokens) VALUES (?, ?)")
+                .bind::<Integer, _>(id)
+                .bind::<Text, _>(tokens)
+                .execute(conn)?;
+            Ok(true)
+        })
+    }
+
+    /// Messages whose tokens match the fts5 query, newest first, decrypted.
+    pub fn search_indexed_messages(
+        &self,
+        user_id: i32,
+        match_query: &str,
+        platform: Option<&str>,
+        since: i32,
+        limit: i64,
+    ) -> Result<Vec<IndexedMessage>, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        let ids: Vec<i32> = diesel::sql_query(
+            "SELECT m.id AS id FROM indexed_messages_fts f \
+             JOIN indexed_messages m ON m.id = f.rowid \
+             WHERE indexed_messages_fts MATCH ? AND m.user_id = ? AND m.sent_at >= ? \
+             AND (? IS NULL OR m.platform = ?) \
+             ORDER BY m.sent_at DESC LIMIT ?"
+        )
+        .bind::<Text, _>(match_query)
+        .bind::<Integer, _>(user_id)
+        .bind::<Integer, _>(since)
+        .bind::<Nullable<Text>, _>(platform)
+        .bind::<Nullable<Text>, _>(platform)
+        .bind::<BigInt, _>(limit)
+        .load::<MatchedId>(&mut conn)?
+        .into_iter()
+        .map(|matched| matched.id)
+        .collect();
+
+        let messages = indexed_messages::table
+            .filter(indexed_messages::id.eq_any(ids.into_iter().map(Some)))
+            .order(indexed_messages::sent_at.desc())
+            .select(IndexedMessage::as_select())
+            .load::<IndexedMessage>(&mut conn)?;
+
+        Ok(messages.into_iter().filter_map(decrypt_message).collect())
+    }
+
+    /// The user's indexed messages of the platform since the given time, newest first, decrypted.
+    pub fn get_indexed_messages(&self, user_id: i32, platform: &str, since: i32, limit: i64) -> Result<Vec<IndexedMessage>, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        let messages = indexed_messages::table
+            .filter(indexed_messages::user_id.eq(user_id))
+            .filter(indexed_messages::platform.eq(platform))
+            .filter(indexed_messages::sent_at.ge(since))
+            .order((indexed_messages::sent_at.desc(), indexed_messages::id.desc()))
+            .limit(limit)
+            .select(IndexedMessage::as_select())
+            .load::<IndexedMessage>(&mut conn)?;
+
+        Ok(messages.into_iter().filter_map(decrypt_message).collect())
+    }
+
+    /// Opens a coverage span for the user's sync, returns its id.
+    pub fn start_index_coverage(&self, user_id: i32, now: i32) -> Result<i32, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        conn.immediate_transaction(|conn| {
+            diesel::insert_into(message_index_coverage::table)
+                .values(&NewMessageIndexCoverage {
+                    user_id,
+                    started_at: now,
+                    covered_until: now,
+                })
+                .execute(conn)?;
+            message_index_coverage::table
+                .filter(message_index_coverage::user_id.eq(user_id))
+                .order(message_index_coverage::id.desc())
+                .select(message_index_coverage::id)
+                .first::<Option<i32>>(conn)?
+                .ok_or(DieselError::NotFound)
+        })
+    }
+
+    pub fn extend_index_coverage(&self, id: i32, now: i32) -> Result<(), DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        diesel::update(message_index_coverage::table.filter(message_index_coverage::id.eq(id)))
+            .set(message_index_coverage::covered_until.eq(now))
+            .execute(&mut conn)?;
+        Ok(())
+    }
+
+    /// The user's coverage spans that reach past the given time, oldest first.
+    pub fn get_index_coverage(&self, user_id: i32, since: i32) -> Result<Vec<MessageIndexCoverage>, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        message_index_coverage::table
+            .filter(message_index_coverage::user_id.eq(user_id))
+            .filter(message_index_coverage::covered_until.ge(since))
+            .order(message_index_coverage::started_at.asc())
+            .select(MessageIndexCoverage::as_select())
+            .load::<MessageIndexCoverage>(&mut conn)
+    }
+
+    /// Users at the per user limit lost their oldest messages, their coverage starts with the oldest one left.
+    pub fn clip_index_coverage_to_trimmed(&self, max_per_user: i64) -> Result<usize, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        diesel::sql_query(
+            "UPDATE message_index_coverage SET started_at = (\
+                SELECT MIN(sent_at) FROM indexed_messages WHERE indexed_messages.user_id = message_index_coverage.user_id\
+            ) WHERE user_id IN (\
+                SELECT user_id FROM indexed_messages GROUP BY user_id HAVING COUNT(*) >= ?\
+            ) AND started_at < (\
+                SELECT MIN(sent_at) FROM indexed_messages WHERE indexed_messages.user_id = message_index_coverage.user_id\
+            )"
+        )
+        .bind::<BigInt, _>(max_per_user)
+        .execute(&mut conn)
+    }
+
+    pub fn delete_old_index_coverage(&self, cutoff: i32) -> Result<usize, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        diesel::delete(message_index_coverage::table.filter(message_index_coverage::covered_until.lt(cutoff)))
+            .execute(&mut conn)
+    }
+
+    /// Drops messages sent before the cutoff, the fts rows go with them through a trigger.
+    pub fn delete_old_indexed_messages(&self, cutoff: i32) -> Result<usize, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        diesel::delete(indexed_messages::table.filter(indexed_messages::sent_at.lt(cutoff)))
+            .execute(&mut conn)
+    }
+
+    /// Keeps only the newest `max_per_user` messages of every user.
+    pub fn trim_indexed_messages(&self, max_per_user: i64) -> Result<usize, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        diesel::sql_query(
+            "DELETE FROM indexed_messages WHERE id IN (\
+                SELECT id FROM (\
+                    SELECT id, ROW_NUMBER() OVER (PARTITION BY user_id ORDER BY sent_at DESC, id DESC) AS position \
+                    FROM indexed_messages\
+                ) WHERE position > ?\
+            )"
+        )
+        .bind::<BigInt, _>(max_per_user)
+        .execute(&mut conn)
+    }
+}
@@ -0,0 +1,70 @@
+use diesel::prelude::*;
+use diesel::result::Error as DieselError;
+use crate::{
+    models::user_models::{NotificationSourceRecord, NewNotificationSourceRecord},
+    schema::notification_sources,
+    utils::encryption::{encrypt, decrypt},
+};
+
+// Nobody answers a notification from last week with "r:"
+const SOURCE_TTL_SECONDS: i32 = 7 * 24 * 3600;
+
+fn now() -> i32 {
+    chrono::Utc::now().timestamp() as i32
+}
+
+impl crate::repositories::user_repository::UserRepository {
+    // Where notifications came from, see utils::notification_sources
+
+    /// Stores the source of a notification that was just sent and drops the user's expired ones.
+    pub fn record_notification_source(&self, user_id: i32, source_type: &str, payload_json: &str) -> Result<(), DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        let encrypted_payload = encrypt(payload_json).map_err(|e| {
+            tracing::error!("Failed to encrypt notification source: {:?}", e);
+            DieselError::RollbackTransaction
+        })?;
+        let current_time = now();
+
+        diesel::delete(
+            notification_sources::table
+                .filter(notification_sources::user_id.eq(user_id))
+                .filter(notification_sources::created_at.le(current_time - SOURCE_TTL_SECONDS))
+        )
+        .execute(&mut conn)?;
+
+        diesel::insert_into(notification_sources::table)
+            .values(&NewNotificationSourceRecord {
+                user_id,
+                source_type: source_type.to_string(),
+                encrypted_payload,
+                created_at: current_time,
+            })
+            .execute(&mut conn)?;
+        Ok(())
+    }
+
+    /// The user's latest notification sources, newest first, payloads decrypted.
+    pub fn get_recent_notification_sources(&self, user_id: i32, limit: i64) -> Result<Vec<NotificationSourceRecord>, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        let sources = notification_sources::table
+            .filter(notification_sources::user_id.eq(user_id))
+            .filter(notification_sources::created_at.gt(now() - SOURCE_TTL_SECONDS))
+            .order((notification_sources::created_at.desc(), notification_sources::id.desc()))
+            .limit(limit)
+            .select(NotificationSourceRecord::as_select())
+            .load::<NotificationSourceRecord>(&mut conn)?;
+
+        Ok(sources.into_iter()
+            .filter_map(|mut source| match decrypt(&source.encrypted_payload) {
+                Ok(payload) => {
+                    source.encrypted_payload = payload;
+                    Some(source)
+                }
+                Err(e) => {
+                    tracing::error!("Failed to decrypt notification source: {:?}", e);
+                    None
+                }
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,168 @@
+use diesel::prelude::*;
+use diesel::result::Error as DieselError;
+use crate::{
+    models::user_models::{OutgoingMessage, NewOutgoingMessage},
+    schema::outgoing_messages,
+    utils::encryption::{encrypt, decrypt},
+};
+
+fn now() -> i32 {
+    chrono::Utc::now().timestamp() as i32
+}
+
+fn decrypt_payload(mut message: OutgoingMessage) -> Option<OutgoingMessage> {
+    match decrypt(&message.encrypted_payload) {
+        Ok(payload) => {
+            message.encrypted_payload = payload;
+            Some(message)
+        }
+        Err(e) => {
+            tracing::error!("Failed to decrypt outgoing message {:?}: {:?}", message.id, e);
+            None
+        }
+    }
+}
+
+impl crate::repositories::user_repository::UserRepository {
+    // Confirmed messages waiting out the user's send delay, see utils::outbox
+
+    pub fn queue_outgoing_message(&self, user_id: i32, action_type: &str, payload_json: &str, send_at: i32) -> Result<(), DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        let encrypted_payload = encrypt(payload_json).map_err(|e| {
+            tracing::error!("Failed to encrypt outgoing message: {:?}", e);
+            DieselError::RollbackTransaction
+        })?;
+
+        let current_time = now();
+        diesel::insert_into(outgoing_messages::table)
+            .values(&NewOutgoingMessage {
+                user_id,
+                action_type: action_type.to_string(),
+                encrypted_payload,
+                status: "queued".to_string(),
+                send_at,
+                created_at: current_time,
+                updated_at: current_time,
+            })
+            .execute(&mut conn)?;
+        Ok(())
+    }
+
+    /// Marks every queued message that is due as sending and returns them decrypted,
+    /// so an overlapping run or a late UNDO can't touch them anymore.
+    pub fn claim_due_outgoing_messages(&self) -> Result<Vec<OutgoingMessage>, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        let current_time = now();
+
+        let claimed = conn.immediate_transaction(|conn| {
+            let due = outgoing_messages::table
+                .filter(outgoing_messages::status.eq("queued"))
+                .filter(outgoing_messages::send_at.le(current_time))
+                .order(outgoing_messages::send_at.asc())
+                .select(OutgoingMessage::as_select())
+                .load::<OutgoingMessage>(conn)?;
+
+            let ids: Vec<Option<i32>> = due.iter().map(|m| m.id).collect();
+            diesel::update(outgoing_messages::table.filter(outgoing_messages::id.eq_any(ids)))
+                .set((
+                    outgoing_messages::status.eq("sending"),
+                    outgoing_messages::claimed_at.eq(Some(current_time)),
+                    outgoing_messages::updated_at.eq(current_time),
+                ))
+                .execute(conn)?;
+            Ok::<_, DieselError>(due)
+        })?;
+
+        Ok(claimed.into_iter().filter_map(decrypt_payload).collect())
+    }
+
+    /// Fails messages that have been sending since before `claimed_before` and returns them
+    /// decrypted. Their run crashed or hung, they may or may not have gone out.
+    pub fn fail_stuck_outgoing_messages(&self, claimed_before: i32) -> Result<Vec<OutgoingMessage>, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        let current_time = now();
+
+        let stuck = conn.immediate_transaction(|conn| {
+            // rows claimed before claimed_at existed only have updated_at
+            let stuck = outgoing_messages::table
+                .filter(outgoing_messages::status.eq("sending"))
+                .filter(
+                    outgoing_messages::claimed_at.lt(claimed_before)
+                        .or(outgoing_messages::claimed_at.is_null().and(outgoing_messages::updated_at.lt(claimed_before)))
+                )
+                .select(OutgoingMessage::as_select())
+                .load::<OutgoingMessage>(conn)?;
+
+            let ids: Vec<Option<i32>> = stuck.iter().map(|m| m.id).collect();
+            diesel::update(outgoing_messages::table.filter(outgoing_messages::id.eq_any(ids)))
+                .set((
+                    outgoing_messages::status.eq("failed"),
+                    outgoing_messages::updated_at.eq(current_time),
+                ))
+                .execute(conn)?;
+            Ok::<_, DieselError>(stuck)
+        })?;
+
+        Ok(stuck.into_iter().filter_map(decrypt_payload).collect())
+    }
+
+    pub fn finish_outgoing_message(&self, message_id: i32, status: &str) -> Result<(), DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        diesel::update(outgoing_messages::table.filter(outgoing_messages::id.eq(message_id)))
+            .set((
+                outgoing_messages::status.eq(status),
+                outgoing_messages::updated_at.eq(now()),
+            ))
+            .execute(&mut conn)?;
+        Ok(())
+    }
+
+    pub fn has_queued_outgoing_messages(&self, user_id: i32) -> Result<bool, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        let count: i64 = outgoing_messages::table
+            .filter(outgoing_messages::user_id.eq(user_id))
+            .filter(outgoing_messages::status.eq("queued"))
+            .count()
+            .get_result(&mut conn)?;
+        Ok(count > 0)
+    }
+
+    /// Cancels the user's most recently queued message. Returns it decrypted, or None if
+    /// nothing was queued anymore (already sent).
+    pub fn cancel_latest_outgoing_message(&self, user_id: i32) -> Result<Option<OutgoingMessage>, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        let current_time = now();
+
+        let cancelled = conn.immediate_transaction(|conn| {
+            let latest = outgoing_messages::table
+                .filter(outgoing_messages::user_id.eq(user_id))
+                .filter(outgoing_messages::status.eq("queued"))
+                .order(outgoing_messages::created_at.desc())
+                .select(OutgoingMessage::as_select())
+                .first::<OutgoingMessage>(conn)
+                .optional()?;
+
+            if let Some(ref message) = latest {
+                diesel::update(outgoing_messages::table.filter(outgoing_messages::id.eq(message.id)))
+                    .set((
+                        outgoing_messages::status.eq("cancelled"),
+                        outgoing_messages::updated_at.eq(current_time),
+                    ))
+                    .execute(conn)?;
+            }
+            Ok::<_, DieselError>(latest)
+        })?;
+
+        Ok(cancelled.and_then(decrypt_payload))
+    }
+
+    pub fn delete_old_outgoing_messages(&self, older_than: i32) -> Result<usize, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        diesel::delete(
+            outgoing_messages::table
+                .filter(outgoing_messages::status.eq_any(vec!["sent", "cancelled", "failed"]))
+                .filter(outgoing_messages::updated_at.lt(older_than))
+        )
+        .execute(&mut conn)
+    }
+}
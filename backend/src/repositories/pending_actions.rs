@@ -0,0 +1,112 @@
+use diesel::prelude::*;
+use diesel::result::Error as DieselError;
+use crate::{
+    models::user_models::{PendingAction, NewPendingAction},
+    schema::pending_actions,
+    utils::encryption::{encrypt, decrypt},
+};
+
+fn now() -> i32 {
+    chrono::Utc::now().timestamp() as i32
+}
+
+impl crate::repositories::user_repository::UserRepository {
+    // Actions waiting for the user's yes/no, see tool_call_utils::confirm
+
+    /// Stores the action under the smallest number the user doesn't have pending and returns it.
+    pub fn create_pending_action(&self, user_id: i32, action_type: &str, payload_json: &str, ttl_seconds: i32) -> Result<i32, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        let encrypted_payload = encrypt(payload_json).map_err(|e| {
+            tracing::error!("Failed to encrypt pending action: {:?}", e);
+            DieselError::RollbackTransaction
+        })?;
+        let current_time = now();
+
+        conn.immediate_transaction(|conn| {
+            diesel::delete(
+                pending_actions::table
+                    .filter(pending_actions::user_id.eq(user_id))
+                    .filter(pending_actions::expires_at.le(current_time))
+            )
+            .execute(conn)?;
+
+            let taken: Vec<i32> = pending_actions::table
+                .filter(pending_actions::user_id.eq(user_id))
+                .select(pending_actions::number)
+                .load(conn)?;
+            let number = (1..).find(|n| !taken.contains(n)).unwrap_or(1);
+
+            diesel::insert_into(pending_actions::table)
+                .values(&NewPendingAction {
+                    user_id,
+                    number,
+                    action_type: action_type.to_string(),
+                    encrypted_payload,
+                    expires_at: current_time + ttl_seconds,
+                    created_at: current_time,
+                })
+                .execute(conn)?;
+            Ok(number)
+        })
+    }
+
+    /// Unexpired actions of the user ordered by number, payloads decrypted.
+    pub fn get_pending_actions(&self, user_id: i32) -> Result<Vec<PendingAction>, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        let actions = pending_actions::table
+            .filter(pending_actions::user_id.eq(user_id))
+            .filter(pending_actions::expires_at.gt(now()))
+            .order(pending_actions::number.asc())
+            .select(PendingAction::as_select())
+            .load::<PendingAction>(&mut conn)?;
+
+        Ok(actions.into_iter()
+            .filter_map(|mut action| match decrypt(&action.encrypted_payload) {
+                Ok(payload) => {
+                    action.encrypted_payload = payload;
+                    Some(action)
+                }
+                Err(e) => {
+                    tracing::error!("Failed to decrypt pending action: {:?}", e);
+                    None
+                }
+            })
+            .collect())
+    }
+
+    /// Replaces the payload after the user changed something, the expiry is left as is.
+    pub fn update_pending_action(&self, user_id: i32, number: i32, payload_json: &str) -> Result<(), DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        let encrypted_payload = encrypt(payload_json).map_err(|e| {
+            tracing::error!("Failed to encrypt pending action: {:?}", e);
+            DieselError::RollbackTransaction
+        })?;
+
+        diesel::update(
+            pending_actions::table
+                .filter(pending_actions::user_id.eq(user_id))
+                .filter(pending_actions::number.eq(number))
+        )
+        .set(pending_actions::encrypted_payload.eq(encrypted_payload))
+        .execute(&mut conn)?;
+        Ok(())
+    }
+
+    /// Returns false if the user had no action with that number, so a double "yes" only runs it once.
+    pub fn delete_pending_action(&self, user_id: i32, number: i32) -> Result<bool, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        let deleted = diesel::delete(
+            pending_actions::table
+                .filter(pending_actions::user_id.eq(user_id))
+                .filter(pending_actions::number.eq(number))
+        )
+        .execute(&mut conn)?;
+        Ok(deleted > 0)
+    }
+
+    pub fn delete_expired_pending_actions(&self) -> Result<usize, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        diesel::delete(pending_actions::table.filter(pending_actions::expires_at.le(now())))
+            .execute(&mut conn)
+    }
+}
@@ -0,0 +1,172 @@
+use diesel::prelude::*;
+use diesel::result::Error as DieselError;
+use crate::{
+    models::user_models::{ScheduledSend, NewScheduledSend},
+    schema::scheduled_sends,
+    utils::encryption::{encrypt, decrypt},
+};
+
+fn now() -> i32 {
+    chrono::Utc::now().timestamp() as i32
+}
+
+fn encrypt_payload(payload_json: &str) -> Result<String, DieselError> {
+    encrypt(payload_json).map_err(|e| {
+        tracing::error!("Failed to encrypt scheduled send: {:?}", e);
+        DieselError::RollbackTransaction
+    })
+}
+
+fn decrypt_payload(mut send: ScheduledSend) -> Option<ScheduledSend> {
+    match decrypt(&send.encrypted_payload) {
+        Ok(payload) => {
+            send.encrypted_payload = payload;
+            Some(send)
+        }
+        Err(e) => {
+            tracing::error!("Failed to decrypt scheduled send {:?}: {:?}", send.id, e);
+            None
+        }
+    }
+}
+
+impl crate::repositories::user_repository::UserRepository {
+    // Messages the user scheduled for later, see utils::scheduled_sends
+
+    /// Stores the send and returns its id, which the user refers to it by.
+    pub fn create_scheduled_send(&self, user_id: i32, action_type: &str, payload_json: &str, send_at: i32) -> Result<i32, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        let encrypted_payload = encrypt_payload(payload_json)?;
+        let current_time = now();
+
+        conn.immediate_transaction(|conn| {
+            diesel::insert_into(scheduled_sends::table)
+                .values(&NewScheduledSend {
+                    user_id,
+                    action_type: action_type.to_string(),
+                    encrypted_payload,
+                    status: "scheduled".to_string(),
+                    send_at,
+                    created_at: current_time,
+                    updated_at: current_time,
+                })
+                .execute(conn)?;
+
+            scheduled_sends::table
+                .filter(scheduled_sends::user_id.eq(user_id))
+                .select(diesel::dsl::max(scheduled_sends::id))
+                .first::<Option<i32>>(conn)?
+                .ok_or(DieselError::NotFound)
+        })
+    }
+
+    /// Marks every scheduled send that is due as sending and returns them decrypted,
+    /// so an overlapping run or a late cancel can't touch them anymore.
+    pub fn claim_due_scheduled_sends(&self) -> Result<Vec<ScheduledSend>, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        let current_time = now();
+
+        let claimed = conn.immediate_transaction(|conn| {
+            let due = scheduled_sends::table
+                .filter(scheduled_sends::status.eq("scheduled"))
+                .filter(scheduled_sends::send_at.le(current_time))
+                .order(scheduled_sends::send_at.asc())
+                .select(ScheduledSend::as_select())
+                .load::<ScheduledSend>(conn)?;
+
+            let ids: Vec<Option<i32>> = due.iter().map(|s| s.id).collect();
+            diesel::update(scheduled_sends::table.filter(scheduled_sends::id.eq_any(ids)))
+                .set((
+                    scheduled_sends::status.eq("sending"),
+                    scheduled_sends::updated_at.eq(current_time),
+                ))
+                .execute(conn)?;
+            Ok::<_, DieselError>(due)
+        })?;
+
+        Ok(claimed.into_iter().filter_map(decrypt_payload).collect())
+    }
+
+    pub fn finish_scheduled_send(&self, send_id: i32, status: &str) -> Result<(), DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        diesel::update(scheduled_sends::table.filter(scheduled_sends::id.eq(send_id)))
+            .set((
+                scheduled_sends::status.eq(status),
+                scheduled_sends::updated_at.eq(now()),
+            ))
+            .execute(&mut conn)?;
+        Ok(())
+    }
+
+    /// The user's sends that haven't gone out yet, soonest first, payloads decrypted.
+    pub fn get_scheduled_sends(&self, user_id: i32) -> Result<Vec<ScheduledSend>, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        let sends = scheduled_sends::table
+            .filter(scheduled_sends::user_id.eq(user_id))
+            .filter(scheduled_sends::status.eq("scheduled"))
+            .order(scheduled_sends::send_at.asc())
+            .select(ScheduledSend::as_select())
+            .load::<ScheduledSend>(&mut conn)?;
+
+        Ok(sends.into_iter().filter_map(decrypt_payload).collect())
+    }
+
+    pub fn get_scheduled_send(&self, user_id: i32, send_id: i32) -> Result<Option<ScheduledSend>, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        let send = scheduled_sends::table
+            .filter(scheduled_sends::id.eq(send_id))
+            .filter(scheduled_sends::user_id.eq(user_id))
+            .filter(scheduled_sends::status.eq("scheduled"))
+            .select(ScheduledSend::as_select())
+            .first::<ScheduledSend>(&mut conn)
+            .optional()?;
+
+        Ok(send.and_then(decrypt_payload))
+    }
+
+    /// Replaces the payload and time of a send that hasn't gone out. False if there is none.
+    pub fn update_scheduled_send(&self, user_id: i32, send_id: i32, payload_json: &str, send_at: i32) -> Result<bool, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        let encrypted_payload = encrypt_payload(payload_json)?;
+        let updated = diesel::update(
+            scheduled_sends::table
+                .filter(scheduled_sends::id.eq(send_id))
+                .filter(scheduled_sends::user_id.eq(user_id))
+                .filter(scheduled_sends::status.eq("scheduled"))
+        )
+        .set((
+            scheduled_sends::encrypted_payload.eq(encrypted_payload),
+            scheduled_sends::send_at.eq(send_at),
+            scheduled_sends::updated_at.eq(now()),
+        ))
+        .execute(&mut conn)?;
+        Ok(updated > 0)
+    }
+
+    /// Cancels a send that hasn't gone out. False if there is none.
+    pub fn cancel_scheduled_send(&self, user_id: i32, send_id: i32) -> Result<bool, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        let cancelled = diesel::update(
+            scheduled_sends::table
+                .filter(scheduled_sends::id.eq(send_id))
+                .filter(scheduled_sends::user_id.eq(user_id))
+                .filter(scheduled_sends::status.eq("scheduled"))
+        )
+        .set((
+            scheduled_sends::status.eq("cancelled"),
+            scheduled_sends::updated_at.eq(now()),
+        ))
+        .execute(&mut conn)?;
+        Ok(cancelled > 0)
+    }
+
+    pub fn delete_old_scheduled_sends(&self, older_than: i32) -> Result<usize, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        diesel::delete(
+            scheduled_sends::table
+                .filter(scheduled_sends::status.eq_any(vec!["sent", "cancelled", "failed"]))
+                .filter(scheduled_sends::updated_at.lt(older_than))
+        )
+        .execute(&mut conn)
+    }
+}
@@ -0,0 +1,180 @@
+use diesel::prelude::*;
+use diesel::result::Error as DieselError;
+use crate::{
+    models::user_models::{SmsJob, NewSmsJob},
+    schema::sms_jobs,
+    utils::encryption::{encrypt, decrypt},
+};
+
+fn now() -> i32 {
+    chrono::Utc::now().timestamp() as i32
+}
+
+impl crate::repositories::user_repository::UserRepository {
+    // Inbound sms job queue, see jobs::sms_worker
+
+    pub fn enqueue_sms_job(&self, user_id: Option<i32>, payload_json: &str) -> Result<(), DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+
+        let encrypted_payload = encrypt(payload_json).map_err(|e| {
+            tracing::error!("Failed to encrypt sms job payload: {:?}", e);
+            DieselError::RollbackTransaction
+        })?;
+
+        let current_time = now();
+        let new_job = NewSmsJob {
+            user_id,
+            encrypted_payload,
+            status: "pending".to_string(),
+            attempts: 0,
+            next_attempt_at: current_time,
+            created_at: current_time,
+            updated_at: current_time,
+        };
+
+        diesel::insert_into(sms_jobs::table)
+            .values(&new_job)
+            .execute(&mut conn)?;
+        Ok(())
+    }
+
+    /// Takes the oldest due pending job and marks it processing so no other worker gets it.
+    /// The returned job has its payload decrypted.
+    pub fn claim_next_sms_job(&self) -> Result<Option<SmsJob>, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        let current_time = now();
+
+        let claimed = conn.immediate_transaction(|conn| {
+            let job = sms_jobs::table
+                .filter(sms_jobs::status.eq("pending"))
+                .filter(sms_jobs::next_attempt_at.le(current_time))
+                .order((sms_jobs::next_attempt_at.asc(), sms_jobs::id.asc()))
+                .select(SmsJob::as_select())
+                .first::<SmsJob>(conn)
+                .optional()?;
+
+            if let Some(ref job) = job {
+                diesel::update(sms_jobs::table.filter(sms_jobs::id.eq(job.id)))
+                    .set((
+                        sms_jobs::status.eq("processing"),
+                        sms_jobs::attempts.eq(job.attempts + 1),
+                        sms_jobs::updated_at.eq(current_time),
+                    ))
+                    .execute(conn)?;
+            }
+            Ok::<_, DieselError>(job)
+        })?;
+
+        match claimed {
+            Some(mut job) => match decrypt(&job.encrypted_payload) {
+                Ok(payload) => {
+                    job.encrypted_payload = payload;
+                    job.attempts += 1;
+                    job.status = "processing".to_string();
+                    Ok(Some(job))
+                }
+                Err(e) => {
+                    tracing::error!("Failed to decrypt sms job {:?} payload: {:?}", job.id, e);
+                    if let Some(id) = job.id {
+                        self.fail_sms_job(id, "Failed to decrypt payload")?;
+                    }
+                    Ok(None)
+                }
+            },
+            None => Ok(None),
+        }
+    }
+
+    pub fn complete_sms_job(&self, job_id: i32) -> Result<(), DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        diesel::update(sms_jobs::table.filter(sms_jobs::id.eq(job_id)))
+            .set((
+                sms_jobs::status.eq("done"),
+                sms_jobs::updated_at.eq(now()),
+            ))
+            .execute(&mut conn)?;
+        Ok(())
+    }
+
+    pub fn retry_sms_job_later(&self, job_id: i32, error: &str, next_attempt_at: i32) -> Result<(), DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        diesel::update(sms_jobs::table.filter(sms_jobs::id.eq(job_id)))
+            .set((
+                sms_jobs::status.eq("pending"),
+                sms_jobs::last_error.eq(Some(error)),
+                sms_jobs::next_attempt_at.eq(next_attempt_at),
+                sms_jobs::updated_at.eq(now()),
+            ))
+            .execute(&mut conn)?;
+        Ok(())
+    }
+
+    /// Dead-letters the job, it stays in the table for admins to look at or requeue.
+    pub fn fail_sms_job(&self, job_id: i32, error: &str) -> Result<(), DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        diesel::update(sms_jobs::table.filter(sms_jobs::id.eq(job_id)))
+            .set((
+                sms_jobs::status.eq("failed"),
+                sms_jobs::last_error.eq(Some(error)),
+                sms_jobs::updated_at.eq(now()),
+            ))
+            .execute(&mut conn)?;
+        Ok(())
+    }
+
+    /// Dead-letters jobs that have been processing since before `stale_before`.
+    /// These are left over from a crash or restart mid-processing and may already have sent a
+    /// reply or run a tool, so they wait for an admin to requeue them instead of running again.
+    pub fn fail_stale_sms_jobs(&self, stale_before: i32) -> Result<usize, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        diesel::update(
+            sms_jobs::table
+                .filter(sms_jobs::status.eq("processing"))
+                .filter(sms_jobs::updated_at.lt(stale_before))
+        )
+        .set((
+            sms_jobs::status.eq("failed"),
+            sms_jobs::last_error.eq(Some("Interrupted while processing")),
+            sms_jobs::updated_at.eq(now()),
+        ))
+        .execute(&mut conn)
+    }
+
+    /// Payloads are left encrypted, admins only need the metadata.
+    pub fn get_failed_sms_jobs(&self) -> Result<Vec<SmsJob>, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        sms_jobs::table
+            .filter(sms_jobs::status.eq("failed"))
+            .order(sms_jobs::updated_at.desc())
+            .select(SmsJob::as_select())
+            .load::<SmsJob>(&mut conn)
+    }
+
+    /// Admin retry for a dead-lettered job. Returns false if no failed job had that id.
+    pub fn requeue_failed_sms_job(&self, job_id: i32) -> Result<bool, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        let updated = diesel::update(
+            sms_jobs::table
+                .filter(sms_jobs::id.eq(job_id))
+                .filter(sms_jobs::status.eq("failed"))
+        )
+        .set((
+            sms_jobs::status.eq("pending"),
+            sms_jobs::attempts.eq(0),
+            sms_jobs::next_attempt_at.eq(now()),
+            sms_jobs::updated_at.eq(now()),
+        ))
+        .execute(&mut conn)?;
+        Ok(updated > 0)
+    }
+
+    pub fn delete_old_sms_jobs(&self, older_than: i32) -> Result<usize, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        diesel::delete(
+            sms_jobs::table
+                .filter(sms_jobs::status.eq_any(vec!["done", "failed"]))
+                .filter(sms_jobs::updated_at.lt(older_than))
+        )
+        .execute(&mut conn)
+    }
+}
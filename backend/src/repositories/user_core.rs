@@ -3,8 +3,8 @@ use diesel::sql_types::Text;
 use diesel::result::Error as DieselError;
 use std::error::Error;
 use crate::{
-    models::user_models::{User, UserSettings, UserInfo, NewUserInfo, NewUserSettings, TempVariable, NewTempVariable},
-    schema::{users, user_settings, temp_variables, user_info},
+    models::user_models::{User, UserSettings, UserInfo, NewUserInfo, NewUserSettings, AwayModeSettings},
+    schema::{users, user_settings, user_info},
     DbPool,
 };
 
@@ -449,68 +449,6 @@ impl UserCore {
         Ok(())
     }
 
-    pub fn set_temp_variable(&self, user_id: i32, event_type: Option<&str>, recipient: Option<&str>, subject: Option<&str>, 
-        content: Option<&str>, start_time: Option<&str>, duration: Option<&str>, event_id: Option<&str>, image_url: Option<&str>) -> Result<(), DieselError> {
-        let mut conn = self.pool.get().expect("Failed to get DB connection");
-
-        // Start a transaction
-        conn.transaction(|conn| {
-            // First set the confirm_send_event flag to true
-            diesel::update(users::table.find(user_id))
-                .set(users::confirm_send_event.eq(event_type))
-                .execute(conn)?;
-
-            // Delete any existing temp variables for this user
-            diesel::delete(temp_variables::table.filter(temp_variables::user_id.eq(user_id)))
-                .execute(conn)?;
-
-            // Create new temp variable
-            let new_temp_var = NewTempVariable {
-                user_id,
-                confirm_send_event_type: event_type.unwrap().to_string(),
-                confirm_send_event_recipient: recipient.map(|s| s.to_string()),
-                confirm_send_event_subject: subject.map(|s| s.to_string()),
-                confirm_send_event_content: content.map(|s| s.to_string()),
-                confirm_send_event_start_time: start_time.map(|s| s.to_string()),
-                confirm_send_event_duration: duration.map(|s| s.to_string()),
-                confirm_send_event_id: event_id.map(|s| s.to_string()),
-                confirm_send_event_image_url: image_url.map(|s| s.to_string()),
-            };
-
-            // Insert the new temp variable
-            diesel::insert_into(temp_variables::table)
-                .values(&new_temp_var)
-                .execute(conn)?;
-
-            Ok(())
-        })
-    }
-
-
-    pub fn get_temp_variable(&self, user_id: i32, service_type: &str) -> Result<Option<(Option<String>, Option<String>, Option<String>, Option<String>, Option<String>, Option<String>, Option<String>)>, DieselError> {
-        let mut conn = self.pool.get().expect("Failed to get DB connection");
-        
-        let temp_var = temp_variables::table
-            .filter(temp_variables::user_id.eq(user_id))
-            .filter(temp_variables::confirm_send_event_type.eq(service_type))
-            .first::<TempVariable>(&mut conn)
-            .optional()?;
-            
-        match temp_var {
-            Some(var) => Ok(Some((
-                var.confirm_send_event_recipient, 
-                var.confirm_send_event_subject, 
-                var.confirm_send_event_content, 
-                var.confirm_send_event_start_time, 
-                var.confirm_send_event_duration, 
-                var.confirm_send_event_id, 
-                var.confirm_send_event_image_url
-            ))),
-            None => Ok(None)
-        }
-    }
-
-
     pub fn update_last_credits_notification(&self, user_id: i32, timestamp: i32) -> Result<(), DieselError> {
         let mut conn = self.pool.get().expect("Failed to get DB connection");
         diesel::update(users::table.find(user_id))
@@ -538,24 +476,6 @@ impl UserCore {
         Ok(())
     }
 
-    pub fn clear_confirm_send_event(&self, user_id: i32) -> Result<(), DieselError> {
-        let mut conn = self.pool.get().expect("Failed to get DB connection");
-        
-        // Start a transaction
-        conn.transaction(|conn| {
-            // Clear the confirm_send_event flag
-            diesel::update(users::table.find(user_id))
-                .set(users::confirm_send_event.eq::<Option<String>>(None))
-                .execute(conn)?;
-
-            // Delete any existing temp variables for this user
-            diesel::delete(temp_variables::table.filter(temp_variables::user_id.eq(user_id)))
-                .execute(conn)?;
-
-            Ok(())
-        })
-    }
-
     pub fn update_digests(&self, user_id: i32, morning_digest: Option<&str>, day_digest: Option<&str>, evening_digest: Option<&str>) -> Result<(), DieselError> {
         use crate::schema::user_settings;
         let mut conn = self.pool.get().expect("Failed to get DB connection");
@@ -602,14 +522,47 @@ impl UserCore {
         // Ensure user settings exist
         self.ensure_user_settings_exist(user_id)?;
 
-        // Update the setting
+        // Update the setting, an explicit change overrides any timed pause
+        diesel::update(user_settings::table.filter(user_settings::user_id.eq(user_id)))
+            .set((
+                user_settings::proactive_agent_on.eq(enabled),
+                user_settings::proactive_paused_until.eq(None::<i32>),
+            ))
+            .execute(&mut conn)?;
+
+        Ok(())
+    }
+
+    /// Turns proactive notifications off, until `until` (unix timestamp) or indefinitely if None.
+    pub fn pause_proactive_agent(&self, user_id: i32, until: Option<i32>) -> Result<(), DieselError> {
+        use crate::schema::user_settings;
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+
+        self.ensure_user_settings_exist(user_id)?;
+
         diesel::update(user_settings::table.filter(user_settings::user_id.eq(user_id)))
-            .set(user_settings::proactive_agent_on.eq(enabled))
+            .set((
+                user_settings::proactive_agent_on.eq(false),
+                user_settings::proactive_paused_until.eq(until),
+            ))
             .execute(&mut conn)?;
 
         Ok(())
     }
 
+    /// Turns proactive notifications back on for every timed pause that has run out.
+    pub fn resume_expired_proactive_pauses(&self, now: i32) -> Result<usize, DieselError> {
+        use crate::schema::user_settings;
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+
+        diesel::update(user_settings::table.filter(user_settings::proactive_paused_until.le(now)))
+            .set((
+                user_settings::proactive_agent_on.eq(true),
+                user_settings::proactive_paused_until.eq(None::<i32>),
+            ))
+            .execute(&mut conn)
+    }
+
     pub fn get_proactive_agent_on(&self, user_id: i32) -> Result<bool, DieselError> {
         use crate::schema::user_settings;
         let mut conn = self.pool.get().expect("Failed to get DB connection");
@@ -1025,6 +978,124 @@ impl UserCore {
         }
     }
 
+    /// User's own OpenRouter key and preferred model, either can be unset.
+    pub fn get_llm_override(&self, user_id: i32) -> Result<(Option<String>, Option<String>), Box<dyn Error>> {
+        use crate::schema::user_settings;
+        use crate::utils::encryption::decrypt;
+
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+
+        let settings = user_settings::table
+            .filter(user_settings::user_id.eq(user_id))
+            .select((
+                user_settings::encrypted_openrouter_api_key,
+                user_settings::llm_model,
+            ))
+            .first::<(Option<String>, Option<String>)>(&mut conn)
+            .optional()?;
+
+        match settings {
+            Some((encrypted_key, model)) => {
+                let api_key = match encrypted_key {
+                    Some(encrypted_key) => Some(decrypt(&encrypted_key)?),
+                    None => None,
+                };
+                Ok((api_key, model.filter(|m| !m.trim().is_empty())))
+            },
+            None => Ok((None, None)),
+        }
+    }
+
+    pub fn update_llm_override(&self, user_id: i32, api_key: Option<&str>, model: Option<&str>) -> Result<(), Box<dyn Error>> {
+        use crate::schema::user_settings;
+        use crate::utils::encryption::encrypt;
+
+        // Ensure user settings exist
+        self.ensure_user_settings_exist(user_id)?;
+
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+
+        let encrypted_key = match api_key {
+            Some(key) if !key.trim().is_empty() => Some(encrypt(key.trim())?),
+            _ => None,
+        };
+
+        diesel::update(user_settings::table.filter(user_settings::user_id.eq(user_id)))
+            .set((
+                user_settings::encrypted_openrouter_api_key.eq(encrypted_key),
+                user_settings::llm_model.eq(model.map(|m| m.trim().to_string()).filter(|m| !m.is_empty())),
+            ))
+            .execute(&mut conn)?;
+
+        Ok(())
+    }
+
+    pub fn update_send_delay(&self, user_id: i32, seconds: i32) -> Result<(), DieselError> {
+        use crate::schema::user_settings;
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+
+        // Ensure user settings exist
+        self.ensure_user_settings_exist(user_id)?;
+
+        diesel::update(user_settings::table.filter(user_settings::user_id.eq(user_id)))
+            .set(user_settings::send_delay_seconds.eq(seconds))
+            .execute(&mut conn)?;
+        Ok(())
+    }
+
+    /// Replaces the away mode settings, the message is encrypted here.
+    pub fn update_away_mode(&self, user_id: i32, away_mode: &AwayModeSettings) -> Result<(), DieselError> {
+        use crate::schema::user_settings;
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+
+        // Ensure user settings exist
+        self.ensure_user_settings_exist(user_id)?;
+
+        let mut away_mode = away_mode.clone();
+        if let Some(message) = away_mode.encrypted_away_message.take() {
+            away_mode.encrypted_away_message = Some(crate::utils::encryption::encrypt(&message).map_err(|e| {
+                tracing::error!("Failed to encrypt away message: {:?}", e);
+                DieselError::RollbackTransaction
+            })?);
+        }
+        diesel::update(user_settings::table.filter(user_settings::user_id.eq(user_id)))
+            .set(&away_mode)
+            .execute(&mut conn)?;
+        Ok(())
+    }
+
+    /// Turns away mode on from now until `ends_at`, keeping the message and contact settings.
+    pub fn start_away_period(&self, user_id: i32, ends_at: Option<i32>) -> Result<(), DieselError> {
+        use crate::schema::user_settings;
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+
+        // Ensure user settings exist
+        self.ensure_user_settings_exist(user_id)?;
+
+        diesel::update(user_settings::table.filter(user_settings::user_id.eq(user_id)))
+            .set((
+                user_settings::away_enabled.eq(true),
+                user_settings::away_starts_at.eq(None::<i32>),
+                user_settings::away_ends_at.eq(ends_at),
+            ))
+            .execute(&mut conn)?;
+        Ok(())
+    }
+
+    /// Turns away mode on or off keeping the rest of its settings.
+    pub fn set_away_enabled(&self, user_id: i32, enabled: bool) -> Result<(), DieselError> {
+        use crate::schema::user_settings;
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+
+        // Ensure user settings exist
+        self.ensure_user_settings_exist(user_id)?;
+
+        diesel::update(user_settings::table.filter(user_settings::user_id.eq(user_id)))
+            .set(user_settings::away_enabled.eq(enabled))
+            .execute(&mut conn)?;
+        Ok(())
+    }
+
     pub fn get_twilio_credentials(&self, user_id: i32) -> Result<(String, String), Box<dyn Error>> {
         use crate::schema::user_settings;
         use crate::utils::encryption::decrypt;
@@ -0,0 +1,63 @@
+use diesel::prelude::*;
+use diesel::result::Error as DieselError;
+use crate::{
+    models::user_models::{UserMemory, NewUserMemory},
+    schema::user_memories,
+    utils::encryption::{encrypt, decrypt},
+};
+
+impl crate::repositories::user_repository::UserRepository {
+    pub fn add_user_memory(&self, user_id: i32, content: &str, category: &str, source: &str) -> Result<(), DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        let encrypted_content = encrypt(content).map_err(|e| {
+            tracing::error!("Failed to encrypt user memory: {:?}", e);
+            DieselError::RollbackTransaction
+        })?;
+
+        diesel::insert_into(user_memories::table)
+            .values(&NewUserMemory {
+                user_id,
+                encrypted_content,
+                category: category.to_string(),
+                source: source.to_string(),
+                created_at: chrono::Utc::now().timestamp() as i32,
+            })
+            .execute(&mut conn)?;
+        Ok(())
+    }
+
+    /// All memories of the user, newest first and decrypted.
+    pub fn get_user_memories(&self, user_id: i32) -> Result<Vec<UserMemory>, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        let memories = user_memories::table
+            .filter(user_memories::user_id.eq(user_id))
+            .order(user_memories::created_at.desc())
+            .select(UserMemory::as_select())
+            .load::<UserMemory>(&mut conn)?;
+
+        Ok(memories.into_iter()
+            .filter_map(|mut memory| match decrypt(&memory.encrypted_content) {
+                Ok(content) => {
+                    memory.encrypted_content = content;
+                    Some(memory)
+                }
+                Err(e) => {
+                    tracing::error!("Failed to decrypt user memory: {:?}", e);
+                    None
+                }
+            })
+            .collect())
+    }
+
+    /// Returns false if the user has no memory with that id.
+    pub fn delete_user_memory(&self, user_id: i32, memory_id: i32) -> Result<bool, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        let deleted = diesel::delete(
+            user_memories::table
+                .filter(user_memories::id.eq(memory_id))
+                .filter(user_memories::user_id.eq(user_id))
+        )
+        .execute(&mut conn)?;
+        Ok(deleted > 0)
+    }
+}
@@ -170,7 +170,9 @@ impl UserRepository {
             .execute(&mut self.pool.get().unwrap())
     }
 
-    pub fn delete_old_message_history(&self, user_id: i32, save_context_limit: i64) -> Result<usize, diesel::result::Error> {
+    /// Deletes messages older than the last `save_context_limit` user messages, but only ones
+    /// already folded into the conversation summary (created at or before `summarized_until`).
+    pub fn delete_old_message_history(&self, user_id: i32, save_context_limit: i64, summarized_until: i32) -> Result<usize, diesel::result::Error> {
         use crate::schema::message_history;
         use diesel::prelude::*;
         let mut conn = self.pool.get().expect("Failed to get DB connection");
@@ -197,7 +199,8 @@ impl UserRepository {
                     // Build delete query
                     let base_delete = diesel::delete(message_history::table)
                         .filter(message_history::user_id.eq(user_id))
-                        .filter(message_history::created_at.lt(timestamp));
+                        .filter(message_history::created_at.lt(timestamp))
+                        .filter(message_history::created_at.le(summarized_until));
 
                     base_delete.execute(conn)
                 },
@@ -207,14 +210,18 @@ impl UserRepository {
     }
 
  
+    /// Adds an email account, or updates the user's account with the same address
+    /// (a reconnect keeps its id, label and monitoring toggle). Returns the account id.
     pub fn set_imap_credentials(
         &self,
         user_id: i32,
+        label: Option<&str>,
         email: &str,
         password: &str,
         imap_server: Option<&str>,
         imap_port: Option<u16>,
-    ) -> Result<(), diesel::result::Error> {
+        smtp: &crate::utils::smtp::SmtpSettings,
+    ) -> Result<i32, diesel::result::Error> {
         use crate::schema::imap_connection;
         let mut conn = self.pool.get().expect("Failed to get DB connection");
 
@@ -222,76 +229,236 @@ impl UserRepository {
         let encrypted_password = encrypt(password)
             .map_err(|_| diesel::result::Error::RollbackTransaction)?;
 
+        // The SMTP login is only stored when it differs from the IMAP one
+        let separate_login = smtp.username != email || smtp.password != password;
+        let encrypted_smtp_password = if separate_login {
+            Some(encrypt(&smtp.password).map_err(|_| diesel::result::Error::RollbackTransaction)?)
+        } else {
+            None
+        };
+
         let current_time = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap()
             .as_secs() as i32;
 
-        // First, delete any existing connections for this user
-        diesel::delete(imap_connection::table)
-            .filter(imap_connection::user_id.eq(user_id))
-            .execute(&mut conn)?;
+        conn.immediate_transaction(|conn| {
+            let existing = imap_connection::table
+                .filter(imap_connection::user_id.eq(user_id))
+                .filter(imap_connection::description.eq(email))
+                .select(imap_connection::id)
+                .first::<Option<i32>>(conn)
+                .optional()?
+                .flatten();
+
+            if let Some(id) = existing {
+                diesel::update(imap_connection::table.filter(imap_connection::id.eq(id)))
+                    .set((
+                        imap_connection::method.eq(imap_server.unwrap_or("gmail")),
+                        imap_connection::encrypted_password.eq(&encrypted_password),
+                        imap_connection::status.eq("active"),
+                        imap_connection::last_update.eq(current_time),
+                        imap_connection::imap_server.eq(imap_server),
+                        imap_connection::imap_port.eq(imap_port.map(|p| p as i32)),
+                        imap_connection::smtp_server.eq(&smtp.server),
+                        imap_connection::smtp_port.eq(smtp.port as i32),
+                        imap_connection::smtp_security.eq(&smtp.security),
+                        imap_connection::smtp_username.eq(separate_login.then(|| smtp.username.clone())),
+                        imap_connection::encrypted_smtp_password.eq(&encrypted_smtp_password),
+                    ))
+                    .execute(conn)?;
+                if let Some(label) = label {
+                    diesel::update(imap_connection::table.filter(imap_connection::id.eq(id)))
+                        .set(imap_connection::label.eq(label))
+                        .execute(conn)?;
+                }
+                return Ok(id);
+            }
 
-        // Create new connection
-        let new_connection = NewImapConnection {
-            user_id,
-            method: imap_server.map(|s| s.to_string()).unwrap_or("gmail".to_string()),
-            encrypted_password,
-            status: "active".to_string(),
-            last_update: current_time,
-            created_on: current_time,
-            description: email.to_string(),
-            expires_in: 0,
-            imap_server: imap_server.map(|s| s.to_string()),
-            imap_port: imap_port.map(|p| p as i32),
-        };
+            // Create new connection
+            let new_connection = NewImapConnection {
+                user_id,
+                method: imap_server.map(|s| s.to_string()).unwrap_or("gmail".to_string()),
+                encrypted_password: encrypted_password.clone(),
+                status: "active".to_string(),
+                last_update: current_time,
+                created_on: current_time,
+                description: email.to_string(),
+                expires_in: 0,
+                imap_server: imap_server.map(|s| s.to_string()),
+                imap_port: imap_port.map(|p| p as i32),
+                smtp_server: Some(smtp.server.clone()),
+                smtp_port: Some(smtp.port as i32),
+                smtp_security: Some(smtp.security.clone()),
+                smtp_username: separate_login.then(|| smtp.username.clone()),
+                encrypted_smtp_password: encrypted_smtp_password.clone(),
+                label: label.map(|l| l.to_string()),
+                monitoring_enabled: true,
+            };
 
-        // Insert the new connection
-        diesel::insert_into(imap_connection::table)
-            .values(&new_connection)
-            .execute(&mut conn)?;
+            // Insert the new connection
+            diesel::insert_into(imap_connection::table)
+                .values(&new_connection)
+                .execute(conn)?;
 
-        Ok(())
+            imap_connection::table
+                .filter(imap_connection::user_id.eq(user_id))
+                .select(diesel::dsl::max(imap_connection::id))
+                .first::<Option<i32>>(conn)?
+                .ok_or(DieselError::NotFound)
+        })
     }
-    
 
-    pub fn get_imap_credentials(
+    /// The user's active email accounts, oldest first. Passwords stay encrypted.
+    pub fn get_imap_accounts(
         &self,
         user_id: i32,
-    ) -> Result<Option<(String, String, Option<String>, Option<i32>)>, diesel::result::Error> {
+    ) -> Result<Vec<crate::models::user_models::ImapConnection>, diesel::result::Error> {
+        use crate::schema::imap_connection;
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+
+        imap_connection::table
+            .filter(imap_connection::user_id.eq(user_id))
+            .filter(imap_connection::status.eq("active"))
+            .order(imap_connection::id.asc())
+            .load::<crate::models::user_models::ImapConnection>(&mut conn)
+    }
+
+    /// One of the user's email accounts with the password decrypted, the first one for None.
+    pub fn get_imap_account(
+        &self,
+        user_id: i32,
+        account_id: Option<i32>,
+    ) -> Result<Option<crate::models::user_models::ImapConnection>, diesel::result::Error> {
         use crate::schema::imap_connection;
         let mut conn = self.pool.get().expect("Failed to get DB connection");
 
-        // Get the active IMAP connection for the user
-        let imap_conn = imap_connection::table
+        let mut query = imap_connection::table
             .filter(imap_connection::user_id.eq(user_id))
             .filter(imap_connection::status.eq("active"))
+            .order(imap_connection::id.asc())
+            .into_boxed();
+        if let Some(account_id) = account_id {
+            query = query.filter(imap_connection::id.eq(account_id));
+        }
+        let imap_conn = query
             .first::<crate::models::user_models::ImapConnection>(&mut conn)
             .optional()?;
 
-        if let Some(conn) = imap_conn {
-            // Decrypt the password
-            match decrypt(&conn.encrypted_password) {
-                Ok(decrypted_password) => Ok(Some((conn.description, decrypted_password, conn.imap_server, conn.imap_port))),
-                Err(_) => Err(diesel::result::Error::RollbackTransaction)
+        match imap_conn {
+            Some(mut imap_conn) => {
+                imap_conn.encrypted_password = decrypt(&imap_conn.encrypted_password)
+                    .map_err(|_| diesel::result::Error::RollbackTransaction)?;
+                Ok(Some(imap_conn))
             }
-        } else {
-            Ok(None)
+            None => Ok(None),
         }
     }
 
+    /// Login of the user's first email account, also used to check whether any is connected.
+    pub fn get_imap_credentials(
+        &self,
+        user_id: i32,
+    ) -> Result<Option<(String, String, Option<String>, Option<i32>)>, diesel::result::Error> {
+        Ok(self
+            .get_imap_account(user_id, None)?
+            .map(|conn| (conn.description, conn.encrypted_password, conn.imap_server, conn.imap_port)))
+    }
+
+    /// How to send mail from one of the user's accounts, the first one for None. Connections
+    /// made before SMTP was configured get the settings derived from the IMAP server.
+    pub fn get_smtp_settings(
+        &self,
+        user_id: i32,
+        account_id: Option<i32>,
+    ) -> Result<Option<crate::utils::smtp::SmtpSettings>, diesel::result::Error> {
+        use crate::utils::smtp::{default_port, SmtpSettings};
+
+        let Some(imap_conn) = self.get_imap_account(user_id, account_id)? else {
+            return Ok(None);
+        };
+        let password = imap_conn.encrypted_password;
+        let Some(server) = imap_conn.smtp_server else {
+            return Ok(Some(SmtpSettings::derived(imap_conn.imap_server.as_deref(), &imap_conn.description, &password)));
+        };
+        let security = imap_conn.smtp_security.unwrap_or_else(|| "starttls".to_string());
+        let smtp_password = match imap_conn.encrypted_smtp_password {
+            Some(encrypted) => decrypt(&encrypted).map_err(|_| diesel::result::Error::RollbackTransaction)?,
+            None => password,
+        };
+        Ok(Some(SmtpSettings {
+            server,
+            port: imap_conn.smtp_port.map(|p| p as u16).unwrap_or_else(|| default_port(&security)),
+            security,
+            username: imap_conn.smtp_username.unwrap_or(imap_conn.description),
+            password: smtp_password,
+        }))
+    }
+
+    /// Renames an account and turns its monitoring on or off. False if there is no such account.
+    pub fn update_imap_account(
+        &self,
+        user_id: i32,
+        account_id: i32,
+        label: Option<&str>,
+        monitoring_enabled: bool,
+    ) -> Result<bool, diesel::result::Error> {
+        use crate::schema::imap_connection;
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+
+        let updated = diesel::update(
+            imap_connection::table
+                .filter(imap_connection::id.eq(account_id))
+                .filter(imap_connection::user_id.eq(user_id))
+        )
+        .set((
+            imap_connection::label.eq(label),
+            imap_connection::monitoring_enabled.eq(monitoring_enabled),
+        ))
+        .execute(&mut conn)?;
+        Ok(updated > 0)
+    }
+
+    /// Deletes one email account with its processed emails and priority senders, or all of them for None.
     pub fn delete_imap_credentials(
         &self,
         user_id: i32,
+        account_id: Option<i32>,
     ) -> Result<(), diesel::result::Error> {
-        use crate::schema::imap_connection;
+        use crate::schema::{imap_connection, processed_emails};
         let connection = &mut self.pool.get().unwrap();
-        
-        diesel::delete(imap_connection::table
-            .filter(imap_connection::user_id.eq(user_id)))
-            .execute(connection)?;
-        
-        Ok(())
+
+        connection.transaction(|connection| {
+            match account_id {
+                Some(account_id) => {
+                    diesel::delete(processed_emails::table
+                        .filter(processed_emails::user_id.eq(user_id))
+                        .filter(processed_emails::imap_connection_id.eq(account_id)))
+                        .execute(connection)?;
+                    diesel::delete(priority_senders::table
+                        .filter(priority_senders::user_id.eq(user_id))
+                        .filter(priority_senders::imap_connection_id.eq(account_id)))
+                        .execute(connection)?;
+                    diesel::delete(imap_connection::table
+                        .filter(imap_connection::user_id.eq(user_id))
+                        .filter(imap_connection::id.eq(account_id)))
+                        .execute(connection)?;
+                }
+                None => {
+                    diesel::delete(processed_emails::table
+                        .filter(processed_emails::user_id.eq(user_id)))
+                        .execute(connection)?;
+                    diesel::delete(priority_senders::table
+                        .filter(priority_senders::user_id.eq(user_id))
+                        .filter(priority_senders::imap_connection_id.is_not_null()))
+                        .execute(connection)?;
+                    diesel::delete(imap_connection::table
+                        .filter(imap_connection::user_id.eq(user_id)))
+                        .execute(connection)?;
+                }
+            }
+            Ok(())
+        })
     }
 
     // log the usage. activity_type either 'call' or 'sms', or the new 'notification'
@@ -701,16 +868,18 @@ impl UserRepository {
         Ok(())
     }
 
-    pub fn get_active_imap_connection_users(&self) -> Result<Vec<i32>, DieselError> {
+    /// (account id, user id) of every active email account with monitoring on.
+    pub fn get_monitored_imap_accounts(&self) -> Result<Vec<(i32, i32)>, DieselError> {
         use crate::schema::imap_connection;
         let mut conn = self.pool.get().expect("Failed to get DB connection");
 
-        let user_ids = imap_connection::table
+        let accounts = imap_connection::table
             .filter(imap_connection::status.eq("active"))
-            .select(imap_connection::user_id)
-            .load::<i32>(&mut conn)?;
+            .filter(imap_connection::monitoring_enabled.eq(true))
+            .select((imap_connection::id, imap_connection::user_id))
+            .load::<(Option<i32>, i32)>(&mut conn)?;
 
-        Ok(user_ids)
+        Ok(accounts.into_iter().filter_map(|(id, user_id)| Some((id?, user_id))).collect())
     }
 
     pub fn has_active_google_tasks(&self, user_id: i32) -> Result<bool, DieselError> {
@@ -1183,13 +1352,13 @@ impl UserRepository {
     }
 
 
-    // Mark an email as processed
-    pub fn mark_email_as_processed(&self, user_id: i32, email_uid: &str) -> Result<(), DieselError> {
+    // Mark an email of one of the user's accounts as processed
+    pub fn mark_email_as_processed(&self, user_id: i32, account_id: i32, email_uid: &str) -> Result<(), DieselError> {
         use crate::schema::processed_emails;
         let mut conn = self.pool.get().expect("Failed to get DB connection");
 
         // First check if the email is already processed
-        let already_processed = self.is_email_processed(user_id, email_uid)?;
+        let already_processed = self.is_email_processed(user_id, account_id, email_uid)?;
         if already_processed {
             tracing::debug!("Email {} for user {} is already marked as processed", email_uid, user_id);
             return Ok(());
@@ -1204,6 +1373,7 @@ impl UserRepository {
             user_id,
             email_uid: email_uid.to_string(),
             processed_at: current_time,
+            imap_connection_id: Some(account_id),
         };
 
         match diesel::insert_into(processed_emails::table)
@@ -1222,12 +1392,13 @@ impl UserRepository {
     }
 
     // Check if an email is processed
-    pub fn is_email_processed(&self, user_id: i32, email_uid: &str) -> Result<bool, DieselError> {
+    pub fn is_email_processed(&self, user_id: i32, account_id: i32, email_uid: &str) -> Result<bool, DieselError> {
         use crate::schema::processed_emails;
         let mut conn = self.pool.get().expect("Failed to get DB connection");
 
         let processed = processed_emails::table
             .filter(processed_emails::user_id.eq(user_id))
+            .filter(processed_emails::imap_connection_id.eq(account_id))
             .filter(processed_emails::email_uid.eq(email_uid))
             .first::<crate::models::user_models::ProcessedEmail>(&mut conn)
             .optional()?;
@@ -1235,13 +1406,14 @@ impl UserRepository {
         Ok(processed.is_some())
     }
 
-    // Get all processed emails for a user
-    pub fn get_processed_emails(&self, user_id: i32) -> Result<Vec<crate::models::user_models::ProcessedEmail>, DieselError> {
+    // Get all processed emails of one of the user's accounts
+    pub fn get_processed_emails(&self, user_id: i32, account_id: i32) -> Result<Vec<crate::models::user_models::ProcessedEmail>, DieselError> {
         use crate::schema::processed_emails;
         let mut conn = self.pool.get().expect("Failed to get DB connection");
 
         let processed = processed_emails::table
             .filter(processed_emails::user_id.eq(user_id))
+            .filter(processed_emails::imap_connection_id.eq(account_id))
             .order_by(processed_emails::processed_at.desc())
             .load::<crate::models::user_models::ProcessedEmail>(&mut conn)?;
 
@@ -1249,12 +1421,13 @@ impl UserRepository {
     }
 
     // Delete a single processed email record
-    pub fn delete_processed_email(&self, user_id: i32, email_uid: &str) -> Result<(), DieselError> {
+    pub fn delete_processed_email(&self, user_id: i32, account_id: i32, email_uid: &str) -> Result<(), DieselError> {
         use crate::schema::processed_emails;
         let mut conn = self.pool.get().expect("Failed to get DB connection");
 
         diesel::delete(processed_emails::table)
             .filter(processed_emails::user_id.eq(user_id))
+            .filter(processed_emails::imap_connection_id.eq(account_id))
             .filter(processed_emails::email_uid.eq(email_uid))
             .execute(&mut conn)?;
 
@@ -0,0 +1,105 @@
+use diesel::prelude::*;
+use diesel::result::Error as DieselError;
+use crate::{
+    models::user_models::{ProcessedWebhookEvent, NewProcessedWebhookEvent},
+    schema::processed_webhook_events,
+};
+
+// A claim older than this is from a request that died before finishing, let the next delivery take it
+const STALE_CLAIM_SECONDS: i32 = 300;
+
+pub enum WebhookClaim {
+    // First delivery, go ahead and process it
+    New,
+    // Another delivery of the same event is being processed right now
+    InProgress,
+    // Already processed, answer with what we answered the first time
+    Done { response_status: i32, response_body: String },
+}
+
+impl crate::repositories::user_repository::UserRepository {
+    /// Atomically records that we're handling `event_id` from `provider`.
+    /// Must be called before any side effect of the webhook.
+    pub fn claim_webhook_event(&self, provider: &str, event_id: &str) -> Result<WebhookClaim, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        let now = chrono::Utc::now().timestamp() as i32;
+
+        conn.immediate_transaction(|conn| {
+            let inserted = diesel::insert_into(processed_webhook_events::table)
+                .values(&NewProcessedWebhookEvent {
+                    provider: provider.to_string(),
+                    event_id: event_id.to_string(),
+                    status: "processing".to_string(),
+                    created_at: now,
+                    updated_at: now,
+                })
+                .on_conflict_do_nothing()
+                .execute(conn)?;
+            if inserted > 0 {
+                return Ok(WebhookClaim::New);
+            }
+
+            let existing = processed_webhook_events::table
+                .filter(processed_webhook_events::provider.eq(provider))
+                .filter(processed_webhook_events::event_id.eq(event_id))
+                .select(ProcessedWebhookEvent::as_select())
+                .first::<ProcessedWebhookEvent>(conn)?;
+
+            if existing.status == "done" {
+                return Ok(WebhookClaim::Done {
+                    response_status: existing.response_status.unwrap_or(200),
+                    response_body: existing.response_body.unwrap_or_default(),
+                });
+            }
+
+            if existing.updated_at < now - STALE_CLAIM_SECONDS {
+                diesel::update(processed_webhook_events::table.filter(processed_webhook_events::id.eq(existing.id)))
+                    .set(processed_webhook_events::updated_at.eq(now))
+                    .execute(conn)?;
+                return Ok(WebhookClaim::New);
+            }
+
+            Ok(WebhookClaim::InProgress)
+        })
+    }
+
+    /// Stores the response so redeliveries get the same answer.
+    pub fn complete_webhook_event(&self, provider: &str, event_id: &str, response_status: i32, response_body: &str) -> Result<(), DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        diesel::update(
+            processed_webhook_events::table
+                .filter(processed_webhook_events::provider.eq(provider))
+                .filter(processed_webhook_events::event_id.eq(event_id))
+        )
+        .set((
+            processed_webhook_events::status.eq("done"),
+            processed_webhook_events::response_status.eq(Some(response_status)),
+            processed_webhook_events::response_body.eq(Some(response_body)),
+            processed_webhook_events::updated_at.eq(chrono::Utc::now().timestamp() as i32),
+        ))
+        .execute(&mut conn)?;
+        Ok(())
+    }
+
+    /// Drops the claim after a transient failure so the provider's retry gets processed.
+    pub fn release_webhook_event(&self, provider: &str, event_id: &str) -> Result<(), DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        diesel::delete(
+            processed_webhook_events::table
+                .filter(processed_webhook_events::provider.eq(provider))
+                .filter(processed_webhook_events::event_id.eq(event_id))
+                .filter(processed_webhook_events::status.eq("processing"))
+        )
+        .execute(&mut conn)?;
+        Ok(())
+    }
+
+    pub fn delete_old_webhook_events(&self, older_than: i32) -> Result<usize, DieselError> {
+        let mut conn = self.pool.get().expect("Failed to get DB connection");
+        diesel::delete(
+            processed_webhook_events::table
+                .filter(processed_webhook_events::created_at.lt(older_than))
+        )
+        .execute(&mut conn)
+    }
+}
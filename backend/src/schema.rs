@@ -1,5 +1,36 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    audio_transcripts (id) {
+        id -> Nullable<Integer>,
+        user_id -> Integer,
+        source_id -> Text,
+        encrypted_transcript -> Text,
+        created_at -> Integer,
+    }
+}
+
+diesel::table! {
+    away_replies (id) {
+        id -> Nullable<Integer>,
+        user_id -> Integer,
+        platform -> Text,
+        contact_key -> Text,
+        replied_at -> Integer,
+    }
+}
+
+diesel::table! {
+    bridge_status_history (id) {
+        id -> Nullable<Integer>,
+        user_id -> Integer,
+        bridge_type -> Text,
+        status -> Text,
+        detail -> Nullable<Text>,
+        created_at -> Integer,
+    }
+}
+
 diesel::table! {
     bridges (id) {
         id -> Nullable<Integer>,
@@ -21,6 +52,28 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    contacts (id) {
+        id -> Nullable<Integer>,
+        user_id -> Integer,
+        encrypted_name -> Text,
+        encrypted_phone -> Nullable<Text>,
+        encrypted_email -> Nullable<Text>,
+        source -> Text,
+        created_at -> Integer,
+    }
+}
+
+diesel::table! {
+    conversation_summaries (id) {
+        id -> Nullable<Integer>,
+        user_id -> Integer,
+        encrypted_summary -> Text,
+        summarized_until -> Integer,
+        updated_at -> Integer,
+    }
+}
+
 diesel::table! {
     conversations (id) {
         id -> Integer,
@@ -74,6 +127,16 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    group_policies (id) {
+        id -> Nullable<Integer>,
+        user_id -> Integer,
+        service_type -> Text,
+        chat_name -> Text,
+        policy -> Text,
+    }
+}
+
 diesel::table! {
     imap_connection (id) {
         id -> Nullable<Integer>,
@@ -87,6 +150,27 @@ diesel::table! {
         expires_in -> Integer,
         imap_server -> Nullable<Text>,
         imap_port -> Nullable<Integer>,
+        smtp_server -> Nullable<Text>,
+        smtp_port -> Nullable<Integer>,
+        smtp_security -> Nullable<Text>,
+        smtp_username -> Nullable<Text>,
+        encrypted_smtp_password -> Nullable<Text>,
+        label -> Nullable<Text>,
+        monitoring_enabled -> Bool,
+    }
+}
+
+diesel::table! {
+    indexed_messages (id) {
+        id -> Nullable<Integer>,
+        user_id -> Integer,
+        platform -> Text,
+        message_key -> Text,
+        encrypted_room_name -> Text,
+        encrypted_sender -> Text,
+        encrypted_content -> Text,
+        message_type -> Text,
+        sent_at -> Integer,
     }
 }
 
@@ -113,6 +197,51 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    message_index_coverage (id) {
+        id -> Nullable<Integer>,
+        user_id -> Integer,
+        started_at -> Integer,
+        covered_until -> Integer,
+    }
+}
+
+diesel::table! {
+    notification_sources (id) {
+        id -> Nullable<Integer>,
+        user_id -> Integer,
+        source_type -> Text,
+        encrypted_payload -> Text,
+        created_at -> Integer,
+    }
+}
+
+diesel::table! {
+    outgoing_messages (id) {
+        id -> Nullable<Integer>,
+        user_id -> Integer,
+        action_type -> Text,
+        encrypted_payload -> Text,
+        status -> Text,
+        send_at -> Integer,
+        created_at -> Integer,
+        updated_at -> Integer,
+        claimed_at -> Nullable<Integer>,
+    }
+}
+
+diesel::table! {
+    pending_actions (id) {
+        id -> Nullable<Integer>,
+        user_id -> Integer,
+        number -> Integer,
+        action_type -> Text,
+        encrypted_payload -> Text,
+        expires_at -> Integer,
+        created_at -> Integer,
+    }
+}
+
 diesel::table! {
     priority_senders (id) {
         id -> Nullable<Integer>,
@@ -120,6 +249,7 @@ diesel::table! {
         sender -> Text,
         service_type -> Text,
         noti_type -> Nullable<Text>,
+        imap_connection_id -> Nullable<Integer>,
     }
 }
 
@@ -129,30 +259,56 @@ diesel::table! {
         user_id -> Integer,
         email_uid -> Text,
         processed_at -> Integer,
+        imap_connection_id -> Nullable<Integer>,
     }
 }
 
 diesel::table! {
-    task_notifications (id) {
+    processed_webhook_events (id) {
+        id -> Nullable<Integer>,
+        provider -> Text,
+        event_id -> Text,
+        status -> Text,
+        response_status -> Nullable<Integer>,
+        response_body -> Nullable<Text>,
+        created_at -> Integer,
+        updated_at -> Integer,
+    }
+}
+
+diesel::table! {
+    scheduled_sends (id) {
         id -> Nullable<Integer>,
         user_id -> Integer,
-        task_id -> Text,
-        notified_at -> Integer,
+        action_type -> Text,
+        encrypted_payload -> Text,
+        status -> Text,
+        send_at -> Integer,
+        created_at -> Integer,
+        updated_at -> Integer,
     }
 }
 
 diesel::table! {
-    temp_variables (id) {
-        id -> Integer,
+    sms_jobs (id) {
+        id -> Nullable<Integer>,
+        user_id -> Nullable<Integer>,
+        encrypted_payload -> Text,
+        status -> Text,
+        attempts -> Integer,
+        next_attempt_at -> Integer,
+        last_error -> Nullable<Text>,
+        created_at -> Integer,
+        updated_at -> Integer,
+    }
+}
+
+diesel::table! {
+    task_notifications (id) {
+        id -> Nullable<Integer>,
         user_id -> Integer,
-        confirm_send_event_type -> Text,
-        confirm_send_event_recipient -> Nullable<Text>,
-        confirm_send_event_subject -> Nullable<Text>,
-        confirm_send_event_content -> Nullable<Text>,
-        confirm_send_event_start_time -> Nullable<Text>,
-        confirm_send_event_duration -> Nullable<Text>,
-        confirm_send_event_id -> Nullable<Text>,
-        confirm_send_event_image_url -> Nullable<Text>,
+        task_id -> Text,
+        notified_at -> Integer,
     }
 }
 
@@ -203,6 +359,17 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    user_memories (id) {
+        id -> Nullable<Integer>,
+        user_id -> Integer,
+        encrypted_content -> Text,
+        category -> Text,
+        source -> Text,
+        created_at -> Integer,
+    }
+}
+
 diesel::table! {
     user_settings (id) {
         id -> Nullable<Integer>,
@@ -232,6 +399,17 @@ diesel::table! {
         encrypted_textbee_api_key -> Nullable<Text>,
         elevenlabs_phone_number_id -> Nullable<Text>,
         proactive_agent_on -> Bool,
+        llm_model -> Nullable<Text>,
+        proactive_paused_until -> Nullable<Integer>,
+        send_delay_seconds -> Integer,
+        away_enabled -> Bool,
+        encrypted_away_message -> Nullable<Text>,
+        away_contact_mode -> Text,
+        away_contacts -> Nullable<Text>,
+        away_reply_interval_hours -> Integer,
+        away_priority_only -> Bool,
+        away_starts_at -> Nullable<Integer>,
+        away_ends_at -> Nullable<Integer>,
     }
 }
 
@@ -262,7 +440,6 @@ diesel::table! {
         discount -> Bool,
         discount_tier -> Nullable<Text>,
         free_reply -> Bool,
-        confirm_send_event -> Nullable<Text>,
         waiting_checks_count -> Integer,
         next_billing_date_timestamp -> Nullable<Integer>,
         phone_number_country -> Nullable<Text>,
@@ -279,36 +456,63 @@ diesel::table! {
     }
 }
 
+diesel::joinable!(audio_transcripts -> users (user_id));
+diesel::joinable!(away_replies -> users (user_id));
+diesel::joinable!(bridge_status_history -> users (user_id));
 diesel::joinable!(bridges -> users (user_id));
 diesel::joinable!(calendar_notifications -> users (user_id));
+diesel::joinable!(contacts -> users (user_id));
+diesel::joinable!(conversation_summaries -> users (user_id));
 diesel::joinable!(conversations -> users (user_id));
+diesel::joinable!(group_policies -> users (user_id));
 diesel::joinable!(imap_connection -> users (user_id));
+diesel::joinable!(indexed_messages -> users (user_id));
 diesel::joinable!(keywords -> users (user_id));
 diesel::joinable!(message_history -> users (user_id));
+diesel::joinable!(message_index_coverage -> users (user_id));
+diesel::joinable!(notification_sources -> users (user_id));
+diesel::joinable!(outgoing_messages -> users (user_id));
+diesel::joinable!(pending_actions -> users (user_id));
 diesel::joinable!(priority_senders -> users (user_id));
 diesel::joinable!(processed_emails -> users (user_id));
-diesel::joinable!(temp_variables -> users (user_id));
+diesel::joinable!(scheduled_sends -> users (user_id));
+diesel::joinable!(sms_jobs -> users (user_id));
 diesel::joinable!(user_info -> users (user_id));
+diesel::joinable!(user_memories -> users (user_id));
 diesel::joinable!(user_settings -> users (user_id));
 diesel::joinable!(waiting_checks -> users (user_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    audio_transcripts,
+    away_replies,
+    bridge_status_history,
     bridges,
     calendar_notifications,
+    contacts,
+    conversation_summaries,
     conversations,
     email_judgments,
     google_calendar,
     google_tasks,
+    group_policies,
     imap_connection,
+    indexed_messages,
     keywords,
     message_history,
+    message_index_coverage,
+    notification_sources,
+    outgoing_messages,
+    pending_actions,
     priority_senders,
     processed_emails,
+    processed_webhook_events,
+    scheduled_sends,
+    sms_jobs,
     task_notifications,
-    temp_variables,
     uber,
     usage_logs,
     user_info,
+    user_memories,
     user_settings,
     users,
     waiting_checks,
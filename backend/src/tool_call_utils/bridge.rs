@@ -11,8 +11,8 @@ pub fn get_search_chat_contacts_tool() -> openai_api_rs::v1::chat_completion::To
         "platform".to_string(),
         Box::new(types::JSONSchemaDefine {
             schema_type: Some(types::JSONSchemaType::String),
-            description: Some("The platform to fetch messages from. Must be 'telegram' or 'whatsapp'.".to_string()),
-            enum_values: Some(vec!["telegram".to_string(), "whatsapp".to_string()]),
+            description: Some("The platform to fetch messages from.".to_string()),
+            enum_values: Some(crate::utils::bridge_service::service_names()),
             ..Default::default()
         }),
     );
@@ -50,8 +50,8 @@ pub fn get_fetch_chat_messages_tool() -> openai_api_rs::v1::chat_completion::Too
         "platform".to_string(),
         Box::new(types::JSONSchemaDefine {
             schema_type: Some(types::JSONSchemaType::String),
-            description: Some("The platform to fetch messages from. Must be 'telegram' or 'whatsapp'.".to_string()),
-            enum_values: Some(vec!["telegram".to_string(), "whatsapp".to_string()]),
+            description: Some("The platform to fetch messages from.".to_string()),
+            enum_values: Some(crate::utils::bridge_service::service_names()),
             ..Default::default()
         }),
     );
@@ -98,8 +98,8 @@ pub fn get_fetch_recent_messages_tool() -> openai_api_rs::v1::chat_completion::T
         "platform".to_string(),
         Box::new(types::JSONSchemaDefine {
             schema_type: Some(types::JSONSchemaType::String),
-            description: Some("The platform to fetch recent messages from. Must be 'telegram' or 'whatsapp'.".to_string()),
-            enum_values: Some(vec!["telegram".to_string(), "whatsapp".to_string()]),
+            description: Some("The platform to fetch recent messages from.".to_string()),
+            enum_values: Some(crate::utils::bridge_service::service_names()),
             ..Default::default()
         }),
     );
@@ -129,6 +129,55 @@ pub fn get_fetch_recent_messages_tool() -> openai_api_rs::v1::chat_completion::T
     }
 }
 
+pub fn get_search_messages_tool() -> openai_api_rs::v1::chat_completion::Tool {
+    use openai_api_rs::v1::{chat_completion, types};
+    use std::collections::HashMap;
+    let mut platforms = crate::utils::bridge_service::service_names();
+    platforms.push("email".to_string());
+    let mut properties = HashMap::new();
+    properties.insert(
+        "query".to_string(),
+        Box::new(types::JSONSchemaDefine {
+            schema_type: Some(types::JSONSchemaType::String),
+            description: Some("Words the message should contain, including the sender or chat name if known (e.g., 'address Tom'). Leave out filler words.".to_string()),
+            ..Default::default()
+        }),
+    );
+    properties.insert(
+        "platform".to_string(),
+        Box::new(types::JSONSchemaDefine {
+            schema_type: Some(types::JSONSchemaType::String),
+            description: Some("Optional: only search this platform. Searches all chats and emails if left out.".to_string()),
+            enum_values: Some(platforms),
+            ..Default::default()
+        }),
+    );
+    properties.insert(
+        "start".to_string(),
+        Box::new(types::JSONSchemaDefine {
+            schema_type: Some(types::JSONSchemaType::String),
+            description: Some("Optional: only messages after this time, RFC3339 in UTC (e.g., '2024-03-16T00:00:00Z'). Use it for things like 'last week'.".to_string()),
+            ..Default::default()
+        }),
+    );
+    chat_completion::Tool {
+        r#type: chat_completion::ToolType::Function,
+        function: types::Function {
+            name: String::from("search_messages"),
+            description: Some(String::from(
+                "Searches the user's received chat messages on all connected platforms and their emails by content. \
+                Use this when the user asks for something someone sent earlier, e.g. 'what was the address Tom sent me last week?'. \
+                Covers the last 90 days of messages received while the service was connected."
+            )),
+            parameters: types::FunctionParameters {
+                schema_type: types::JSONSchemaType::Object,
+                properties: Some(properties),
+                required: Some(vec![String::from("query")]),
+            },
+        },
+    }
+}
+
 pub fn get_send_chat_message_tool() -> openai_api_rs::v1::chat_completion::Tool {
     use openai_api_rs::v1::{chat_completion, types};
     use std::collections::HashMap;
@@ -137,8 +186,8 @@ pub fn get_send_chat_message_tool() -> openai_api_rs::v1::chat_completion::Tool
         "platform".to_string(),
         Box::new(types::JSONSchemaDefine {
             schema_type: Some(types::JSONSchemaType::String),
-            description: Some("The platform to fetch recent messages from. Must be 'telegram' or 'whatsapp'.".to_string()),
-            enum_values: Some(vec!["telegram".to_string(), "whatsapp".to_string()]),
+            description: Some("The platform to fetch recent messages from.".to_string()),
+            enum_values: Some(crate::utils::bridge_service::service_names()),
             ..Default::default()
         }),
     );
@@ -154,7 +203,15 @@ pub fn get_send_chat_message_tool() -> openai_api_rs::v1::chat_completion::Tool
         "message".to_string(),
         Box::new(types::JSONSchemaDefine {
             schema_type: Some(types::JSONSchemaType::String),
-            description: Some("The message content to send.".to_string()),
+            description: Some("The message content to send. Used as the caption when an attachment is sent, can be empty then.".to_string()),
+            ..Default::default()
+        }),
+    );
+    properties.insert(
+        "send_attachment".to_string(),
+        Box::new(types::JSONSchemaDefine {
+            schema_type: Some(types::JSONSchemaType::Boolean),
+            description: Some("Whether to send the photo, audio or file the user attached to their message along with it, e.g. 'send this to Mom'. Defaults to true when there is an attachment.".to_string()),
             ..Default::default()
         }),
     );
@@ -164,7 +221,8 @@ pub fn get_send_chat_message_tool() -> openai_api_rs::v1::chat_completion::Tool
             name: String::from("send_chat_message"),
             description: Some(String::from(
                 "Sends a message to a specific chat on the specified platform. \
-                Use this when the user asks to send a message to a contact or group on Telegram or WhatsApp. \
+                Use this when the user asks to send a message to a contact or group on Telegram or WhatsApp, \
+                or to send on a photo, voice message or file they attached. \
                 This tool will fuzzy search for the chat_name, confirm with the user, and require their confirmation before sending."
             )),
             parameters: types::FunctionParameters {
@@ -195,21 +253,22 @@ use crate::models::user_models::User;
 use crate::api::twilio_sms::TwilioResponse;
 
 #[derive(Deserialize)]
-struct SendChatMessageArgs {
-    platform: String,
-    chat_name: String,
-    message: String,
+pub struct SendChatMessageArgs {
+    pub platform: String,
+    pub chat_name: String,
+    pub message: String,
+    pub send_attachment: Option<bool>,
 }
 
 pub async fn handle_send_chat_message(
     state: &Arc<AppState>,
     user_id: i32,
-    args: &str,
+    args: SendChatMessageArgs,
     user: &User,
-    image_url: Option<&str>
+    media_url: Option<&str>
 ) -> Result<(StatusCode, [(HeaderName, &'static str); 1], Json<TwilioResponse>), Box<dyn std::error::Error>> {
-    let args: SendChatMessageArgs = serde_json::from_str(args)?;
-   
+    let media_url = media_url.filter(|_| args.send_attachment.unwrap_or(true));
+
     // Get user settings to check confirmation preference
     let user_settings = state.user_core.get_user_settings(user_id)?;
     // First search for the chat room
@@ -266,13 +325,39 @@ pub async fn handle_send_chat_message(
     // If confirmation is not required, send the message directly
     if !user_settings.require_confirmation {
         let message = args.message.clone();
+        // Still goes through the outbox so UNDO works without confirmations too
+        let action = crate::tool_call_utils::confirm::ActionPayload::ChatMessage {
+            platform: args.platform.clone(),
+            recipient: exact_name.clone(),
+            room_id: None,
+            message: message.clone(),
+            media_url: media_url.map(|url| url.to_string()),
+        };
+        if let Some(queued_msg) = crate::utils::outbox::queue_if_delayed(state, user, &action) {
+            if let Err(e) = crate::api::twilio_utils::send_conversation_message(
+                state,
+                &queued_msg,
+                None,
+                user,
+            ).await {
+                eprintln!("Failed to send queued message notice: {}", e);
+            }
+            return Ok((
+                StatusCode::OK,
+                [(axum::http::header::CONTENT_TYPE, "application/json")],
+                Json(TwilioResponse {
+                    message: queued_msg,
+                })
+            ));
+        }
         match crate::utils::bridge::send_bridge_message(
             &args.platform,
             state,
             user_id,
             &exact_name,
+            None,
             &message,
-            image_url.map(|url| url.to_string()),
+            media_url.map(|url| url.to_string()),
         ).await {
             Ok(_) => {
                 let capitalized_platform = args.platform.chars().next().map(|c| c.to_uppercase().collect::<String>()).unwrap_or_default() + &args.platform[1..];
@@ -314,102 +399,41 @@ pub async fn handle_send_chat_message(
             }
         }
     }
-    // If confirmation is required, continue
-    // Set the temporary variable for WhatsApp message
-    if let Err(e) = state.user_core.set_temp_variable(
-        user_id,
-        Some(&args.platform),
-        Some(&exact_name),
-        None,
-        Some(&args.message),
-        None,
-        None,
-        None,
-        image_url,
-    ) {
-        tracing::error!("Failed to set temporary variable: {}", e);
-        let capitalized_platform = args.platform.chars().next().map(|c| c.to_uppercase().collect::<String>()).unwrap_or_default() + &args.platform[1..];
-        if let Err(e) = crate::api::twilio_utils::send_conversation_message(
-            state,
-            format!("Failed to prepare {} message sending. (contact rasmus@ahtava.com)", capitalized_platform).as_str(),
-            None,
-            user,
-        ).await {
-            tracing::error!("Failed to send error message: {}", e);
-        }
-        return Ok((
-            StatusCode::OK,
-            [(axum::http::header::CONTENT_TYPE, "application/json")],
-            Json(TwilioResponse {
-                message: "Failed to prepare message sending".to_string(),
-            })
-        ));
-    }
-    tracing::info!("Successfully set temporary variable");
-    // Format the confirmation message with the found contact name and image if present
-    let capitalized_platform = args.platform.chars().next().map(|c| c.to_uppercase().collect::<String>()).unwrap_or_default() + &args.platform[1..];
-    let confirmation_msg = if image_url.is_some() {
-        format!(
-            "Send {} to '{}' with the above image and a caption '{}' (reply 'Yes' to confirm, otherwise it will be discarded)",
-            capitalized_platform, exact_name, args.message
-        )
-    } else {
-        format!(
-            "Send {} to '{}' with content: '{}' (reply 'Yes' to confirm, otherwise it will be discarded)",
-            capitalized_platform, exact_name, args.message
-        )
+    // If confirmation is required, store the message until the user answers
+    let action = crate::tool_call_utils::confirm::ActionPayload::ChatMessage {
+        platform: args.platform.clone(),
+        recipient: exact_name,
+        room_id: None,
+        message: args.message,
+        media_url: media_url.map(|url| url.to_string()),
     };
-    // Send the confirmation message
-    match crate::api::twilio_utils::send_conversation_message(
-        state,
-        &confirmation_msg,
-        None,
-        user,
-    ).await {
-        Ok(_) => {
-            // Deduct credits for the confirmation message
-            if let Err(e) = crate::utils::usage::deduct_user_credits(state, user_id, "message", None) {
-                tracing::error!("Failed to deduct user credits: {}", e);
-            }
-            Ok((
-                StatusCode::OK,
-                [(axum::http::header::CONTENT_TYPE, "application/json")],
-                Json(TwilioResponse {
-                    message: "Message confirmation sent".to_string(),
-                })
-            ))
-        }
+    let message = match crate::tool_call_utils::confirm::propose_action(state, user, action).await {
+        Ok(prompt) => prompt,
         Err(e) => {
-            eprintln!("Failed to send confirmation message: {}", e);
-            Ok((
-                StatusCode::OK,
-                [(axum::http::header::CONTENT_TYPE, "application/json")],
-                Json(TwilioResponse {
-                    message: "Failed to send message confirmation".to_string(),
-                })
-            ))
+            tracing::error!("Failed to propose chat message: {}", e);
+            "Failed to send message confirmation".to_string()
         }
-    }
+    };
+    Ok((
+        StatusCode::OK,
+        [(axum::http::header::CONTENT_TYPE, "application/json")],
+        Json(TwilioResponse {
+            message,
+        })
+    ))
 }
 
 #[derive(Deserialize)]
-struct SearchChatContactsArgs {
-    platform: String,
-    search_term: String,
+pub struct SearchChatContactsArgs {
+    pub platform: String,
+    pub search_term: String,
 }
 
 pub async fn handle_search_chat_contacts(
     state: &Arc<AppState>,
     user_id: i32,
-    args: &str,
+    args: SearchChatContactsArgs,
 ) -> String {
-    let args: SearchChatContactsArgs = match serde_json::from_str(args) {
-        Ok(args) => args,
-        Err(e) => {
-            eprintln!("Failed to parse search arguments: {}", e);
-            return "Failed to parse search request.".to_string();
-        }
-    };
     match crate::utils::bridge::search_bridge_rooms(
         &args.platform,
         state,
@@ -454,24 +478,17 @@ pub async fn handle_search_chat_contacts(
 }
 
 #[derive(Deserialize)]
-struct FetchChatMessagesArgs {
-    platform: String,
-    chat_name: String,
-    limit: Option<u64>,
+pub struct FetchChatMessagesArgs {
+    pub platform: String,
+    pub chat_name: String,
+    pub limit: Option<u64>,
 }
 
 pub async fn handle_fetch_chat_messages(
     state: &Arc<AppState>,
     user_id: i32,
-    args: &str,
+    args: FetchChatMessagesArgs,
 ) -> String {
-    let args: FetchChatMessagesArgs = match serde_json::from_str(args) {
-        Ok(args) => args,
-        Err(e) => {
-            eprintln!("Failed to parse chat messages arguments: {}", e);
-            return "Failed to parse chat messages request.".to_string();
-        }
-    };
 
     match crate::utils::bridge::fetch_bridge_room_messages(
         &args.platform,
@@ -528,28 +545,24 @@ pub async fn handle_fetch_chat_messages(
 use chrono::DateTime;
 
 #[derive(Deserialize)]
-struct FetchRecentMessagesArgs {
-    platform: String,
-    start: String,
+pub struct FetchRecentMessagesArgs {
+    pub platform: String,
+    // The legacy voice path sends none, the last day then
+    #[serde(default)]
+    pub start: Option<String>,
 }
 
 pub async fn handle_fetch_recent_messages(
     state: &Arc<AppState>,
     user_id: i32,
-    args: &str,
+    args: FetchRecentMessagesArgs,
 ) -> String {
-    let args: FetchRecentMessagesArgs = match serde_json::from_str(args) {
-        Ok(args) => args,
-        Err(e) => {
-            eprintln!("Failed to parse recent messages arguments: {}", e);
-            return "Failed to parse recent messages request.".to_string();
-        }
-    };
     let capitalized_platform = args.platform.chars().next().map(|c| c.to_uppercase().collect::<String>()).unwrap_or_default() + &args.platform[1..];
     // Parse the RFC3339 timestamps into Unix timestamps
-    let start_time = match DateTime::parse_from_rfc3339(&args.start) {
-        Ok(dt) => dt.timestamp(),
-        Err(e) => {
+    let start_time = match args.start.as_deref().map(DateTime::parse_from_rfc3339) {
+        None => (chrono::Utc::now() - chrono::Duration::days(1)).timestamp(),
+        Some(Ok(dt)) => dt.timestamp(),
+        Some(Err(e)) => {
             eprintln!("Failed to parse start time: {}", e);
             return "Invalid start time format. Please use RFC3339 format.".to_string();
         }
@@ -603,3 +616,157 @@ pub async fn handle_fetch_recent_messages(
         }
     }
 }
+
+#[derive(Deserialize)]
+pub struct SearchMessagesArgs {
+    pub query: String,
+    pub platform: Option<String>,
+    pub start: Option<String>,
+}
+
+pub async fn handle_search_messages(
+    state: &Arc<AppState>,
+    user_id: i32,
+    args: SearchMessagesArgs,
+) -> String {
+    let since = match args.start.as_deref().map(DateTime::parse_from_rfc3339) {
+        Some(Ok(dt)) => (dt.timestamp() as i32).max(crate::utils::message_index::retention_start()),
+        Some(Err(e)) => {
+            eprintln!("Failed to parse start time: {}", e);
+            return "Invalid start time format. Please use RFC3339 format.".to_string();
+        }
+        None => crate::utils::message_index::retention_start(),
+    };
+    let platform = args.platform.as_deref().filter(|p| !p.is_empty());
+
+    let messages = crate::utils::message_index::search(state, user_id, &args.query, platform, since, 10);
+    if messages.is_empty() {
+        return format!("No messages or emails matching '{}' found.", args.query);
+    }
+
+    let timezone = state.user_core.get_user_info(user_id).ok().and_then(|info| info.timezone);
+    let mut response = String::new();
+    for (i, indexed) in messages.into_iter().enumerate() {
+        let platform_name = crate::utils::bridge_service::service_for(&indexed.platform)
+            .map(|service| service.display_name().to_string())
+            .unwrap_or_else(|| "Email".to_string());
+        let msg = crate::utils::bridge::indexed_to_bridge_message(indexed, timezone.clone());
+        let content = if msg.content.chars().count() > 300 {
+            let truncated: String = msg.content.chars().take(297).collect();
+            format!("{}...", truncated)
+        } else {
+            msg.content
+        };
+        if i > 0 {
+            response.push_str("\n\n");
+        }
+        response.push_str(&format!("{}. {} - {} from {} at {}:\n{}",
+            i + 1,
+            platform_name,
+            msg.room_name,
+            msg.sender_display_name,
+            msg.formatted_timestamp,
+            content
+        ));
+    }
+    response
+}
+
+use futures::future::BoxFuture;
+use crate::tool_call_utils::registry::{Tool, ToolContext, ToolOutput};
+
+pub struct SendChatMessage;
+
+impl Tool for SendChatMessage {
+    type Args = SendChatMessageArgs;
+    const NAME: &'static str = "send_chat_message";
+
+    fn definition() -> openai_api_rs::v1::chat_completion::Tool {
+        get_send_chat_message_tool()
+    }
+
+    fn execute<'a>(ctx: &'a ToolContext<'a>, args: Self::Args) -> BoxFuture<'a, ToolOutput> {
+        Box::pin(async move {
+            match handle_send_chat_message(ctx.state, ctx.user.id, args, ctx.user, ctx.media_url).await {
+                Ok((_, _, Json(twilio_response))) => ToolOutput::Handled(twilio_response.message),
+                Err(e) => {
+                    tracing::error!("Failed to handle chat message sending: {}", e);
+                    ToolOutput::Handled("Failed to process chat message request".to_string())
+                }
+            }
+        })
+    }
+}
+
+pub struct FetchChatMessages;
+
+impl Tool for FetchChatMessages {
+    type Args = FetchChatMessagesArgs;
+    const NAME: &'static str = "fetch_chat_messages";
+
+    fn definition() -> openai_api_rs::v1::chat_completion::Tool {
+        get_fetch_chat_messages_tool()
+    }
+
+    fn execute<'a>(ctx: &'a ToolContext<'a>, args: Self::Args) -> BoxFuture<'a, ToolOutput> {
+        Box::pin(async move {
+            ToolOutput::Answer(handle_fetch_chat_messages(ctx.state, ctx.user.id, args).await)
+        })
+    }
+}
+
+pub struct FetchRecentMessages;
+
+impl Tool for FetchRecentMessages {
+    type Args = FetchRecentMessagesArgs;
+    const NAME: &'static str = "fetch_recent_messages";
+
+    fn definition() -> openai_api_rs::v1::chat_completion::Tool {
+        get_fetch_recent_messages_tool()
+    }
+
+    fn execute<'a>(ctx: &'a ToolContext<'a>, args: Self::Args) -> BoxFuture<'a, ToolOutput> {
+        Box::pin(async move {
+            ToolOutput::Answer(handle_fetch_recent_messages(ctx.state, ctx.user.id, args).await)
+        })
+    }
+}
+
+pub struct SearchMessages;
+
+impl Tool for SearchMessages {
+    type Args = SearchMessagesArgs;
+    const NAME: &'static str = "search_messages";
+
+    fn definition() -> openai_api_rs::v1::chat_completion::Tool {
+        get_search_messages_tool()
+    }
+
+    fn execute<'a>(ctx: &'a ToolContext<'a>, args: Self::Args) -> BoxFuture<'a, ToolOutput> {
+        Box::pin(async move {
+            ToolOutput::Answer(handle_search_messages(ctx.state, ctx.user.id, args).await)
+        })
+    }
+}
+
+pub struct SearchChatContacts;
+
+impl Tool for SearchChatContacts {
+    type Args = SearchChatContactsArgs;
+    const NAME: &'static str = "search_chat_contacts";
+
+    fn definition() -> openai_api_rs::v1::chat_completion::Tool {
+        get_search_chat_contacts_tool()
+    }
+
+    // over sms the model should use the room name from the message directly
+    fn available_on(channel: crate::tool_call_utils::registry::Channel) -> bool {
+        channel == crate::tool_call_utils::registry::Channel::Voice
+    }
+
+    fn execute<'a>(ctx: &'a ToolContext<'a>, args: Self::Args) -> BoxFuture<'a, ToolOutput> {
+        Box::pin(async move {
+            ToolOutput::Answer(handle_search_chat_contacts(ctx.state, ctx.user.id, args).await)
+        })
+    }
+}
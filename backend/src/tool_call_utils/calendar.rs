@@ -123,16 +123,8 @@ pub struct CalendarEventArgs {
 pub async fn handle_fetch_calendar_events(
     state: &Arc<AppState>,
     user_id: i32,
-    args: &str,
+    c: CalendarTimeFrame,
 ) -> String {
-    let c: CalendarTimeFrame = match serde_json::from_str(args) {
-        Ok(q) => q,
-        Err(e) => {
-            eprintln!("Failed to parse calendar time frame: {}", e);
-            return "Failed to parse calendar request.".to_string();
-        }
-    };
-
     match crate::handlers::google_calendar::handle_calendar_fetching(&state, user_id, &c.start, &c.end).await {
         Ok(Json(response)) => {
             if let Some(events) = response.get("events") {
@@ -226,11 +218,9 @@ pub async fn handle_fetch_calendar_events(
 pub async fn handle_create_calendar_event(
     state: &Arc<AppState>,
     user_id: i32,
-    args: &str,
+    args: CalendarEventArgs,
     user: &crate::models::user_models::User,
 ) -> Result<(axum::http::StatusCode, [(axum::http::HeaderName, &'static str); 1], axum::Json<crate::api::twilio_sms::TwilioResponse>), Box<dyn std::error::Error>> {
-    let args: CalendarEventArgs = serde_json::from_str(args)?;
-
     // Get user settings to check confirmation preference
     let user_settings = state.user_core.get_user_settings(user_id)?;
     let user_info= state.user_core.get_user_info(user_id)?;
@@ -302,79 +292,70 @@ pub async fn handle_create_calendar_event(
         }
     }
     
-    // Format the confirmation message for cases where confirmation is required
-    let confirmation_msg = if let Some(ref desc) = args.description {
-        format!(
-            "Create event: '{}' starting {} for {} minutes with description: '{}' (yes-> send, no -> discard) (free reply)",
-            args.summary, formatted_time, args.duration_minutes, desc
-        )
-    } else {
-        format!(
-            "Create event: '{}' starting {} for {} minutes (yes-> send, no -> discard) (free reply)",
-            args.summary, formatted_time, args.duration_minutes
-        )
+    // Otherwise ask first, the event is created once the user confirms
+    let action = crate::tool_call_utils::confirm::ActionPayload::CalendarEvent {
+        summary: args.summary.clone(),
+        start_time: start_time.with_timezone(&chrono::Utc).to_rfc3339(),
+        duration_minutes: args.duration_minutes,
+        description: args.description.clone(),
+        add_notification: args.add_notification.unwrap_or(true),
     };
-
-    // Set the temporary variable for calendar event
-    if let Err(e) = state.user_core.set_temp_variable(
-        user_id,
-        Some("calendar"),
-        None,
-        Some(&args.summary),
-        Some(&args.description.unwrap_or_default()),
-        Some(&args.start_time),
-        Some(&args.duration_minutes.to_string()),
-        None,
-        None,
-    ) {
-        tracing::error!("Failed to set temporary variable: {}", e);
-        if let Err(e) = crate::api::twilio_utils::send_conversation_message(
-            &state,
-            "Failed to prepare calendar event creation. (not charged, contact rasmus@ahtava.com)",
-            None,
-            user,
-        ).await {
-            tracing::error!("Failed to send error message: {}", e);
+    let message = match crate::tool_call_utils::confirm::propose_action(state, user, action).await {
+        Ok(prompt) => prompt,
+        Err(e) => {
+            tracing::error!("Failed to propose calendar event: {}", e);
+            "Failed to send calendar event confirmation".to_string()
         }
-        return Ok((
-            axum::http::StatusCode::OK,
-            [(axum::http::header::CONTENT_TYPE, "application/json")],
-            axum::Json(crate::api::twilio_sms::TwilioResponse {
-                message: "Failed to prepare calendar event creation".to_string(),
-            })
-        ));
+    };
+    Ok((
+        axum::http::StatusCode::OK,
+        [(axum::http::header::CONTENT_TYPE, "application/json")],
+        axum::Json(crate::api::twilio_sms::TwilioResponse {
+            message,
+        })
+    ))
+}
+
+
+use futures::future::BoxFuture;
+use crate::tool_call_utils::registry::{Tool, ToolContext, ToolOutput};
+
+pub struct FetchCalendarEvents;
+
+impl Tool for FetchCalendarEvents {
+    type Args = CalendarTimeFrame;
+    const NAME: &'static str = "fetch_calendar_events";
+
+    fn definition() -> openai_api_rs::v1::chat_completion::Tool {
+        get_fetch_calendar_event_tool()
     }
 
-    // Send the confirmation message
-    match crate::api::twilio_utils::send_conversation_message(
-        &state,
-        &confirmation_msg,
-        None,
-        user,
-    ).await {
-        Ok(_) => {
-            // Deduct credits for the confirmation message
-            if let Err(e) = crate::utils::usage::deduct_user_credits(&state, user_id, "message", None) {
-                eprintln!("Failed to deduct user credits: {}", e);
-            }
-            Ok((
-                axum::http::StatusCode::OK,
-                [(axum::http::header::CONTENT_TYPE, "application/json")],
-                axum::Json(crate::api::twilio_sms::TwilioResponse {
-                    message: "Calendar event confirmation sent".to_string(),
-                })
-            ))
-        }
-        Err(e) => {
-            eprintln!("Failed to send confirmation message: {}", e);
-            Ok((
-                axum::http::StatusCode::OK,
-                [(axum::http::header::CONTENT_TYPE, "application/json")],
-                axum::Json(crate::api::twilio_sms::TwilioResponse {
-                    message: "Failed to send calendar event confirmation".to_string(),
-                })
-            ))
-        }
+    fn execute<'a>(ctx: &'a ToolContext<'a>, args: Self::Args) -> BoxFuture<'a, ToolOutput> {
+        Box::pin(async move {
+            ToolOutput::Answer(handle_fetch_calendar_events(ctx.state, ctx.user.id, args).await)
+        })
     }
 }
 
+pub struct CreateCalendarEvent;
+
+impl Tool for CreateCalendarEvent {
+    type Args = CalendarEventArgs;
+    const NAME: &'static str = "create_calendar_event";
+
+    fn definition() -> openai_api_rs::v1::chat_completion::Tool {
+        get_create_calendar_event_tool()
+    }
+
+    fn execute<'a>(ctx: &'a ToolContext<'a>, args: Self::Args) -> BoxFuture<'a, ToolOutput> {
+        Box::pin(async move {
+            match handle_create_calendar_event(ctx.state, ctx.user.id, args, ctx.user).await {
+                Ok((_, _, Json(twilio_response))) => ToolOutput::Handled(twilio_response.message),
+                Err(e) => {
+                    tracing::error!("Failed to handle calendar event creation: {}", e);
+                    ToolOutput::Handled("Failed to process calendar event request".to_string())
+                }
+            }
+        })
+    }
+}
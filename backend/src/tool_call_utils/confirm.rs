@@ -4,345 +4,820 @@ use axum::http::StatusCode;
 use axum::Json;
 use std::sync::Arc;
 use regex::Regex;
+use serde::{Deserialize, Serialize};
+use openai_api_rs::v1::{chat_completion, types};
 use crate::api::twilio_sms::TwilioResponse;
-use crate::api::twilio_sms::TwilioMessageResponse;
+use crate::utils::llm_provider::{LlmChain, LlmPurpose};
 
-pub struct ConfirmationResult {
-    pub should_continue: bool,
-    pub response: Option<(StatusCode, [(axum::http::HeaderName, &'static str); 1], Json<TwilioResponse>)>,
+/// Something a tool wants to do on the user's behalf that waits for a yes first.
+/// Stored as json in `pending_actions`, the tag doubles as the action_type column.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ActionPayload {
+    CalendarEvent {
+        summary: String,
+        start_time: String, // rfc3339
+        duration_minutes: i32,
+        description: Option<String>,
+        #[serde(default = "default_true")]
+        add_notification: bool,
+    },
+    ChatMessage {
+        platform: String,
+        recipient: String, // exact room name
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        room_id: Option<String>, // when answering a notification, sent there instead of looking up the name
+        message: String,
+        #[serde(alias = "image_url")]
+        media_url: Option<String>, // data url of the attachment
+    },
+    EmailReply {
+        email_id: String,
+        subject: String,
+        response_text: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        account_id: Option<i32>, // the account the email is in, fixed when proposed
+    },
+    Task {
+        title: String,
+        description: Option<String>,
+        due_time: Option<String>, // rfc3339
+    },
+    Email {
+        to: String,
+        subject: String,
+        body: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        account_id: Option<i32>, // the account it goes out from, fixed when proposed
+    },
+    // a text to the user themselves
+    Sms {
+        message: String,
+    },
+    // one of the above, sent later by utils::scheduled_sends
+    ScheduledSend {
+        send_at: String, // rfc3339
+        send: Box<ActionPayload>,
+    },
 }
 
-pub async fn handle_confirmation(
-    state: &Arc<AppState>,
-    user: &User,
-    event_type: &str,
-    user_message: &str,
-    is_test: bool,
-) -> ConfirmationResult {
-    // Default values
-    let mut should_continue = false;
-    let mut response = None;
-
-    let user_response = user_message.trim().to_lowercase();
-
-    if event_type == "calendar" {
-        // Handle calendar event confirmation
-        // Get the calendar event details from temp variables
-        let (summary, start_time, duration, description) = match state.user_core.get_temp_variable(user.id, "calendar") {
-            Ok(Some((_, summary, description, start_time, duration_str, _, _))) => (
-                summary.unwrap_or_default(),
-                start_time.unwrap_or_default(),
-                duration_str.unwrap_or_default().parse::<i32>().unwrap_or(30),
-                description
-            ),
-            _ => {
-                response = Some((
-                    StatusCode::OK,
-                    [(axum::http::header::CONTENT_TYPE, "application/json")],
-                    Json(TwilioResponse {
-                        message: "Failed to create calendar event due to internal error.".to_string(),
-                    })
-                ));
-
-                // Clear the confirmation state
-                if let Err(e) = state.user_core.clear_confirm_send_event(user.id) {
-                    tracing::error!("Failed to clear confirmation state: {}", e);
-                }
-                return ConfirmationResult {
-                    should_continue,
-                    response,
-                };
-            }
-        };
-        match user_response.as_str() {
-            "yes" => {
+fn default_true() -> bool {
+    true
+}
 
-                // Create the calendar event
-                let event_request = crate::handlers::google_calendar::CreateEventRequest {
-                    start_time: match chrono::DateTime::parse_from_rfc3339(&start_time) {
-                        Ok(dt) => dt.with_timezone(&chrono::Utc),
-                        Err(e) => {
-                            tracing::error!("Failed to parse start time: {}", e);
-                            response = Some((
-                                StatusCode::OK,
-                                [(axum::http::header::CONTENT_TYPE, "application/json")],
-                                Json(TwilioResponse {
-                                    message: "Failed to create calendar event due to invalid start time.".to_string(),
-                                })
-                            ));
-                            // Clear the confirmation state
-                            if let Err(e) = state.user_core.clear_confirm_send_event(user.id) {
-                                tracing::error!("Failed to clear confirmation state: {}", e);
-                            }
-                            return ConfirmationResult {
-                                should_continue,
-                                response,
-                            };
-                        }
-                    },
-                    duration_minutes: duration,
-                    summary,
-                    description,
-                    add_notification: true,
-                };
+/// Fields the user can change before confirming. Each only applies to the action types that have it.
+#[derive(Debug, Default, Deserialize)]
+pub struct ActionChanges {
+    #[serde(default)]
+    pub summary: Option<String>,
+    #[serde(default)]
+    pub start_time: Option<String>,
+    #[serde(default)]
+    pub duration_minutes: Option<f64>, // the schema says number, models send 30 as well as 30.0
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub message: Option<String>,
+    #[serde(default)]
+    pub response_text: Option<String>,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub due_time: Option<String>,
+}
 
-                let auth_user = crate::handlers::auth_middleware::AuthUser {
-                    user_id: user.id,
-                    is_admin: false,
-                };
+fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().collect::<String>() + chars.as_str(),
+        None => String::new(),
+    }
+}
 
-                match crate::handlers::google_calendar::create_calendar_event(
-                    axum::extract::State(state.clone()),
-                    auth_user,
-                    Json(event_request),
-                ).await {
-                    Ok(_) => {
-                        let confirmation_msg = "Calendar event created successfully!";
-                        if !is_test {
-                            if let Err(e) = crate::api::twilio_utils::send_conversation_message(
-                                &state,
-                                confirmation_msg,
-                                None,
-                                user,
-                            ).await {
-                                tracing::error!("Failed to send confirmation message: {}", e);
-                            }
-                        }
-
-                        response = Some((
-                            StatusCode::OK,
-                            [(axum::http::header::CONTENT_TYPE, "application/json")],
-                            Json(TwilioResponse {
-                                message: confirmation_msg.to_string(),
-                            })
-                        ));
-                    }
-                    Err((status, Json(error))) => {
-                        let error_msg = format!("Failed to create calendar event: {} (not charged)", error["error"].as_str().unwrap_or("Unknown error"));
-                        if !is_test {
-                            if let Err(e) = crate::api::twilio_utils::send_conversation_message(
-                                &state,
-                                &error_msg,
-                                None,
-                                user,
-                            ).await {
-                                tracing::error!("Failed to send error message: {}", e);
-                            }
-                        }
-                        response = Some((
-                            StatusCode::OK,
-                            [(axum::http::header::CONTENT_TYPE, "application/json")],
-                            Json(TwilioResponse {
-                                message: error_msg,
-                            })
-                        ));
-                    }
+// "image", "audio", "video" or "file" for a data url, other urls are taken as images
+fn attachment_kind(url: &str) -> &'static str {
+    match url.strip_prefix("data:").and_then(|data| data.split(';').next()) {
+        Some(mime) => crate::utils::bridge::media_kind(mime),
+        None => "image",
+    }
+}
+
+pub fn format_local(time: &str, timezone: &str) -> String {
+    let tz: chrono_tz::Tz = timezone.parse().unwrap_or(chrono_tz::UTC);
+    match chrono::DateTime::parse_from_rfc3339(time) {
+        Ok(dt) => dt.with_timezone(&tz).format("%B %d at %I:%M %p %Z").to_string(),
+        Err(_) => time.to_string(),
+    }
+}
+
+fn parse_time(time: &str) -> Result<chrono::DateTime<chrono::Utc>, String> {
+    chrono::DateTime::parse_from_rfc3339(time)
+        .map(|dt| dt.with_timezone(&chrono::Utc))
+        .map_err(|_| format!("'{}' is not a valid time", time))
+}
+
+impl ActionPayload {
+    pub fn action_type(&self) -> &'static str {
+        match self {
+            ActionPayload::CalendarEvent { .. } => "calendar_event",
+            ActionPayload::ChatMessage { .. } => "chat_message",
+            ActionPayload::EmailReply { .. } => "email_reply",
+            ActionPayload::Task { .. } => "task",
+            ActionPayload::Email { .. } => "email",
+            ActionPayload::Sms { .. } => "sms",
+            ActionPayload::ScheduledSend { .. } => "scheduled_send",
+        }
+    }
+
+    /// What the user is asked to confirm, times in their timezone.
+    pub fn describe(&self, timezone: &str) -> String {
+        match self {
+            ActionPayload::CalendarEvent { summary, start_time, duration_minutes, description, .. } => match description {
+                Some(desc) if !desc.is_empty() => format!(
+                    "Create event: '{}' starting {} for {} minutes with description: '{}'",
+                    summary, format_local(start_time, timezone), duration_minutes, desc
+                ),
+                _ => format!(
+                    "Create event: '{}' starting {} for {} minutes",
+                    summary, format_local(start_time, timezone), duration_minutes
+                ),
+            },
+            ActionPayload::ChatMessage { platform, recipient, message, media_url, .. } => {
+                if let Some(url) = media_url {
+                    format!(
+                        "Send {} to '{}' with the attached {} and a caption '{}'",
+                        capitalize(platform), recipient, attachment_kind(url), message
+                    )
+                } else {
+                    format!("Send {} to '{}' with content: '{}'", capitalize(platform), recipient, message)
                 }
             }
-            _ => {
-                should_continue = true;
+            ActionPayload::EmailReply { subject, response_text, .. } => {
+                format!("Send email response '{}' regarding '{}'", response_text, subject)
             }
-        }
-    } else if event_type == "telegram" || event_type == "whatsapp" {
-        // Get the message details from temp variables
-        let details = match state.user_core.get_temp_variable(user.id, event_type) {
-            Ok(Some((recipient, _, message_content, _, _, _, image_url))) => {
-                (recipient.unwrap_or_default(), message_content.unwrap_or_default(), image_url.unwrap_or("".to_string()))
+            ActionPayload::Task { title, due_time, .. } => match due_time {
+                Some(due) => format!("Create task '{}' due {}", title, format_local(due, timezone)),
+                None => format!("Create task '{}'", title),
             },
-            _ => {
-                response = Some((
-                    StatusCode::OK,
-                    [(axum::http::header::CONTENT_TYPE, "application/json")],
-                    Json(TwilioResponse {
-                        message: format!("Failed to send {} message due to internal error.", event_type),
-                    })
-                ));
-                // Clear the confirmation state
-                if let Err(e) = state.user_core.clear_confirm_send_event(user.id) {
-                    tracing::error!("Failed to clear confirmation state: {}", e);
-                }
-                return ConfirmationResult {
-                    should_continue,
-                    response,
-                };
+            ActionPayload::Email { to, subject, body, .. } => {
+                format!("Send email to {} with subject '{}': '{}'", to, subject, body)
             }
-        };
+            ActionPayload::Sms { message } => format!("Text you: '{}'", message),
+            ActionPayload::ScheduledSend { send_at, send } => {
+                format!("On {}: {}", format_local(send_at, timezone), send.describe(timezone))
+            }
+        }
+    }
 
-        let (recipient, message_content, image_url) = details;
-        let image_url_option = if !image_url.is_empty() {
-            Some(image_url)
+    /// Applies the changes that make sense for this action. Errors if a value is invalid
+    /// or nothing applied, so the user gets told instead of confirming the old version.
+    pub fn apply_changes(&mut self, changes: &ActionChanges) -> Result<(), String> {
+        let mut changed = false;
+        match self {
+            ActionPayload::CalendarEvent { summary, start_time, duration_minutes, description, .. } => {
+                if let Some(new_start) = &changes.start_time {
+                    *start_time = parse_time(new_start)?.to_rfc3339();
+                    changed = true;
+                }
+                if let Some(new_duration) = changes.duration_minutes {
+                    let new_duration = new_duration.round() as i32;
+                    if new_duration <= 0 {
+                        return Err("The duration has to be at least a minute".to_string());
+                    }
+                    *duration_minutes = new_duration;
+                    changed = true;
+                }
+                if let Some(new_summary) = changes.summary.as_ref().or(changes.title.as_ref()) {
+                    *summary = new_summary.clone();
+                    changed = true;
+                }
+                if let Some(new_description) = &changes.description {
+                    *description = Some(new_description.clone()).filter(|d| !d.is_empty());
+                    changed = true;
+                }
+            }
+            ActionPayload::ChatMessage { message, .. } => {
+                if let Some(new_message) = changes.message.as_ref().or(changes.response_text.as_ref()) {
+                    *message = new_message.clone();
+                    changed = true;
+                }
+            }
+            ActionPayload::EmailReply { response_text, .. } => {
+                if let Some(new_text) = changes.response_text.as_ref().or(changes.message.as_ref()) {
+                    *response_text = new_text.clone();
+                    changed = true;
+                }
+            }
+            ActionPayload::Task { title, description, due_time } => {
+                if let Some(new_title) = changes.title.as_ref().or(changes.summary.as_ref()) {
+                    *title = new_title.clone();
+                    changed = true;
+                }
+                if let Some(new_description) = &changes.description {
+                    *description = Some(new_description.clone()).filter(|d| !d.is_empty());
+                    changed = true;
+                }
+                if let Some(new_due) = changes.due_time.as_ref().or(changes.start_time.as_ref()) {
+                    *due_time = Some(parse_time(new_due)?.to_rfc3339());
+                    changed = true;
+                }
+            }
+            ActionPayload::Email { subject, body, .. } => {
+                if let Some(new_subject) = changes.summary.as_ref().or(changes.title.as_ref()) {
+                    *subject = new_subject.clone();
+                    changed = true;
+                }
+                if let Some(new_body) = changes.message.as_ref().or(changes.response_text.as_ref()) {
+                    *body = new_body.clone();
+                    changed = true;
+                }
+            }
+            ActionPayload::Sms { message } => {
+                if let Some(new_message) = changes.message.as_ref().or(changes.response_text.as_ref()) {
+                    *message = new_message.clone();
+                    changed = true;
+                }
+            }
+            ActionPayload::ScheduledSend { send_at, send } => {
+                if let Some(new_time) = changes.start_time.as_ref().or(changes.due_time.as_ref()) {
+                    *send_at = parse_time(new_time)?.to_rfc3339();
+                    changed = true;
+                }
+                // the rest is about what gets sent, only an error if the time didn't change either
+                match send.apply_changes(changes) {
+                    Ok(()) => changed = true,
+                    Err(e) if !changed => return Err(e),
+                    Err(_) => {}
+                }
+            }
+        }
+        if changed {
+            Ok(())
         } else {
-            None
+            Err("I couldn't tell what to change".to_string())
+        }
+    }
+
+    /// Carries the action out. Both arms are texts for the user.
+    pub async fn execute(&self, state: &Arc<AppState>, user: &User) -> Result<String, String> {
+        let auth_user = || crate::handlers::auth_middleware::AuthUser {
+            user_id: user.id,
+            is_admin: false,
         };
 
-        match user_response.as_str() {
-            "yes" => {
-                // Send the message
+        match self {
+            ActionPayload::CalendarEvent { summary, start_time, duration_minutes, description, add_notification } => {
+                let event_request = crate::handlers::google_calendar::CreateEventRequest {
+                    start_time: parse_time(start_time)
+                        .map_err(|_| "Failed to create calendar event due to invalid start time.".to_string())?,
+                    duration_minutes: *duration_minutes,
+                    summary: summary.clone(),
+                    description: description.clone(),
+                    add_notification: *add_notification,
+                };
+                match crate::handlers::google_calendar::create_calendar_event(
+                    axum::extract::State(state.clone()),
+                    auth_user(),
+                    Json(event_request),
+                ).await {
+                    Ok(_) => Ok("Calendar event created successfully!".to_string()),
+                    Err((_, Json(error))) => Err(format!(
+                        "Failed to create calendar event: {} (not charged)",
+                        error["error"].as_str().unwrap_or("Unknown error")
+                    )),
+                }
+            }
+            ActionPayload::ChatMessage { platform, recipient, room_id, message, media_url } => {
                 match crate::utils::bridge::send_bridge_message(
-                    event_type,
+                    platform,
                     state,
                     user.id,
-                    &recipient,
-                    &message_content,
-                    image_url_option,
+                    recipient,
+                    room_id.as_deref(),
+                    message,
+                    media_url.clone(),
                 ).await {
-                    Ok(_) => {
-                        // Send confirmation via Twilio
-                        let confirmation_msg = format!("Message sent successfully to {}", recipient);
-                        if !is_test {
-                            if let Err(e) = crate::api::twilio_utils::send_conversation_message(
-                                &state,
-                                &confirmation_msg,
-                                None,
-                                user,
-                            ).await {
-                                tracing::error!("Failed to send confirmation message: {}", e);
-                            }
-                        }
-
-                        response = Some((
-                            StatusCode::OK,
-                            [(axum::http::header::CONTENT_TYPE, "application/json")],
-                            Json(TwilioResponse {
-                                message: confirmation_msg,
-                            })
-                        ));
-                    }
-                    Err(e) => {
-                        tracing::debug!("sending failed to send the message");
-                        let error_msg = format!("Failed to send message: {}", e);
-                        if !is_test {
-                            if let Err(send_err) = crate::api::twilio_utils::send_conversation_message(
-                                &state,
-                                &error_msg,
-                                None,
-                                user,
-                            ).await {
-                                tracing::error!("Failed to send error message: {}", send_err);
-                            }
-                        }
-
-                        response = Some((
-                            StatusCode::OK,
-                            [(axum::http::header::CONTENT_TYPE, "application/json")],
-                            Json(TwilioResponse {
-                                message: error_msg,
-                            })
-                        ));
+                    Ok(_) => Ok(format!("Message sent successfully to {}", recipient)),
+                    Err(e) => Err(format!("Failed to send message: {}", e)),
+                }
+            }
+            ActionPayload::EmailReply { email_id, subject, response_text, account_id } => {
+                // a bare uid would be looked up in the default account
+                let email_id = match (account_id, crate::handlers::imap_handlers::parse_email_id(email_id)) {
+                    (Some(account_id), Some((None, uid))) => format!("{}:{}", account_id, uid),
+                    _ => email_id.clone(),
+                };
+                let email_request = crate::handlers::imap_handlers::EmailResponseRequest {
+                    email_id,
+                    response_text: response_text.clone(),
+                };
+                match crate::handlers::imap_handlers::respond_to_email(
+                    axum::extract::State(state.clone()),
+                    auth_user(),
+                    Json(email_request),
+                ).await {
+                    Ok(_) => Ok(format!("Email response sent successfully regarding '{}'", subject)),
+                    Err((_, Json(error))) => Err(format!(
+                        "Failed to send email response: {} (not charged)",
+                        error["error"].as_str().unwrap_or("Unknown error")
+                    )),
+                }
+            }
+            ActionPayload::Task { title, description, due_time } => {
+                let due_time = match due_time {
+                    Some(due) => Some(parse_time(due)?),
+                    None => None,
+                };
+                let task_request = crate::handlers::google_tasks::CreateTaskRequest {
+                    title: title.clone(),
+                    description: description.clone(),
+                    due_time,
+                };
+                match crate::handlers::google_tasks::create_task(state, user.id, &task_request).await {
+                    Ok(_) => Ok(format!("Task '{}' created.", title)),
+                    Err((StatusCode::UNAUTHORIZED, _)) => Err("You need to connect your Google Tasks first. Visit the website to set it up.".to_string()),
+                    Err((_, Json(error))) => {
+                        tracing::error!("Failed to create task: {:?}", error);
+                        Err("Failed to create task. Please try again later. (not charged)".to_string())
                     }
                 }
             }
-            _ => {
-                should_continue = true;
+            ActionPayload::Email { to, subject, body, account_id } => {
+                // smtp is blocking, keep it off the runtime workers
+                let send_state = state.clone();
+                let user_id = user.id;
+                let account_id = *account_id;
+                let (send_to, send_subject, send_body) = (to.clone(), subject.clone(), body.clone());
+                let sent = tokio::task::spawn_blocking(move || {
+                    crate::handlers::imap_handlers::send_email_smtp(&send_state, user_id, account_id, &send_to, &send_subject, &send_body, false)
+                })
+                .await
+                .unwrap_or_else(|e| Err(format!("Email send task failed: {}", e)));
+                match sent {
+                    Ok(()) => Ok(format!("Email '{}' sent to {}", subject, to)),
+                    Err(e) => Err(format!("Failed to send email to {}: {} (not charged)", to, e)),
+                }
+            }
+            ActionPayload::Sms { message } => {
+                match crate::api::twilio_utils::send_conversation_message(state, message, None, user).await.map_err(|e| e.to_string()) {
+                    Ok(_) => Ok("Text sent.".to_string()),
+                    Err(e) => Err(format!("Failed to send the text: {}", e)),
+                }
+            }
+            ActionPayload::ScheduledSend { send_at, send } => {
+                crate::utils::scheduled_sends::schedule(state, user, send_at, send)
             }
         }
-    } else if event_type == "email" {
-        // Get the email response details from temp variables
-        let email_details = match state.user_core.get_temp_variable(user.id, "email") {
-            Ok(Some((recipient, subject, response_text, _, _, email_id, _))) => {
-                (
-                    recipient.unwrap_or_default(),
-                    subject.unwrap_or_default(),
-                    response_text.unwrap_or_default(),
-                    email_id.unwrap_or_default()
-                )
-            },
-            _ => {
-                response = Some((
-                    StatusCode::OK,
-                    [(axum::http::header::CONTENT_TYPE, "application/json")],
-                    Json(TwilioResponse {
-                        message: "Failed to send email response due to internal error.".to_string(),
-                    })
-                ));
-
-                // Clear the confirmation state
-                if let Err(e) = state.user_core.clear_confirm_send_event(user.id) {
-                    tracing::error!("Failed to clear confirmation state: {}", e);
+    }
+}
+
+pub fn user_timezone(state: &Arc<AppState>, user_id: i32) -> String {
+    state.user_core.get_user_info(user_id)
+        .ok()
+        .and_then(|info| info.timezone)
+        .unwrap_or_else(|| "UTC".to_string())
+}
+
+fn prompt_for(number: i32, description: &str, numbered: bool) -> String {
+    if numbered {
+        format!("#{} {} (yes {} / no {}, or tell me what to change) (free reply)", number, description, number, number)
+    } else {
+        format!("{} (yes -> confirm, no -> discard, or tell me what to change) (free reply)", description)
+    }
+}
+
+/// Stores the action and texts the user the confirmation prompt, which is also returned.
+/// Pending actions expire after PENDING_ACTION_TTL_MINUTES (default 60).
+pub async fn propose_action(state: &Arc<AppState>, user: &User, action: ActionPayload) -> Result<String, String> {
+    let ttl_minutes = std::env::var("PENDING_ACTION_TTL_MINUTES")
+        .ok()
+        .and_then(|v| v.parse::<i32>().ok())
+        .unwrap_or(60);
+    let payload = serde_json::to_string(&action).map_err(|e| e.to_string())?;
+    let number = state.user_repository
+        .create_pending_action(user.id, action.action_type(), &payload, ttl_minutes * 60)
+        .map_err(|e| format!("Failed to store pending action: {}", e))?;
+
+    // Number the prompt only once there's something to tell apart
+    let pending_count = state.user_repository.get_pending_actions(user.id).map(|a| a.len()).unwrap_or(1);
+    let prompt = prompt_for(number, &action.describe(&user_timezone(state, user.id)), pending_count > 1);
+
+    // Box<dyn Error> isn't Send, turn it into a string right away
+    if let Err(e) = crate::api::twilio_utils::send_conversation_message(state, &prompt, None, user).await.map_err(|e| e.to_string()) {
+        tracing::error!("Failed to send confirmation message: {}", e);
+        return Err("Failed to send the confirmation message".to_string());
+    }
+
+    // Deduct credits for the confirmation message
+    if let Err(e) = crate::utils::usage::deduct_user_credits(state, user.id, "message", None) {
+        tracing::error!("Failed to deduct user credits: {}", e);
+    }
+
+    Ok(prompt)
+}
+
+/// Lists what is waiting for an answer.
+pub fn list_pending_actions(state: &Arc<AppState>, user: &User) -> String {
+    let actions = match state.user_repository.get_pending_actions(user.id) {
+        Ok(actions) => actions,
+        Err(e) => {
+            tracing::error!("Failed to get pending actions: {}", e);
+            return "Failed to get the pending actions.".to_string();
+        }
+    };
+    if actions.is_empty() {
+        return "Nothing is waiting for confirmation.".to_string();
+    }
+    let timezone = user_timezone(state, user.id);
+    actions.iter()
+        .filter_map(|action| {
+            let payload: ActionPayload = serde_json::from_str(&action.encrypted_payload).ok()?;
+            Some(format!("#{} {}", action.number, payload.describe(&timezone)))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Confirms, cancels or changes pending action `number`. Returns the text for the user.
+pub async fn resolve_action(
+    state: &Arc<AppState>,
+    user: &User,
+    number: i32,
+    intent: &str,
+    changes: &ActionChanges,
+) -> String {
+    let actions = state.user_repository.get_pending_actions(user.id).unwrap_or_default();
+    let Some(action) = actions.iter().find(|a| a.number == number) else {
+        return format!("There's no pending action #{}, it may have expired.", number);
+    };
+    let mut payload: ActionPayload = match serde_json::from_str(&action.encrypted_payload) {
+        Ok(payload) => payload,
+        Err(e) => {
+            tracing::error!("Failed to read pending action {} of user {}: {}", number, user.id, e);
+            let _ = state.user_repository.delete_pending_action(user.id, number);
+            return "Failed to read the pending action, please ask again.".to_string();
+        }
+    };
+    let timezone = user_timezone(state, user.id);
+
+    match intent {
+        "cancel" => {
+            if let Err(e) = state.user_repository.delete_pending_action(user.id, number) {
+                tracing::error!("Failed to delete pending action: {}", e);
+            }
+            return format!("Discarded: {}", payload.describe(&timezone));
+        }
+        "modify" | "modify_and_confirm" => {
+            if let Err(e) = payload.apply_changes(changes) {
+                return format!("{}. {}", e, prompt_for(number, &payload.describe(&timezone), actions.len() > 1));
+            }
+            if intent == "modify" {
+                let json = serde_json::to_string(&payload).unwrap_or_default();
+                if let Err(e) = state.user_repository.update_pending_action(user.id, number, &json) {
+                    tracing::error!("Failed to update pending action: {}", e);
+                    return "Failed to save the change, please try again.".to_string();
                 }
-                return ConfirmationResult {
-                    should_continue,
-                    response,
-                };
+                return format!("Updated. {}", prompt_for(number, &payload.describe(&timezone), actions.len() > 1));
             }
-        };
+        }
+        "confirm" => {}
+        _ => return prompt_for(number, &payload.describe(&timezone), actions.len() > 1),
+    }
 
-        let (recipient, subject, response_text, email_id) = email_details;
+    // Delete first so a double "yes" can't run it twice
+    match state.user_repository.delete_pending_action(user.id, number) {
+        Ok(true) => {}
+        Ok(false) => return "That was already taken care of.".to_string(),
+        Err(e) => {
+            tracing::error!("Failed to delete pending action: {}", e);
+            return "Failed to confirm, please try again.".to_string();
+        }
+    }
+    if let Some(queued) = crate::utils::outbox::queue_if_delayed(state, user, &payload) {
+        return queued;
+    }
+    match payload.execute(state, user).await {
+        Ok(message) => message,
+        Err(message) => message,
+    }
+}
 
-        match user_response.as_str() {
-            "yes" => {
+#[derive(Deserialize)]
+struct InterpretedReply {
+    intent: String,
+    #[serde(default)]
+    number: Option<i32>,
+    #[serde(flatten)]
+    changes: ActionChanges,
+}
 
-                let email_request = crate::handlers::imap_handlers::EmailResponseRequest {
-                    email_id: email_id.clone(),
-                    response_text: response_text.clone(),
-                };
+fn change_properties() -> std::collections::HashMap<String, Box<types::JSONSchemaDefine>> {
+    let string_field = |description: &str| Box::new(types::JSONSchemaDefine {
+        schema_type: Some(types::JSONSchemaType::String),
+        description: Some(description.to_string()),
+        ..Default::default()
+    });
 
-                let auth_user = crate::handlers::auth_middleware::AuthUser {
-                    user_id: user.id,
-                    is_admin: false,
-                };
+    let mut properties = std::collections::HashMap::new();
+    properties.insert("summary".to_string(), string_field("New title for a calendar event"));
+    properties.insert("start_time".to_string(), string_field("New start time for a calendar event or new send time for a scheduled message, in RFC3339 format in UTC (e.g., '2024-03-23T14:30:00Z')"));
+    properties.insert(
+        "duration_minutes".to_string(),
+        Box::new(types::JSONSchemaDefine {
+            schema_type: Some(types::JSONSchemaType::Number),
+            description: Some("New duration for a calendar event in minutes".to_string()),
+            ..Default::default()
+        }),
+    );
+    properties.insert("description".to_string(), string_field("New description for a calendar event or task"));
+    properties.insert("message".to_string(), string_field("The complete new text of a chat message, email or text"));
+    properties.insert("response_text".to_string(), string_field("The complete new text of an email reply"));
+    properties.insert("title".to_string(), string_field("New title for a task or new subject for an email"));
+    properties.insert("due_time".to_string(), string_field("New due time for a task in RFC3339 format in UTC"));
+    properties
+}
 
-                match crate::handlers::imap_handlers::respond_to_email(
-                    axum::extract::State(state.clone()),
-                    auth_user,
-                    Json(email_request),
-                ).await {
-                    Ok(_) => {
-                        let confirmation_msg = format!("Email response sent successfully to {} regarding '{}'", recipient, subject);
-                        if !is_test {
-                            if let Err(e) = crate::api::twilio_utils::send_conversation_message(
-                                &state,
-                                &confirmation_msg,
-                                None,
-                                user,
-                            ).await {
-                                tracing::error!("Failed to send confirmation message: {}", e);
-                            }
-                        }
-
-                        response = Some((
-                            StatusCode::OK,
-                            [(axum::http::header::CONTENT_TYPE, "application/json")],
-                            Json(TwilioResponse {
-                                message: confirmation_msg,
-                            })
-                        ));
-                    }
-                    Err((status, Json(error))) => {
-                        let error_msg = format!("Failed to send email response: {} (not charged)", error["error"].as_str().unwrap_or("Unknown error"));
-                        if !is_test {
-                            if let Err(e) = crate::api::twilio_utils::send_conversation_message(
-                                &state,
-                                &error_msg,
-                                None,
-                                user,
-                            ).await {
-                                tracing::error!("Failed to send error message: {}", e);
-                            }
-                        }
-                        response = Some((
-                            StatusCode::OK,
-                            [(axum::http::header::CONTENT_TYPE, "application/json")],
-                            Json(TwilioResponse {
-                                message: error_msg,
-                            })
-                        ));
-                    }
+fn interpret_reply_tool() -> chat_completion::Tool {
+    let mut properties = change_properties();
+    properties.insert(
+        "intent".to_string(),
+        Box::new(types::JSONSchemaDefine {
+            schema_type: Some(types::JSONSchemaType::String),
+            description: Some("confirm: go ahead as is. cancel: discard. modify: change it but ask again. modify_and_confirm: change it and go ahead (e.g. 'send it but say ...'). unrelated: the message is not about the pending actions.".to_string()),
+            enum_values: Some(vec![
+                "confirm".to_string(),
+                "cancel".to_string(),
+                "modify".to_string(),
+                "modify_and_confirm".to_string(),
+                "unrelated".to_string(),
+            ]),
+            ..Default::default()
+        }),
+    );
+    properties.insert(
+        "number".to_string(),
+        Box::new(types::JSONSchemaDefine {
+            schema_type: Some(types::JSONSchemaType::Number),
+            description: Some("Number of the pending action the reply is about. Leave out if it isn't clear.".to_string()),
+            ..Default::default()
+        }),
+    );
+
+    chat_completion::Tool {
+        r#type: chat_completion::ToolType::Function,
+        function: types::Function {
+            name: String::from("interpret_reply"),
+            description: Some(String::from("Records what the user wants done with their pending actions")),
+            parameters: types::FunctionParameters {
+                schema_type: types::JSONSchemaType::Object,
+                properties: Some(properties),
+                required: Some(vec![String::from("intent")]),
+            },
+        },
+    }
+}
+
+async fn interpret_reply(
+    state: &Arc<AppState>,
+    user: &User,
+    pending: &str,
+    message: &str,
+) -> Option<InterpretedReply> {
+    let llm = match LlmChain::for_purpose(state, LlmPurpose::Evaluation, Some(user.id)) {
+        Ok(llm) => llm,
+        Err(e) => {
+            tracing::error!("Failed to set up llm chain for pending action reply: {}", e);
+            return None;
+        }
+    };
+    let timezone = user_timezone(state, user.id);
+    let tz: chrono_tz::Tz = timezone.parse().unwrap_or(chrono_tz::UTC);
+    let now = chrono::Utc::now().with_timezone(&tz).format("%A %B %d %Y %I:%M %p %Z");
+
+    let request = chat_completion::ChatCompletionRequest::new(
+        llm.primary_model().to_string(),
+        vec![
+            chat_completion::ChatCompletionMessage {
+                role: chat_completion::MessageRole::system,
+                content: chat_completion::Content::Text(format!(
+                    "The assistant asked the user to confirm the actions below. Decide what the user's reply means for them and call interpret_reply. \
+                    Only fill in the fields the user wants changed, with the complete new value. When the user dictates new wording for a message, use their exact words. \
+                    The user's timezone is {} and it is now {}.\n\nPending actions:\n{}",
+                    timezone, now, pending
+                )),
+                name: None,
+                tool_calls: None,
+                tool_call_id: None,
+            },
+            chat_completion::ChatCompletionMessage {
+                role: chat_completion::MessageRole::user,
+                content: chat_completion::Content::Text(message.to_string()),
+                name: None,
+                tool_calls: None,
+                tool_call_id: None,
+            },
+        ],
+    )
+    .tools(vec![interpret_reply_tool()])
+    .tool_choice(chat_completion::ToolChoiceType::Required)
+    .max_tokens(300);
+
+    match llm.chat_completion(request).await {
+        Ok(result) => {
+            let args = result.choices.first()?
+                .message.tool_calls.as_ref()?
+                .first()?
+                .function.arguments.clone()?;
+            match serde_json::from_str::<InterpretedReply>(&args) {
+                Ok(reply) => Some(reply),
+                Err(e) => {
+                    tracing::error!("Failed to parse pending action reply: {} ({})", e, args);
+                    None
                 }
             }
-            _ => {
-                should_continue = true;
+        }
+        Err(e) => {
+            tracing::error!("Failed to interpret pending action reply: {}", e);
+            None
+        }
+    }
+}
+
+// Free-form replies come right after the prompt, later messages are taken as something new
+const FREE_FORM_REPLY_WINDOW_SECONDS: i32 = 15 * 60;
+
+// Whether a message that isn't a plain yes/no could be about a pending action at all,
+// so the llm call is skipped for everything that obviously isn't
+fn may_be_pending_reply(message: &str) -> bool {
+    let cues = Regex::new(r"(?i)\b(yes|yeah|yep|no|nope|ok|okay|sure|fine|send|cancel|stop|discard|don'?t|do not|change|instead|make it|rather|say|write|tell|add|remove|move|wait|later|earlier|confirm|go ahead|actually|that one|both|all|none|first|second|last)\b|#\s*\d").unwrap();
+    message.chars().count() <= 300 && cues.is_match(message)
+}
+
+/// Handles an sms reply to pending actions. Plain "yes", "no 2" etc. are matched directly,
+/// anything else that looks like it could be a reply goes through a small llm call, billed
+/// like a message when it was one. Returns None when the message isn't about the pending
+/// actions so the agent answers it as usual.
+pub async fn handle_pending_reply(
+    state: &Arc<AppState>,
+    user: &User,
+    user_message: &str,
+    is_test: bool,
+) -> Option<(StatusCode, [(axum::http::HeaderName, &'static str); 1], Json<TwilioResponse>)> {
+    let actions = match state.user_repository.get_pending_actions(user.id) {
+        Ok(actions) if !actions.is_empty() => actions,
+        Ok(_) => return None,
+        Err(e) => {
+            tracing::error!("Failed to get pending actions: {}", e);
+            return None;
+        }
+    };
+
+    let quick_reply = Regex::new(r"(?i)^\s*(yes|y|yep|yeah|ok|okay|confirm|send it|no|n|nope|cancel|discard)\s*#?\s*(\d+)?\s*[.!]*\s*$").unwrap();
+    let (intent, number, changes) = if let Some(caps) = quick_reply.captures(user_message) {
+        let intent = match caps[1].to_lowercase().as_str() {
+            "no" | "n" | "nope" | "cancel" | "discard" => "cancel",
+            _ => "confirm",
+        };
+        (intent.to_string(), caps.get(2).and_then(|m| m.as_str().parse::<i32>().ok()), ActionChanges::default())
+    } else {
+        let now = chrono::Utc::now().timestamp() as i32;
+        let latest_proposed = actions.iter().filter(|action| action.expires_at > now).map(|action| action.created_at).max()?;
+        if now - latest_proposed > FREE_FORM_REPLY_WINDOW_SECONDS || !may_be_pending_reply(user_message) {
+            return None;
+        }
+        let timezone = user_timezone(state, user.id);
+        let pending = actions.iter()
+            .filter_map(|action| {
+                let mut payload: ActionPayload = serde_json::from_str(&action.encrypted_payload).ok()?;
+                let description = payload.describe(&timezone);
+                // the attachment is a data url, the model only needs to know it's there
+                if let ActionPayload::ChatMessage { media_url: Some(media_url), .. } = &mut payload {
+                    *media_url = format!("({})", attachment_kind(media_url));
+                }
+                let values = serde_json::to_string(&payload).unwrap_or_default();
+                Some(format!("#{} {} | current values: {}", action.number, description, values))
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let reply = interpret_reply(state, user, &pending, user_message).await?;
+        if reply.intent == "unrelated" {
+            return None;
+        }
+        // Unrelated messages are billed with the agent's answer
+        if let Err(e) = crate::utils::usage::deduct_user_credits(state, user.id, "message", None) {
+            tracing::error!("Failed to deduct user credits: {}", e);
+        }
+        (reply.intent, reply.number, reply.changes)
+    };
+
+    let response = match number {
+        Some(number) => resolve_action(state, user, number, &intent, &changes).await,
+        None if actions.len() == 1 => resolve_action(state, user, actions[0].number, &intent, &changes).await,
+        None if intent == "cancel" => {
+            for action in actions.iter() {
+                if let Err(e) = state.user_repository.delete_pending_action(user.id, action.number) {
+                    tracing::error!("Failed to delete pending action: {}", e);
+                }
             }
+            "Discarded all pending actions.".to_string()
+        }
+        None => format!("Which one? Reply e.g. 'yes {}'.\n{}", actions[0].number, list_pending_actions(state, user)),
+    };
+
+    if !is_test {
+        if let Err(e) = crate::api::twilio_utils::send_conversation_message(state, &response, None, user).await.map_err(|e| e.to_string()) {
+            tracing::error!("Failed to send pending action reply: {}", e);
         }
     }
 
-    // Clear the confirmation state
-    if let Err(e) = state.user_core.clear_confirm_send_event(user.id) {
-        tracing::error!("Failed to clear confirmation state: {}", e);
+    Some((
+        StatusCode::OK,
+        [(axum::http::header::CONTENT_TYPE, "application/json")],
+        Json(TwilioResponse {
+            message: response,
+        })
+    ))
+}
+
+pub fn get_resolve_pending_action_tool() -> chat_completion::Tool {
+    let mut properties = change_properties();
+    properties.insert(
+        "decision".to_string(),
+        Box::new(types::JSONSchemaDefine {
+            schema_type: Some(types::JSONSchemaType::String),
+            description: Some("confirm to carry the action out, cancel to discard it, modify to change it (the user is asked again), list to hear what is pending".to_string()),
+            enum_values: Some(vec![
+                "confirm".to_string(),
+                "cancel".to_string(),
+                "modify".to_string(),
+                "list".to_string(),
+            ]),
+            ..Default::default()
+        }),
+    );
+    properties.insert(
+        "number".to_string(),
+        Box::new(types::JSONSchemaDefine {
+            schema_type: Some(types::JSONSchemaType::Number),
+            description: Some("Number of the pending action, can be left out when only one is pending".to_string()),
+            ..Default::default()
+        }),
+    );
+
+    chat_completion::Tool {
+        r#type: chat_completion::ToolType::Function,
+        function: types::Function {
+            name: String::from("resolve_pending_action"),
+            description: Some(String::from("Confirms, cancels or changes an action that is waiting for the user's confirmation (messages, emails, calendar events and tasks). Use this when the user answers a confirmation question.")),
+            parameters: types::FunctionParameters {
+                schema_type: types::JSONSchemaType::Object,
+                properties: Some(properties),
+                required: Some(vec![String::from("decision")]),
+            },
+        },
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ResolvePendingActionArgs {
+    pub decision: String,
+    #[serde(default)]
+    pub number: Option<i32>,
+    #[serde(flatten)]
+    pub changes: ActionChanges,
+}
+
+use futures::future::BoxFuture;
+use crate::tool_call_utils::registry::{Tool, ToolContext, ToolOutput};
+
+pub struct ResolvePendingAction;
+
+impl Tool for ResolvePendingAction {
+    type Args = ResolvePendingActionArgs;
+    const NAME: &'static str = "resolve_pending_action";
+
+    fn definition() -> chat_completion::Tool {
+        get_resolve_pending_action_tool()
     }
 
-    ConfirmationResult {
-        should_continue,
-        response,
+    fn execute<'a>(ctx: &'a ToolContext<'a>, args: Self::Args) -> BoxFuture<'a, ToolOutput> {
+        Box::pin(async move {
+            if args.decision == "list" {
+                return ToolOutput::Answer(list_pending_actions(ctx.state, ctx.user));
+            }
+            let number = match args.number {
+                Some(number) => number,
+                None => match ctx.state.user_repository.get_pending_actions(ctx.user.id) {
+                    Ok(actions) if actions.len() == 1 => actions[0].number,
+                    Ok(actions) if actions.is_empty() => return ToolOutput::Answer("Nothing is waiting for confirmation.".to_string()),
+                    Ok(_) => return ToolOutput::Answer(format!("Several actions are pending, ask which one:\n{}", list_pending_actions(ctx.state, ctx.user))),
+                    Err(e) => {
+                        tracing::error!("Failed to get pending actions: {}", e);
+                        return ToolOutput::Answer("Failed to get the pending actions.".to_string());
+                    }
+                },
+            };
+            ToolOutput::Answer(resolve_action(ctx.state, ctx.user, number, &args.decision, &args.changes).await)
+        })
     }
 }
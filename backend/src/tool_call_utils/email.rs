@@ -2,16 +2,18 @@ use crate::handlers::imap_handlers::{self, ImapError};
 use crate::AppState;
 use std::sync::Arc;
 
+const ACCOUNT_DESCRIPTION: &str = "Only when the user names one of their email accounts, e.g. 'work' for 'check my work email'. Leave out to look at all accounts.";
+
 pub fn get_fetch_emails_tool() -> openai_api_rs::v1::chat_completion::Tool {
     use openai_api_rs::v1::{chat_completion, types};
     use std::collections::HashMap;
 
     let mut email_properties = HashMap::new();
     email_properties.insert(
-        "param".to_string(),
+        "account".to_string(),
         Box::new(types::JSONSchemaDefine {
             schema_type: Some(types::JSONSchemaType::String),
-            description: Some("Can be anything, will fetch last 5 emails regardless".to_string()),
+            description: Some(ACCOUNT_DESCRIPTION.to_string()),
             ..Default::default()
         }),
     );
@@ -20,7 +22,7 @@ pub fn get_fetch_emails_tool() -> openai_api_rs::v1::chat_completion::Tool {
         r#type: chat_completion::ToolType::Function,
         function: types::Function {
             name: String::from("fetch_emails"),
-            description: Some(String::from("Fetches the last 5 emails using IMAP. Use this when user asks about their recent emails or wants to check their inbox.")),
+            description: Some(String::from("Fetches the last 5 emails using IMAP, across all of the user's email accounts unless one is named. Use this when user asks about their recent emails or wants to check their inbox.")),
             parameters: types::FunctionParameters {
                 schema_type: types::JSONSchemaType::Object,
                 properties: Some(email_properties),
@@ -43,6 +45,14 @@ pub fn get_fetch_specific_email_tool() -> openai_api_rs::v1::chat_completion::To
             ..Default::default()
         }),
     );
+    specific_email_properties.insert(
+        "account".to_string(),
+        Box::new(types::JSONSchemaDefine {
+            schema_type: Some(types::JSONSchemaType::String),
+            description: Some(ACCOUNT_DESCRIPTION.to_string()),
+            ..Default::default()
+        }),
+    );
 
     chat_completion::Tool {
         r#type: chat_completion::ToolType::Function,
@@ -58,13 +68,30 @@ pub fn get_fetch_specific_email_tool() -> openai_api_rs::v1::chat_completion::To
     }
 }
 
-pub async fn handle_fetch_emails(state: &Arc<AppState>, user_id: i32) -> String {
+// The account the tool call names, None for all of them
+fn resolve_account_arg(state: &Arc<AppState>, user_id: i32, account: Option<&str>) -> Result<Option<i32>, String> {
+    match account.filter(|name| !name.trim().is_empty()) {
+        Some(name) => imap_handlers::resolve_account(state, user_id, name).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// The latest emails of the named account, or of all accounts for None.
+pub async fn handle_fetch_emails(state: &Arc<AppState>, user_id: i32, account: Option<&str>) -> String {
     let auth_user = crate::handlers::auth_middleware::AuthUser {
         user_id,
         is_admin: false,
     };
 
-    let query_obj = crate::handlers::imap_handlers::FetchEmailsQuery { limit: None };
+    let account_id = match resolve_account_arg(state, user_id, account) {
+        Ok(account_id) => account_id,
+        Err(e) => return e,
+    };
+    // Which account an email is from only matters when there is more than one
+    let show_account = account_id.is_none()
+        && state.user_repository.get_imap_accounts(user_id).map(|accounts| accounts.len() > 1).unwrap_or(false);
+
+    let query_obj = crate::handlers::imap_handlers::FetchEmailsQuery { limit: None, account: account_id };
 
     match crate::handlers::imap_handlers::fetch_full_imap_emails(
         axum::extract::State(state.clone()),
@@ -81,6 +108,10 @@ pub async fn handle_fetch_emails(state: &Arc<AppState>, user_id: i32) -> String
                         let date_formatted = email.get("date_formatted")
                             .and_then(|d| d.as_str())
                             .unwrap_or("Unknown date");
+                        let date_formatted = match email.get("account").and_then(|a| a.as_str()) {
+                            Some(account) if show_account => format!("{}, {}", date_formatted, account),
+                            _ => date_formatted.to_string(),
+                        };
                         
                         if i == 0 {
                             response.push_str(&format!("{}. {} from {} ({}):\n", i + 1, subject, from, date_formatted));
@@ -116,12 +147,14 @@ pub async fn handle_fetch_emails(state: &Arc<AppState>, user_id: i32) -> String
     }
 }
 
-pub async fn handle_fetch_specific_email(state: &Arc<AppState>, user_id: i32, query: &str) -> String {
-    // Create OpenAI client for email selection
-    let client = match crate::tool_call_utils::utils::create_openai_client(&state) {
-        Ok(client) => client,
+/// The id of the email best matching the query, among the latest ones of one account or all for None.
+pub async fn handle_fetch_specific_email(state: &Arc<AppState>, user_id: i32, query: &str, account_id: Option<i32>) -> String {
+
+    // Picking the email is part of answering the user so it runs on the agent models
+    let llm = match crate::utils::llm_provider::LlmChain::for_purpose(state, crate::utils::llm_provider::LlmPurpose::Agent, Some(user_id)) {
+        Ok(llm) => llm,
         Err(e) => {
-            eprintln!("Failed to create OpenAI client: {}", e);
+            eprintln!("Failed to set up llm chain: {}", e);
             return "Failed to process email search".to_string();
         }
     };
@@ -130,7 +163,7 @@ pub async fn handle_fetch_specific_email(state: &Arc<AppState>, user_id: i32, qu
     let user_id_clone = user_id.clone();
 
     // Fetch the latest 20 emails with full content
-    match crate::handlers::imap_handlers::fetch_emails_imap(&state_clone, user_id_clone, true, Some(20), false, false).await {
+    match crate::handlers::imap_handlers::fetch_emails_imap(&state_clone, user_id_clone, account_id, true, Some(20), false, false).await {
         Ok(emails) => {
             if emails.is_empty() {
                 return "No emails found".to_string();
@@ -140,8 +173,9 @@ pub async fn handle_fetch_specific_email(state: &Arc<AppState>, user_id: i32, qu
             let mut formatted_emails = String::new();
             for email in emails.iter() {
                 let formatted_email = format!(
-                    "email_id {}:\nFrom: {}\nSubject: {}\nDate: {}\n\n{}\n\n",
+                    "email_id {}:\nAccount: {}\nFrom: {}\nSubject: {}\nDate: {}\n\n{}\n\n",
                     email.id,
+                    email.account,
                     email.from.as_deref().unwrap_or("Unknown"),
                     email.subject.as_deref().unwrap_or("No subject"),
                     email.date_formatted.as_deref().unwrap_or("No date"),
@@ -151,9 +185,7 @@ pub async fn handle_fetch_specific_email(state: &Arc<AppState>, user_id: i32, qu
             }
 
             // Use LLM to select the most relevant email
-            match crate::tool_call_utils::utils::select_most_relevant_email(&client, 
-                openai_api_rs::v1::common::GPT4_O.to_string(),
-                query, &formatted_emails).await {
+            match crate::tool_call_utils::utils::select_most_relevant_email(&llm, query, &formatted_emails).await {
                 Ok((selected_email_id, _)) => selected_email_id,
                 Err(e) => {
                     eprintln!("Failed to select relevant email: {}", e);
@@ -175,3 +207,171 @@ pub async fn handle_fetch_specific_email(state: &Arc<AppState>, user_id: i32, qu
     }
 }
 
+
+pub fn get_respond_to_email_tool() -> openai_api_rs::v1::chat_completion::Tool {
+    use openai_api_rs::v1::{chat_completion, types};
+    use std::collections::HashMap;
+
+    let mut respond_properties = HashMap::new();
+    respond_properties.insert(
+        "email_id".to_string(),
+        Box::new(types::JSONSchemaDefine {
+            schema_type: Some(types::JSONSchemaType::String),
+            description: Some("The ID of the email to reply to, as returned by fetch_specific_email".to_string()),
+            ..Default::default()
+        }),
+    );
+    respond_properties.insert(
+        "response_text".to_string(),
+        Box::new(types::JSONSchemaDefine {
+            schema_type: Some(types::JSONSchemaType::String),
+            description: Some("The text of the reply to send".to_string()),
+            ..Default::default()
+        }),
+    );
+
+    chat_completion::Tool {
+        r#type: chat_completion::ToolType::Function,
+        function: types::Function {
+            name: String::from("respond_to_email"),
+            description: Some(String::from("Reply to a specific email. Find the email first with fetch_specific_email to get its ID. The user will be asked to confirm before anything is sent.")),
+            parameters: types::FunctionParameters {
+                schema_type: types::JSONSchemaType::Object,
+                properties: Some(respond_properties),
+                required: Some(vec![String::from("email_id"), String::from("response_text")]),
+            },
+        },
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct RespondToEmailArgs {
+    pub email_id: String,
+    pub response_text: String,
+}
+
+/// Stores the reply as a pending action and texts the user the confirmation prompt.
+/// The actual send happens in confirm.rs once the user answers yes.
+pub async fn handle_respond_to_email(
+    state: &Arc<AppState>,
+    user: &crate::models::user_models::User,
+    args: RespondToEmailArgs,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let email = imap_handlers::fetch_single_email_imap(state, user.id, &args.email_id).await
+        .map_err(|e| format!("Failed to fetch email details: {:?}", e))?;
+    let subject = email.subject.unwrap_or_else(|| "No subject".to_string());
+
+    let account_id = match imap_handlers::parse_email_id(&args.email_id) {
+        Some((Some(account_id), _)) => Some(account_id),
+        _ => imap_handlers::default_account_id(state, user.id),
+    };
+    let action = crate::tool_call_utils::confirm::ActionPayload::EmailReply {
+        email_id: args.email_id,
+        subject,
+        response_text: args.response_text,
+        account_id,
+    };
+    let confirmation_message = crate::tool_call_utils::confirm::propose_action(state, user, action).await?;
+
+    Ok(confirmation_message)
+}
+
+use futures::future::BoxFuture;
+use crate::tool_call_utils::registry::{Tool, ToolContext, ToolOutput};
+
+#[derive(serde::Deserialize)]
+pub struct FetchEmailsArgs {
+    #[serde(default)]
+    pub account: Option<String>,
+}
+
+pub struct FetchEmails;
+
+impl Tool for FetchEmails {
+    type Args = FetchEmailsArgs;
+    const NAME: &'static str = "fetch_emails";
+
+    fn definition() -> openai_api_rs::v1::chat_completion::Tool {
+        get_fetch_emails_tool()
+    }
+
+    fn execute<'a>(ctx: &'a ToolContext<'a>, args: Self::Args) -> BoxFuture<'a, ToolOutput> {
+        Box::pin(async move {
+            ToolOutput::Answer(handle_fetch_emails(ctx.state, ctx.user.id, args.account.as_deref()).await)
+        })
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct EmailQuery {
+    pub query: String,
+    #[serde(default)]
+    pub account: Option<String>,
+}
+
+pub struct FetchSpecificEmail;
+
+impl Tool for FetchSpecificEmail {
+    type Args = EmailQuery;
+    const NAME: &'static str = "fetch_specific_email";
+
+    fn definition() -> openai_api_rs::v1::chat_completion::Tool {
+        get_fetch_specific_email_tool()
+    }
+
+    fn execute<'a>(ctx: &'a ToolContext<'a>, args: Self::Args) -> BoxFuture<'a, ToolOutput> {
+        Box::pin(async move {
+            let account_id = match resolve_account_arg(ctx.state, ctx.user.id, args.account.as_deref()) {
+                Ok(account_id) => account_id,
+                Err(e) => return ToolOutput::Answer(e),
+            };
+            // First get the email ID
+            let email_id = handle_fetch_specific_email(ctx.state, ctx.user.id, &args.query, account_id).await;
+            let auth_user = crate::handlers::auth_middleware::AuthUser {
+                user_id: ctx.user.id,
+                is_admin: false,
+            };
+
+            // Then fetch the complete email with that ID
+            match imap_handlers::fetch_single_imap_email(axum::extract::State(ctx.state.clone()), auth_user, axum::extract::Path(email_id.clone())).await {
+                Ok(email) => {
+                    let email = &email["email"];
+                    // Format the response with all email details, id is needed for replying
+                    ToolOutput::Answer(format!(
+                        "Email ID: {}\nAccount: {}\nFrom: {}\nSubject: {}\nDate: {}\n\n{}",
+                        email_id,
+                        email["account"],
+                        email["from"],
+                        email["subject"],
+                        email["date_formatted"],
+                        email["body"]
+                    ))
+                },
+                Err(_) => ToolOutput::Answer("Failed to fetch the complete email".to_string()),
+            }
+        })
+    }
+}
+
+pub struct RespondToEmail;
+
+impl Tool for RespondToEmail {
+    type Args = RespondToEmailArgs;
+    const NAME: &'static str = "respond_to_email";
+
+    fn definition() -> openai_api_rs::v1::chat_completion::Tool {
+        get_respond_to_email_tool()
+    }
+
+    fn execute<'a>(ctx: &'a ToolContext<'a>, args: Self::Args) -> BoxFuture<'a, ToolOutput> {
+        Box::pin(async move {
+            match handle_respond_to_email(ctx.state, ctx.user, args).await {
+                Ok(confirmation) => ToolOutput::Handled(confirmation),
+                Err(e) => {
+                    tracing::error!("Failed to prepare email response: {}", e);
+                    ToolOutput::Answer("Failed to prepare the email reply. Please try again later.".to_string())
+                }
+            }
+        })
+    }
+}
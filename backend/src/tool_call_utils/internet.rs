@@ -215,34 +215,41 @@ pub async fn handle_qr_scan(image_url: Option<&str>) -> String {
 }
 
 pub async fn scan_qr_code(image_url: &str) -> Result<MenuContent, Box<dyn Error>> {
-    tracing::info!("Starting QR code scan for URL: {}", image_url);
-    
-    // Download the image
-    tracing::info!("Downloading image...");
-    let response = match reqwest::get(image_url).await {
-        Ok(resp) => {
-            if !resp.status().is_success() {
-                tracing::error!("Failed to download image. Status: {}", resp.status());
-                return Err(format!("Failed to download image. Status: {}", resp.status()).into());
+    tracing::info!("Starting QR code scan for URL: {}", image_url.chars().take(100).collect::<String>());
+
+    // MMS images come in as data urls, the Twilio copy is deleted once downloaded
+    let image_bytes: Vec<u8> = if let Some(data) = image_url.strip_prefix("data:") {
+        use base64::Engine as _;
+        let encoded = data.split_once(";base64,").map(|(_, b)| b).ok_or("Unsupported data url")?;
+        base64::engine::general_purpose::STANDARD.decode(encoded)?
+    } else {
+        // Download the image
+        tracing::info!("Downloading image...");
+        let response = match reqwest::get(image_url).await {
+            Ok(resp) => {
+                if !resp.status().is_success() {
+                    tracing::error!("Failed to download image. Status: {}", resp.status());
+                    return Err(format!("Failed to download image. Status: {}", resp.status()).into());
+                }
+                resp
+            },
+            Err(e) => {
+                tracing::error!("Failed to make request: {}", e);
+                return Err(Box::new(e));
             }
-            resp
-        },
-        Err(e) => {
-            tracing::error!("Failed to make request: {}", e);
-            return Err(Box::new(e));
-        }
-    };
+        };
     
-    // Get image bytes
-    tracing::info!("Getting image bytes...");
-    let image_bytes = match response.bytes().await {
-        Ok(bytes) => {
-            tracing::info!("Downloaded {} bytes", bytes.len());
-            bytes
-        },
-        Err(e) => {
-            tracing::error!("Failed to get image bytes: {}", e);
-            return Err(Box::new(e));
+        // Get image bytes
+        tracing::info!("Getting image bytes...");
+        match response.bytes().await {
+            Ok(bytes) => {
+                tracing::info!("Downloaded {} bytes", bytes.len());
+                bytes.to_vec()
+            },
+            Err(e) => {
+                tracing::error!("Failed to get image bytes: {}", e);
+                return Err(Box::new(e));
+            }
         }
     };
     
@@ -362,3 +369,156 @@ pub async fn handle_directions_tool(
         }
     }
 }
+
+use futures::future::BoxFuture;
+use serde::Deserialize;
+use crate::tool_call_utils::registry::{Channel, NoArgs, Tool, ToolContext, ToolOutput};
+
+pub struct ScanQrCode;
+
+impl Tool for ScanQrCode {
+    type Args = NoArgs;
+    const NAME: &'static str = "scan_qr_code";
+
+    fn definition() -> chat_completion::Tool {
+        get_scan_qr_code_tool()
+    }
+
+    // needs the picture attached to the message
+    fn available_on(channel: Channel) -> bool {
+        channel == Channel::Sms
+    }
+
+    fn execute<'a>(ctx: &'a ToolContext<'a>, _args: Self::Args) -> BoxFuture<'a, ToolOutput> {
+        Box::pin(async move {
+            tracing::debug!("Executing scan_qr_code tool call with url: {:#?}", ctx.image_url);
+            ToolOutput::Answer(handle_qr_scan(ctx.image_url).await)
+        })
+    }
+}
+
+#[derive(Deserialize)]
+pub struct PerplexityArgs {
+    pub query: String,
+}
+
+pub struct AskPerplexity;
+
+impl Tool for AskPerplexity {
+    type Args = PerplexityArgs;
+    const NAME: &'static str = "ask_perplexity";
+
+    fn definition() -> chat_completion::Tool {
+        get_ask_perplexity_tool()
+    }
+
+    fn execute<'a>(ctx: &'a ToolContext<'a>, args: Self::Args) -> BoxFuture<'a, ToolOutput> {
+        Box::pin(async move {
+            let (query, sys_prompt) = match ctx.channel {
+                Channel::Sms => (
+                    format!("User info: {}. Query: {}", ctx.user_given_info, args.query),
+                    format!("You are assisting an AI text messaging service. The questions you receive are from text messaging conversations where users are seeking information or help. Please note: 1. Provide clear, conversational responses that can be easily read from a small screen 2. Avoid using any markdown, HTML, or other markup languages 3. Keep responses concise but informative 4. When listing multiple points, use simple numbering (1, 2, 3) 5. Focus on the most relevant information that addresses the user's immediate needs. This is what you should know about the user who this information is going to in their own words: {}", ctx.user_given_info),
+                ),
+                Channel::Voice => (
+                    args.query,
+                    "You are assisting an AI voice calling service. The questions you receive are from voice conversations where users are seeking information or help. Please note: 1. Provide clear, conversational responses that can be easily read aloud 2. Avoid using any markdown, HTML, or other markup languages 3. Keep responses concise but informative 4. Use natural language sentence structure 5. When listing multiple points, use simple numbering (1, 2, 3) or natural language transitions (First... Second... Finally...) 6. Focus on the most relevant information that addresses the user's immediate needs 7. If specific numbers, dates, or proper names are important, spell them out clearly 8. Format numerical data in a way that's easy to read aloud (e.g., twenty-five percent instead of 25%) Your responses will be incorporated into a voice conversation, so clarity and natural flow are essential.".to_string(),
+                ),
+            };
+
+            match crate::utils::tool_exec::ask_perplexity(ctx.state, &query, &sys_prompt).await {
+                Ok(answer) => ToolOutput::Answer(answer),
+                Err(e) => {
+                    tracing::error!("Failed to get perplexity answer: {}", e);
+                    ToolOutput::Answer("Failed to get an answer right now. Please try again later.".to_string())
+                }
+            }
+        })
+    }
+}
+
+#[derive(Deserialize)]
+pub struct FirecrawlArgs {
+    pub query: String,
+}
+
+pub struct FirecrawlSearch;
+
+impl Tool for FirecrawlSearch {
+    type Args = FirecrawlArgs;
+    const NAME: &'static str = "search_firecrawl";
+
+    fn definition() -> chat_completion::Tool {
+        get_firecrawl_search_tool()
+    }
+
+    fn execute<'a>(_ctx: &'a ToolContext<'a>, args: Self::Args) -> BoxFuture<'a, ToolOutput> {
+        Box::pin(async move {
+            match crate::utils::tool_exec::handle_firecrawl_search(args.query, 5).await {
+                Ok(answer) => ToolOutput::Answer(answer),
+                Err(e) => {
+                    tracing::error!("Failed to get fire crawl answer: {}", e);
+                    ToolOutput::Answer("Failed to search the web right now. Please try again later.".to_string())
+                }
+            }
+        })
+    }
+}
+
+#[derive(Deserialize)]
+pub struct WeatherArgs {
+    pub location: String,
+    pub units: String,
+}
+
+pub struct GetWeather;
+
+impl Tool for GetWeather {
+    type Args = WeatherArgs;
+    const NAME: &'static str = "get_weather";
+
+    fn definition() -> chat_completion::Tool {
+        get_weather_tool()
+    }
+
+    fn execute<'a>(ctx: &'a ToolContext<'a>, args: Self::Args) -> BoxFuture<'a, ToolOutput> {
+        Box::pin(async move {
+            match crate::utils::tool_exec::get_weather(ctx.state, &args.location, &args.units, ctx.user.id).await {
+                Ok(answer) => ToolOutput::Answer(answer),
+                Err(e) => {
+                    tracing::error!("Failed to get weather answer: {}", e);
+                    ToolOutput::Answer("Failed to get the weather right now. Please try again later.".to_string())
+                }
+            }
+        })
+    }
+}
+
+#[derive(Deserialize)]
+pub struct DirectionsArgs {
+    pub start_address: String,
+    pub end_address: String,
+    pub mode: Option<String>,
+}
+
+pub struct GetDirections;
+
+impl Tool for GetDirections {
+    type Args = DirectionsArgs;
+    const NAME: &'static str = "get_directions";
+
+    fn definition() -> chat_completion::Tool {
+        get_directions_tool()
+    }
+
+    fn execute<'a>(_ctx: &'a ToolContext<'a>, args: Self::Args) -> BoxFuture<'a, ToolOutput> {
+        Box::pin(async move {
+            match handle_directions_tool(args.start_address, args.end_address, args.mode).await {
+                Ok(answer) => ToolOutput::Answer(answer),
+                Err(e) => {
+                    tracing::error!("Failed to get directions answer: {}", e);
+                    ToolOutput::Answer("Failed to get directions right now. Please try again later.".to_string())
+                }
+            }
+        })
+    }
+}
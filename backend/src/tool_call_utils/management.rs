@@ -37,10 +37,8 @@ pub struct ProactiveAgentArgs {
 pub async fn handle_set_proactive_agent(
     state: &Arc<AppState>,
     user_id: i32,
-    args: &str,
+    args: ProactiveAgentArgs,
 ) -> Result<String, Box<dyn Error>> {
-    let args: ProactiveAgentArgs = serde_json::from_str(args)?;
-
     // Assuming there's a method to update the proactive agent status for the user
     state.user_core.update_proactive_agent_on(user_id, args.enabled).map_err(|e| Box::new(e) as Box<dyn Error>)?;
 
@@ -122,10 +120,8 @@ pub struct WaitingCheckArgs {
 pub async fn handle_create_waiting_check(
     state: &Arc<AppState>,
     user_id: i32,
-    args: &str,
+    args: WaitingCheckArgs,
 ) -> Result<String, Box<dyn Error>> {
-    let args: WaitingCheckArgs = serde_json::from_str(args)?;
-
     let new_check = crate::models::user_models::NewWaitingCheck {
         user_id,
         content: args.content,
@@ -137,3 +133,52 @@ pub async fn handle_create_waiting_check(
 
     Ok("I'll keep an eye out for that and notify you when I find it.".to_string())
 }
+
+use futures::future::BoxFuture;
+use crate::tool_call_utils::registry::{Tool, ToolContext, ToolOutput};
+
+pub struct CreateWaitingCheck;
+
+impl Tool for CreateWaitingCheck {
+    type Args = WaitingCheckArgs;
+    const NAME: &'static str = "create_waiting_check";
+
+    fn definition() -> openai_api_rs::v1::chat_completion::Tool {
+        get_create_waiting_check_tool()
+    }
+
+    fn execute<'a>(ctx: &'a ToolContext<'a>, args: Self::Args) -> BoxFuture<'a, ToolOutput> {
+        Box::pin(async move {
+            match handle_create_waiting_check(ctx.state, ctx.user.id, args).await {
+                Ok(answer) => ToolOutput::Answer(answer),
+                Err(e) => {
+                    tracing::error!("Failed to create waiting check: {}", e);
+                    ToolOutput::Answer("Sorry, I couldn't create a waiting check. (Contact rasmus@ahtava.com pls:D)".to_string())
+                }
+            }
+        })
+    }
+}
+
+pub struct UpdateMonitoringStatus;
+
+impl Tool for UpdateMonitoringStatus {
+    type Args = ProactiveAgentArgs;
+    const NAME: &'static str = "update_monitoring_status";
+
+    fn definition() -> openai_api_rs::v1::chat_completion::Tool {
+        get_update_monitoring_status_tool()
+    }
+
+    fn execute<'a>(ctx: &'a ToolContext<'a>, args: Self::Args) -> BoxFuture<'a, ToolOutput> {
+        Box::pin(async move {
+            match handle_set_proactive_agent(ctx.state, ctx.user.id, args).await {
+                Ok(answer) => ToolOutput::Answer(answer),
+                Err(e) => {
+                    tracing::error!("Failed to toggle monitoring status: {}", e);
+                    ToolOutput::Answer("Sorry, I failed to toggle monitoring status. (Contact rasmus@ahtava.com pls:D)".to_string())
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,169 @@
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use openai_api_rs::v1::chat_completion;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+use crate::models::user_models::User;
+use crate::AppState;
+
+/// Where a tool call came from. Most tools behave the same on both, some shape
+/// their answer for the medium (read aloud vs small screen).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Channel {
+    Sms,
+    Voice,
+}
+
+pub struct ToolContext<'a> {
+    pub state: &'a Arc<AppState>,
+    pub user: &'a User,
+    pub channel: Channel,
+    pub image_url: Option<&'a str>, // first image of the incoming message, sms only
+    pub media_url: Option<&'a str>, // first attachment of any kind, sms only
+    pub user_given_info: &'a str,   // what the user has told about themselves in settings
+}
+
+pub enum ToolOutput {
+    // Result for the model to build its answer from
+    Answer(String),
+    // Tool already messaged the user itself (confirmation prompts etc.), nothing left to answer
+    Handled(String),
+}
+
+/// Args type for tools that take no parameters. Unknown fields are ignored.
+#[derive(Deserialize)]
+pub struct NoArgs {}
+
+/// A capability the assistant can use. Implemented once and registered in
+/// `ToolRegistry::new`, which makes it available to the sms agent and as
+/// `/api/call/{NAME}` for the voice agent.
+pub trait Tool: Send + Sync + 'static {
+    type Args: DeserializeOwned + Send + 'static;
+
+    const NAME: &'static str;
+
+    fn definition() -> chat_completion::Tool;
+
+    fn available_on(_channel: Channel) -> bool {
+        true
+    }
+
+    fn execute<'a>(ctx: &'a ToolContext<'a>, args: Self::Args) -> BoxFuture<'a, ToolOutput>;
+}
+
+// Object safe view of a Tool so different Args types can live in one list
+trait ErasedTool: Send + Sync {
+    fn definition(&self) -> chat_completion::Tool;
+    fn available_on(&self, channel: Channel) -> bool;
+    fn call<'a>(&self, ctx: &'a ToolContext<'a>, args: &str) -> Result<BoxFuture<'a, ToolOutput>, serde_json::Error>;
+}
+
+struct Registered<T>(PhantomData<fn() -> T>);
+
+impl<T: Tool> ErasedTool for Registered<T> {
+    fn definition(&self) -> chat_completion::Tool {
+        T::definition()
+    }
+
+    fn available_on(&self, channel: Channel) -> bool {
+        T::available_on(channel)
+    }
+
+    fn call<'a>(&self, ctx: &'a ToolContext<'a>, args: &str) -> Result<BoxFuture<'a, ToolOutput>, serde_json::Error> {
+        let args: T::Args = serde_json::from_str(args)?;
+        Ok(T::execute(ctx, args))
+    }
+}
+
+pub struct ToolRegistry {
+    tools: Vec<(&'static str, Box<dyn ErasedTool>)>,
+}
+
+impl ToolRegistry {
+    pub fn new() -> Self {
+        use crate::tool_call_utils::{bridge, calendar, confirm, email, internet, management, scheduled, tasks};
+
+        let mut registry = Self { tools: Vec::new() };
+        registry.register::<bridge::SendChatMessage>();
+        registry.register::<bridge::FetchChatMessages>();
+        registry.register::<bridge::FetchRecentMessages>();
+        registry.register::<bridge::SearchChatContacts>();
+        registry.register::<bridge::SearchMessages>();
+        registry.register::<email::FetchEmails>();
+        registry.register::<email::FetchSpecificEmail>();
+        registry.register::<email::RespondToEmail>();
+        registry.register::<calendar::FetchCalendarEvents>();
+        registry.register::<calendar::CreateCalendarEvent>();
+        registry.register::<tasks::FetchTasks>();
+        registry.register::<tasks::CreateTask>();
+        registry.register::<confirm::ResolvePendingAction>();
+        registry.register::<scheduled::ScheduleMessage>();
+        registry.register::<scheduled::ManageScheduledMessages>();
+        registry.register::<management::CreateWaitingCheck>();
+        registry.register::<management::UpdateMonitoringStatus>();
+        registry.register::<internet::ScanQrCode>();
+        registry.register::<internet::AskPerplexity>();
+        registry.register::<internet::FirecrawlSearch>();
+        registry.register::<internet::GetWeather>();
+        registry.register::<internet::GetDirections>();
+        registry
+    }
+
+    pub fn register<T: Tool>(&mut self) {
+        debug_assert!(
+            !self.tools.iter().any(|(name, _)| *name == T::NAME),
+            "tool {} registered twice",
+            T::NAME
+        );
+        self.tools.push((T::NAME, Box::new(Registered::<T>(PhantomData))));
+    }
+
+    pub fn definitions(&self, channel: Channel) -> Vec<chat_completion::Tool> {
+        self.tools
+            .iter()
+            .filter(|(_, tool)| tool.available_on(channel))
+            .map(|(_, tool)| tool.definition())
+            .collect()
+    }
+
+    pub fn names(&self, channel: Channel) -> Vec<&'static str> {
+        self.tools
+            .iter()
+            .filter(|(_, tool)| tool.available_on(channel))
+            .map(|(name, _)| *name)
+            .collect()
+    }
+
+    /// Runs a tool by name with raw JSON arguments. Returns None if the tool is unknown,
+    /// not available on the context's channel or the arguments don't parse.
+    pub async fn execute(&self, name: &str, ctx: &ToolContext<'_>, args: &str) -> Option<ToolOutput> {
+        let tool = match self.tools.iter().find(|(n, _)| *n == name) {
+            Some((_, tool)) if tool.available_on(ctx.channel) => tool,
+            _ => {
+                tracing::warn!("Unknown tool requested: {} ({:?})", name, ctx.channel);
+                return None;
+            }
+        };
+
+        // Check if user has access to this tool
+        if crate::tool_call_utils::utils::requires_subscription(name, ctx.user.sub_tier.clone(), ctx.user.discount) {
+            tracing::info!("Attempted to use subscription-only tool {} without proper subscription", name);
+            return Some(ToolOutput::Answer(format!("This feature ({}) requires a subscription. Please visit our website to subscribe.", name)));
+        }
+
+        let args = if args.trim().is_empty() { "{}" } else { args };
+        match tool.call(ctx, args) {
+            Ok(future) => {
+                tracing::debug!("Executing {} tool call", name);
+                Some(future.await)
+            }
+            Err(e) => {
+                tracing::error!("Failed to parse arguments for {}: {}", name, e);
+                None
+            }
+        }
+    }
+}
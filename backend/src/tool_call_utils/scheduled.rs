@@ -0,0 +1,252 @@
+use crate::AppState;
+use crate::models::user_models::User;
+use crate::tool_call_utils::confirm::ActionPayload;
+use std::sync::Arc;
+use serde::Deserialize;
+use openai_api_rs::v1::{chat_completion, types};
+use std::collections::HashMap;
+
+fn string_field(description: &str) -> Box<types::JSONSchemaDefine> {
+    Box::new(types::JSONSchemaDefine {
+        schema_type: Some(types::JSONSchemaType::String),
+        description: Some(description.to_string()),
+        ..Default::default()
+    })
+}
+
+const SEND_AT_DESCRIPTION: &str = "When to send it. Local time in the user's timezone as 'YYYY-MM-DDTHH:MM' (e.g. '2024-03-23T08:00'), or RFC3339 with an offset.";
+
+pub fn get_schedule_message_tool() -> chat_completion::Tool {
+    let mut properties = HashMap::new();
+    properties.insert(
+        "channel".to_string(),
+        Box::new(types::JSONSchemaDefine {
+            schema_type: Some(types::JSONSchemaType::String),
+            description: Some("chat: a message to a contact or group on a chat platform. email: a new email. sms: a text to the user themselves.".to_string()),
+            enum_values: Some(vec!["chat".to_string(), "email".to_string(), "sms".to_string()]),
+            ..Default::default()
+        }),
+    );
+    properties.insert(
+        "platform".to_string(),
+        Box::new(types::JSONSchemaDefine {
+            schema_type: Some(types::JSONSchemaType::String),
+            description: Some("The chat platform, only for chat.".to_string()),
+            enum_values: Some(crate::utils::bridge_service::service_names()),
+            ..Default::default()
+        }),
+    );
+    properties.insert("chat_name".to_string(), string_field("The chat or contact name to send to, only for chat. Doesn't have to be exact since fuzzy search is used."));
+    properties.insert("to".to_string(), string_field("The recipient's email address, only for email."));
+    properties.insert("account".to_string(), string_field("Only for email and only when the user names one of their email accounts to send from, e.g. 'work'. Leave out to send from the default account."));
+    properties.insert("subject".to_string(), string_field("The email subject, only for email."));
+    properties.insert("message".to_string(), string_field("The message, email body or text to send."));
+    properties.insert("send_at".to_string(), string_field(SEND_AT_DESCRIPTION));
+
+    chat_completion::Tool {
+        r#type: chat_completion::ToolType::Function,
+        function: types::Function {
+            name: String::from("schedule_message"),
+            description: Some(String::from(
+                "Schedules a chat message, an email or a text to the user to be sent at a later time, \
+                e.g. 'text Mike on Telegram at 8am tomorrow: happy birthday'. Use send_chat_message or respond_to_email instead when it should go out now."
+            )),
+            parameters: types::FunctionParameters {
+                schema_type: types::JSONSchemaType::Object,
+                properties: Some(properties),
+                required: Some(vec![String::from("channel"), String::from("message"), String::from("send_at")]),
+            },
+        },
+    }
+}
+
+pub fn get_manage_scheduled_messages_tool() -> chat_completion::Tool {
+    let mut properties = HashMap::new();
+    properties.insert(
+        "action".to_string(),
+        Box::new(types::JSONSchemaDefine {
+            schema_type: Some(types::JSONSchemaType::String),
+            description: Some("list to see what is scheduled, update to change the text or time of one, cancel to call one off".to_string()),
+            enum_values: Some(vec!["list".to_string(), "update".to_string(), "cancel".to_string()]),
+            ..Default::default()
+        }),
+    );
+    properties.insert(
+        "id".to_string(),
+        Box::new(types::JSONSchemaDefine {
+            schema_type: Some(types::JSONSchemaType::Number),
+            description: Some("Number of the scheduled message, for update and cancel".to_string()),
+            ..Default::default()
+        }),
+    );
+    properties.insert("message".to_string(), string_field("The complete new text, for update"));
+    properties.insert("send_at".to_string(), string_field(SEND_AT_DESCRIPTION));
+
+    chat_completion::Tool {
+        r#type: chat_completion::ToolType::Function,
+        function: types::Function {
+            name: String::from("manage_scheduled_messages"),
+            description: Some(String::from("Lists, changes or cancels messages the user has scheduled for later.")),
+            parameters: types::FunctionParameters {
+                schema_type: types::JSONSchemaType::Object,
+                properties: Some(properties),
+                required: Some(vec![String::from("action")]),
+            },
+        },
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ScheduleMessageArgs {
+    pub channel: String,
+    pub platform: Option<String>,
+    pub chat_name: Option<String>,
+    pub to: Option<String>,
+    pub subject: Option<String>,
+    #[serde(default)]
+    pub account: Option<String>,
+    pub message: String,
+    pub send_at: String,
+}
+
+#[derive(Deserialize)]
+pub struct ManageScheduledMessagesArgs {
+    pub action: String,
+    pub id: Option<i32>,
+    pub message: Option<String>,
+    pub send_at: Option<String>,
+}
+
+/// Turns the args into what gets sent. Chat names are looked up now so the user
+/// confirms the actual chat and a typo doesn't surface only at send time.
+async fn build_send(state: &Arc<AppState>, user_id: i32, args: ScheduleMessageArgs) -> Result<ActionPayload, String> {
+    match args.channel.as_str() {
+        "chat" => {
+            let (Some(platform), Some(chat_name)) = (args.platform, args.chat_name) else {
+                return Err("Scheduling a chat message needs the platform and the chat name.".to_string());
+            };
+            let rooms = crate::utils::bridge::search_bridge_rooms(&platform, state, user_id, &chat_name)
+                .await
+                .map_err(|_| format!(
+                    "Failed to find contact. Please make sure you're connected to {} bridge.",
+                    crate::utils::bridge::capitalize(&platform)
+                ))?;
+            let Some(best_match) = rooms.first() else {
+                return Err(format!("No {} contacts found matching '{}'.", crate::utils::bridge::capitalize(&platform), chat_name));
+            };
+            Ok(ActionPayload::ChatMessage {
+                platform,
+                recipient: best_match.display_name.trim_end_matches(" (WA)").trim_end_matches(" (Telegram)").to_string(),
+                room_id: None,
+                message: args.message,
+                media_url: None,
+            })
+        }
+        "email" => match args.to {
+            Some(to) if to.contains('@') => {
+                // pinned now so the email goes out from the same account the user confirmed
+                let account_id = match args.account.as_deref().filter(|name| !name.trim().is_empty()) {
+                    Some(name) => crate::handlers::imap_handlers::resolve_account(state, user_id, name)?,
+                    None => crate::handlers::imap_handlers::default_account_id(state, user_id)
+                        .ok_or_else(|| "No email account is connected.".to_string())?,
+                };
+                Ok(ActionPayload::Email {
+                    to,
+                    subject: args.subject.unwrap_or_default(),
+                    body: args.message,
+                    account_id: Some(account_id),
+                })
+            }
+            _ => Err("Scheduling an email needs the recipient's email address.".to_string()),
+        },
+        "sms" => Ok(ActionPayload::Sms { message: args.message }),
+        other => Err(format!("Can't schedule a '{}' message, use chat, email or sms.", other)),
+    }
+}
+
+pub fn handle_manage_scheduled_messages(state: &Arc<AppState>, user: &User, args: ManageScheduledMessagesArgs) -> String {
+    if args.action == "list" {
+        return crate::utils::scheduled_sends::list_text(state, user);
+    }
+    let Some(id) = args.id else {
+        return format!("Which one? The scheduled messages are:\n{}", crate::utils::scheduled_sends::list_text(state, user));
+    };
+    match args.action.as_str() {
+        "cancel" => crate::utils::scheduled_sends::cancel(state, user, id),
+        "update" => match crate::utils::scheduled_sends::update(state, user.id, id, args.message.as_deref(), args.send_at.as_deref()) {
+            Ok(description) => description,
+            Err(e) => e,
+        },
+        other => format!("Unknown action '{}', use list, update or cancel.", other),
+    }
+}
+
+use futures::future::BoxFuture;
+use crate::tool_call_utils::registry::{Tool, ToolContext, ToolOutput};
+
+pub struct ScheduleMessage;
+
+impl Tool for ScheduleMessage {
+    type Args = ScheduleMessageArgs;
+    const NAME: &'static str = "schedule_message";
+
+    fn definition() -> chat_completion::Tool {
+        get_schedule_message_tool()
+    }
+
+    fn execute<'a>(ctx: &'a ToolContext<'a>, args: Self::Args) -> BoxFuture<'a, ToolOutput> {
+        Box::pin(async move {
+            // Validate the time now rather than after the user said yes
+            let timezone = crate::tool_call_utils::confirm::user_timezone(ctx.state, ctx.user.id);
+            let send_at = match crate::utils::scheduled_sends::parse_send_time(&args.send_at, &timezone)
+                .and_then(|time| crate::utils::scheduled_sends::check_send_time(time).map(|_| time))
+            {
+                Ok(time) => time.to_rfc3339(),
+                Err(e) => return ToolOutput::Answer(e),
+            };
+            let send = match build_send(ctx.state, ctx.user.id, args).await {
+                Ok(send) => send,
+                Err(e) => return ToolOutput::Answer(e),
+            };
+
+            let require_confirmation = ctx.state.user_core.get_user_settings(ctx.user.id)
+                .map(|settings| settings.require_confirmation)
+                .unwrap_or(false);
+            if !require_confirmation {
+                return match crate::utils::scheduled_sends::schedule(ctx.state, ctx.user, &send_at, &send) {
+                    Ok(message) => ToolOutput::Answer(message),
+                    Err(e) => ToolOutput::Answer(e),
+                };
+            }
+
+            let action = ActionPayload::ScheduledSend {
+                send_at,
+                send: Box::new(send),
+            };
+            match crate::tool_call_utils::confirm::propose_action(ctx.state, ctx.user, action).await {
+                Ok(prompt) => ToolOutput::Handled(prompt),
+                Err(e) => {
+                    tracing::error!("Failed to propose scheduled message: {}", e);
+                    ToolOutput::Answer("Failed to prepare the scheduled message. Please try again later.".to_string())
+                }
+            }
+        })
+    }
+}
+
+pub struct ManageScheduledMessages;
+
+impl Tool for ManageScheduledMessages {
+    type Args = ManageScheduledMessagesArgs;
+    const NAME: &'static str = "manage_scheduled_messages";
+
+    fn definition() -> chat_completion::Tool {
+        get_manage_scheduled_messages_tool()
+    }
+
+    fn execute<'a>(ctx: &'a ToolContext<'a>, args: Self::Args) -> BoxFuture<'a, ToolOutput> {
+        Box::pin(async move {
+            ToolOutput::Answer(handle_manage_scheduled_messages(ctx.state, ctx.user, args))
+        })
+    }
+}
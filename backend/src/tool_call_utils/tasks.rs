@@ -91,7 +91,6 @@ pub struct CreateTaskArgs {
 pub async fn handle_fetch_tasks(
     state: &Arc<AppState>,
     user_id: i32,
-    args: &str,
 ) -> String {
     match crate::handlers::google_tasks::get_tasks(state, user_id).await {
         Ok(Json(response)) => {
@@ -147,16 +146,8 @@ pub async fn handle_fetch_tasks(
 pub async fn handle_create_task(
     state: &Arc<AppState>,
     user_id: i32,
-    args: &str,
+    args: CreateTaskArgs,
 ) -> String {
-    let args: CreateTaskArgs = match serde_json::from_str(args) {
-        Ok(args) => args,
-        Err(e) => {
-            eprintln!("Failed to parse create task arguments: {}", e);
-            return "Failed to create task due to invalid arguments.".to_string();
-        }
-    };
-
     // Convert due_time string to DateTime<Utc> if provided
     let due_time = if let Some(dt_str) = args.due_time {
         match chrono::DateTime::parse_from_rfc3339(&dt_str) {
@@ -189,3 +180,64 @@ pub async fn handle_create_task(
     }
 }
 
+
+use futures::future::BoxFuture;
+use crate::tool_call_utils::registry::{NoArgs, Tool, ToolContext, ToolOutput};
+
+pub struct FetchTasks;
+
+impl Tool for FetchTasks {
+    type Args = NoArgs;
+    const NAME: &'static str = "fetch_tasks";
+
+    fn definition() -> openai_api_rs::v1::chat_completion::Tool {
+        get_fetch_tasks_tool()
+    }
+
+    fn execute<'a>(ctx: &'a ToolContext<'a>, _args: Self::Args) -> BoxFuture<'a, ToolOutput> {
+        Box::pin(async move {
+            ToolOutput::Answer(handle_fetch_tasks(ctx.state, ctx.user.id).await)
+        })
+    }
+}
+
+pub struct CreateTask;
+
+impl Tool for CreateTask {
+    type Args = CreateTaskArgs;
+    const NAME: &'static str = "create_task";
+
+    fn definition() -> openai_api_rs::v1::chat_completion::Tool {
+        get_create_tasks_tool()
+    }
+
+    fn execute<'a>(ctx: &'a ToolContext<'a>, args: Self::Args) -> BoxFuture<'a, ToolOutput> {
+        Box::pin(async move {
+            let require_confirmation = ctx.state.user_core.get_user_settings(ctx.user.id)
+                .map(|settings| settings.require_confirmation)
+                .unwrap_or(false);
+            if !require_confirmation {
+                return ToolOutput::Answer(handle_create_task(ctx.state, ctx.user.id, args).await);
+            }
+
+            // Validate the due time now rather than after the user said yes
+            if let Some(due) = args.due_time.as_deref() {
+                if chrono::DateTime::parse_from_rfc3339(due).is_err() {
+                    return ToolOutput::Answer(format!("Invalid due time '{}', it must be in RFC3339 format.", due));
+                }
+            }
+            let action = crate::tool_call_utils::confirm::ActionPayload::Task {
+                title: args.title,
+                description: args.description,
+                due_time: args.due_time,
+            };
+            match crate::tool_call_utils::confirm::propose_action(ctx.state, ctx.user, action).await {
+                Ok(prompt) => ToolOutput::Handled(prompt),
+                Err(e) => {
+                    tracing::error!("Failed to propose task: {}", e);
+                    ToolOutput::Answer("Failed to prepare the task. Please try again later.".to_string())
+                }
+            }
+        })
+    }
+}
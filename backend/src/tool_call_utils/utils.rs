@@ -8,6 +8,7 @@ use std::env;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use crate::AppState;
+use crate::utils::llm_provider::LlmChain;
 use std::sync::Arc;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -16,39 +17,18 @@ pub struct ChatMessage {
     pub content: chat_completion::Content,
 }
 
-// Function to create OpenAI client
+// Function to create OpenAI client against OpenRouter with the instance key.
+// Model calls with a purpose should go through llm_provider::LlmChain instead.
 pub fn create_openai_client(
     state: &Arc<AppState>,
 ) -> Result<OpenAIClient, Box<dyn std::error::Error>> {
+    let api_key = crate::utils::llm_provider::openrouter_api_key(state)?;
 
-    let is_self_hosted= std::env::var("ENVIRONMENT") == Ok("self_hosted".to_string());
-    let api_key: String;
-    if is_self_hosted {
-
-        api_key = match state.user_core.get_settings_for_tier3() {
-            Ok((_, _, Some(api_key), _, _, _)) => {
-                tracing::info!("✅ Successfully retrieved self hosted Twilio Auth Token");
-                api_key
-            },
-            Err(e) => {
-                tracing::error!("❌ Failed to get self hosted Twilio Auth Token: {}", e);
-                return Err("❌ Failed to get self hosted Twilio Auth Token".into());
-            },
-            _ => {
-                tracing::error!("❌ Failed to get self hosted Twilio Auth Token");
-                return Err("❌ Failed to get self hosted Twilio Auth Token".into());
-            }
-        };
-    } else {
-        api_key = env::var("OPENROUTER_API_KEY")?;
-        
-    }
-
-        OpenAIClient::builder()
-            .with_endpoint("https://openrouter.ai/api/v1")
-            .with_api_key(api_key)
-            .build()
-            .map_err(|e| e.into())
+    OpenAIClient::builder()
+        .with_endpoint("https://openrouter.ai/api/v1")
+        .with_api_key(api_key)
+        .build()
+        .map_err(|e| e.into())
 }
 
 // Function to create evaluation tool properties
@@ -147,7 +127,7 @@ fn extract_text_from_content(content: &chat_completion::Content) -> String {
 }
 
 pub async fn perform_clarification_check(
-    client: &OpenAIClient,
+    llm: &LlmChain,
     messages: &[ChatMessage],
     user_message: &str,
     ai_response: &str,
@@ -227,14 +207,14 @@ pub async fn perform_clarification_check(
     });
 
     let clarify_req = chat_completion::ChatCompletionRequest::new(
-        "openai/gpt-4o-mini".to_string(),
+        llm.primary_model().to_string(),
         clarify_messages,
     )
     .tools(create_clarify_tools())
     .tool_choice(chat_completion::ToolChoiceType::Required)
     .max_tokens(100);
 
-    match client.chat_completion(clarify_req).await {
+    match llm.chat_completion(clarify_req).await {
         Ok(result) => {
             if let Some(tool_calls) = result.choices[0].message.tool_calls.as_ref() {
                 if let Some(first_call) = tool_calls.first() {
@@ -274,7 +254,7 @@ pub async fn perform_clarification_check(
 }
 
 pub async fn perform_evaluation(
-    client: &OpenAIClient,
+    llm: &LlmChain,
     messages: &[ChatMessage],
     user_message: &str,
     ai_response: &str,
@@ -325,14 +305,14 @@ pub async fn perform_evaluation(
     ];
 
     let eval_req = chat_completion::ChatCompletionRequest::new(
-        "openai/gpt-4o-mini".to_string(),
+        llm.primary_model().to_string(),
         eval_messages,
     )
     .tools(create_eval_tools())
     .tool_choice(chat_completion::ToolChoiceType::Required)
     .max_tokens(200);
 
-    match client.chat_completion(eval_req).await {
+    match llm.chat_completion(eval_req).await {
         Ok(result) => {
             if let Some(tool_calls) = result.choices[0].message.tool_calls.as_ref() {
                 if let Some(first_call) = tool_calls.first() {
@@ -445,8 +425,7 @@ pub struct EmailSelectResponse {
 }
 
 pub async fn select_most_relevant_email(
-    client: &OpenAIClient,
-    model: String,
+    llm: &LlmChain,
     query: &str,
     emails: &str,
 ) -> Result<(String, Option<String>), Box<dyn std::error::Error>> {
@@ -490,14 +469,14 @@ pub async fn select_most_relevant_email(
     ];
 
     let select_req = chat_completion::ChatCompletionRequest::new(
-        model,
+        llm.primary_model().to_string(),
         select_messages,
     )
     .tools(select_tools)
     .tool_choice(chat_completion::ToolChoiceType::Required)
     .max_tokens(200);
 
-    match client.chat_completion(select_req).await {
+    match llm.chat_completion(select_req).await {
         Ok(result) => {
             if let Some(tool_calls) = result.choices[0].message.tool_calls.as_ref() {
                 if let Some(first_call) = tool_calls.first() {
@@ -0,0 +1,177 @@
+use std::sync::Arc;
+
+use crate::handlers::imap_handlers::ImapEmailPreview;
+use crate::AppState;
+
+pub const CONTACT_MODES: [&str; 3] = ["all", "allowlist", "denylist"];
+pub const MAX_MESSAGE_CHARS: usize = 500;
+// Replies are remembered this long, see the cleanup job in jobs::scheduler
+pub const MAX_REPLY_INTERVAL_HOURS: i32 = 30 * 24;
+
+const DEFAULT_MESSAGE: &str = "Hi {name}, I'm away until {until} and not reading messages here. I'll get back to you after that.";
+const DEFAULT_MESSAGE_OPEN_ENDED: &str = "Hi {name}, I'm away for now and not reading messages here. I'll get back to you when I'm back.";
+
+// Automated senders, answering them is useless at best and a reply loop at worst
+const NO_REPLY_MARKERS: &[&str] = &["noreply", "no-reply", "donotreply", "do-not-reply", "mailer-daemon", "postmaster", "bounce"];
+const AUTO_REPLY_SUBJECTS: &[&str] = &["automatic reply", "auto:", "autoreply", "auto-reply", "out of office", "undeliverable", "delivery status"];
+
+/// Turns away mode on from now until `ends_at` (None until turned off), for the AWAY command.
+/// A new away period answers everyone again.
+pub fn start(state: &AppState, user_id: i32, ends_at: Option<i32>) -> Result<(), diesel::result::Error> {
+    state.user_repository.clear_away_replies(user_id)?;
+    state.user_core.start_away_period(user_id, ends_at)
+}
+
+/// The user's away mode while it is on, see the away_ columns of user_settings.
+pub struct AwayMode {
+    message: String,
+    contact_mode: String,
+    contacts: Vec<String>,
+    reply_interval_hours: i32,
+    pub priority_only: bool,
+    ends_at: Option<i32>,
+}
+
+impl AwayMode {
+    /// The away mode if it is on and within its start and end times right now.
+    pub fn active(state: &AppState, user_id: i32) -> Option<Self> {
+        let settings = state.user_core.get_user_settings(user_id).ok()?;
+        if !settings.away_enabled {
+            return None;
+        }
+        let now = chrono::Utc::now().timestamp() as i32;
+        if settings.away_starts_at.is_some_and(|start| now < start) || settings.away_ends_at.is_some_and(|end| now >= end) {
+            return None;
+        }
+
+        let message = settings.encrypted_away_message
+            .as_deref()
+            .and_then(|message| {
+                crate::utils::encryption::decrypt(message)
+                    .map_err(|e| tracing::error!("Failed to decrypt away message of user {}: {:?}", user_id, e))
+                    .ok()
+            })
+            .filter(|message| !message.trim().is_empty())
+            .unwrap_or_else(|| match settings.away_ends_at {
+                Some(_) => DEFAULT_MESSAGE.to_string(),
+                None => DEFAULT_MESSAGE_OPEN_ENDED.to_string(),
+            });
+        let contacts = settings.away_contacts
+            .as_deref()
+            .and_then(|contacts| serde_json::from_str(contacts).ok())
+            .unwrap_or_default();
+
+        Some(AwayMode {
+            message,
+            contact_mode: settings.away_contact_mode,
+            contacts,
+            reply_interval_hours: settings.away_reply_interval_hours.clamp(1, MAX_REPLY_INTERVAL_HOURS),
+            priority_only: settings.away_priority_only,
+            ends_at: settings.away_ends_at,
+        })
+    }
+
+    // Whether the allowlist or denylist lets the contact, known by any of `names`, get a reply
+    fn allows(&self, names: &[&str]) -> bool {
+        let listed = self.contacts.iter().any(|contact| {
+            let contact = contact.trim().to_lowercase();
+            !contact.is_empty() && names.iter().any(|name| name.to_lowercase().contains(&contact))
+        });
+        match self.contact_mode.as_str() {
+            "allowlist" => listed,
+            "denylist" => !listed,
+            _ => true,
+        }
+    }
+
+    fn reply_text(&self, state: &Arc<AppState>, user_id: i32, name: &str) -> String {
+        let until = match self.ends_at.and_then(|end| chrono::DateTime::from_timestamp(end as i64, 0)) {
+            Some(end) => {
+                let timezone = crate::tool_call_utils::confirm::user_timezone(state, user_id);
+                let tz: chrono_tz::Tz = timezone.parse().unwrap_or(chrono_tz::UTC);
+                end.with_timezone(&tz).format("%A %B %d at %I:%M %p").to_string()
+            }
+            None => "further notice".to_string(),
+        };
+        self.message.replace("{name}", name.trim()).replace("{until}", &until)
+    }
+
+    // Records the reply, false if the contact already got one within the interval
+    fn claim(&self, state: &AppState, user_id: i32, platform: &str, contact_key: &str) -> bool {
+        match state.user_repository.claim_away_reply(user_id, platform, contact_key, self.reply_interval_hours * 3600) {
+            Ok(claimed) => claimed,
+            Err(e) => {
+                tracing::error!("Failed to record away reply for user {}: {}", user_id, e);
+                false
+            }
+        }
+    }
+
+    /// The automatic reply for a direct chat message, None when the contact shouldn't get one
+    /// (not on the list or already answered recently). The caller sends it to the room.
+    pub fn chat_reply(
+        &self,
+        state: &Arc<AppState>,
+        user_id: i32,
+        platform: &str,
+        room_id: &str,
+        chat_name: &str,
+        sender_name: &str,
+    ) -> Option<String> {
+        if !self.allows(&[chat_name, sender_name]) || !self.claim(state, user_id, platform, room_id) {
+            return None;
+        }
+        Some(self.reply_text(state, user_id, chat_name))
+    }
+
+    /// Answers the email from the account it came to when its sender should get a reply.
+    /// Only logs on failure, the email is handled as usual either way.
+    pub async fn reply_to_email(&self, state: &Arc<AppState>, user_id: i32, email: &ImapEmailPreview) {
+        // Mailing lists, newsletters and other auto replies, see RFC 3834
+        if email.auto_generated {
+            return;
+        }
+        let Some(address) = email.from_email.as_deref().map(|a| a.trim().to_lowercase()) else {
+            return;
+        };
+        let subject = email.subject.clone().unwrap_or_default();
+        let subject_lower = subject.to_lowercase();
+        if !address.contains('@')
+            || NO_REPLY_MARKERS.iter().any(|marker| address.contains(marker))
+            || AUTO_REPLY_SUBJECTS.iter().any(|marker| subject_lower.starts_with(marker))
+        {
+            return;
+        }
+        // Nor the user's own accounts, e.g. forwarding from one to another
+        let own_addresses = state.user_repository.get_imap_accounts(user_id).unwrap_or_default();
+        if own_addresses.iter().any(|account| account.description.to_lowercase() == address) {
+            return;
+        }
+        let name = email.from.as_deref().unwrap_or(&address);
+        if !self.allows(&[name, &address]) || !self.claim(state, user_id, "email", &address) {
+            return;
+        }
+
+        let reply = self.reply_text(state, user_id, name);
+        let reply_subject = if subject.is_empty() {
+            "Automatic reply".to_string()
+        } else {
+            format!("Automatic reply: {}", subject)
+        };
+        // The claim keeps a parallel check from answering too, it only stays when the reply went out
+        let send_state = state.clone();
+        let account_id = email.account_id;
+        let to = address.clone();
+        let sent = tokio::task::spawn_blocking(move || {
+            crate::handlers::imap_handlers::send_email_smtp(&send_state, user_id, Some(account_id), &to, &reply_subject, &reply, true)
+        })
+        .await
+        .unwrap_or_else(|e| Err(format!("Email send task failed: {}", e)));
+        if let Err(e) = sent {
+            tracing::error!("Failed to send away reply email for user {}: {}", user_id, e);
+            if let Err(e) = state.user_repository.release_away_reply(user_id, "email", &address) {
+                tracing::error!("Failed to release away reply claim for user {}: {}", user_id, e);
+            }
+        }
+    }
+}
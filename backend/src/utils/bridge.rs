@@ -2,9 +2,10 @@ use std::sync::Arc;
 use anyhow::{anyhow, Result};
 use matrix_sdk::{
     Client as MatrixClient,
+    media::{MediaFormat, MediaRequestParameters},
     room::Room,
     ruma::{
-        events::room::message::{RoomMessageEventContent, SyncRoomMessageEvent, MessageType},
+        events::room::message::{AudioMessageEventContent, RoomMessageEventContent, SyncRoomMessageEvent, MessageType, Relation},
         events::AnySyncTimelineEvent,
     },
 };
@@ -12,6 +13,8 @@ use matrix_sdk::{
 
 use serde::{Deserialize, Serialize};
 use crate::AppState;
+use crate::models::user_models::{IndexedMessage, NewIndexedMessage};
+use crate::utils::bridge_service::{detect_service, require_service};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct BridgeRoom {
@@ -61,37 +64,169 @@ fn format_timestamp(timestamp: i64, timezone: Option<String>) -> String {
     formatted
 }
 
-fn get_bridge_bot_username(service: &str) -> String {
-    let env_key = format!("{}_BRIDGE_BOT", service.to_uppercase());
-    std::env::var(env_key).unwrap_or_else(|_| format!("@{}bot:", service).to_string())
+fn format_voice_note(transcript: &str) -> String {
+    format!("🎤 Voice message: {}", transcript)
 }
 
-fn get_sender_prefix(service: &str) -> String {
-    format!("{}_", service)
+/// A message from the local index in the shape the live fetches return.
+pub fn indexed_to_bridge_message(message: IndexedMessage, timezone: Option<String>) -> BridgeMessage {
+    let timestamp = message.sent_at as i64;
+    BridgeMessage {
+        sender: message.encrypted_sender.clone(),
+        sender_display_name: message.encrypted_sender,
+        content: message.encrypted_content,
+        timestamp,
+        formatted_timestamp: format_timestamp(timestamp, timezone),
+        message_type: message.message_type,
+        room_name: message.encrypted_room_name,
+        media_url: None,
+    }
 }
 
-fn get_room_suffix(service: &str) -> String {
-    match service {
-        "whatsapp" => "(WA)".to_string(),
-        "telegram" => "(Telegram)".to_string(),
-        _ => format!(" ({})", service.to_uppercase()),
+// Index of the chat the user meant from (chat name, last activity) pairs: exact name,
+// then the most active one containing it, then the most similar one.
+fn find_chat_match(chats: &[(String, i64)], search_term: &str) -> Option<usize> {
+    let search_term_lower = search_term.trim().to_lowercase();
+    if let Some(i) = chats.iter().position(|(name, _)| name.to_lowercase() == search_term_lower) {
+        tracing::info!("Found exact match for room");
+        return Some(i);
+    }
+    if let Some((i, _)) = chats.iter()
+        .enumerate()
+        .filter(|(_, (name, _))| name.to_lowercase().contains(&search_term_lower))
+        .max_by_key(|(_, (_, last_activity))| *last_activity) {
+        tracing::info!("Found substring match for room");
+        return Some(i);
     }
+    let best_match = chats.iter()
+        .enumerate()
+        .map(|(i, (name, _))| (strsim::jaro_winkler(&search_term_lower, &name.to_lowercase()), i))
+        .filter(|(score, _)| *score >= 0.7)
+        .max_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    best_match.map(|(score, i)| {
+        tracing::info!("Found similar match with score {}", score);
+        i
+    })
 }
 
-fn infer_service(room_name: &str, sender_localpart: &str) -> Option<String> {
-    let sender_localpart = sender_localpart.trim().to_lowercase();
-    let room_name = room_name.to_lowercase();
+// Most messages the local paths read before deciding, a few weeks of an active user
+const INDEX_SCAN_LIMIT: i64 = 3000;
+
+// The chat's latest messages from the local index. None when the index has fewer than asked
+// for or missed some of the time they span, then the homeserver has to answer.
+fn indexed_room_messages(
+    state: &Arc<AppState>,
+    service: &str,
+    user_id: i32,
+    chat_name: &str,
+    limit: u64,
+    timezone: Option<String>,
+) -> Option<(Vec<BridgeMessage>, String)> {
+    let room_suffix = require_service(service).ok()?.room_suffix();
+    let messages = state.user_repository
+        .get_indexed_messages(user_id, service, crate::utils::message_index::retention_start(), INDEX_SCAN_LIMIT)
+        .ok()?;
+
+    // newest first, so the first message of each room is its last activity
+    let mut room_names: Vec<String> = Vec::new();
+    let mut chats: Vec<(String, i64)> = Vec::new();
+    for message in &messages {
+        if !room_names.contains(&message.encrypted_room_name) {
+            let chat = message.encrypted_room_name.split(room_suffix).next().unwrap_or(&message.encrypted_room_name).trim();
+            chats.push((chat.to_string(), message.sent_at as i64));
+            room_names.push(message.encrypted_room_name.clone());
+        }
+    }
+    let room_name = room_names.get(find_chat_match(&chats, chat_name)?)?.clone();
+
+    let room_messages: Vec<BridgeMessage> = messages.into_iter()
+        .filter(|message| message.encrypted_room_name == room_name)
+        .take(limit as usize)
+        .map(|message| indexed_to_bridge_message(message, timezone.clone()))
+        .collect();
+    if (room_messages.len() as u64) < limit {
+        return None;
+    }
+    let oldest = room_messages.last()?.timestamp as i32;
+    if !crate::utils::message_index::covers(state, user_id, service, oldest) {
+        return None;
+    }
+    tracing::debug!("Answering {} chat messages from the local index", service);
+    Some((room_messages, room_name))
+}
 
-    if room_name.contains("(wa)") || sender_localpart.starts_with("whatsapp_") || sender_localpart.starts_with("whatsapp") {
-        println!("Detected WhatsApp");
-        return Some("whatsapp".to_string());
+// Latest message of the 10 most recently active chats since start_time from the local index,
+// when the index saw everything since then.
+fn indexed_recent_messages(
+    state: &Arc<AppState>,
+    service: &str,
+    user_id: i32,
+    start_time: i64,
+    timezone: Option<String>,
+) -> Option<Vec<BridgeMessage>> {
+    if !crate::utils::message_index::covers(state, user_id, service, start_time as i32) {
+        return None;
     }
-    if room_name.contains("(tg)") || sender_localpart.starts_with("telegram_") || sender_localpart.starts_with("telegram") {
-        println!("Detected Telegram");
-        return Some("telegram".to_string());
+    let messages = state.user_repository
+        .get_indexed_messages(user_id, service, start_time as i32, INDEX_SCAN_LIMIT)
+        .ok()?;
+
+    let mut latest: Vec<IndexedMessage> = Vec::new();
+    for message in messages {
+        if latest.len() >= 10 {
+            break;
+        }
+        if latest.iter().all(|seen| seen.encrypted_room_name != message.encrypted_room_name) {
+            latest.push(message);
+        }
+    }
+    tracing::debug!("Answering recent {} messages from the local index", service);
+    Some(latest.into_iter().map(|message| indexed_to_bridge_message(message, timezone.clone())).collect())
+}
+
+/// Text for a bridged voice note: its transcript when one can be made, the usual placeholder otherwise.
+/// Transcripts are stored so a note is only transcribed once.
+async fn voice_note_text(
+    state: &Arc<AppState>,
+    client: &MatrixClient,
+    user_id: i32,
+    event_id: &str,
+    audio: &AudioMessageEventContent,
+) -> String {
+    match state.user_repository.get_audio_transcript(user_id, event_id) {
+        Ok(Some(transcript)) => return format_voice_note(&transcript),
+        Ok(None) => {}
+        Err(e) => tracing::error!("Failed to get audio transcript for user {}: {}", user_id, e),
+    }
+
+    let request = MediaRequestParameters {
+        source: audio.source.clone(),
+        format: MediaFormat::File,
+    };
+    let bytes = match client.media().get_media_content(&request, true).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            tracing::warn!("Failed to download voice note {}: {}", event_id, e);
+            return "📎 AUDIO".into();
+        }
+    };
+    let content_type = audio.info.as_ref()
+        .and_then(|info| info.mimetype.clone())
+        .unwrap_or_else(|| "audio/ogg".to_string());
+
+    match crate::utils::transcription::transcribe(state, bytes, &content_type).await {
+        Ok(transcript) if !transcript.is_empty() => {
+            if let Err(e) = state.user_repository.save_audio_transcript(user_id, event_id, &transcript) {
+                tracing::error!("Failed to save audio transcript for user {}: {}", user_id, e);
+            }
+            format_voice_note(&transcript)
+        }
+        Ok(_) => "📎 AUDIO".into(),
+        Err(e) => {
+            tracing::debug!("Voice note {} not transcribed: {}", event_id, e);
+            "📎 AUDIO".into()
+        }
     }
-    println!("No service detected");
-    None
 }
 
 pub async fn fetch_bridge_messages(
@@ -118,18 +253,19 @@ pub async fn fetch_bridge_messages(
         return Err(anyhow!("{} bridge is not connected. Please log in first.", capitalize(&service)));
     }
 
-    let room_suffix = get_room_suffix(service);
+    // The index doesn't know what's been read, unread_only always goes to the homeserver
+    if !unread_only {
+        if let Some(indexed) = indexed_recent_messages(state, service, user_id, start_time, user_info.timezone.clone()) {
+            return Ok(indexed);
+        }
+    }
 
-    let sender_prefix = get_sender_prefix(service);
+    let bridge_service = require_service(service)?;
+    let room_suffix = bridge_service.room_suffix().to_string();
 
-    let service_cap = capitalize(&service);
+    let sender_prefix = bridge_service.puppet_prefix().to_string();
 
-    let skip_terms = vec![
-        format!("{}bot", service),
-        format!("{}-bridge", service),
-        format!("{} Bridge", service_cap),
-        format!("{} bridge bot", service_cap),
-    ];
+    let skip_terms = bridge_service.control_room_terms();
 
     // Structure to hold room info
     struct RoomInfo {
@@ -227,6 +363,8 @@ pub async fn fetch_bridge_messages(
         let user_timezone = user_timezone.clone();
         let room_suffix = room_suffix.clone();
         let sender_prefix = sender_prefix.clone();
+        let state = state.clone();
+        let client = client.clone();
 
         futures.push(async move {
             let mut options = matrix_sdk::room::MessagesOptions::backward();
@@ -240,10 +378,10 @@ pub async fn fetch_bridge_messages(
                             if let AnySyncTimelineEvent::MessageLike(
                                 matrix_sdk::ruma::events::AnySyncMessageLikeEvent::RoomMessage(msg)
                             ) = any_sync_event {
-                                let (sender, timestamp, content) = match msg {
+                                let (sender, timestamp, content, event_id) = match msg {
                                     SyncRoomMessageEvent::Original(e) => {
                                         let timestamp = i64::from(e.origin_server_ts.0) / 1000;
-                                        (e.sender, timestamp, e.content)
+                                        (e.sender, timestamp, e.content, e.event_id.to_string())
                                     }
                                     _ => continue,
                                 };
@@ -259,7 +397,7 @@ pub async fn fetch_bridge_messages(
                                     MessageType::Image(_) => ("image", "📎 IMAGE".into()),
                                     MessageType::Video(_) => ("video", "📎 VIDEO".into()),
                                     MessageType::File(_) => ("file", "📎 FILE".into()),
-                                    MessageType::Audio(_) => ("audio", "📎 AUDIO".into()),
+                                    MessageType::Audio(a) => ("audio", voice_note_text(&state, &client, user_id, &event_id, &a).await),
                                     MessageType::Location(_) => ("location", "📍 LOCATION".into()),
                                     MessageType::Emote(t) => ("emote", t.body),
                                     _ => continue,
@@ -317,6 +455,7 @@ pub async fn send_bridge_message(
     state: &Arc<AppState>,
     user_id: i32,
     chat_name: &str,
+    room_id: Option<&str>, // known room, e.g. the one a notification came from, chat_name is then only for display
     message: &str,
     media_url: Option<String>,
 ) -> Result<BridgeMessage> {
@@ -330,142 +469,198 @@ pub async fn send_bridge_message(
         return Err(anyhow!("{} bridge is not connected. Please log in first.", capitalize(&service)));
     }
 
-    // Get all joined rooms
-    let joined_rooms = client.joined_rooms();
-    let room_suffix = get_room_suffix(service);
-    let sender_prefix = get_sender_prefix(service);
-    let service_cap = capitalize(&service);
-    let skip_terms = vec![
-        format!("{}bot", service),
-        format!("{}-bridge", service),
-        format!("{} Bridge", service_cap),
-        format!("{} bridge bot", service_cap),
-    ];
-    let search_term_lower = chat_name.trim().to_lowercase();
-
-    let mut futures = Vec::new();
-    for room in joined_rooms {
-        let room_suffix = room_suffix.clone();
-        let sender_prefix = sender_prefix.clone();
-        let skip_terms = skip_terms.clone();
-        futures.push(async move {
-            let display_name = match room.display_name().await {
-                Ok(n) => n.to_string(),
-                Err(_) => return None,
-            };
-            if skip_terms.iter().any(|t| display_name.contains(t)) {
-                return None;
-            }
-            // Get room members
-            let members = match room.members(matrix_sdk::RoomMemberships::JOIN).await {
-                Ok(members) => members,
-                Err(_) => return None,
-            };
-            let has_service_member = members.iter().any(|member| member.user_id().localpart().starts_with(&sender_prefix));
-            if !has_service_member {
-                return None;
+    // The recorded room if there is one, rooms get renamed and names aren't unique
+    let known_room = room_id
+        .and_then(|id| matrix_sdk::ruma::OwnedRoomId::try_from(id).ok())
+        .and_then(|id| client.get_room(&id));
+    let room = match known_room {
+        Some(room) => room,
+        None => {
+            // Get all joined rooms
+            let joined_rooms = client.joined_rooms();
+            let bridge_service = require_service(service)?;
+            let room_suffix = bridge_service.room_suffix().to_string();
+            let sender_prefix = bridge_service.puppet_prefix().to_string();
+            let skip_terms = bridge_service.control_room_terms();
+            let search_term_lower = chat_name.trim().to_lowercase();
+
+            let mut futures = Vec::new();
+            for room in joined_rooms {
+                let room_suffix = room_suffix.clone();
+                let sender_prefix = sender_prefix.clone();
+                let skip_terms = skip_terms.clone();
+                futures.push(async move {
+                    let display_name = match room.display_name().await {
+                        Ok(n) => n.to_string(),
+                        Err(_) => return None,
+                    };
+                    if skip_terms.iter().any(|t| display_name.contains(t)) {
+                        return None;
+                    }
+                    // Get room members
+                    let members = match room.members(matrix_sdk::RoomMemberships::JOIN).await {
+                        Ok(members) => members,
+                        Err(_) => return None,
+                    };
+                    let has_service_member = members.iter().any(|member| member.user_id().localpart().starts_with(&sender_prefix));
+                    if !has_service_member {
+                        return None;
+                    }
+                    let chat_name_part = if display_name.contains(&room_suffix) {
+                        display_name
+                            .split(&room_suffix)
+                            .next()
+                            .unwrap_or(&display_name)
+                            .trim()
+                            .to_string()
+                    } else {
+                        display_name.trim().to_string()
+                    };
+                    Some((room, chat_name_part))
+                });
             }
-            let chat_name_part = if display_name.contains(&room_suffix) {
-                display_name
-                    .split(&room_suffix)
-                    .next()
-                    .unwrap_or(&display_name)
-                    .trim()
-                    .to_string()
-            } else {
-                display_name.trim().to_string()
-            };
-            Some((room, chat_name_part))
-        });
-    }
 
-    // Collect results
-    let found_rooms: Vec<(Room, String)> = join_all(futures)
-        .await
-        .into_iter()
-        .flatten()
-        .collect();
-    // Find exact match
-    let target_room = found_rooms.iter()
-        .find(|(_, name)| name.to_lowercase() == search_term_lower)
-        .map(|(room, _)| room.clone());
-    let room = match target_room {
-        Some(r) => r,
-        None => {
-            // Provide a helpful error message listing similar rooms
-            let similar_rooms: Vec<String> = found_rooms
-                .iter()
-                .filter(|(_, name)| name.to_lowercase().contains(&search_term_lower))
-                .map(|(_, name)| name.clone())
+            // Collect results
+            let found_rooms: Vec<(Room, String)> = join_all(futures)
+                .await
+                .into_iter()
+                .flatten()
                 .collect();
-            let error_msg = if similar_rooms.is_empty() {
-                format!("Could not find exact matching {} room for '{}'", capitalize(&service), chat_name)
-            } else {
-                format!(
-                    "Could not find exact matching {} room for '{}'. Did you mean one of these?\n{}",
-                    capitalize(&service),
-                    chat_name,
-                    similar_rooms.join("\n")
-                )
-            };
-            return Err(anyhow!(error_msg));
+            // Find exact match
+            let target_room = found_rooms.iter()
+                .find(|(_, name)| name.to_lowercase() == search_term_lower)
+                .map(|(room, _)| room.clone());
+            match target_room {
+                Some(r) => r,
+                None => {
+                    // Provide a helpful error message listing similar rooms
+                    let similar_rooms: Vec<String> = found_rooms
+                        .iter()
+                        .filter(|(_, name)| name.to_lowercase().contains(&search_term_lower))
+                        .map(|(_, name)| name.clone())
+                        .collect();
+                    let error_msg = if similar_rooms.is_empty() {
+                        format!("Could not find exact matching {} room for '{}'", capitalize(&service), chat_name)
+                    } else {
+                        format!(
+                            "Could not find exact matching {} room for '{}'. Did you mean one of these?\n{}",
+                            capitalize(&service),
+                            chat_name,
+                            similar_rooms.join("\n")
+                        )
+                    };
+                    return Err(anyhow!(error_msg));
+                }
+            }
         }
     };
 
     use matrix_sdk::{
         ruma::events::room::message::{
             RoomMessageEventContent, MessageType, ImageMessageEventContent,
+            AudioMessageEventContent, VideoMessageEventContent, FileMessageEventContent,
         },
+        ruma::events::room::{ImageInfo, message::{AudioInfo, FileInfo, VideoInfo}},
     };
 
-    // Create message content
-    let content = RoomMessageEventContent::text_plain(message);
-
+    let mut message_type = "text";
+    let mut sent_media_url = None;
     if let Some(url) = media_url {
-        // ── 1. Download the image and get MIME type ────────────────────────────────
-        let resp = reqwest::get(&url).await?;
-        // Get MIME type from headers before consuming the response
-        let mime: mime_guess::mime::Mime = resp
-            .headers()
-            .get(reqwest::header::CONTENT_TYPE)
-            .and_then(|h| h.to_str().ok())
-            .and_then(|s| s.parse().ok())
-            .unwrap_or_else(|| mime_guess::MimeGuess::from_path(&url).first_or_octet_stream());
-
-        // Now consume the response to get the bytes
-        let bytes = resp.bytes().await?;
-        let size = bytes.len();
+        // ── 1. Download the attachment and get MIME type ─────────────────────────
+        // MMS attachments are passed along as data urls, the Twilio copy is already deleted
+        let (mime, bytes): (mime_guess::mime::Mime, Vec<u8>) = if let Some(data) = url.strip_prefix("data:") {
+            use base64::Engine as _;
+            let (mime_str, encoded) = data.split_once(";base64,").ok_or_else(|| anyhow!("Unsupported data url"))?;
+            (
+                mime_str.parse().unwrap_or(mime_guess::mime::APPLICATION_OCTET_STREAM),
+                base64::engine::general_purpose::STANDARD.decode(encoded)?,
+            )
+        } else {
+            let resp = reqwest::get(&url).await?;
+            // Get MIME type from headers before consuming the response
+            let mime = resp
+                .headers()
+                .get(reqwest::header::CONTENT_TYPE)
+                .and_then(|h| h.to_str().ok())
+                .and_then(|s| s.parse().ok())
+                .unwrap_or_else(|| mime_guess::MimeGuess::from_path(&url).first_or_octet_stream());
+
+            // Now consume the response to get the bytes
+            (mime, resp.bytes().await?.to_vec())
+        };
+        let size = Some(matrix_sdk::ruma::UInt::new(bytes.len() as u64).unwrap_or_default());
+        message_type = media_kind(mime.essence_str());
 
         // ── 2. Filename (best-effort) ────────────────────────────────────────────
-        let filename = std::path::Path::new(&url)
-            .file_name()
-            .and_then(|p| p.to_str())
-            .unwrap_or("file");
+        // data urls have no name, make one up from the type so the other side can open it
+        let filename = if url.starts_with("data:") {
+            let extension = mime_guess::get_mime_extensions(&mime)
+                .and_then(|exts| exts.first())
+                .copied()
+                .unwrap_or("bin");
+            format!("{}.{}", message_type, extension)
+        } else {
+            std::path::Path::new(&url)
+                .file_name()
+                .and_then(|p| p.to_str())
+                .unwrap_or("file")
+                .to_string()
+        };
+        // The body is the caption when a filename is set, or the filename when there is no caption
+        let body = if message.trim().is_empty() { filename.clone() } else { message.to_owned() };
 
         // ── 3. Upload to the homeserver ──────────────────────────────────────────
         let upload_resp = client
             .media()
-            .upload(&mime, bytes.to_vec(), None)
+            .upload(&mime, bytes, None)
             .await?;
 
         let mxc: matrix_sdk::ruma::OwnedMxcUri = upload_resp.content_uri;
-
-        // ── 4. Build the image-message content with caption in *one* event ──────
-        let mut img = ImageMessageEventContent::plain(
-            message.to_owned(),   // ← this is the caption / body
-            mxc,
-        );
-
-        // Optional but nice: add basic metadata so bridges & clients know the size
-        let mut imageinfo = matrix_sdk::ruma::events::room::ImageInfo::new();
-        imageinfo.size = Some(matrix_sdk::ruma::UInt::new(size as u64).unwrap_or_default());
-        img.info = Some(Box::new(imageinfo));
-
-        // Wrap it as a generic “m.room.message”
-        let content = RoomMessageEventContent::new(MessageType::Image(img));
+        sent_media_url = Some(mxc.to_string());
+
+        // ── 4. Build m.image / m.audio / m.video / m.file with the caption in *one* event
+        // size and mimetype let bridges & clients know what they are getting
+        let msgtype = match message_type {
+            "image" => {
+                let mut info = ImageInfo::new();
+                info.mimetype = Some(mime.essence_str().to_string());
+                info.size = size;
+                let mut content = ImageMessageEventContent::plain(body, mxc);
+                content.filename = Some(filename);
+                content.info = Some(Box::new(info));
+                MessageType::Image(content)
+            }
+            "audio" => {
+                let mut info = AudioInfo::new();
+                info.mimetype = Some(mime.essence_str().to_string());
+                info.size = size;
+                let mut content = AudioMessageEventContent::plain(body, mxc);
+                content.filename = Some(filename);
+                content.info = Some(Box::new(info));
+                MessageType::Audio(content)
+            }
+            "video" => {
+                let mut info = VideoInfo::new();
+                info.mimetype = Some(mime.essence_str().to_string());
+                info.size = size;
+                let mut content = VideoMessageEventContent::plain(body, mxc);
+                content.filename = Some(filename);
+                content.info = Some(Box::new(info));
+                MessageType::Video(content)
+            }
+            _ => {
+                let mut info = FileInfo::new();
+                info.mimetype = Some(mime.essence_str().to_string());
+                info.size = size;
+                let mut content = FileMessageEventContent::plain(body, mxc);
+                content.filename = Some(filename);
+                content.info = Some(Box::new(info));
+                MessageType::File(content)
+            }
+        };
 
         // ── 5. Send it ───────────────────────────────────────────────────────────
-        room.send(content).await?;
+        room.send(RoomMessageEventContent::new(msgtype)).await?;
     } else {
         // plain text
         room.send(RoomMessageEventContent::text_plain(message)).await?;
@@ -481,12 +676,22 @@ pub async fn send_bridge_message(
         content: message.to_string(),
         timestamp: current_timestamp,
         formatted_timestamp: format_timestamp(current_timestamp, user_info.timezone),
-        message_type: "text".to_string(),
+        message_type: message_type.to_string(),
         room_name: room.display_name().await?.to_string(),
-        media_url: None,
+        media_url: sent_media_url,
     })
 }
 
+/// Matrix message type an attachment is sent as: "image", "audio", "video" or "file".
+pub fn media_kind(mime: &str) -> &'static str {
+    match mime.split('/').next().unwrap_or("").trim().to_lowercase().as_str() {
+        "image" => "image",
+        "audio" => "audio",
+        "video" => "video",
+        _ => "file",
+    }
+}
+
 
 use matrix_sdk::RoomMemberships;
 use strsim;
@@ -524,19 +729,18 @@ pub async fn fetch_bridge_room_messages(
         return Err(anyhow!("{} bridge not found", capitalize(&service)));
     }
 
+    let user_info = state.user_core.get_user_info(user_id)?;
+    if let Some(indexed) = indexed_room_messages(state, service, user_id, chat_name, limit.unwrap_or(20), user_info.timezone.clone()) {
+        return Ok(indexed);
+    }
+
     let client = crate::utils::matrix_auth::get_cached_client(user_id, &state).await?;
 
-    let room_suffix = get_room_suffix(service);
-    let sender_prefix = get_sender_prefix(service);
+    let bridge_service = require_service(service)?;
+    let room_suffix = bridge_service.room_suffix().to_string();
+    let sender_prefix = bridge_service.puppet_prefix().to_string();
     let joined_rooms = client.joined_rooms();
-    let search_term_lower = chat_name.trim().to_lowercase();
-    let service_cap = capitalize(&service);
-    let skip_terms = vec![
-        format!("{}bot", service),
-        format!("{}-bridge", service),
-        format!("{} Bridge", service_cap),
-        format!("{} bridge bot", service_cap),
-    ];
+    let skip_terms = bridge_service.control_room_terms();
 
     let mut futures = Vec::new();
     for room in joined_rooms {
@@ -601,52 +805,28 @@ pub async fn fetch_bridge_room_messages(
         .collect();
 
 
-    // Find matching room with optimized search
-    let matching_room = {
-        // Try exact match first (fastest)
-        if let Some(room) = bridge_rooms.iter().find(|r| r.chat_name.to_lowercase() == search_term_lower) {
-            tracing::info!("Found exact match for room");
-            Some(room)
-        }
-        // Then try substring match
-        else if let Some(room) = bridge_rooms.iter()
-            .filter(|r| r.chat_name.to_lowercase().contains(&search_term_lower))
-            .max_by_key(|r| r.last_activity) {
-            tracing::info!("Found substring match for room");
-            Some(room)
-        }
-        // Finally try similarity match
-        else {
-            let best_match = bridge_rooms.iter()
-                .map(|r| (strsim::jaro_winkler(&search_term_lower, &r.chat_name.to_lowercase()), r))
-                .filter(|(score, _)| *score >= 0.7)
-                .max_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
-            
-            if let Some((score, room)) = best_match {
-                tracing::info!("Found similar match with score {}", score);
-                Some(room)
-            } else {
-                None
-            }
-        }
-    };
+    let chats: Vec<(String, i64)> = bridge_rooms.iter()
+        .map(|r| (r.chat_name.clone(), r.last_activity))
+        .collect();
+    let matching_room = find_chat_match(&chats, chat_name).map(|i| &bridge_rooms[i]);
 
-    let user_settings = state.user_core.get_user_settings(user_id)?;
-    let user_info = state.user_core.get_user_info(user_id)?;
     match matching_room {
-        Some(room) => fetch_messages_from_room(service, room.room.clone(), limit, user_info.timezone).await,
+        Some(room) => fetch_messages_from_room(service, state, &client, user_id, room.room.clone(), limit, user_info.timezone).await,
         None => Err(anyhow!("No matching {} room found for '{}'", capitalize(&service), chat_name))
     }
 }
 
 async fn fetch_messages_from_room(
     service: &str,
+    state: &Arc<AppState>,
+    client: &MatrixClient,
+    user_id: i32,
     room: matrix_sdk::room::Room,
     limit: Option<u64>,
     timezone: Option<String>,
 ) -> Result<(Vec<BridgeMessage>, String)> {
     let room_name = room.display_name().await?.to_string();
-    let sender_prefix = get_sender_prefix(service);
+    let sender_prefix = require_service(service)?.puppet_prefix().to_string();
     let mut options = MessagesOptions::backward();
     options.limit = matrix_sdk::ruma::UInt::new(limit.unwrap_or(20)).unwrap();
 
@@ -663,8 +843,8 @@ async fn fetch_messages_from_room(
             if let Ok(AnySyncTimelineEvent::MessageLike(
                 matrix_sdk::ruma::events::AnySyncMessageLikeEvent::RoomMessage(msg)
             )) = event.raw().deserialize() {
-                let (sender, timestamp, content) = match msg {
-                    SyncRoomMessageEvent::Original(e) => (e.sender, i64::from(e.origin_server_ts.0) / 1000, e.content),
+                let (sender, timestamp, content, event_id) = match msg {
+                    SyncRoomMessageEvent::Original(e) => (e.sender, i64::from(e.origin_server_ts.0) / 1000, e.content, e.event_id.to_string()),
                     _ => return None,
                 };
 
@@ -678,7 +858,7 @@ async fn fetch_messages_from_room(
                     MessageType::Image(i) => ("image", if i.body.is_empty() { "📎 IMAGE".into() } else { i.body }),
                     MessageType::Video(v) => ("video", if v.body.is_empty() { "📎 VIDEO".into() } else { v.body }),
                     MessageType::File(f) => ("file", if f.body.is_empty() { "📎 FILE".into() } else { f.body }),
-                    MessageType::Audio(a) => ("audio", if a.body.is_empty() { "📎 AUDIO".into() } else { a.body }),
+                    MessageType::Audio(a) => ("audio", voice_note_text(state, client, user_id, &event_id, &a).await),
                     MessageType::Location(l) => ("location", "📍 LOCATION".into()), // Location has no body field
                     MessageType::Emote(t) => ("emote", t.body),
                     _ => return None,
@@ -714,6 +894,43 @@ async fn fetch_messages_from_room(
 
 use std::time::{SystemTime, UNIX_EPOCH};
 
+// A chat with more than one other person. The user's own account and puppet don't count,
+// whether their puppet has joined differs by bridge and by chat.
+async fn is_group_room(room: &Room, sender_prefix: &str, matrix_localpart: &str, phone_digits: &str) -> bool {
+    match room.members(RoomMemberships::JOIN).await {
+        Ok(members) => members
+            .iter()
+            .map(|member| member.user_id().localpart())
+            .filter(|localpart| localpart.starts_with(sender_prefix))
+            .filter(|localpart| !is_own_localpart(localpart, matrix_localpart, phone_digits))
+            .count() > 1,
+        Err(e) => {
+            tracing::warn!("Failed to get members of {}: {}", room.room_id(), e);
+            false
+        }
+    }
+}
+
+// The user's Matrix account, or their puppet, which bridges name after the phone number
+fn is_own_localpart(localpart: &str, matrix_localpart: &str, phone_digits: &str) -> bool {
+    localpart == matrix_localpart || (phone_digits.len() >= 6 && localpart.contains(phone_digits))
+}
+
+// Whether the message the event replies to was written by the user
+async fn is_reply_to_user(room: &Room, in_reply_to: &matrix_sdk::ruma::EventId, matrix_localpart: &str, phone_digits: &str) -> bool {
+    let replied = match room.event(in_reply_to, None).await {
+        Ok(replied) => replied,
+        Err(e) => {
+            tracing::debug!("Failed to fetch replied event {}: {}", in_reply_to, e);
+            return false;
+        }
+    };
+    match replied.raw().get_field::<matrix_sdk::ruma::OwnedUserId>("sender") {
+        Ok(Some(sender)) => is_own_localpart(sender.localpart(), matrix_localpart, phone_digits),
+        _ => false,
+    }
+}
+
 pub async fn handle_bridge_message(
     event: OriginalSyncRoomMessageEvent,
     room: Room,
@@ -735,15 +952,6 @@ pub async fn handle_bridge_message(
     let age_ms = now.saturating_sub(message_ts.into()); // Use saturating_sub to handle any potential clock skew
     const HALF_HOUR_MS: u64 = 30 * 60 * 1000;
 
-    if age_ms > HALF_HOUR_MS {
-        tracing::debug!(
-            "Skipping old message: age {} ms (event ID: {})",
-            age_ms,
-            event.event_id
-        );
-        return;
-    }
-
     // Get room name
     let room_name = match room.display_name().await {
         Ok(name) => name.to_string(),
@@ -756,27 +964,14 @@ pub async fn handle_bridge_message(
     let sender_localpart = event.sender.localpart().to_string();
 
 
-    let service = match infer_service(&room_name, &sender_localpart) {
-        Some(s) => s,
-        None => {
-            tracing::error!("Could not infer service, skipping");
-            return;
-        }
-    };
-
-
-    let room_suffix = get_room_suffix(&service);
-    let sender_prefix = get_sender_prefix(&service);
-
-    if !room_name.contains(&room_suffix) {
-        tracing::debug!("Skipping non-{} room", service);
-        return;
-    }
-
-    if !sender_localpart.starts_with(&sender_prefix) {
-        tracing::debug!("Skipping non-{} sender", service);
+    // Both the portal room name and the puppet sender have to belong to the same bridge
+    let Some(bridge_service) = detect_service(&room_name, &sender_localpart) else {
+        tracing::debug!("Message is not from a bridged chat, skipping");
         return;
-    }
+    };
+    let service = bridge_service.name();
+    let room_suffix = bridge_service.room_suffix();
+    let sender_prefix = bridge_service.puppet_prefix();
 
     // Find the user ID for this Matrix client
     let client_user_id = client.user_id().unwrap().to_string();
@@ -794,63 +989,128 @@ pub async fn handle_bridge_message(
     };
 
     let user_id = user.id;
-    // Check if user has valid subscription
     let has_valid_sub = state.user_repository.has_valid_subscription_tier(user_id, "tier 2").unwrap_or(false) || 
         state.user_repository.has_valid_subscription_tier(user_id, "self_hosted").unwrap_or(false);
-    if !has_valid_sub {
-        tracing::debug!("User {} does not have valid subscription for WhatsApp monitoring", user_id);
-        return;
-    }
-    if !state.user_core.get_proactive_agent_on(user_id).unwrap_or(true) {
-        tracing::debug!("User {} does not have monitoring enabled", user_id);
-        return;
-    }
-
+    // Away replies still go out while notifications are off or paused
+    let away_mode = crate::utils::away_mode::AwayMode::active(&state, user_id);
+    let monitoring_on = state.user_core.get_proactive_agent_on(user_id).unwrap_or(true);
+    // Every message is indexed, only live ones of monitored users go further
+    let handled = age_ms <= HALF_HOUR_MS && has_valid_sub && (monitoring_on || away_mode.is_some());
+
+
+    // Mentions and replies decide whether a group message gets through, read them before the content is moved
+    let mentioned_localparts: Vec<String> = event.content.mentions
+        .as_ref()
+        .map(|m| m.user_ids.iter().map(|id| id.localpart().to_string()).collect())
+        .unwrap_or_default();
+    let in_reply_to = match &event.content.relates_to {
+        Some(Relation::Reply { in_reply_to }) => Some(in_reply_to.event_id.clone()),
+        _ => None,
+    };
+    let formatted_body = match &event.content.msgtype {
+        MessageType::Text(t) => t.formatted.as_ref().map(|f| f.body.clone()),
+        _ => None,
+    };
 
     // Extract message content
-    let content = match event.content.msgtype {
-        MessageType::Text(t) => t.body,
-        MessageType::Notice(n) => n.body,
-        MessageType::Image(_) => "📎 IMAGE".into(),
-        MessageType::Video(_) => "📎 VIDEO".into(),
-        MessageType::File(_) => "📎 FILE".into(),
-        MessageType::Audio(_) => "📎 AUDIO".into(),
-        MessageType::Location(_) => "📍 LOCATION".into(),
-        MessageType::Emote(t) => t.body,
+    let (message_type, content) = match event.content.msgtype {
+        MessageType::Text(t) => ("text", t.body),
+        MessageType::Notice(n) => ("notice", n.body),
+        MessageType::Image(_) => ("image", "📎 IMAGE".into()),
+        MessageType::Video(_) => ("video", "📎 VIDEO".into()),
+        MessageType::File(_) => ("file", "📎 FILE".into()),
+        // transcribing costs, only for messages that may notify
+        MessageType::Audio(a) if handled => ("audio", voice_note_text(&state, &client, user_id, event.event_id.as_str(), &a).await),
+        MessageType::Audio(_) => ("audio", "📎 AUDIO".into()),
+        MessageType::Location(_) => ("location", "📍 LOCATION".into()),
+        MessageType::Emote(t) => ("emote", t.body),
         _ => return,
     };
 
-    if user_id == 1 { // if admin for debugging
+    if handled && user_id == 1 { // if admin for debugging
         println!("message: {}", content);
     }
 
     // Skip error messages
-    if content.contains("Failed to bridge media") ||
-       content.contains("media no longer available") ||
-       content.contains("Decrypting message from WhatsApp failed") ||
-       content.starts_with("* Failed to") {
+    if bridge_service.is_bridge_error(&content) {
         tracing::debug!("Skipping error message because content contained error messages");
         return;
     }
 
     let chat_name = room_name
-        .split(&room_suffix)
+        .split(room_suffix)
         .next()
         .unwrap_or(&room_name)
         .trim()
         .to_string();
     
     let sender_name = sender_localpart
-        .strip_prefix(&sender_prefix)
+        .strip_prefix(sender_prefix)
         .unwrap_or(&sender_localpart)
         .to_string();
 
+    // Kept locally for fetch_chat_messages and search_messages
+    crate::utils::message_index::index_message(&state, NewIndexedMessage {
+        user_id,
+        platform: service.to_string(),
+        message_key: event.event_id.to_string(),
+        encrypted_room_name: room_name.clone(),
+        encrypted_sender: sender_name.clone(),
+        encrypted_content: content.clone(),
+        message_type: message_type.to_string(),
+        sent_at: (i64::from(message_ts) / 1000) as i32,
+    });
+
+    if age_ms > HALF_HOUR_MS {
+        tracing::debug!(
+            "Skipping old message: age {} ms (event ID: {})",
+            age_ms,
+            event.event_id
+        );
+        return;
+    }
+    if !has_valid_sub {
+        tracing::debug!("User {} does not have valid subscription for {} monitoring", user_id, service);
+        return;
+    }
+    if !monitoring_on && away_mode.is_none() {
+        tracing::debug!("User {} does not have monitoring enabled", user_id);
+        return;
+    }
+
+    let phone_digits: String = user.phone_number.chars().filter(|c| c.is_ascii_digit()).collect();
+    let is_group = is_group_room(&room, sender_prefix, local_user_id, &phone_digits).await;
+
+    // Direct chats get the away reply, messages the user sent from their phone don't
+    if let Some(away) = &away_mode {
+        if !is_group && !is_own_localpart(&sender_localpart, local_user_id, &phone_digits) {
+            if let Some(reply) = away.chat_reply(&state, user_id, service, room.room_id().as_str(), &chat_name, &sender_name) {
+                let room = room.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = room.send(RoomMessageEventContent::text_plain(reply)).await {
+                        tracing::error!("Failed to send away reply for user {}: {}", user_id, e);
+                    }
+                });
+            }
+        }
+    }
+    if !monitoring_on {
+        return;
+    }
+
+    // Lets the user answer the notification with "r: .."
+    let source = crate::utils::notification_sources::NotificationSource::Chat {
+        platform: service.to_string(),
+        room_id: room.room_id().to_string(),
+        chat_name: chat_name.clone(),
+    };
+
     let waiting_checks = state.user_repository.get_waiting_checks(user_id, "messaging").unwrap_or(Vec::new());
 
-    let priority_senders = state.user_repository.get_priority_senders(user_id, &service).unwrap_or(Vec::new());
+    let priority_senders = state.user_repository.get_priority_senders(user_id, service).unwrap_or(Vec::new());
 
-    fn trim_for_sms(service: &str, sender: &str, content: &str) -> String {
-        let prefix = format!("{} from ", capitalize(&service));
+    fn trim_for_sms(service_cap: &str, sender: &str, content: &str) -> String {
+        let prefix = format!("{} from ", service_cap);
         let separator = ": ";
         let max_len = 157;
 
@@ -872,12 +1132,12 @@ pub async fn handle_bridge_message(
         format!("{}{}{}{}", prefix, sender_trimmed, separator, content_trimmed)
     }
 
-    let service_cap = capitalize(&service);
+    let service_cap = bridge_service.display_name();
 
         // FAST CHECKS SECOND - Check priority senders if active
     for priority_sender in &priority_senders {
         let clean_priority_sender = priority_sender.sender
-            .split(&room_suffix)
+            .split(room_suffix)
             .next()
             .unwrap_or(&priority_sender.sender)
             .trim()
@@ -898,10 +1158,11 @@ pub async fn handle_bridge_message(
                     // User has enough credits, proceed with notification
                     let state_clone = state.clone();
                     let content_clone = content.clone();
-                    let message = trim_for_sms(&service, &priority_sender.sender, &content_clone);
+                    let message = trim_for_sms(service_cap, &priority_sender.sender, &content_clone);
                     let first_message = format!("Hello, you have an important {} message from {}.", service_cap, priority_sender.sender);
                    
                     // Spawn a new task for sending notification
+                    let source = source.clone();
                     tokio::spawn(async move {
                         // Send the notification
                         crate::proactive::utils::send_notification(
@@ -910,6 +1171,7 @@ pub async fn handle_bridge_message(
                             &message,
                             notification_type,
                             Some(first_message),
+                            Some(source),
                         ).await;
                        
                     });
@@ -922,10 +1184,76 @@ pub async fn handle_bridge_message(
         }
     }
 
+    // While away only priority senders get through
+    if away_mode.as_ref().is_some_and(|away| away.priority_only) {
+        tracing::debug!("User {} is away, only priority senders notify", user_id);
+        return;
+    }
+
+    // Groups only get through when they concern the user, unless the group is set to "always".
+    // Groups without a policy are "mention", so busy groups don't cost an importance check per message
+    if is_group {
+        let policy = state.user_repository.get_group_policy(user_id, service, &chat_name)
+            .unwrap_or_else(|e| {
+                tracing::error!("Failed to get group policy for user {}: {}", user_id, e);
+                None
+            })
+            .unwrap_or_else(|| "mention".to_string());
+
+        if policy != "always" {
+            let content_lower = content.to_lowercase();
+            let mentioned = mentioned_localparts.iter().any(|l| is_own_localpart(l, local_user_id, &phone_digits))
+                || formatted_body.as_deref().is_some_and(|f| f.contains(&format!("@{}:", local_user_id)))
+                || (phone_digits.len() >= 6 && content.contains(&format!("@{}", phone_digits)))
+                || user.nickname.as_deref()
+                    .filter(|n| !n.trim().is_empty())
+                    .is_some_and(|n| content_lower.contains(&format!("@{}", n.trim().to_lowercase())));
+            let replied = policy == "reply" && match &in_reply_to {
+                Some(event_id) => is_reply_to_user(&room, event_id, local_user_id, &phone_digits).await,
+                None => false,
+            };
+
+            if !mentioned && !replied {
+                tracing::debug!("Skipping group message in {} for user {}, policy {}", service, user_id, policy);
+                return;
+            }
+
+            // Being mentioned or replied to is reason enough, no need to ask the model
+            match crate::utils::usage::check_user_credits(&state, &user, "noti_msg", None).await {
+                Ok(()) => {
+                    let state_clone = state.clone();
+                    let message = trim_for_sms(service_cap, &format!("{} in {}", sender_name, chat_name), &content);
+                    let first_message = if replied {
+                        format!("Hello, {} replied to you in {} on {}.", sender_name, chat_name, service_cap)
+                    } else {
+                        format!("Hello, {} mentioned you in {} on {}.", sender_name, chat_name, service_cap)
+                    };
+                    let notification_type = format!("{}_group_mention", service);
+                    let source = source.clone();
+                    tokio::spawn(async move {
+                        crate::proactive::utils::send_notification(
+                            &state_clone,
+                            user_id,
+                            &message,
+                            notification_type,
+                            Some(first_message),
+                            Some(source),
+                        ).await;
+                    });
+                }
+                Err(e) => {
+                    tracing::warn!("User {} does not have enough credits for group mention notification: {}", user_id, e);
+                }
+            }
+            return;
+        }
+    }
+
         if !waiting_checks.is_empty() {
         // Check if any waiting checks match the message
         if let Ok((check_id_option, message, first_message)) = crate::proactive::utils::check_waiting_check_match(
             &state,
+            user_id,
             &format!("{} from {}: {}", service_cap, chat_name, content),
             &waiting_checks,
         ).await {
@@ -952,6 +1280,7 @@ pub async fn handle_bridge_message(
                
                 // Send notification
                 let state_clone = state.clone();
+                let source = source.clone();
                 tokio::spawn(async move {
                     crate::proactive::utils::send_notification(
                         &state_clone,
@@ -959,6 +1288,7 @@ pub async fn handle_bridge_message(
                         &message,
                         notification_type,
                         Some(first_message),
+                        Some(source),
                     ).await;
                 });
                 return;
@@ -980,7 +1310,7 @@ pub async fn handle_bridge_message(
         return;
     }
 
-    if let Ok((is_critical, message, first_message)) = crate::proactive::utils::check_message_importance(&state, &format!("{} from {}: {}", service_cap, chat_name, content)).await {
+    if let Ok((is_critical, message, first_message)) = crate::proactive::utils::check_message_importance(&state, user_id, &format!("{} from {}: {}", service_cap, chat_name, content)).await {
         if is_critical {
             let message = message.unwrap_or(format!("Critical {} message found, failed to get content, but you can check your {} to see it.", service_cap, service));
             let first_message = first_message.unwrap_or(format!("Hey, I found some critical {} message.", service_cap));
@@ -988,6 +1318,7 @@ pub async fn handle_bridge_message(
             // Spawn a new task for sending critical message notification
             let state_clone = state.clone();
             let notification_type = format!("{}_critical", service);
+            let source = source.clone();
             tokio::spawn(async move {
                 crate::proactive::utils::send_notification(
                     &state_clone,
@@ -995,6 +1326,7 @@ pub async fn handle_bridge_message(
                     &message,
                     notification_type,
                     Some(first_message),
+                    Some(source),
                 ).await;
             });
         }
@@ -1008,7 +1340,7 @@ pub async fn search_bridge_rooms(
     user_id: i32,
     search_term: &str,
 ) -> Result<Vec<BridgeRoom>> {
-    let bridge_bot_username = get_bridge_bot_username(service);
+    let bridge_service = require_service(service)?;
     // Validate bridge connection first
     let bridge = state.user_repository.get_bridge(user_id, service)?;
     if bridge.map(|b| b.status != "connected").unwrap_or(true) {
@@ -1017,16 +1349,10 @@ pub async fn search_bridge_rooms(
     let client = crate::utils::matrix_auth::get_cached_client(user_id, &state).await?;
     let joined_rooms = client.joined_rooms();
     let search_term_lower = search_term.trim().to_lowercase();
-    let room_suffix = get_room_suffix(service);
-    let sender_prefix = get_sender_prefix(service); // Define here for cloning into futures
-    let service_cap = capitalize(service);
+    let room_suffix = bridge_service.room_suffix().to_string();
+    let sender_prefix = bridge_service.puppet_prefix().to_string(); // Define here for cloning into futures
     // Add skip_terms to avoid control/admin rooms (e.g., "Telegram bridge bot")
-    let skip_terms = vec![
-        format!("{}bot", service),
-        format!("{}-bridge", service),
-        format!("{} Bridge", service_cap),
-        format!("{} bridge bot", service_cap),
-    ];
+    let skip_terms = bridge_service.control_room_terms();
     // Process rooms in parallel
     let room_futures = joined_rooms.into_iter().map(|room| {
         let room_suffix = room_suffix.clone();
@@ -1134,15 +1460,10 @@ pub async fn fetch_recent_bridge_contacts(
     let client = crate::utils::matrix_auth::get_cached_client(user_id, state).await?;
     let joined_rooms = client.joined_rooms();
 
-    let room_suffix = get_room_suffix(service);
-    let sender_prefix = get_sender_prefix(service);
-    let service_cap = capitalize(service);
-    let skip_terms = vec![
-        format!("{}bot", service),
-        format!("{}-bridge", service),
-        format!("{} Bridge", service_cap),
-        format!("{} bridge bot", service_cap),
-    ];
+    let bridge_service = require_service(service)?;
+    let room_suffix = bridge_service.room_suffix().to_string();
+    let sender_prefix = bridge_service.puppet_prefix().to_string();
+    let skip_terms = bridge_service.control_room_terms();
 
     // Process rooms in parallel
     let room_futures = joined_rooms.into_iter().map(|room| {
@@ -0,0 +1,135 @@
+use std::sync::Arc;
+
+use futures::stream::{self, StreamExt};
+use tokio::time::{sleep, Duration};
+
+use crate::handlers::bridge_auth;
+use crate::utils::bridge_service::{all_services, BridgeService};
+use crate::AppState;
+
+// Checks run every 30 minutes, so the user hears about it after about 1.5 hours of being logged out
+const FAILURES_BEFORE_NOTICE: usize = 3;
+
+// Users checked at the same time, every check keeps a Matrix client busy waiting on bots
+const MAX_CONCURRENT_CHECKS: usize = 8;
+
+/// Pings every connected bridge through its bot. A bridge that doesn't answer or says it is
+/// logged out gets its sync commands again. After FAILURES_BEFORE_NOTICE checks in a row where
+/// the bot said the login is gone it is marked disconnected and the user is texted, a bot that
+/// doesn't answer only gets logged. Disconnected bridges keep being pinged and are marked
+/// connected again once the bot reports the login. Run by the scheduler.
+pub async fn check_all_bridges(state: &Arc<AppState>) {
+    let user_ids = match state.user_repository.get_users_with_matrix_bridge_connections() {
+        Ok(user_ids) => user_ids,
+        Err(e) => {
+            tracing::error!("Failed to get users with bridges for health check: {}", e);
+            return;
+        }
+    };
+
+    // Every check waits on a bot, a few users go in parallel
+    stream::iter(user_ids)
+        .for_each_concurrent(MAX_CONCURRENT_CHECKS, |user_id| check_user_bridges(state, user_id))
+        .await;
+}
+
+async fn check_user_bridges(state: &Arc<AppState>, user_id: i32) {
+    for service in all_services() {
+        match state.user_repository.get_bridge(user_id, service.name()) {
+            Ok(Some(bridge)) if bridge.status == "connected" => check_bridge(state, service, user_id).await,
+            Ok(Some(bridge)) if bridge.status == "disconnected" => check_disconnected_bridge(state, service, user_id).await,
+            Ok(_) => {}
+            Err(e) => tracing::error!("Failed to get {} bridge of user {}: {}", service.name(), user_id, e),
+        }
+    }
+}
+
+async fn check_bridge(state: &Arc<AppState>, service: &'static dyn BridgeService, user_id: i32) {
+    let (status, detail) = match bridge_auth::check_connection(service, state, user_id).await {
+        Ok(true) => ("ok", None),
+        first => {
+            // Only the bot saying the login is gone counts as failed, not answering can be
+            // the bot or the homeserver being slow and says nothing about the login
+            let logged_out = matches!(first, Ok(false));
+            let first_problem = problem(first);
+            tracing::warn!("{} bridge of user {} failed the health check: {}", service.display_name(), user_id, first_problem);
+
+            if let Err(e) = bridge_auth::resync_bridge(service, state, user_id).await {
+                tracing::error!("Failed to resync {} bridge of user {}: {}", service.display_name(), user_id, e);
+            }
+            // let the bot finish the sync before asking again
+            sleep(Duration::from_secs(10)).await;
+
+            match bridge_auth::check_connection(service, state, user_id).await {
+                Ok(true) => ("resynced", Some(first_problem)),
+                second @ Ok(false) => ("failed", Some(problem(second))),
+                second if logged_out => ("failed", Some(problem(second))),
+                second => ("unreachable", Some(problem(second))),
+            }
+        }
+    };
+
+    if let Err(e) = state.user_repository.record_bridge_status(user_id, service.name(), status, detail) {
+        tracing::error!("Failed to record {} bridge status for user {}: {}", service.name(), user_id, e);
+    }
+    if status != "failed" {
+        return;
+    }
+
+    let failures = state.user_repository.count_consecutive_bridge_failures(user_id, service.name()).unwrap_or(0);
+    if failures < FAILURES_BEFORE_NOTICE {
+        return;
+    }
+
+    tracing::warn!("{} bridge of user {} failed {} checks in a row, marking it disconnected", service.display_name(), user_id, failures);
+    if let Err(e) = state.user_repository.set_bridge_status(user_id, service.name(), "disconnected") {
+        tracing::error!("Failed to mark {} bridge of user {} disconnected: {}", service.name(), user_id, e);
+        return;
+    }
+    if let Err(e) = state.user_repository.record_bridge_status(user_id, service.name(), "disconnected", None) {
+        tracing::error!("Failed to record {} bridge status for user {}: {}", service.name(), user_id, e);
+    }
+
+    let user = match state.user_core.find_by_id(user_id) {
+        Ok(Some(user)) => user,
+        Ok(None) => return,
+        Err(e) => {
+            tracing::error!("Failed to get user {} for bridge notice: {}", user_id, e);
+            return;
+        }
+    };
+    let notice = format!("{} disconnected, reply RECONNECT {}.", service.display_name(), service.reconnect_hint());
+    // Box<dyn Error> isn't Send, turn it into a string right away
+    if let Err(e) = crate::api::twilio_utils::send_conversation_message(state, &notice, None, &user).await.map_err(|e| e.to_string()) {
+        tracing::error!("Failed to send bridge disconnect notice to user {}: {}", user_id, e);
+    }
+}
+
+fn problem(result: anyhow::Result<bool>) -> String {
+    match result {
+        Ok(_) => "bridge bot reports the login is gone".to_string(),
+        Err(e) => e.to_string(),
+    }
+}
+
+// A bridge the health check marked disconnected, flipped back when the bot reports the login again,
+// e.g. the bridge came back by itself or the user logged in on the bridge side
+async fn check_disconnected_bridge(state: &Arc<AppState>, service: &'static dyn BridgeService, user_id: i32) {
+    match bridge_auth::check_connection(service, state, user_id).await {
+        Ok(true) => {}
+        Ok(false) => return,
+        Err(e) => {
+            tracing::debug!("{} bridge of user {} still not answering: {}", service.display_name(), user_id, e);
+            return;
+        }
+    }
+
+    tracing::info!("{} bridge of user {} is logged in again, marking it connected", service.display_name(), user_id);
+    if let Err(e) = state.user_repository.set_bridge_status(user_id, service.name(), "connected") {
+        tracing::error!("Failed to mark {} bridge of user {} connected: {}", service.name(), user_id, e);
+        return;
+    }
+    if let Err(e) = state.user_repository.record_bridge_status(user_id, service.name(), "reconnected", None) {
+        tracing::error!("Failed to record {} bridge status for user {}: {}", service.name(), user_id, e);
+    }
+}
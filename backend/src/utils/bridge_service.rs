@@ -0,0 +1,525 @@
+use anyhow::{anyhow, Result};
+use matrix_sdk::ruma::events::room::{message::MessageType, MediaSource};
+use tokio::time::Duration;
+
+/// What the bridge bot gives the user to finish logging in, handed to the frontend as is.
+pub enum LoginArtifact {
+    PairingCode(String),
+    LoginUrl(String),
+    QrCodeUrl(String),
+    /// The bot wants something pasted back, e.g. a token or cookies, see bridge_auth::send_login_input
+    Instructions(String),
+}
+
+impl LoginArtifact {
+    /// Field of the connect response the frontend reads
+    pub fn response_key(&self) -> &'static str {
+        match self {
+            LoginArtifact::PairingCode(_) => "pairing_code",
+            LoginArtifact::LoginUrl(_) => "login_url",
+            LoginArtifact::QrCodeUrl(_) => "qr_code_url",
+            LoginArtifact::Instructions(_) => "instructions",
+        }
+    }
+
+    pub fn value(&self) -> &str {
+        match self {
+            LoginArtifact::PairingCode(value)
+            | LoginArtifact::LoginUrl(value)
+            | LoginArtifact::QrCodeUrl(value)
+            | LoginArtifact::Instructions(value) => value,
+        }
+    }
+}
+
+/// Everything that differs between the mautrix bridges. The connect flow in handlers::bridge_auth
+/// and the room helpers in utils::bridge are written once against this.
+pub trait BridgeService: Send + Sync {
+    /// Key used for bridges.bridge_type, priority senders and notification types
+    fn name(&self) -> &'static str;
+
+    fn display_name(&self) -> &'static str;
+
+    /// Localpart prefix of the puppet users the bridge creates for contacts
+    fn puppet_prefix(&self) -> &'static str;
+
+    /// What the bridge appends to portal room names
+    fn room_suffix(&self) -> &'static str;
+
+    fn bot_user_id(&self) -> Result<String> {
+        let env_key = format!("{}_BRIDGE_BOT", self.name().to_uppercase());
+        std::env::var(&env_key).map_err(|_| anyhow!("{} not set", env_key))
+    }
+
+    /// True when a message from this sender in this room came through this bridge
+    fn owns_room(&self, room_name: &str, sender_localpart: &str) -> bool {
+        room_name.contains(self.room_suffix()) && sender_localpart.starts_with(self.puppet_prefix())
+    }
+
+    /// Room name fragments of the bot's own management rooms, which are never chats
+    fn control_room_terms(&self) -> Vec<String> {
+        vec![
+            format!("{}bot", self.name()),
+            format!("{}-bridge", self.name()),
+            format!("{} Bridge", self.display_name()),
+            format!("{} bridge bot", self.display_name()),
+        ]
+    }
+
+    /// Sent before logging in to drop a login left half done
+    fn cancel_command(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn login_command(&self, phone_number: &str) -> String;
+
+    /// Picks the login artifact out of a bot message. Errors end the connect attempt.
+    fn login_artifact(&self, message: &MessageType) -> Result<Option<LoginArtifact>>;
+
+    fn is_login_success(&self, body: &str) -> bool;
+
+    fn is_login_failure(&self, body: &str) -> bool {
+        let body = body.to_lowercase();
+        [
+            "error",
+            "failed",
+            "timeout",
+            "disconnected",
+            "invalid code",
+            "connection lost",
+            "authentication failed",
+            "login failed",
+        ]
+        .iter()
+        .any(|pattern| body.contains(pattern))
+    }
+
+    /// Some bots only report the login when asked again
+    fn login_poll_command(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// How many times and how often the bot is checked for the login result
+    fn monitor_schedule(&self) -> (u32, Duration) {
+        (60, Duration::from_secs(3))
+    }
+
+    /// Commands that create portal rooms for existing chats after login
+    fn sync_commands(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Commands sent on disconnect, in order
+    fn logout_commands(&self) -> &'static [&'static str];
+
+    /// Asks the bot whether the login is still alive, see bridge_health
+    fn ping_command(&self) -> &'static str {
+        "ping"
+    }
+
+    /// Reads the answer to ping_command, None when it says neither
+    fn ping_result(&self, body: &str) -> Option<bool> {
+        let body = body.to_lowercase();
+        if body.contains("not logged in") || body.contains("logged out") || body.contains("not connected") || body.contains("disconnected") {
+            Some(false)
+        } else if body.contains("logged in as") || body.contains("connected") {
+            Some(true)
+        } else {
+            None
+        }
+    }
+
+    /// Ends "{service} disconnected, reply RECONNECT .." in the health check sms
+    fn reconnect_hint(&self) -> &'static str {
+        "to log in again"
+    }
+
+    /// Bridge notices that look like messages but should never reach the user
+    fn is_bridge_error(&self, body: &str) -> bool {
+        body.contains("Failed to bridge media")
+            || body.contains("media no longer available")
+            || body.contains(&format!("Decrypting message from {} failed", self.display_name()))
+            || body.starts_with("* Failed to")
+    }
+}
+
+fn text_body(message: &MessageType) -> Option<&str> {
+    match message {
+        MessageType::Text(text) => Some(&text.body),
+        MessageType::Notice(notice) => Some(&notice.body),
+        _ => None,
+    }
+}
+
+// mautrix bots post the login QR code as an unencrypted image
+fn qr_code_artifact(message: &MessageType) -> Option<LoginArtifact> {
+    let MessageType::Image(image) = message else { return None };
+    match &image.source {
+        MediaSource::Plain(url) => Some(LoginArtifact::QrCodeUrl(url.to_string())),
+        MediaSource::Encrypted(_) => {
+            tracing::error!("Unexpected encrypted QR code");
+            None
+        }
+    }
+}
+
+pub struct WhatsApp;
+
+impl BridgeService for WhatsApp {
+    fn name(&self) -> &'static str {
+        "whatsapp"
+    }
+
+    fn display_name(&self) -> &'static str {
+        "WhatsApp"
+    }
+
+    fn puppet_prefix(&self) -> &'static str {
+        "whatsapp_"
+    }
+
+    fn room_suffix(&self) -> &'static str {
+        "(WA)"
+    }
+
+    fn cancel_command(&self) -> Option<&'static str> {
+        Some("!wa cancel")
+    }
+
+    fn login_command(&self, phone_number: &str) -> String {
+        format!("!wa login phone {}", phone_number)
+    }
+
+    fn login_artifact(&self, message: &MessageType) -> Result<Option<LoginArtifact>> {
+        let Some(body) = text_body(message) else { return Ok(None) };
+        // The instructions that come with the code mention it too
+        if body.contains("Input the pairing code") {
+            return Ok(None);
+        }
+        let clean_body = body.replace('`', "").replace('*', "");
+        let re = regex::Regex::new(r"([A-Z0-9]{4}-[A-Z0-9]{4})")?;
+        Ok(re.captures(&clean_body).map(|captures| LoginArtifact::PairingCode(captures[1].to_string())))
+    }
+
+    fn is_login_success(&self, body: &str) -> bool {
+        body.contains("Successfully logged in as")
+    }
+
+    fn sync_commands(&self) -> &'static [&'static str] {
+        &["!wa sync contacts --create-portals", "!wa sync groups --create-portals"]
+    }
+
+    fn logout_commands(&self) -> &'static [&'static str] {
+        &["!wa logout", "!wa delete-all-portals", "!wa delete-session"]
+    }
+
+    fn ping_command(&self) -> &'static str {
+        "!wa ping"
+    }
+
+    fn reconnect_hint(&self) -> &'static str {
+        "for a pairing code"
+    }
+}
+
+pub struct Telegram;
+
+impl BridgeService for Telegram {
+    fn name(&self) -> &'static str {
+        "telegram"
+    }
+
+    fn display_name(&self) -> &'static str {
+        "Telegram"
+    }
+
+    fn puppet_prefix(&self) -> &'static str {
+        "telegram_"
+    }
+
+    fn room_suffix(&self) -> &'static str {
+        "(Telegram)"
+    }
+
+    fn cancel_command(&self) -> Option<&'static str> {
+        Some("!tg cancel")
+    }
+
+    fn login_command(&self, _phone_number: &str) -> String {
+        "!tg login".to_string()
+    }
+
+    fn login_artifact(&self, message: &MessageType) -> Result<Option<LoginArtifact>> {
+        let Some(body) = text_body(message) else { return Ok(None) };
+        let clean_body = body.replace('`', "").replace('*', "");
+        // The url comes inside a markdown link [text](url)
+        let re = regex::Regex::new(r"\((https?://[^\)]+)\)")?;
+        Ok(re.captures(&clean_body).map(|captures| LoginArtifact::LoginUrl(captures[1].to_string())))
+    }
+
+    fn is_login_success(&self, body: &str) -> bool {
+        body.contains("Logged in") || body.contains("You are already logged in")
+    }
+
+    fn login_poll_command(&self) -> Option<&'static str> {
+        Some("login")
+    }
+
+    fn monitor_schedule(&self) -> (u32, Duration) {
+        (120, Duration::from_secs(5))
+    }
+
+    fn sync_commands(&self) -> &'static [&'static str] {
+        &["sync contacts", "sync chats"]
+    }
+
+    fn logout_commands(&self) -> &'static [&'static str] {
+        &["logout", "clean-rooms"]
+    }
+}
+
+pub struct Signal;
+
+impl BridgeService for Signal {
+    fn name(&self) -> &'static str {
+        "signal"
+    }
+
+    fn display_name(&self) -> &'static str {
+        "Signal"
+    }
+
+    fn puppet_prefix(&self) -> &'static str {
+        "signal_"
+    }
+
+    fn room_suffix(&self) -> &'static str {
+        "(Signal)"
+    }
+
+    fn login_command(&self, _phone_number: &str) -> String {
+        "login".to_string()
+    }
+
+    fn login_artifact(&self, message: &MessageType) -> Result<Option<LoginArtifact>> {
+        match message {
+            MessageType::Image(_) => Ok(qr_code_artifact(message)),
+            MessageType::Text(text) if text.body.contains("error") => Err(anyhow!("Error from bot: {}", text.body)),
+            MessageType::Notice(notice) if notice.body.contains("error") => Err(anyhow!("Error from bot: {}", notice.body)),
+            _ => Ok(None),
+        }
+    }
+
+    fn is_login_success(&self, body: &str) -> bool {
+        body.contains("successful login") || body.contains("Logged in")
+    }
+
+    fn is_login_failure(&self, body: &str) -> bool {
+        let body = body.to_lowercase();
+        [
+            "error",
+            "failed",
+            "timeout",
+            "disconnected",
+            "invalid",
+            "connection lost",
+            "authentication failed",
+            "login failed",
+        ]
+        .iter()
+        .any(|pattern| body.contains(pattern))
+    }
+
+    // Portals are created as messages arrive, so nothing to sync
+
+    fn logout_commands(&self) -> &'static [&'static str] {
+        &["logout", "delete-all-portals", "delete-session"]
+    }
+}
+
+// The bridges below are configured with a username template of "<name>_{{.}}" and a room name
+// template ending in "(<Display name>)", see notes-for-matrix-bridges.md
+
+pub struct Discord;
+
+impl BridgeService for Discord {
+    fn name(&self) -> &'static str {
+        "discord"
+    }
+
+    fn display_name(&self) -> &'static str {
+        "Discord"
+    }
+
+    fn puppet_prefix(&self) -> &'static str {
+        "discord_"
+    }
+
+    fn room_suffix(&self) -> &'static str {
+        "(Discord)"
+    }
+
+    fn login_command(&self, _phone_number: &str) -> String {
+        "login".to_string()
+    }
+
+    fn login_artifact(&self, message: &MessageType) -> Result<Option<LoginArtifact>> {
+        Ok(qr_code_artifact(message))
+    }
+
+    fn is_login_success(&self, body: &str) -> bool {
+        body.contains("Successfully logged in")
+    }
+
+    // DMs are bridged as they come in, servers have to be bridged one by one in the bot room
+
+    fn logout_commands(&self) -> &'static [&'static str] {
+        &["logout"]
+    }
+}
+
+pub struct Slack;
+
+impl BridgeService for Slack {
+    fn name(&self) -> &'static str {
+        "slack"
+    }
+
+    fn display_name(&self) -> &'static str {
+        "Slack"
+    }
+
+    fn puppet_prefix(&self) -> &'static str {
+        "slack_"
+    }
+
+    fn room_suffix(&self) -> &'static str {
+        "(Slack)"
+    }
+
+    fn cancel_command(&self) -> Option<&'static str> {
+        Some("cancel")
+    }
+
+    fn login_command(&self, _phone_number: &str) -> String {
+        "login token".to_string()
+    }
+
+    fn login_artifact(&self, message: &MessageType) -> Result<Option<LoginArtifact>> {
+        // The bot asks for the xoxc token and then the d cookie from the Slack web app
+        Ok(text_body(message)
+            .filter(|body| body.to_lowercase().contains("token"))
+            .map(|body| LoginArtifact::Instructions(body.to_string())))
+    }
+
+    fn is_login_success(&self, body: &str) -> bool {
+        body.contains("Successfully logged in")
+    }
+
+    // Pasting the token takes a while
+    fn monitor_schedule(&self) -> (u32, Duration) {
+        (120, Duration::from_secs(5))
+    }
+
+    fn logout_commands(&self) -> &'static [&'static str] {
+        &["logout"]
+    }
+}
+
+/// Instagram and Messenger are two instances of mautrix-meta, they only differ in naming
+pub struct Meta {
+    name: &'static str,
+    display_name: &'static str,
+    puppet_prefix: &'static str,
+    room_suffix: &'static str,
+}
+
+impl BridgeService for Meta {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn display_name(&self) -> &'static str {
+        self.display_name
+    }
+
+    fn puppet_prefix(&self) -> &'static str {
+        self.puppet_prefix
+    }
+
+    fn room_suffix(&self) -> &'static str {
+        self.room_suffix
+    }
+
+    fn cancel_command(&self) -> Option<&'static str> {
+        Some("cancel")
+    }
+
+    fn login_command(&self, _phone_number: &str) -> String {
+        "login".to_string()
+    }
+
+    fn login_artifact(&self, message: &MessageType) -> Result<Option<LoginArtifact>> {
+        // Meta has no QR login, the bot asks for the browser cookies of a logged in session
+        Ok(text_body(message)
+            .filter(|body| body.to_lowercase().contains("cookie"))
+            .map(|body| LoginArtifact::Instructions(body.to_string())))
+    }
+
+    fn is_login_success(&self, body: &str) -> bool {
+        body.contains("Successfully logged in")
+    }
+
+    // Copying the cookies takes a while
+    fn monitor_schedule(&self) -> (u32, Duration) {
+        (120, Duration::from_secs(5))
+    }
+
+    fn logout_commands(&self) -> &'static [&'static str] {
+        &["logout"]
+    }
+}
+
+pub static WHATSAPP: WhatsApp = WhatsApp;
+pub static TELEGRAM: Telegram = Telegram;
+pub static SIGNAL: Signal = Signal;
+pub static DISCORD: Discord = Discord;
+pub static SLACK: Slack = Slack;
+pub static INSTAGRAM: Meta = Meta {
+    name: "instagram",
+    display_name: "Instagram",
+    puppet_prefix: "instagram_",
+    room_suffix: "(Instagram)",
+};
+pub static MESSENGER: Meta = Meta {
+    name: "messenger",
+    display_name: "Messenger",
+    puppet_prefix: "messenger_",
+    room_suffix: "(Messenger)",
+};
+
+pub fn all_services() -> [&'static dyn BridgeService; 7] {
+    [&WHATSAPP, &TELEGRAM, &SIGNAL, &DISCORD, &SLACK, &INSTAGRAM, &MESSENGER]
+}
+
+/// Names of every bridge, for tool parameter enums
+pub fn service_names() -> Vec<String> {
+    all_services().iter().map(|service| service.name().to_string()).collect()
+}
+
+/// Looks up a service by its name, e.g. a bridge_type or a platform the LLM picked
+pub fn service_for(name: &str) -> Option<&'static dyn BridgeService> {
+    let name = name.trim().to_lowercase();
+    all_services().into_iter().find(|service| service.name() == name)
+}
+
+/// Same as service_for, for callers that can only continue with a known service
+pub fn require_service(name: &str) -> Result<&'static dyn BridgeService> {
+    service_for(name).ok_or_else(|| anyhow!("Unsupported bridge service: {}", name))
+}
+
+/// Which bridge a message came through, from its room and the puppet that sent it
+pub fn detect_service(room_name: &str, sender_localpart: &str) -> Option<&'static dyn BridgeService> {
+    let sender_localpart = sender_localpart.trim();
+    all_services().into_iter().find(|service| service.owns_room(room_name, sender_localpart))
+}
@@ -0,0 +1,163 @@
+use std::env;
+use std::sync::Arc;
+
+use openai_api_rs::v1::chat_completion;
+
+use crate::models::user_models::MessageHistory;
+use crate::utils::llm_provider::{LlmChain, LlmPurpose};
+use crate::AppState;
+
+const SUMMARY_PROMPT: &str = "You maintain a running memory of a conversation between a user and their SMS/voice assistant lightfriend. You get the current summary (possibly empty) and messages that happened after it. Return an updated summary in plain text, at most 120 words, written in third person about the user. Keep facts, decisions, open requests and names the assistant may need later. Drop greetings, small talk and anything already resolved unless it may come up again. Never include raw tool output or JSON.";
+
+/// Rolling memory for the agent. Everything up to `summarized_until` lives in the summary,
+/// newer messages are replayed as is.
+pub struct ConversationContext {
+    pub summary: Option<String>,
+    pub recent: Vec<MessageHistory>, // oldest first, user and assistant messages only
+}
+
+// Rough token count, good enough for deciding when to summarize
+fn estimate_tokens(text: &str) -> usize {
+    text.len() / 4 + 4
+}
+
+fn token_budget() -> usize {
+    env::var("CONVERSATION_TOKEN_BUDGET")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(1500)
+}
+
+/// Summary plus the unsummarized messages of the last `max_exchanges` user messages.
+pub fn load_context(state: &Arc<AppState>, user_id: i32, max_exchanges: i64) -> ConversationContext {
+    let (summary, summarized_until) = match state.user_repository.get_conversation_summary(user_id) {
+        Ok(Some((summary, until))) => (Some(summary), until),
+        Ok(None) => (None, 0),
+        Err(e) => {
+            tracing::error!("Failed to get conversation summary for user {}: {}", user_id, e);
+            (None, 0)
+        }
+    };
+
+    let recent = state.user_repository
+        .get_conversation_history(user_id, max_exchanges, false)
+        .unwrap_or_default()
+        .into_iter()
+        .rev()
+        .filter(|msg| msg.created_at > summarized_until)
+        .filter(|msg| msg.role == "user" || msg.role == "assistant")
+        .collect();
+
+    ConversationContext { summary, recent }
+}
+
+/// Folds older messages into the summary once the unsummarized history is over the token
+/// budget. The newest messages worth half the budget stay verbatim.
+pub async fn refresh_summary(state: &Arc<AppState>, user_id: i32) {
+    let (summary, summarized_until) = match state.user_repository.get_conversation_summary(user_id) {
+        Ok(Some((summary, until))) => (summary, until),
+        Ok(None) => (String::new(), 0),
+        Err(e) => {
+            tracing::error!("Failed to get conversation summary for user {}: {}", user_id, e);
+            return;
+        }
+    };
+    let messages = match state.user_repository.get_messages_since(user_id, summarized_until) {
+        Ok(messages) => messages,
+        Err(e) => {
+            tracing::error!("Failed to get messages to summarize for user {}: {}", user_id, e);
+            return;
+        }
+    };
+
+    let budget = token_budget();
+    let total: usize = messages.iter().map(|m| estimate_tokens(&m.encrypted_content)).sum();
+    if total <= budget {
+        return;
+    }
+
+    let mut keep_tokens = 0;
+    let mut split = messages.len();
+    while split > 0 {
+        let tokens = estimate_tokens(&messages[split - 1].encrypted_content);
+        if keep_tokens + tokens > budget / 2 {
+            break;
+        }
+        keep_tokens += tokens;
+        split -= 1;
+    }
+    let to_fold = &messages[..split];
+    let Some(last_folded) = to_fold.last() else { return };
+    let new_until = last_folded.created_at;
+
+    let transcript = to_fold
+        .iter()
+        .map(|m| format!("{}: {}", m.role, m.encrypted_content))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let llm = match LlmChain::for_purpose(state, LlmPurpose::Summary, Some(user_id)) {
+        Ok(llm) => llm,
+        Err(e) => {
+            tracing::error!("Failed to set up summary llm chain: {}", e);
+            return;
+        }
+    };
+    let request = chat_completion::ChatCompletionRequest::new(
+        llm.primary_model().to_string(),
+        vec![
+            chat_completion::ChatCompletionMessage {
+                role: chat_completion::MessageRole::system,
+                content: chat_completion::Content::Text(SUMMARY_PROMPT.to_string()),
+                name: None,
+                tool_calls: None,
+                tool_call_id: None,
+            },
+            chat_completion::ChatCompletionMessage {
+                role: chat_completion::MessageRole::user,
+                content: chat_completion::Content::Text(format!(
+                    "Current summary:\n{}\n\nNew messages:\n{}",
+                    if summary.is_empty() { "(none)" } else { summary.as_str() },
+                    transcript
+                )),
+                name: None,
+                tool_calls: None,
+                tool_call_id: None,
+            },
+        ],
+    )
+    .max_tokens(300);
+
+    let new_summary = match llm.chat_completion(request).await {
+        Ok(result) => result.choices.first().and_then(|c| c.message.content.clone()),
+        Err(e) => {
+            tracing::error!("Failed to summarize conversation for user {}: {}", user_id, e);
+            return;
+        }
+    };
+    let Some(new_summary) = new_summary.filter(|s| !s.trim().is_empty()) else {
+        tracing::warn!("Empty conversation summary for user {}, keeping the old one", user_id);
+        return;
+    };
+
+    match state.user_repository.save_conversation_summary(user_id, new_summary.trim(), new_until) {
+        Ok(()) => tracing::debug!("Folded {} messages into the conversation summary of user {}", to_fold.len(), user_id),
+        Err(e) => tracing::error!("Failed to save conversation summary for user {}: {}", user_id, e),
+    }
+}
+
+/// Deletes history beyond the last `max_exchanges` user messages, as far as the summary
+/// already covers it. Run after refresh_summary so nothing goes unsummarized.
+pub fn prune_history(state: &Arc<AppState>, user_id: i32, max_exchanges: i64) {
+    let summarized_until = match state.user_repository.get_conversation_summary(user_id) {
+        Ok(Some((_, until))) => until,
+        Ok(None) => return,
+        Err(e) => {
+            tracing::error!("Failed to get conversation summary for user {}: {}", user_id, e);
+            return;
+        }
+    };
+    if let Err(e) = state.user_repository.delete_old_message_history(user_id, max_exchanges, summarized_until) {
+        tracing::error!("Failed to clean up old message history for user {}: {}", user_id, e);
+    }
+}
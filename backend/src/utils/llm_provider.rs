@@ -0,0 +1,309 @@
+use std::env;
+use std::error::Error;
+use std::sync::Arc;
+use std::time::Duration;
+
+use openai_api_rs::v1::{
+    api::OpenAIClient,
+    chat_completion::{ChatCompletionRequest, ChatCompletionResponse},
+    common::GPT4_O,
+};
+
+use crate::AppState;
+
+const OPENROUTER_ENDPOINT: &str = "https://openrouter.ai/api/v1";
+const OPENAI_ENDPOINT: &str = "https://api.openai.com/v1";
+const LOCAL_ENDPOINT: &str = "http://localhost:11434/v1"; // ollama default, llama.cpp server works the same
+
+/// What a model call is used for. Each purpose gets its own ordered chain of
+/// providers so e.g. the cheap classification calls can run on a local model
+/// while the agent stays on a cloud one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LlmPurpose {
+    Agent,             // the sms agent answering the user
+    ImportanceCheck,   // is an incoming message critical
+    Digest,            // morning/day/evening digests
+    WaitingCheckMatch, // does a message satisfy a waiting check
+    Evaluation,        // small helpers: clarification/eval checks, picking an email
+    Summary,           // rolling conversation summary
+    MemoryExtraction,  // picking durable facts about the user out of messages
+}
+
+impl LlmPurpose {
+    fn env_key(&self) -> &'static str {
+        match self {
+            LlmPurpose::Agent => "AGENT",
+            LlmPurpose::ImportanceCheck => "IMPORTANCE",
+            LlmPurpose::Digest => "DIGEST",
+            LlmPurpose::WaitingCheckMatch => "WAITING_CHECK",
+            LlmPurpose::Evaluation => "EVALUATION",
+            LlmPurpose::Summary => "SUMMARY",
+            LlmPurpose::MemoryExtraction => "MEMORY",
+        }
+    }
+
+    // What we used before the chains were configurable
+    fn default_chain(&self) -> String {
+        match self {
+            LlmPurpose::Evaluation | LlmPurpose::Summary | LlmPurpose::MemoryExtraction => "openrouter:openai/gpt-4o-mini".to_string(),
+            _ => format!("openrouter:{}", GPT4_O),
+        }
+    }
+}
+
+/// A single OpenAI compatible endpoint + model.
+#[derive(Clone, Debug)]
+pub struct LlmProvider {
+    pub name: String,
+    pub endpoint: String,
+    pub api_key: String,
+    pub model: String,
+    pub timeout: Duration,
+}
+
+impl LlmProvider {
+    fn client(&self) -> Result<OpenAIClient, Box<dyn Error>> {
+        OpenAIClient::builder()
+            .with_endpoint(self.endpoint.clone())
+            .with_api_key(self.api_key.clone())
+            .build()
+            .map_err(|e| e.into())
+    }
+}
+
+/// Ordered providers for one purpose. Calls go to the first one and fall through
+/// to the next on error or timeout.
+pub struct LlmChain {
+    pub purpose: LlmPurpose,
+    pub providers: Vec<LlmProvider>,
+}
+
+impl LlmChain {
+    /// Builds the chain from env:
+    ///   LLM_<PURPOSE>_MODELS=openrouter:openai/gpt-4o,local:llama3.1:8b
+    ///   LLM_PROVIDER_<NAME>_URL / LLM_PROVIDER_<NAME>_API_KEY for anything other than openrouter
+    ///   LLM_TIMEOUT_SECONDS per attempt, default 30
+    /// If `user_id` is given and the user has their own OpenRouter key or model set,
+    /// that goes first in the chain.
+    pub fn for_purpose(
+        state: &Arc<AppState>,
+        purpose: LlmPurpose,
+        user_id: Option<i32>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let timeout = Duration::from_secs(
+            env::var("LLM_TIMEOUT_SECONDS")
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .unwrap_or(30),
+        );
+        let chain_str = env::var(format!("LLM_{}_MODELS", purpose.env_key()))
+            .ok()
+            .filter(|v| !v.trim().is_empty())
+            .unwrap_or_else(|| purpose.default_chain());
+
+        let mut providers = parse_provider_chain(state, &chain_str, timeout, &format!("{:?}", purpose));
+
+        if let Some(user_id) = user_id {
+            if let Some(user_provider) = user_override(state, user_id, &providers, timeout) {
+                providers.insert(0, user_provider);
+            }
+        }
+
+        if providers.is_empty() {
+            return Err(format!("No usable llm providers configured for {:?}", purpose).into());
+        }
+        Ok(Self { purpose, providers })
+    }
+
+    /// Model of the first provider, for logging and usage records.
+    pub fn primary_model(&self) -> &str {
+        &self.providers[0].model
+    }
+
+    pub async fn chat_completion(
+        &self,
+        request: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, Box<dyn Error>> {
+        // keep only the message, Box<dyn Error> is not Send and this runs inside spawned tasks
+        let mut last_error = String::new();
+        for provider in self.providers.iter() {
+            let client = match provider.client() {
+                Ok(client) => client,
+                Err(e) => {
+                    last_error = format!("{}: {}", provider.name, e);
+                    continue;
+                }
+            };
+            let mut attempt = request.clone();
+            attempt.model = provider.model.clone();
+
+            match tokio::time::timeout(provider.timeout, client.chat_completion(attempt)).await {
+                Ok(Ok(response)) => return Ok(response),
+                Ok(Err(e)) => {
+                    tracing::warn!("{:?} call to {} ({}) failed: {}", self.purpose, provider.name, provider.model, e);
+                    last_error = format!("{}: {}", provider.name, e);
+                }
+                Err(_) => {
+                    tracing::warn!("{:?} call to {} ({}) timed out after {:?}", self.purpose, provider.name, provider.model, provider.timeout);
+                    last_error = format!("{}: timed out", provider.name);
+                }
+            }
+        }
+        Err(format!("All llm providers failed for {:?}, last error: {}", self.purpose, last_error).into())
+    }
+}
+
+/// Providers from a chain string like "openrouter:openai/gpt-4o,local:llama3.1:8b".
+/// Entries whose endpoint isn't configured are skipped, `label` is only for the log.
+pub fn parse_provider_chain(
+    state: &Arc<AppState>,
+    chain_str: &str,
+    timeout: Duration,
+    label: &str,
+) -> Vec<LlmProvider> {
+    let mut providers = Vec::new();
+    for entry in chain_str.split(',').map(|e| e.trim()).filter(|e| !e.is_empty()) {
+        // split on the first colon only, ollama model names have colons too
+        let (name, model) = match entry.split_once(':') {
+            Some((name, model)) => (name.trim().to_lowercase(), model.trim().to_string()),
+            None => ("openrouter".to_string(), entry.to_string()),
+        };
+        match provider_endpoint(state, &name) {
+            Ok((endpoint, api_key)) => providers.push(LlmProvider {
+                name,
+                endpoint,
+                api_key,
+                model,
+                timeout,
+            }),
+            Err(e) => tracing::warn!("Skipping llm provider {} for {}: {}", name, label, e),
+        }
+    }
+    providers
+}
+
+/// The instance wide OpenRouter key, from the env or the self hosted owner's settings.
+pub fn openrouter_api_key(state: &Arc<AppState>) -> Result<String, Box<dyn Error>> {
+    let is_self_hosted = env::var("ENVIRONMENT") == Ok("self_hosted".to_string());
+    if is_self_hosted {
+        match state.user_core.get_settings_for_tier3() {
+            Ok((_, _, Some(api_key), _, _, _)) => Ok(api_key),
+            Err(e) => {
+                tracing::error!("❌ Failed to get self hosted OpenRouter key: {}", e);
+                Err("❌ Failed to get self hosted OpenRouter key".into())
+            }
+            _ => Err("❌ Self hosted OpenRouter key not set".into()),
+        }
+    } else {
+        Ok(env::var("OPENROUTER_API_KEY")?)
+    }
+}
+
+fn provider_endpoint(state: &Arc<AppState>, name: &str) -> Result<(String, String), Box<dyn Error>> {
+    let env_name = name.to_uppercase().replace('-', "_");
+    let url = env::var(format!("LLM_PROVIDER_{}_URL", env_name)).ok();
+    let api_key = env::var(format!("LLM_PROVIDER_{}_API_KEY", env_name)).ok();
+
+    match name {
+        "openrouter" => Ok((
+            url.unwrap_or_else(|| OPENROUTER_ENDPOINT.to_string()),
+            match api_key {
+                Some(key) => key,
+                None => openrouter_api_key(state)?,
+            },
+        )),
+        "openai" => Ok((
+            url.unwrap_or_else(|| OPENAI_ENDPOINT.to_string()),
+            match api_key {
+                Some(key) => key,
+                None => env::var("OPENAI_API_KEY")?,
+            },
+        )),
+        // local servers don't check the key but the client wants one
+        "local" => Ok((
+            url.unwrap_or_else(|| LOCAL_ENDPOINT.to_string()),
+            api_key.unwrap_or_else(|| "local".to_string()),
+        )),
+        _ => match url {
+            Some(url) => Ok((url, api_key.unwrap_or_else(|| "none".to_string()))),
+            None => Err(format!("LLM_PROVIDER_{}_URL not set", env_name).into()),
+        },
+    }
+}
+
+/// Models users may pick without their own OpenRouter key, from LLM_USER_MODELS
+/// (comma separated OpenRouter ids). Unset means none, the instance key only runs the
+/// operator's chains.
+pub fn model_allowed_on_instance_key(model: &str) -> bool {
+    env::var("LLM_USER_MODELS")
+        .map(|v| v.split(',').any(|m| m.trim() == model))
+        .unwrap_or(false)
+}
+
+/// Whether OpenRouter knows the model id.
+pub async fn openrouter_model_exists(model: &str) -> Result<bool, String> {
+    #[derive(serde::Deserialize)]
+    struct ModelList {
+        data: Vec<ModelEntry>,
+    }
+    #[derive(serde::Deserialize)]
+    struct ModelEntry {
+        id: String,
+    }
+
+    let list: ModelList = reqwest::Client::new()
+        .get(format!("{}/models", OPENROUTER_ENDPOINT))
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?
+        .error_for_status()
+        .map_err(|e| e.to_string())?
+        .json()
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(list.data.iter().any(|m| m.id == model))
+}
+
+// User's own OpenRouter key and/or preferred model. On self hosted the stored key
+// is the instance key and already used by the normal chain. Without their own key a
+// model only applies if the operator allows it, so nobody runs their pick on the instance bill.
+fn user_override(
+    state: &Arc<AppState>,
+    user_id: i32,
+    providers: &[LlmProvider],
+    timeout: Duration,
+) -> Option<LlmProvider> {
+    if env::var("ENVIRONMENT") == Ok("self_hosted".to_string()) {
+        return None;
+    }
+    let (user_key, user_model) = match state.user_core.get_llm_override(user_id) {
+        Ok(o) => o,
+        Err(e) => {
+            tracing::error!("Failed to get llm override for user {}: {}", user_id, e);
+            return None;
+        }
+    };
+    let user_model = match user_key {
+        Some(_) => user_model,
+        None => user_model.filter(|m| model_allowed_on_instance_key(m)),
+    };
+    if user_key.is_none() && user_model.is_none() {
+        return None;
+    }
+
+    let default_openrouter = providers.iter().find(|p| p.name == "openrouter");
+    let model = user_model.or_else(|| default_openrouter.map(|p| p.model.clone()))?;
+    let api_key = match user_key {
+        Some(key) => key,
+        None => openrouter_api_key(state).ok()?,
+    };
+
+    Some(LlmProvider {
+        name: "openrouter (user)".to_string(),
+        endpoint: OPENROUTER_ENDPOINT.to_string(),
+        api_key,
+        model,
+        timeout,
+    })
+}
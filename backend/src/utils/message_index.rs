@@ -0,0 +1,194 @@
+use std::sync::Arc;
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::models::user_models::{IndexedMessage, NewIndexedMessage};
+use crate::AppState;
+
+// Anything older is only found live on the homeserver like before
+pub const RETENTION_DAYS: i64 = 90;
+const MAX_MESSAGES_PER_USER: i64 = 20_000;
+// Long emails are cut, the start is what gets asked about
+const MAX_CONTENT_CHARS: usize = 4000;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Lowercased words of the text as keyed hashes, so the fts table never holds readable text.
+/// Same words give the same tokens, which is all the search needs.
+fn hashed_tokens(text: &str) -> Vec<String> {
+    let Ok(key) = std::env::var("ENCRYPTION_KEY") else {
+        tracing::error!("ENCRYPTION_KEY not set, can't index messages");
+        return Vec::new();
+    };
+    let mut words: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() >= 2)
+        .map(|word| word.to_lowercase())
+        .collect();
+    words.sort();
+    words.dedup();
+
+    words
+        .iter()
+        .filter_map(|word| {
+            let mut mac = HmacSha256::new_from_slice(format!("message-index:{}", key).as_bytes()).ok()?;
+            mac.update(word.as_bytes());
+            // letters first so fts5 never reads a token as a number or operator
+            Some(format!("t{}", hex::encode(&mac.finalize().into_bytes()[..8])))
+        })
+        .collect()
+}
+
+/// Adds a bridged message or email to the user's index. The room name, sender and content are
+/// searchable. Only logs on failure, indexing never holds up the message itself.
+pub fn index_message(state: &AppState, mut message: NewIndexedMessage) {
+    if message.encrypted_content.chars().count() > MAX_CONTENT_CHARS {
+        message.encrypted_content = message.encrypted_content.chars().take(MAX_CONTENT_CHARS).collect();
+    }
+    let tokens = hashed_tokens(&format!(
+        "{} {} {}",
+        message.encrypted_room_name, message.encrypted_sender, message.encrypted_content
+    ));
+    if tokens.is_empty() {
+        return;
+    }
+
+    if let Err(e) = state.user_repository.index_message(&message, &tokens.join(" ")) {
+        tracing::error!("Failed to index {} message for user {}: {}", message.platform, message.user_id, e);
+    }
+}
+
+/// Indexed messages with all the words of the query, newest first. When nothing has all of
+/// them, ones with any of them.
+pub fn search(
+    state: &AppState,
+    user_id: i32,
+    query: &str,
+    platform: Option<&str>,
+    since: i32,
+    limit: i64,
+) -> Vec<IndexedMessage> {
+    let tokens = hashed_tokens(query);
+    if tokens.is_empty() {
+        return Vec::new();
+    }
+
+    let mut results = Vec::new();
+    for match_query in [tokens.join(" "), tokens.join(" OR ")] {
+        results = state.user_repository
+            .search_indexed_messages(user_id, &match_query, platform, since, limit)
+            .unwrap_or_else(|e| {
+                tracing::error!("Failed to search indexed messages for user {}: {}", user_id, e);
+                Vec::new()
+            });
+        if !results.is_empty() || tokens.len() == 1 {
+            break;
+        }
+    }
+    results
+}
+
+// Coverage is moved forward at most this often, a sync answers at least every 30 seconds
+const COVERAGE_STEP_SECONDS: i64 = 60;
+// Gap between two spans, or between the last one and now, that still counts as covered
+const COVERAGE_SLACK_SECONDS: i32 = 2 * 60;
+
+/// One run of a user's Matrix sync loop. Every message the sync delivers is indexed, so while
+/// it keeps succeeding the index has everything. The span starts with the first successful sync,
+/// a sync loop that failed starts a new one, what happened in between may have been skipped.
+pub struct IndexCoverage {
+    state: Arc<AppState>,
+    user_id: i32,
+    span: std::sync::Mutex<Option<(i32, i64)>>, // span id, when it was last moved forward
+}
+
+impl IndexCoverage {
+    pub fn new(state: &Arc<AppState>, user_id: i32) -> Arc<Self> {
+        Arc::new(Self {
+            state: state.clone(),
+            user_id,
+            span: std::sync::Mutex::new(None),
+        })
+    }
+
+    /// Called after every successful sync.
+    pub fn synced(&self) {
+        let now = chrono::Utc::now().timestamp();
+        let mut span = self.span.lock().unwrap_or_else(|e| e.into_inner());
+        match *span {
+            Some((_, extended_at)) if now - extended_at < COVERAGE_STEP_SECONDS => {}
+            Some((id, _)) => {
+                if let Err(e) = self.state.user_repository.extend_index_coverage(id, now as i32) {
+                    tracing::error!("Failed to extend message index coverage for user {}: {}", self.user_id, e);
+                    return;
+                }
+                *span = Some((id, now));
+            }
+            None => match self.state.user_repository.start_index_coverage(self.user_id, now as i32) {
+                Ok(id) => *span = Some((id, now)),
+                Err(e) => tracing::error!("Failed to start message index coverage for user {}: {}", self.user_id, e),
+            },
+        }
+    }
+}
+
+/// Whether every message of the platform since `from` went through the index: the bridge was
+/// connected by then and the sync ran without a gap from then until now.
+pub fn covers(state: &AppState, user_id: i32, platform: &str, from: i32) -> bool {
+    if from < retention_start() {
+        return false;
+    }
+    let connected_at = match state.user_repository.get_bridge(user_id, platform) {
+        Ok(Some(bridge)) => bridge.created_at.unwrap_or(i32::MAX),
+        _ => return false,
+    };
+    if connected_at > from {
+        return false;
+    }
+    let spans = match state.user_repository.get_index_coverage(user_id, from) {
+        Ok(spans) => spans,
+        Err(e) => {
+            tracing::error!("Failed to get message index coverage for user {}: {}", user_id, e);
+            return false;
+        }
+    };
+
+    let now = chrono::Utc::now().timestamp() as i32;
+    let mut covered_until: Option<i32> = None;
+    for span in spans {
+        let reaches = match covered_until {
+            None => span.started_at <= from,
+            Some(until) => span.started_at <= until + COVERAGE_SLACK_SECONDS,
+        };
+        if reaches {
+            covered_until = Some(covered_until.map_or(span.covered_until, |until| until.max(span.covered_until)));
+        } else if covered_until.is_some() {
+            break;
+        }
+    }
+    covered_until.is_some_and(|until| until >= now - COVERAGE_SLACK_SECONDS)
+}
+
+/// Oldest time the index still has messages from.
+pub fn retention_start() -> i32 {
+    (chrono::Utc::now() - chrono::Duration::days(RETENTION_DAYS)).timestamp() as i32
+}
+
+/// Applies the retention limits, run daily by the scheduler.
+pub fn prune(state: &AppState) {
+    match state.user_repository.delete_old_indexed_messages(retention_start()) {
+        Ok(count) => tracing::debug!("Removed {} expired messages from the index", count),
+        Err(e) => tracing::error!("Failed to remove expired indexed messages: {}", e),
+    }
+    if let Err(e) = state.user_repository.delete_old_index_coverage(retention_start()) {
+        tracing::error!("Failed to remove expired message index coverage: {}", e);
+    }
+    match state.user_repository.trim_indexed_messages(MAX_MESSAGES_PER_USER) {
+        Ok(count) => tracing::debug!("Trimmed {} indexed messages over the per user limit", count),
+        Err(e) => tracing::error!("Failed to trim indexed messages: {}", e),
+    }
+    if let Err(e) = state.user_repository.clip_index_coverage_to_trimmed(MAX_MESSAGES_PER_USER) {
+        tracing::error!("Failed to clip message index coverage to trimmed messages: {}", e);
+    }
+}
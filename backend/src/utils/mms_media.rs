@@ -0,0 +1,309 @@
+use std::sync::Arc;
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use openai_api_rs::v1::chat_completion;
+
+use crate::api::twilio_sms::MediaItem;
+use crate::models::user_models::User;
+use crate::utils::llm_provider::{LlmChain, LlmPurpose};
+use crate::AppState;
+
+// Twilio caps MMS at 10 attachments, a handful of images is plenty for one answer
+const MAX_IMAGES: usize = 5;
+// Roughly 6k tokens of document text for the summary call
+const MAX_PDF_CHARS: usize = 24_000;
+
+const PDF_SUMMARY_PROMPT: &str = "You summarize a document for someone reading it on a basic phone. Say what the document is and list the key facts: names, amounts, dates, deadlines and anything the reader has to do. Plain text, no formatting, at most 80 words.";
+
+/// Attachments turned into something the agent can use.
+#[derive(Default)]
+pub struct PreparedMedia {
+    pub images: Vec<String>, // data urls, sent to the vision model together
+    pub notes: Vec<String>, // text about the other attachments, appended to the user's message
+    pub unsupported: Vec<String>, // content types we can't open
+    pub attachments: Vec<String>, // data urls of everything downloaded, forwarded as is over the bridges
+}
+
+impl PreparedMedia {
+    pub fn is_empty(&self) -> bool {
+        self.images.is_empty() && self.notes.is_empty()
+    }
+}
+
+enum MediaKind {
+    Image,
+    Contact,
+    Pdf,
+    Audio,
+    Unsupported,
+}
+
+fn media_kind(content_type: &str) -> MediaKind {
+    let content_type = content_type.split(';').next().unwrap_or("").trim().to_lowercase();
+    match content_type.as_str() {
+        // what the vision models accept
+        "image/jpeg" | "image/jpg" | "image/png" | "image/gif" | "image/webp" => MediaKind::Image,
+        "text/vcard" | "text/x-vcard" | "text/directory" => MediaKind::Contact,
+        "application/pdf" => MediaKind::Pdf,
+        t if t.starts_with("audio/") => MediaKind::Audio,
+        _ => MediaKind::Unsupported,
+    }
+}
+
+async fn load_media(state: &Arc<AppState>, user: &User, item: &MediaItem) -> Result<Vec<u8>, String> {
+    // Admin test messages carry the image inline
+    if let Some(data) = item.url.strip_prefix("data:") {
+        let encoded = data.split_once(";base64,").map(|(_, b)| b).ok_or("Unsupported data url")?;
+        return BASE64.decode(encoded).map_err(|e| e.to_string());
+    }
+    // Box<dyn Error> isn't Send, turn it into a string right away
+    match crate::api::twilio_utils::download_twilio_media(state, &item.url, user).await {
+        Ok(bytes) => Ok(bytes),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+pub struct VCardContact {
+    pub name: String,
+    pub phone: Option<String>,
+    pub email: Option<String>,
+}
+
+fn unescape_vcard(value: &str) -> String {
+    value.replace("\\n", " ").replace("\\N", " ").replace("\\,", ",").replace("\\;", ";").trim().to_string()
+}
+
+/// Contacts in a vCard file, one file can hold several. Only FN/N, the first TEL and the first EMAIL are kept.
+pub fn parse_vcards(text: &str) -> Vec<VCardContact> {
+    // Long lines are folded by starting the continuation with a space or tab
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let line = line.trim_end_matches('\r');
+        if line.starts_with(' ') || line.starts_with('\t') {
+            if let Some(last) = lines.last_mut() {
+                last.push_str(&line[1..]);
+                continue;
+            }
+        }
+        lines.push(line.to_string());
+    }
+
+    let mut contacts = Vec::new();
+    let mut name: Option<String> = None;
+    let mut structured_name: Option<String> = None;
+    let mut phone: Option<String> = None;
+    let mut email: Option<String> = None;
+
+    for line in lines {
+        let Some((key, value)) = line.split_once(':') else { continue };
+        // "item1.TEL;type=CELL" -> "TEL"
+        let key = key.split(';').next().unwrap_or("");
+        let key = key.rsplit('.').next().unwrap_or("").to_uppercase();
+        match key.as_str() {
+            "BEGIN" => {
+                name = None;
+                structured_name = None;
+                phone = None;
+                email = None;
+            }
+            "FN" => name = Some(unescape_vcard(value)).filter(|v| !v.is_empty()),
+            "N" => {
+                // family;given;additional;prefix;suffix
+                let parts: Vec<&str> = value.split(';').collect();
+                let given = parts.get(1).copied().unwrap_or("");
+                let family = parts.first().copied().unwrap_or("");
+                let joined = format!("{} {}", unescape_vcard(given), unescape_vcard(family));
+                structured_name = Some(joined.trim().to_string()).filter(|v| !v.is_empty());
+            }
+            "TEL" if phone.is_none() => {
+                let value = value.trim_start_matches("tel:");
+                phone = Some(unescape_vcard(value)).filter(|v| !v.is_empty());
+            }
+            "EMAIL" if email.is_none() => email = Some(unescape_vcard(value)).filter(|v| !v.is_empty()),
+            "END" => {
+                if let Some(name) = name.take().or(structured_name.take()) {
+                    contacts.push(VCardContact { name, phone: phone.take(), email: email.take() });
+                }
+            }
+            _ => {}
+        }
+    }
+    contacts
+}
+
+fn save_contacts(state: &Arc<AppState>, user_id: i32, bytes: &[u8]) -> String {
+    let text = String::from_utf8_lossy(bytes);
+    let contacts = parse_vcards(&text);
+    if contacts.is_empty() {
+        return "The user sent a contact card but it had no name in it.".to_string();
+    }
+
+    let mut described = Vec::new();
+    for contact in &contacts {
+        let details = [contact.phone.as_deref(), contact.email.as_deref()]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(", ");
+        let description = if details.is_empty() {
+            contact.name.clone()
+        } else {
+            format!("{} ({})", contact.name, details)
+        };
+        match state.user_repository.add_contact(user_id, &contact.name, contact.phone.as_deref(), contact.email.as_deref(), "mms") {
+            Ok(true) => described.push(format!("{} - saved to their contacts", description)),
+            Ok(false) => described.push(format!("{} - already in their contacts", description)),
+            Err(e) => {
+                tracing::error!("Failed to save contact for user {}: {}", user_id, e);
+                described.push(format!("{} - could not be saved", description));
+            }
+        }
+    }
+    format!("The user sent contact card(s): {}", described.join("; "))
+}
+
+async fn extract_pdf_text(bytes: Vec<u8>) -> Option<String> {
+    // pdf-extract is blocking and panics on some malformed files, keep it off the runtime
+    match tokio::task::spawn_blocking(move || pdf_extract::extract_text_from_mem(&bytes)).await {
+        Ok(Ok(text)) => {
+            let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+            if text.is_empty() { None } else { Some(text) }
+        }
+        Ok(Err(e)) => {
+            tracing::warn!("Failed to extract pdf text: {}", e);
+            None
+        }
+        Err(e) => {
+            tracing::warn!("Pdf text extraction panicked: {}", e);
+            None
+        }
+    }
+}
+
+async fn summarize_pdf(state: &Arc<AppState>, user_id: i32, text: &str) -> Option<String> {
+    let llm = match LlmChain::for_purpose(state, LlmPurpose::Summary, Some(user_id)) {
+        Ok(llm) => llm,
+        Err(e) => {
+            tracing::error!("Failed to set up summary llm chain: {}", e);
+            return None;
+        }
+    };
+    let text: String = text.chars().take(MAX_PDF_CHARS).collect();
+    let request = chat_completion::ChatCompletionRequest::new(
+        llm.primary_model().to_string(),
+        vec![
+            chat_completion::ChatCompletionMessage {
+                role: chat_completion::MessageRole::system,
+                content: chat_completion::Content::Text(PDF_SUMMARY_PROMPT.to_string()),
+                name: None,
+                tool_calls: None,
+                tool_call_id: None,
+            },
+            chat_completion::ChatCompletionMessage {
+                role: chat_completion::MessageRole::user,
+                content: chat_completion::Content::Text(text),
+                name: None,
+                tool_calls: None,
+                tool_call_id: None,
+            },
+        ],
+    )
+    .max_tokens(200);
+
+    match llm.chat_completion(request).await {
+        Ok(result) => result.choices.first()
+            .and_then(|c| c.message.content.clone())
+            .filter(|s| !s.trim().is_empty()),
+        Err(e) => {
+            tracing::error!("Failed to summarize pdf for user {}: {}", user_id, e);
+            None
+        }
+    }
+}
+
+/// Downloads every attachment of an MMS and turns it into images for the vision model
+/// or notes for the agent. vCards are saved to the user's contacts on the way and
+/// every attachment is kept as a data url in case the user wants it sent to a chat.
+pub async fn prepare_media(state: &Arc<AppState>, user: &User, items: &[MediaItem]) -> PreparedMedia {
+    let mut prepared = PreparedMedia::default();
+
+    for item in items {
+        let kind = media_kind(&item.content_type);
+        if let MediaKind::Image = kind {
+            if prepared.images.len() >= MAX_IMAGES {
+                tracing::debug!("Skipping image over the limit of {} for user {}", MAX_IMAGES, user.id);
+                continue;
+            }
+        }
+
+        let bytes = match load_media(state, user, item).await {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                tracing::error!("Failed to download media {} for user {}: {}", item.sid, user.id, e);
+                if let MediaKind::Unsupported = kind {
+                    prepared.unsupported.push(item.content_type.clone());
+                } else {
+                    prepared.notes.push(format!("The user sent a {} attachment that could not be downloaded.", item.content_type));
+                }
+                continue;
+            }
+        };
+        // Even what we can't open can still be sent on to a chat
+        let data_url = format!("data:{};base64,{}", item.content_type, BASE64.encode(&bytes));
+        prepared.attachments.push(data_url.clone());
+
+        match kind {
+            MediaKind::Image => prepared.images.push(data_url),
+            MediaKind::Contact => prepared.notes.push(save_contacts(state, user.id, &bytes)),
+            MediaKind::Pdf => {
+                let note = match extract_pdf_text(bytes).await {
+                    Some(text) => match summarize_pdf(state, user.id, &text).await {
+                        Some(summary) => format!("The user sent a PDF document. Summary of it: {}", summary.trim()),
+                        None => format!(
+                            "The user sent a PDF document. Its text begins: {}",
+                            text.chars().take(1500).collect::<String>()
+                        ),
+                    },
+                    None => "The user sent a PDF document with no readable text, most likely a scan.".to_string(),
+                };
+                prepared.notes.push(note);
+            }
+            MediaKind::Audio => {
+                let note = match crate::utils::transcription::transcribe(state, bytes, &item.content_type).await {
+                    Ok(transcript) if !transcript.is_empty() => format!("The user sent a voice message. Transcript: {}", transcript),
+                    Ok(_) => "The user sent a voice message but no speech could be heard in it.".to_string(),
+                    Err(e) => {
+                        tracing::warn!("Failed to transcribe mms audio for user {}: {}", user.id, e);
+                        format!(
+                            "The user sent an audio file ({}) that could not be transcribed, ask them to send it as text.",
+                            item.content_type
+                        )
+                    }
+                };
+                prepared.notes.push(note);
+            }
+            MediaKind::Unsupported => prepared.unsupported.push(item.content_type.clone()),
+        }
+    }
+
+    prepared
+}
+
+/// Removes the attachments from Twilio once they are downloaded.
+pub async fn delete_media(state: &Arc<AppState>, user: &User, items: &[MediaItem]) {
+    for item in items.iter().filter(|item| !item.sid.is_empty()) {
+        tracing::debug!("Attempting to delete media with SID: {}", item.sid);
+        match crate::api::twilio_utils::delete_twilio_message_media(state, &item.sid, user).await {
+            Ok(_) => tracing::debug!("Successfully deleted media: {}", item.sid),
+            Err(e) => tracing::error!("Failed to delete media {}: {}", item.sid, e),
+        }
+    }
+}
+
+/// Reply for an MMS that had nothing we could open.
+pub fn unsupported_reply(content_types: &[String]) -> String {
+    format!(
+        "Sorry, I can't open {} attachments. I can read photos, contact cards, PDFs and voice messages, or just send your question as text. (you were not charged for this message)",
+        content_types.join(", ")
+    )
+}
@@ -0,0 +1,162 @@
+use std::sync::Arc;
+
+use axum::http::StatusCode;
+use axum::Json;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::api::twilio_sms::TwilioResponse;
+use crate::models::user_models::User;
+use crate::tool_call_utils::confirm::ActionPayload;
+use crate::AppState;
+
+// How far back "reply to N" reaches
+const MAX_REPLY_INDEX: i64 = 10;
+
+/// What a notification was about, stored when it goes out so the user can answer it with "r: ..".
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NotificationSource {
+    Chat {
+        platform: String, // bridge service name
+        room_id: String,
+        chat_name: String, // room name without the bridge suffix
+    },
+    Email {
+        email_id: String, // see imap_handlers::format_email_id
+        subject: String,
+    },
+}
+
+impl NotificationSource {
+    fn source_type(&self) -> &'static str {
+        match self {
+            NotificationSource::Chat { .. } => "chat",
+            NotificationSource::Email { .. } => "email",
+        }
+    }
+
+    fn reply_action(&self, text: &str) -> ActionPayload {
+        match self {
+            NotificationSource::Chat { platform, room_id, chat_name } => ActionPayload::ChatMessage {
+                platform: platform.clone(),
+                recipient: chat_name.clone(),
+                room_id: Some(room_id.clone()),
+                message: text.to_string(),
+                media_url: None,
+            },
+            NotificationSource::Email { email_id, subject } => ActionPayload::EmailReply {
+                email_id: email_id.clone(),
+                subject: subject.clone(),
+                response_text: text.to_string(),
+                account_id: crate::handlers::imap_handlers::parse_email_id(email_id).and_then(|(account_id, _)| account_id),
+            },
+        }
+    }
+}
+
+/// Remembers where a sent notification came from. Only logs on failure, the notification is out already.
+pub fn record(state: &Arc<AppState>, user_id: i32, source: Option<&NotificationSource>) {
+    let Some(source) = source else {
+        return;
+    };
+    let payload = match serde_json::to_string(source) {
+        Ok(payload) => payload,
+        Err(e) => {
+            tracing::error!("Failed to serialize notification source: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = state.user_repository.record_notification_source(user_id, source.source_type(), &payload) {
+        tracing::error!("Failed to record notification source for user {}: {}", user_id, e);
+    }
+}
+
+/// "r: text", "reply: text", "r 2: text" or "reply to 2: text". Returns which notification,
+/// 1 being the latest, and the text to send.
+pub fn parse_reply(body: &str) -> Option<(usize, String)> {
+    let reply = Regex::new(r"(?is)^\s*(?:r|reply)(?:\s*(?:to\s+)?#?(\d+))?\s*:\s*(.+?)\s*$").unwrap();
+    let caps = reply.captures(body)?;
+    let index = match caps.get(1) {
+        Some(m) => m.as_str().parse::<usize>().ok().filter(|n| *n >= 1)?,
+        None => 1,
+    };
+    Some((index, caps[2].to_string()))
+}
+
+// Same path as the send tools: confirmation if the user wants one, otherwise the outbox or straight out.
+// The bool tells if the user was already texted.
+async fn send_reply(state: &Arc<AppState>, user: &User, source: &NotificationSource, text: &str) -> (String, bool) {
+    let action = source.reply_action(text);
+    let require_confirmation = state.user_core.get_user_settings(user.id)
+        .map(|settings| settings.require_confirmation)
+        .unwrap_or(true);
+
+    if require_confirmation {
+        return match crate::tool_call_utils::confirm::propose_action(state, user, action).await {
+            Ok(prompt) => (prompt, true),
+            Err(e) => {
+                tracing::error!("Failed to propose notification reply: {}", e);
+                ("Failed to send the reply confirmation".to_string(), false)
+            }
+        };
+    }
+
+    let response = match crate::utils::outbox::queue_if_delayed(state, user, &action) {
+        Some(queued_msg) => queued_msg,
+        None => match action.execute(state, user).await {
+            Ok(msg) => msg,
+            Err(e) => e,
+        },
+    };
+    if let Err(e) = crate::utils::usage::deduct_user_credits(state, user.id, "message", None) {
+        tracing::error!("Failed to deduct user credits: {}", e);
+    }
+    (response, false)
+}
+
+/// Routes "r: .." and "reply to 2: .." to the chat or email thread of that notification,
+/// without the agent guessing the recipient. Returns None when the message isn't a reply command.
+pub async fn handle_notification_reply(
+    state: &Arc<AppState>,
+    user: &User,
+    user_message: &str,
+    is_test: bool,
+) -> Option<(StatusCode, [(axum::http::HeaderName, &'static str); 1], Json<TwilioResponse>)> {
+    let (index, text) = parse_reply(user_message)?;
+    tracing::info!("Routing reply to notification {} for user {}", index, user.id);
+
+    let sources = state.user_repository.get_recent_notification_sources(user.id, MAX_REPLY_INDEX)
+        .unwrap_or_else(|e| {
+            tracing::error!("Failed to get notification sources for user {}: {}", user.id, e);
+            Vec::new()
+        });
+    let source = sources.get(index - 1)
+        .and_then(|record| serde_json::from_str::<NotificationSource>(&record.encrypted_payload).ok());
+
+    let (response, already_sent) = match source {
+        Some(source) => send_reply(state, user, &source, &text).await,
+        None if sources.is_empty() => (
+            "There's no recent chat or email notification to reply to, tell me who to send it to instead.".to_string(),
+            false,
+        ),
+        None => (
+            format!("I can only reply to your last {} notifications, 'r: ..' answers the latest.", sources.len()),
+            false,
+        ),
+    };
+
+    if !is_test && !already_sent {
+        if let Err(e) = crate::api::twilio_utils::send_conversation_message(state, &response, None, user).await.map_err(|e| e.to_string()) {
+            tracing::error!("Failed to send notification reply response: {}", e);
+        }
+    }
+
+    Some((
+        StatusCode::OK,
+        [(axum::http::header::CONTENT_TYPE, "application/json")],
+        Json(TwilioResponse {
+            message: response,
+        })
+    ))
+}
@@ -0,0 +1,137 @@
+use std::sync::Arc;
+
+use crate::models::user_models::User;
+use crate::tool_call_utils::confirm::ActionPayload;
+use crate::AppState;
+
+// Longer than this and the user has probably forgotten what they confirmed
+pub const MAX_SEND_DELAY_SECONDS: i32 = 600;
+// A send that hasn't finished in this long was cut off by a crash or hangs
+const STUCK_SENDING_SECONDS: i32 = 600;
+
+/// Puts a confirmed chat message or email reply in the outbox when the user has a send delay.
+/// Returns the text for the user if it was queued, None means send it now.
+pub fn queue_if_delayed(state: &Arc<AppState>, user: &User, action: &ActionPayload) -> Option<String> {
+    if !matches!(action, ActionPayload::ChatMessage { .. } | ActionPayload::EmailReply { .. }) {
+        return None;
+    }
+    let delay = state.user_core.get_user_settings(user.id)
+        .map(|settings| settings.send_delay_seconds.clamp(0, MAX_SEND_DELAY_SECONDS))
+        .unwrap_or(0);
+    if delay == 0 {
+        return None;
+    }
+
+    let payload = serde_json::to_string(action).ok()?;
+    let send_at = chrono::Utc::now().timestamp() as i32 + delay;
+    if let Err(e) = state.user_repository.queue_outgoing_message(user.id, action.action_type(), &payload, send_at) {
+        // the user already confirmed, better to send it than to drop it
+        tracing::error!("Failed to queue outgoing message for user {}, sending right away: {}", user.id, e);
+        return None;
+    }
+
+    let timezone = state.user_core.get_user_info(user.id)
+        .ok()
+        .and_then(|info| info.timezone)
+        .unwrap_or_else(|| "UTC".to_string());
+    Some(format!(
+        "Queued: {}. It goes out in {} seconds, reply UNDO to stop it.",
+        action.describe(&timezone),
+        delay
+    ))
+}
+
+/// Cancels the most recently queued message, the reply for the UNDO command.
+pub fn undo_latest(state: &Arc<AppState>, user: &User) -> String {
+    match state.user_repository.cancel_latest_outgoing_message(user.id) {
+        Ok(Some(message)) => match serde_json::from_str::<ActionPayload>(&message.encrypted_payload) {
+            Ok(ActionPayload::ChatMessage { recipient, .. }) => format!("Cancelled, your message to {} was not sent.", recipient),
+            Ok(ActionPayload::EmailReply { subject, .. }) => format!("Cancelled, your reply regarding '{}' was not sent.", subject),
+            _ => "Cancelled, it was not sent.".to_string(),
+        },
+        Ok(None) => "Nothing to undo, the message has already been sent.".to_string(),
+        Err(e) => {
+            tracing::error!("Failed to cancel outgoing message for user {}: {}", user.id, e);
+            "Couldn't cancel right now, try again immediately.".to_string()
+        }
+    }
+}
+
+/// Fails messages left sending by a crashed or hung delivery run and tells their users.
+/// They aren't retried since they may have gone out already. Run by the scheduler.
+pub async fn fail_stuck_messages(state: &Arc<AppState>) {
+    let claimed_before = chrono::Utc::now().timestamp() as i32 - STUCK_SENDING_SECONDS;
+    let messages = match state.user_repository.fail_stuck_outgoing_messages(claimed_before) {
+        Ok(messages) => messages,
+        Err(e) => {
+            tracing::error!("Failed to fail stuck outgoing messages: {}", e);
+            return;
+        }
+    };
+
+    for message in messages {
+        tracing::warn!("Outgoing message {:?} of user {} was stuck sending, marked failed", message.id, message.user_id);
+        let user = match state.user_core.find_by_id(message.user_id) {
+            Ok(Some(user)) => user,
+            _ => continue,
+        };
+        let description = match serde_json::from_str::<ActionPayload>(&message.encrypted_payload) {
+            Ok(action) => {
+                let timezone = state.user_core.get_user_info(user.id)
+                    .ok()
+                    .and_then(|info| info.timezone)
+                    .unwrap_or_else(|| "UTC".to_string());
+                action.describe(&timezone)
+            }
+            Err(_) => "a queued message".to_string(),
+        };
+        let text = format!("Something went wrong while sending, this may not have gone out: {}. Please check and send it again if needed.", description);
+        if let Err(e) = crate::api::twilio_utils::send_conversation_message(state, &text, None, &user).await.map_err(|e| e.to_string()) {
+            tracing::error!("Failed to tell user {} about a stuck message: {}", user.id, e);
+        }
+    }
+}
+
+/// Sends everything whose delay has passed and texts the user the outcome. Run by the scheduler.
+pub async fn deliver_due_messages(state: &Arc<AppState>) {
+    let messages = match state.user_repository.claim_due_outgoing_messages() {
+        Ok(messages) => messages,
+        Err(e) => {
+            tracing::error!("Failed to claim outgoing messages: {}", e);
+            return;
+        }
+    };
+
+    for message in messages {
+        let Some(message_id) = message.id else { continue };
+        let user = match state.user_core.find_by_id(message.user_id) {
+            Ok(Some(user)) => user,
+            _ => {
+                tracing::error!("No user {} for outgoing message {}", message.user_id, message_id);
+                let _ = state.user_repository.finish_outgoing_message(message_id, "failed");
+                continue;
+            }
+        };
+        let action: ActionPayload = match serde_json::from_str(&message.encrypted_payload) {
+            Ok(action) => action,
+            Err(e) => {
+                tracing::error!("Outgoing message {} has an invalid payload: {}", message_id, e);
+                let _ = state.user_repository.finish_outgoing_message(message_id, "failed");
+                continue;
+            }
+        };
+
+        let (status, reply) = match action.execute(state, &user).await {
+            Ok(reply) => ("sent", reply),
+            Err(reply) => ("failed", reply),
+        };
+        if let Err(e) = state.user_repository.finish_outgoing_message(message_id, status) {
+            tracing::error!("Failed to mark outgoing message {} {}: {}", message_id, status, e);
+        }
+
+        // Delivery confirmation, free like the other confirmation replies
+        if let Err(e) = crate::api::twilio_utils::send_conversation_message(state, &reply, None, &user).await.map_err(|e| e.to_string()) {
+            tracing::error!("Failed to send delivery confirmation to user {}: {}", user.id, e);
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use std::sync::Arc;
+
+use chrono::TimeZone;
+
+use crate::models::user_models::{ScheduledSend, User};
+use crate::tool_call_utils::confirm::{format_local, user_timezone, ActionChanges, ActionPayload};
+use crate::AppState;
+
+// Further out is more likely a misread date than a plan
+const MAX_DAYS_AHEAD: i64 = 366;
+
+/// Reads a send time the model or the user gave. Times with an offset are taken as is,
+/// ones without ("2024-03-23T08:00", "2024-03-23 08:00") are in the user's timezone.
+pub fn parse_send_time(time: &str, timezone: &str) -> Result<chrono::DateTime<chrono::Utc>, String> {
+    if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(time.trim()) {
+        return Ok(dt.with_timezone(&chrono::Utc));
+    }
+    let tz: chrono_tz::Tz = timezone.parse().unwrap_or(chrono_tz::UTC);
+    ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|format| chrono::NaiveDateTime::parse_from_str(time.trim(), format).ok())
+        // earliest() also settles the hour that repeats when the clocks go back
+        .and_then(|naive| tz.from_local_datetime(&naive).earliest())
+        .map(|dt| dt.with_timezone(&chrono::Utc))
+        .ok_or_else(|| format!("'{}' is not a valid time", time))
+}
+
+pub fn check_send_time(send_at: chrono::DateTime<chrono::Utc>) -> Result<(), String> {
+    let now = chrono::Utc::now();
+    // a minute of slack for the time it took to ask
+    if send_at < now - chrono::Duration::minutes(1) {
+        return Err("That time has already passed".to_string());
+    }
+    if send_at > now + chrono::Duration::days(MAX_DAYS_AHEAD) {
+        return Err("Messages can be scheduled at most a year ahead".to_string());
+    }
+    Ok(())
+}
+
+fn is_schedulable(action: &ActionPayload) -> bool {
+    matches!(action, ActionPayload::ChatMessage { .. } | ActionPayload::Email { .. } | ActionPayload::Sms { .. })
+}
+
+/// One line for listings, e.g. "#4 March 23 at 08:00 AM EET: Send Telegram to 'Mike' ...".
+pub fn describe_send(send: &ScheduledSend, timezone: &str) -> Option<String> {
+    let action: ActionPayload = serde_json::from_str(&send.encrypted_payload).ok()?;
+    let send_at = chrono::DateTime::from_timestamp(send.send_at as i64, 0)?.to_rfc3339();
+    Some(format!("#{} {}: {}", send.id?, format_local(&send_at, timezone), action.describe(timezone)))
+}
+
+/// Stores the action to be sent at `send_at` (rfc3339). Both arms are texts for the user.
+pub fn schedule(state: &Arc<AppState>, user: &User, send_at: &str, action: &ActionPayload) -> Result<String, String> {
+    if !is_schedulable(action) {
+        return Err("Only chat messages, emails and texts can be scheduled.".to_string());
+    }
+    let timezone = user_timezone(state, user.id);
+    let send_time = parse_send_time(send_at, &timezone)?;
+    check_send_time(send_time)?;
+
+    let payload = serde_json::to_string(action).map_err(|e| e.to_string())?;
+    let id = state.user_repository
+        .create_scheduled_send(user.id, action.action_type(), &payload, send_time.timestamp() as i32)
+        .map_err(|e| {
+            tracing::error!("Failed to store scheduled send for user {}: {}", user.id, e);
+            "Failed to schedule the message, please try again.".to_string()
+        })?;
+
+    Ok(format!(
+        "Scheduled #{} for {}: {}. Text SCHEDULED to see what's coming up or UNSCHEDULE {} to cancel it.",
+        id,
+        format_local(&send_time.to_rfc3339(), &timezone),
+        action.describe(&timezone),
+        id
+    ))
+}
+
+/// The user's upcoming sends, the reply for the SCHEDULED command.
+pub fn list_text(state: &Arc<AppState>, user: &User) -> String {
+    let sends = match state.user_repository.get_scheduled_sends(user.id) {
+        Ok(sends) => sends,
+        Err(e) => {
+            tracing::error!("Failed to get scheduled sends for user {}: {}", user.id, e);
+            return "Failed to get your scheduled messages.".to_string();
+        }
+    };
+    if sends.is_empty() {
+        return "Nothing is scheduled.".to_string();
+    }
+    let timezone = user_timezone(state, user.id);
+    let lines: Vec<String> = sends.iter().filter_map(|send| describe_send(send, &timezone)).collect();
+    format!("{}\nReply UNSCHEDULE and the number to cancel one.", lines.join("\n"))
+}
+
+/// Cancels scheduled send `id`, the reply for the UNSCHEDULE command.
+pub fn cancel(state: &Arc<AppState>, user: &User, id: i32) -> String {
+    let timezone = user_timezone(state, user.id);
+    let description = state.user_repository
+        .get_scheduled_send(user.id, id)
+        .ok()
+        .flatten()
+        .and_then(|send| describe_send(&send, &timezone));
+    match state.user_repository.cancel_scheduled_send(user.id, id) {
+        Ok(true) => match description {
+            Some(description) => format!("Cancelled {}", description),
+            None => format!("Cancelled #{}.", id),
+        },
+        Ok(false) => format!("There's no scheduled message #{}, it may have been sent already.", id),
+        Err(e) => {
+            tracing::error!("Failed to cancel scheduled send {} of user {}: {}", id, user.id, e);
+            "Couldn't cancel right now, please try again.".to_string()
+        }
+    }
+}
+
+/// Changes the text and/or time of scheduled send `id`. Returns the new description.
+pub fn update(
+    state: &Arc<AppState>,
+    user_id: i32,
+    id: i32,
+    message: Option<&str>,
+    send_at: Option<&str>,
+) -> Result<String, String> {
+    let send = match state.user_repository.get_scheduled_send(user_id, id) {
+        Ok(Some(send)) => send,
+        Ok(None) => return Err(format!("There's no scheduled message #{}, it may have been sent already.", id)),
+        Err(e) => {
+            tracing::error!("Failed to get scheduled send {} of user {}: {}", id, user_id, e);
+            return Err("Failed to get the scheduled message.".to_string());
+        }
+    };
+    let mut action: ActionPayload = serde_json::from_str(&send.encrypted_payload)
+        .map_err(|_| "Failed to read the scheduled message.".to_string())?;
+    let timezone = user_timezone(state, user_id);
+
+    if let Some(message) = message {
+        action.apply_changes(&ActionChanges {
+            message: Some(message.to_string()),
+            ..Default::default()
+        })?;
+    }
+    let new_send_at = match send_at {
+        Some(time) => {
+            let time = parse_send_time(time, &timezone)?;
+            check_send_time(time)?;
+            time.timestamp() as i32
+        }
+        None => send.send_at,
+    };
+
+    let payload = serde_json::to_string(&action).map_err(|e| e.to_string())?;
+    match state.user_repository.update_scheduled_send(user_id, id, &payload, new_send_at) {
+        Ok(true) => {}
+        Ok(false) => return Err(format!("Scheduled message #{} was already sent.", id)),
+        Err(e) => {
+            tracing::error!("Failed to update scheduled send {} of user {}: {}", id, user_id, e);
+            return Err("Failed to save the change, please try again.".to_string());
+        }
+    }
+    let send_at = chrono::DateTime::from_timestamp(new_send_at as i64, 0).unwrap_or_default().to_rfc3339();
+    Ok(format!("Updated #{} {}: {}", id, format_local(&send_at, &timezone), action.describe(&timezone)))
+}
+
+/// Sends everything that is due and texts the user the outcome. Run by the scheduler.
+pub async fn deliver_due_sends(state: &Arc<AppState>) {
+    let sends = match state.user_repository.claim_due_scheduled_sends() {
+        Ok(sends) => sends,
+        Err(e) => {
+            tracing::error!("Failed to claim scheduled sends: {}", e);
+            return;
+        }
+    };
+
+    for send in sends {
+        let Some(send_id) = send.id else { continue };
+        let user = match state.user_core.find_by_id(send.user_id) {
+            Ok(Some(user)) => user,
+            _ => {
+                tracing::error!("No user {} for scheduled send {}", send.user_id, send_id);
+                let _ = state.user_repository.finish_scheduled_send(send_id, "failed");
+                continue;
+            }
+        };
+        let action: ActionPayload = match serde_json::from_str(&send.encrypted_payload) {
+            Ok(action) if is_schedulable(&action) => action,
+            _ => {
+                tracing::error!("Scheduled send {} has an invalid payload", send_id);
+                let _ = state.user_repository.finish_scheduled_send(send_id, "failed");
+                continue;
+            }
+        };
+
+        let result = action.execute(state, &user).await;
+        let status = if result.is_ok() { "sent" } else { "failed" };
+        if let Err(e) = state.user_repository.finish_scheduled_send(send_id, status) {
+            tracing::error!("Failed to mark scheduled send {} {}: {}", send_id, status, e);
+        }
+
+        // A text to the user is its own confirmation
+        let reply = match result {
+            Ok(_) if matches!(action, ActionPayload::Sms { .. }) => continue,
+            Ok(reply) => format!("Scheduled #{}: {}", send_id, reply),
+            Err(reply) => format!("Scheduled #{}: {}", send_id, reply),
+        };
+        if let Err(e) = crate::api::twilio_utils::send_conversation_message(state, &reply, None, &user).await.map_err(|e| e.to_string()) {
+            tracing::error!("Failed to send scheduled send result to user {}: {}", user.id, e);
+        }
+    }
+}
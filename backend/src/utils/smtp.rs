@@ -0,0 +1,335 @@
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::SmtpTransport;
+use serde::Deserialize;
+
+/// "starttls" upgrades a plain connection (usually port 587), "tls" is implicit TLS (usually 465).
+pub const SECURITY_MODES: [&str; 2] = ["starttls", "tls"];
+
+const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(5);
+// JSON DNS API, used for the SRV lookups so no resolver is needed
+const DNS_OVER_HTTPS_URL: &str = "https://cloudflare-dns.com/dns-query";
+const SRV_RECORD_TYPE: u16 = 33;
+
+/// How mail is sent for an email connection, see the smtp_ columns of imap_connection.
+#[derive(Debug, Clone)]
+pub struct SmtpSettings {
+    pub server: String,
+    pub port: u16,
+    pub security: String,
+    pub username: String,
+    pub password: String,
+}
+
+pub fn default_port(security: &str) -> u16 {
+    match security {
+        "tls" => 465,
+        _ => 587,
+    }
+}
+
+/// The SMTP host guessed from the IMAP one, for when nothing better is known.
+pub fn derived_server(imap_server: Option<&str>) -> String {
+    imap_server.unwrap_or("smtp.gmail.com").replace("imap", "smtp")
+}
+
+impl SmtpSettings {
+    /// What connections made before SMTP was configured use: the derived host,
+    /// STARTTLS on 587 and the IMAP login.
+    pub fn derived(imap_server: Option<&str>, email: &str, password: &str) -> Self {
+        SmtpSettings {
+            server: derived_server(imap_server),
+            port: default_port("starttls"),
+            security: "starttls".to_string(),
+            username: email.to_string(),
+            password: password.to_string(),
+        }
+    }
+
+    pub fn mailer(&self) -> Result<SmtpTransport, String> {
+        let builder = match self.security.as_str() {
+            "tls" => SmtpTransport::relay(&self.server),
+            _ => SmtpTransport::starttls_relay(&self.server),
+        }
+        .map_err(|e| format!("Invalid SMTP server {}: {}", self.server, e))?;
+
+        Ok(builder
+            .port(self.port)
+            .credentials(Credentials::new(self.username.clone(), self.password.clone()))
+            .build())
+    }
+
+    /// Connects and logs in without sending anything. Blocking.
+    pub fn verify(&self) -> Result<(), String> {
+        match self.mailer()?.test_connection() {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(format!("SMTP server {}:{} did not respond", self.server, self.port)),
+            Err(e) => Err(format!("Failed to log in to SMTP server {}:{}: {}", self.server, self.port, e)),
+        }
+    }
+}
+
+/// Mail servers found for an email domain. IMAP is always implicit TLS since that is
+/// all the IMAP code speaks.
+#[derive(Debug, Default)]
+pub struct DiscoveredSettings {
+    pub imap: Option<(String, u16)>,
+    pub smtp: Option<(String, u16, String)>,
+}
+
+/// Looks up the mail servers for the address's domain: the domain's own autoconfig file,
+/// then Thunderbird's ISP database, then SRV records (RFC 6186 and 8314).
+/// Whatever isn't found is None, the caller falls back to the old defaults.
+pub async fn discover(email: &str) -> DiscoveredSettings {
+    let mut found = DiscoveredSettings::default();
+    let Some((_, domain)) = email.trim().rsplit_once('@') else {
+        return found;
+    };
+    let domain = domain.to_lowercase();
+    if !is_public_domain(&domain) {
+        return found;
+    }
+    let Some(client) = discovery_client(None) else {
+        return found;
+    };
+
+    // Only the domain's own servers get the address, Thunderbird's database just the domain.
+    // The domain's hosts are resolved first and the request pinned to a public address
+    let autoconfig_urls = [
+        (Some(format!("autoconfig.{}", domain)), format!("https://autoconfig.{}/mail/config-v1.1.xml", domain), Some(email)),
+        (Some(domain.clone()), format!("https://{}/.well-known/autoconfig/mail/config-v1.1.xml", domain), Some(email)),
+        (None, format!("https://autoconfig.thunderbird.net/v1.1/{}", domain), None),
+    ];
+    for (host, url, address) in autoconfig_urls {
+        if found.imap.is_some() && found.smtp.is_some() {
+            break;
+        }
+        let client = match host {
+            Some(host) => {
+                let Some(addr) = public_address(&host, 443).await else {
+                    continue;
+                };
+                let Some(client) = discovery_client(Some((&host, addr))) else {
+                    continue;
+                };
+                client
+            }
+            None => client.clone(),
+        };
+        let Some(xml) = fetch_autoconfig(&client, &url, address).await else {
+            continue;
+        };
+        let settings = parse_autoconfig(&xml, email, &domain);
+        found.imap = found.imap.or(settings.imap);
+        found.smtp = found.smtp.or(settings.smtp);
+    }
+
+    if found.imap.is_none() {
+        found.imap = lookup_srv(&client, &format!("_imaps._tcp.{}", domain)).await;
+    }
+    if found.smtp.is_none() {
+        found.smtp = match lookup_srv(&client, &format!("_submissions._tcp.{}", domain)).await {
+            Some((host, port)) => Some((host, port, "tls".to_string())),
+            None => lookup_srv(&client, &format!("_submission._tcp.{}", domain))
+                .await
+                .map(|(host, port)| (host, port, "starttls".to_string())),
+        };
+    }
+
+    // The servers come from whoever controls the domain, don't connect to our own network
+    if let Some((host, port)) = found.imap.take() {
+        match public_address(&host, port).await {
+            Some(_) => found.imap = Some((host, port)),
+            None => tracing::warn!("Discovered IMAP server {} for {} isn't public, ignoring it", host, domain),
+        }
+    }
+    if let Some((host, port, security)) = found.smtp.take() {
+        match public_address(&host, port).await {
+            Some(_) => found.smtp = Some((host, port, security)),
+            None => tracing::warn!("Discovered SMTP server {} for {} isn't public, ignoring it", host, domain),
+        }
+    }
+    tracing::debug!("Discovered mail servers for {}: {:?}", domain, found);
+    found
+}
+
+// A domain that can only mean a host on the internet. It ends up in URLs and DNS names, so no
+// IP literals or single labels like "localhost" that would reach our own network.
+fn is_public_domain(domain: &str) -> bool {
+    let labels: Vec<&str> = domain.split('.').collect();
+    labels.len() >= 2
+        && domain.parse::<std::net::IpAddr>().is_err()
+        && labels.iter().all(|label| {
+            !label.is_empty()
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+        // no top level domain is all digits, e.g. a shortened ip like 127.1
+        && !labels[labels.len() - 1].chars().all(|c| c.is_ascii_digit())
+}
+
+// A redirect could point the lookup anywhere, e.g. at the internal network, so none are followed.
+// `pin` makes the client connect to an already checked address instead of resolving again.
+fn discovery_client(pin: Option<(&str, SocketAddr)>) -> Option<reqwest::Client> {
+    let mut builder = reqwest::Client::builder()
+        .timeout(DISCOVERY_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none());
+    if let Some((host, addr)) = pin {
+        builder = builder.resolve(host, addr);
+    }
+    match builder.build() {
+        Ok(client) => Some(client),
+        Err(e) => {
+            tracing::error!("Failed to create client for mail server discovery: {}", e);
+            None
+        }
+    }
+}
+
+/// Resolves the host and returns an address to connect to, None if it doesn't resolve or any
+/// of its addresses is loopback, private, link-local or otherwise not on the public internet.
+async fn public_address(host: &str, port: u16) -> Option<SocketAddr> {
+    if host.parse::<IpAddr>().is_ok() {
+        return None;
+    }
+    let addrs: Vec<SocketAddr> = tokio::time::timeout(DISCOVERY_TIMEOUT, tokio::net::lookup_host((host, port)))
+        .await
+        .ok()?
+        .ok()?
+        .collect();
+    if addrs.is_empty() || !addrs.iter().all(|addr| is_public_ip(addr.ip())) {
+        return None;
+    }
+    addrs.into_iter().next()
+}
+
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let octets = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                || octets[0] == 0
+                || (octets[0] == 100 && (octets[1] & 0xc0) == 64) // shared address space 100.64.0.0/10
+                || (octets[0] == 198 && (octets[1] & 0xfe) == 18) // benchmarking 198.18.0.0/15
+                || octets[0] >= 240)
+        }
+        IpAddr::V6(ip) => {
+            if let Some(mapped) = ip.to_ipv4_mapped() {
+                return is_public_ip(IpAddr::V4(mapped));
+            }
+            let first = ip.segments()[0];
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                || (first & 0xfe00) == 0xfc00 // unique local fc00::/7
+                || (first & 0xffc0) == 0xfe80) // link-local fe80::/10
+        }
+    }
+}
+
+async fn fetch_autoconfig(client: &reqwest::Client, url: &str, email: Option<&str>) -> Option<String> {
+    let mut request = client.get(url);
+    if let Some(email) = email {
+        request = request.query(&[("emailaddress", email)]);
+    }
+    let response = request.send().await.ok()?;
+    if !response.status().is_success() {
+        return None;
+    }
+    response.text().await.ok()
+}
+
+fn child_text(node: roxmltree::Node, name: &str) -> Option<String> {
+    node.children()
+        .find(|child| child.has_tag_name(name))
+        .and_then(|child| child.text())
+        .map(|text| text.trim().to_string())
+}
+
+// Hostname and port of a server element, with the placeholders autoconfig files may use filled in
+fn server_address(node: roxmltree::Node, email: &str, domain: &str) -> Option<(String, u16)> {
+    let local_part = email.split('@').next().unwrap_or_default();
+    let hostname = child_text(node, "hostname")?
+        .replace("%EMAILADDRESS%", email)
+        .replace("%EMAILLOCALPART%", local_part)
+        .replace("%EMAILDOMAIN%", domain);
+    let port = child_text(node, "port")?.parse().ok()?;
+    Some((hostname, port))
+}
+
+fn parse_autoconfig(xml: &str, email: &str, domain: &str) -> DiscoveredSettings {
+    let Ok(document) = roxmltree::Document::parse(xml) else {
+        return DiscoveredSettings::default();
+    };
+    let servers = |tag: &'static str, kind: &'static str| {
+        document
+            .descendants()
+            .filter(move |node| node.has_tag_name(tag) && node.attribute("type") == Some(kind))
+    };
+
+    let imap = servers("incomingServer", "imap")
+        .filter(|node| child_text(*node, "socketType").as_deref() == Some("SSL"))
+        .find_map(|node| server_address(node, email, domain));
+    let smtp = servers("outgoingServer", "smtp").find_map(|node| {
+        let security = match child_text(node, "socketType")?.as_str() {
+            "SSL" => "tls",
+            "STARTTLS" => "starttls",
+            _ => return None,
+        };
+        let (host, port) = server_address(node, email, domain)?;
+        Some((host, port, security.to_string()))
+    });
+    DiscoveredSettings { imap, smtp }
+}
+
+#[derive(Deserialize)]
+struct DnsResponse {
+    #[serde(rename = "Answer", default)]
+    answer: Vec<DnsAnswer>,
+}
+
+#[derive(Deserialize)]
+struct DnsAnswer {
+    #[serde(rename = "type")]
+    record_type: u16,
+    data: String,
+}
+
+// The highest priority target of an SRV record, None when there is none or it's "."
+async fn lookup_srv(client: &reqwest::Client, name: &str) -> Option<(String, u16)> {
+    let response: DnsResponse = client
+        .get(DNS_OVER_HTTPS_URL)
+        .query(&[("name", name), ("type", "SRV")])
+        .header("accept", "application/dns-json")
+        .send()
+        .await
+        .ok()?
+        .json()
+        .await
+        .ok()?;
+
+    response
+        .answer
+        .iter()
+        .filter(|answer| answer.record_type == SRV_RECORD_TYPE)
+        .filter_map(|answer| {
+            // "priority weight port target."
+            let mut parts = answer.data.split_whitespace();
+            let priority: u16 = parts.next()?.parse().ok()?;
+            let _weight = parts.next()?;
+            let port: u16 = parts.next()?.parse().ok()?;
+            let target = parts.next()?.trim_end_matches('.');
+            (!target.is_empty() && port != 0).then(|| (priority, target.to_string(), port))
+        })
+        .min_by_key(|(priority, ..)| *priority)
+        .map(|(_, host, port)| (host, port))
+}
@@ -0,0 +1,107 @@
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::utils::llm_provider::parse_provider_chain;
+use crate::AppState;
+
+// OpenAI rejects anything bigger
+const MAX_AUDIO_BYTES: usize = 25 * 1024 * 1024;
+
+#[derive(Deserialize)]
+struct TranscriptionResponse {
+    text: String,
+}
+
+// Whisper servers go by the file extension, not the part's content type
+fn file_extension(content_type: &str) -> &'static str {
+    let content_type = content_type.split(';').next().unwrap_or("").trim().to_lowercase();
+    match content_type.as_str() {
+        "audio/ogg" | "audio/opus" => "ogg",
+        "audio/mpeg" | "audio/mp3" => "mp3",
+        "audio/mp4" | "audio/m4a" | "audio/x-m4a" | "audio/aac" => "m4a",
+        "audio/wav" | "audio/x-wav" | "audio/wave" => "wav",
+        "audio/webm" => "webm",
+        "audio/flac" | "audio/x-flac" => "flac",
+        "audio/amr" => "amr",
+        "audio/3gpp" => "3gp",
+        _ => "ogg",
+    }
+}
+
+/// Speech to text through any OpenAI compatible `/audio/transcriptions` endpoint, e.g. OpenAI
+/// itself or a locally hosted Whisper server. Configured like the llm chains:
+///   TRANSCRIPTION_MODELS=openai:whisper-1,local:whisper-large-v3 (default openai:whisper-1)
+///   LLM_PROVIDER_<NAME>_URL / LLM_PROVIDER_<NAME>_API_KEY for the endpoints
+///   TRANSCRIPTION_TIMEOUT_SECONDS per attempt, default 60
+/// TRANSCRIPTION_MODELS=off turns transcription off.
+pub async fn transcribe(state: &Arc<AppState>, audio: Vec<u8>, content_type: &str) -> Result<String, String> {
+    let chain_str = env::var("TRANSCRIPTION_MODELS")
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+        .unwrap_or_else(|| "openai:whisper-1".to_string());
+    if chain_str.trim() == "off" {
+        return Err("Transcription is turned off".to_string());
+    }
+    if audio.len() > MAX_AUDIO_BYTES {
+        return Err(format!("Audio is too long to transcribe ({} bytes)", audio.len()));
+    }
+
+    let timeout = Duration::from_secs(
+        env::var("TRANSCRIPTION_TIMEOUT_SECONDS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(60),
+    );
+    let providers = parse_provider_chain(state, &chain_str, timeout, "transcription");
+    if providers.is_empty() {
+        return Err("No usable transcription providers configured".to_string());
+    }
+
+    let file_name = format!("voice.{}", file_extension(content_type));
+    let client = reqwest::Client::new();
+    let mut last_error = String::new();
+    for provider in providers.iter() {
+        let part = match reqwest::multipart::Part::bytes(audio.clone())
+            .file_name(file_name.clone())
+            .mime_str(content_type.split(';').next().unwrap_or("audio/ogg").trim())
+        {
+            Ok(part) => part,
+            Err(e) => return Err(format!("Invalid audio content type {}: {}", content_type, e)),
+        };
+        let form = reqwest::multipart::Form::new()
+            .part("file", part)
+            .text("model", provider.model.clone());
+
+        let response = client
+            .post(format!("{}/audio/transcriptions", provider.endpoint.trim_end_matches('/')))
+            .bearer_auth(&provider.api_key)
+            .multipart(form)
+            .timeout(provider.timeout)
+            .send()
+            .await;
+
+        match response {
+            Ok(response) if response.status().is_success() => match response.json::<TranscriptionResponse>().await {
+                Ok(body) => return Ok(body.text.trim().to_string()),
+                Err(e) => {
+                    tracing::warn!("Unreadable transcription response from {}: {}", provider.name, e);
+                    last_error = format!("{}: {}", provider.name, e);
+                }
+            },
+            Ok(response) => {
+                let status = response.status();
+                let body = response.text().await.unwrap_or_default();
+                tracing::warn!("Transcription with {} ({}) failed: {} {}", provider.name, provider.model, status, body);
+                last_error = format!("{}: {}", provider.name, status);
+            }
+            Err(e) => {
+                tracing::warn!("Transcription request to {} ({}) failed: {}", provider.name, provider.model, e);
+                last_error = format!("{}: {}", provider.name, e);
+            }
+        }
+    }
+    Err(format!("All transcription providers failed, last error: {}", last_error))
+}
@@ -0,0 +1,226 @@
+use std::collections::HashSet;
+use std::env;
+use std::num::NonZeroU32;
+use std::sync::Arc;
+
+use diesel::result::Error as DieselError;
+use governor::{Quota, RateLimiter};
+use openai_api_rs::v1::{chat_completion, types};
+use serde::Deserialize;
+
+use crate::models::user_models::UserMemory;
+use crate::utils::llm_provider::{LlmChain, LlmPurpose};
+use crate::AppState;
+
+pub const CATEGORIES: [&str; 5] = ["person", "schedule", "health", "preference", "other"];
+const MAX_EXTRACTED_PER_MESSAGE: usize = 3;
+
+const EXTRACTION_PROMPT: &str = r#"You pick out durable facts about the user from a message they sent to their assistant. Durable means still true and useful weeks from now: people in their life and how to reach them, routines and working hours, health facts like allergies, lasting preferences. Ignore one-off requests, questions, moods, plans for a single day and anything already in the known facts.
+
+Write each fact as a short third person sentence, e.g. "Sister is Laura (WhatsApp: Laura K)", "Works 9-17 on weekdays", "Allergic to nuts".
+
+Return one fact per line as `category|fact` where category is one of person, schedule, health, preference, other. Return an empty string when there is nothing worth keeping, which is the usual case."#;
+
+fn words(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| w.chars().count() >= 3)
+        .map(|w| w.to_lowercase())
+        .collect()
+}
+
+// Share of `query` words found in `content`
+fn overlap(query: &HashSet<String>, content: &str) -> f32 {
+    if query.is_empty() {
+        return 0.0;
+    }
+    let content = words(content);
+    query.iter().filter(|w| content.contains(*w)).count() as f32 / query.len() as f32
+}
+
+/// Memories to put in a prompt. Everything when there are few, otherwise the ones sharing
+/// the most words with `query`, newer first on ties.
+pub fn relevant_memories(state: &Arc<AppState>, user_id: i32, query: &str, limit: usize) -> Vec<UserMemory> {
+    let memories = match state.user_repository.get_user_memories(user_id) {
+        Ok(memories) => memories,
+        Err(e) => {
+            tracing::error!("Failed to get memories for user {}: {}", user_id, e);
+            return Vec::new();
+        }
+    };
+    if memories.len() <= limit {
+        return memories;
+    }
+
+    let query = words(query);
+    let mut scored: Vec<(f32, UserMemory)> = memories
+        .into_iter()
+        .map(|m| (overlap(&query, &m.encrypted_content), m))
+        .collect();
+    // stable, so newest first is kept within equal scores
+    scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+    scored.into_iter().take(limit).map(|(_, m)| m).collect()
+}
+
+pub fn format_memories(memories: &[UserMemory]) -> String {
+    memories
+        .iter()
+        .map(|m| format!("- {}", m.encrypted_content))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// "remember that .." from the user
+pub fn remember(state: &Arc<AppState>, user_id: i32, content: &str) -> Result<(), DieselError> {
+    state.user_repository.add_user_memory(user_id, content.trim(), "other", "user")
+}
+
+/// "forget that .." from the user. Deletes the best matching memories and returns their contents,
+/// nothing is deleted unless at least half of the words match.
+pub fn forget(state: &Arc<AppState>, user_id: i32, phrase: &str) -> Result<Vec<String>, DieselError> {
+    let query = words(phrase);
+    let memories = state.user_repository.get_user_memories(user_id)?;
+
+    let scored: Vec<(f32, &UserMemory)> = memories.iter().map(|m| (overlap(&query, &m.encrypted_content), m)).collect();
+    let best = scored.iter().map(|(score, _)| *score).fold(0.0, f32::max);
+    if best < 0.5 {
+        return Ok(Vec::new());
+    }
+
+    let mut forgotten = Vec::new();
+    for (score, memory) in scored {
+        if score < best {
+            continue;
+        }
+        if let Some(id) = memory.id {
+            if state.user_repository.delete_user_memory(user_id, id)? {
+                forgotten.push(memory.encrypted_content.clone());
+            }
+        }
+    }
+    Ok(forgotten)
+}
+
+/// Picks durable facts out of a user's message and stores them. Runs in the background
+/// after the message is answered. MEMORY_EXTRACTION=off turns it off,
+/// MEMORY_EXTRACTIONS_PER_HOUR caps the model calls per user, default 20.
+pub async fn extract_memories(state: &Arc<AppState>, user_id: i32, message: &str) {
+    if env::var("MEMORY_EXTRACTION").map(|v| v == "off").unwrap_or(false) {
+        return;
+    }
+    // nothing durable fits in a few characters
+    if message.trim().chars().count() < 15 {
+        return;
+    }
+
+    let per_hour = env::var("MEMORY_EXTRACTIONS_PER_HOUR")
+        .ok()
+        .and_then(|v| v.parse::<u32>().ok())
+        .and_then(NonZeroU32::new)
+        .unwrap_or(NonZeroU32::new(20).unwrap());
+    let limiter_key = user_id.to_string();
+    let entry = state.memory_extraction_limiter
+        .entry(limiter_key.clone())
+        .or_insert_with(|| RateLimiter::keyed(Quota::per_hour(per_hour)));
+    if entry.value().check_key(&limiter_key).is_err() {
+        tracing::debug!("Memory extraction rate limit reached for user {}", user_id);
+        return;
+    }
+    drop(entry);
+
+    let existing = match state.user_repository.get_user_memories(user_id) {
+        Ok(memories) => memories,
+        Err(e) => {
+            tracing::error!("Failed to get memories for user {}: {}", user_id, e);
+            return;
+        }
+    };
+    let known = if existing.is_empty() { "(none)".to_string() } else { format_memories(&existing) };
+
+    let llm = match LlmChain::for_purpose(state, LlmPurpose::MemoryExtraction, Some(user_id)) {
+        Ok(llm) => llm,
+        Err(e) => {
+            tracing::error!("Failed to set up memory extraction llm chain: {}", e);
+            return;
+        }
+    };
+
+    let mut properties = std::collections::HashMap::new();
+    properties.insert(
+        "facts".to_string(),
+        Box::new(types::JSONSchemaDefine {
+            schema_type: Some(types::JSONSchemaType::String),
+            description: Some("New facts, one per line as category|fact, or empty".to_string()),
+            ..Default::default()
+        }),
+    );
+    let tools = vec![chat_completion::Tool {
+        r#type: chat_completion::ToolType::Function,
+        function: types::Function {
+            name: String::from("save_facts"),
+            description: Some(String::from("Saves durable facts about the user")),
+            parameters: types::FunctionParameters {
+                schema_type: types::JSONSchemaType::Object,
+                properties: Some(properties),
+                required: Some(vec![String::from("facts")]),
+            },
+        },
+    }];
+
+    let request = chat_completion::ChatCompletionRequest::new(
+        llm.primary_model().to_string(),
+        vec![
+            chat_completion::ChatCompletionMessage {
+                role: chat_completion::MessageRole::system,
+                content: chat_completion::Content::Text(EXTRACTION_PROMPT.to_string()),
+                name: None,
+                tool_calls: None,
+                tool_call_id: None,
+            },
+            chat_completion::ChatCompletionMessage {
+                role: chat_completion::MessageRole::user,
+                content: chat_completion::Content::Text(format!("Known facts:\n{}\n\nMessage:\n{}", known, message)),
+                name: None,
+                tool_calls: None,
+                tool_call_id: None,
+            },
+        ],
+    )
+    .tools(tools)
+    .tool_choice(chat_completion::ToolChoiceType::Required)
+    .max_tokens(200);
+
+    let args = match llm.chat_completion(request).await {
+        Ok(result) => result.choices.first()
+            .and_then(|c| c.message.tool_calls.as_ref())
+            .and_then(|calls| calls.first())
+            .and_then(|call| call.function.arguments.clone()),
+        Err(e) => {
+            tracing::error!("Failed to extract memories for user {}: {}", user_id, e);
+            return;
+        }
+    };
+
+    #[derive(Deserialize)]
+    struct FactsResponse {
+        facts: String,
+    }
+    let Some(facts) = args.and_then(|args| serde_json::from_str::<FactsResponse>(&args).ok()) else {
+        return;
+    };
+
+    let known_lower: HashSet<String> = existing.iter().map(|m| m.encrypted_content.to_lowercase()).collect();
+    for line in facts.facts.lines().take(MAX_EXTRACTED_PER_MESSAGE) {
+        let (category, fact) = match line.split_once('|') {
+            Some((category, fact)) => (category.trim().to_lowercase(), fact.trim()),
+            None => ("other".to_string(), line.trim()),
+        };
+        if fact.is_empty() || known_lower.contains(&fact.to_lowercase()) {
+            continue;
+        }
+        let category = if CATEGORIES.contains(&category.as_str()) { category } else { "other".to_string() };
+        match state.user_repository.add_user_memory(user_id, fact, &category, "extracted") {
+            Ok(()) => tracing::debug!("Stored extracted {} memory for user {}", category, user_id),
+            Err(e) => tracing::error!("Failed to store extracted memory for user {}: {}", user_id, e),
+        }
+    }
+}